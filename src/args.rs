@@ -1,5 +1,9 @@
-use crate::commands::{InstallArgs, ListJobArgs, RunArgs};
-use clap::{Parser, Subcommand, arg};
+use crate::commands::{
+    CancelArgs, CleanArgs, ConfigArgs, ExecPlanArgs, ExecuteArgs, FixArgs, FlakyArgs, InitArgs, InstallArgs, ListJobArgs, LogsArgs,
+    MigrateArgs, PlanArgs, RunArgs, SimulateArgs, TestArgs, ValidateArgs,
+};
+use crate::diagnostics::ErrorFormat;
+use clap::{ArgAction, Parser, Subcommand};
 use std::path::PathBuf;
 
 /// The app's command-line arguments.
@@ -29,6 +33,18 @@ pub struct Args {
     #[arg(long, short = 'c', value_name = "PATH", global = true)]
     pub config: Option<PathBuf>,
 
+    /// Apply the named overlay from the configuration; may be repeated [default: `ci` when running under CI].
+    #[arg(long, value_name = "NAME", global = true)]
+    pub overlay: Vec<String>,
+
+    /// Only read included configuration files from the cache, without fetching them.
+    #[arg(long, action = ArgAction::SetTrue, global = true)]
+    pub offline: bool,
+
+    /// How to report the error which ends the program, as text or as a line of JSON on stderr.
+    #[arg(long, value_name = "FORMAT", global = true, default_value_t = ErrorFormat::Human, value_enum)]
+    pub error_format: ErrorFormat,
+
     /// Flattened `RunArgs` for when no subcommand is specified
     #[command(flatten)]
     #[expect(clippy::struct_field_names, reason = "Necessary for flattening RunArgs")]
@@ -40,11 +56,53 @@ pub enum Commands {
     /// Runs a set of jobs.
     Run(RunArgs),
 
+    /// Runs the workspace's tests, with cargo-nextest when it's installed, without needing any configuration.
+    Test(TestArgs),
+
+    /// Runs the fix commands of fixable steps, such as `cargo fmt` for a formatting check.
+    Fix(FixArgs),
+
+    /// Writes the fully resolved execution plan of a set of jobs.
+    Plan(PlanArgs),
+
+    /// Executes a plan written by the plan subcommand, or a slice of it.
+    ExecPlan(ExecPlanArgs),
+
+    /// Runs a step handed over through the executor protocol on this machine, as the built-in local executor.
+    Execute(ExecuteArgs),
+
+    /// Runs the parts of the repository's hosted CI workflows which can run locally, before they run in the cloud.
+    Simulate(SimulateArgs),
+
+    /// Cancels one job of a run in progress, letting the rest of the run continue.
+    Cancel(CancelArgs),
+
     /// Lists all the jobs defined in configuration.
     ListJobs(ListJobArgs),
 
     /// Installs or updates the tools defined in configuration.
     Install(InstallArgs),
+
+    /// Checks the configuration for likely mistakes.
+    Validate(ValidateArgs),
+
+    /// Inspects the configuration.
+    Config(ConfigArgs),
+
+    /// Lists the steps whose outcome changes most often from one run to the next.
+    Flaky(FlakyArgs),
+
+    /// Searches the logs of past runs.
+    Logs(LogsArgs),
+
+    /// Creates a configuration file from a shared template.
+    Init(InitArgs),
+
+    /// Converts a legacy [workspace.metadata.ci] configuration into a ci.toml file.
+    Migrate(MigrateArgs),
+
+    /// Frees up space in the target directory.
+    Clean(CleanArgs),
 }
 
 impl Args {
@@ -0,0 +1,83 @@
+use crate::long_paths;
+use anyhow::Context;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Keeps copies of the artifacts other jobs consume, taken when the job producing them succeeds, so that they can be
+/// restored once they're gone from the workspace, or when the producing job is skipped.
+#[derive(Debug)]
+pub struct ArtifactStore {
+    dir: PathBuf,
+}
+
+impl ArtifactStore {
+    /// The artifact store of a workspace, kept in its target directory.
+    #[must_use]
+    pub fn new(target_dir: &Path) -> Self {
+        Self {
+            dir: target_dir.join("ci-artifacts"),
+        }
+    }
+
+    /// Whether the store holds the artifacts of a job, which means the job succeeded at some point.
+    #[must_use]
+    pub fn has(&self, job_id: &str) -> bool {
+        self.dir.join(job_id).is_dir()
+    }
+
+    /// Replaces the stored artifacts of a job with those in the workspace, skipping the ones which don't exist.
+    pub fn store(&self, job_id: &str, workspace_root: &Path, artifacts: &[&Path]) -> anyhow::Result<()> {
+        let job_dir = self.dir.join(job_id);
+        match long_paths::extended(&job_dir).and_then(fs::remove_dir_all) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("unable to clear the stored artifacts of job '{job_id}'"));
+            }
+            _ => {}
+        }
+
+        fs::create_dir_all(long_paths::extended(&job_dir)?)
+            .with_context(|| format!("unable to create artifact store directory {}", job_dir.display()))?;
+
+        for &artifact in artifacts {
+            let source = workspace_root.join(artifact);
+            if source.exists() {
+                copy(&source, &job_dir.join(artifact))
+                    .with_context(|| format!("unable to store artifact '{}' of job '{job_id}'", artifact.display()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies a stored artifact of a job back into the workspace, returning whether the store has it.
+    pub fn restore(&self, job_id: &str, workspace_root: &Path, artifact: &Path) -> anyhow::Result<bool> {
+        let stored = self.dir.join(job_id).join(artifact);
+        if !stored.exists() {
+            return Ok(false);
+        }
+
+        copy(&stored, &workspace_root.join(artifact))
+            .with_context(|| format!("unable to restore artifact '{}' of job '{job_id}'", artifact.display()))?;
+        Ok(true)
+    }
+}
+
+/// Copies a file, or a directory along with everything in it, creating the parent directories of the destination.
+fn copy(source: &Path, dest: &Path) -> io::Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(long_paths::extended(parent)?)?;
+    }
+
+    if !source.is_dir() {
+        return fs::copy(long_paths::extended(source)?, long_paths::extended(dest)?).map(|_| ());
+    }
+
+    fs::create_dir_all(long_paths::extended(dest)?)?;
+    for entry in fs::read_dir(long_paths::extended(source)?)? {
+        let entry = entry?;
+        copy(&entry.path(), &dest.join(entry.file_name()))?;
+    }
+
+    Ok(())
+}
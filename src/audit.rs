@@ -0,0 +1,206 @@
+use crate::config::AuditPolicy;
+use anyhow::Context;
+use cargo_metadata::Metadata;
+use core::fmt;
+use serde_json::Value;
+
+/// What's wrong with a dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    Vulnerability,
+    Unmaintained,
+    Unsound,
+    Yanked,
+    License,
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Vulnerability => "vulnerability",
+            Self::Unmaintained => "unmaintained",
+            Self::Unsound => "unsound",
+            Self::Yanked => "yanked",
+            Self::License => "license",
+        })
+    }
+}
+
+/// A problem found with one of the workspace's dependencies, whichever tool reported it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub package: String,
+    pub version: String,
+
+    /// The advisory reporting the problem, such as `RUSTSEC-2020-0071`.
+    pub advisory: Option<String>,
+
+    pub title: String,
+}
+
+impl Finding {
+    /// Whether the finding fails an audit under the given policy.
+    #[must_use]
+    pub fn fails(&self, policy: &AuditPolicy) -> bool {
+        if self.advisory.as_deref().is_some_and(|id| policy.ignores(id)) {
+            return false;
+        }
+
+        match self.kind {
+            FindingKind::Vulnerability | FindingKind::License => true,
+            FindingKind::Unmaintained | FindingKind::Unsound | FindingKind::Yanked => policy.deny_warnings(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {} {}", self.kind, self.package, self.version)?;
+        if let Some(advisory) = &self.advisory {
+            write!(f, " ({advisory})")?;
+        }
+
+        if !self.title.is_empty() {
+            write!(f, ": {}", self.title)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Picks the findings out of the report written by `cargo audit --json`.
+///
+/// Vulnerabilities are listed under `vulnerabilities.list`, while the other kinds of advisories, along with yanked
+/// crates, are listed under `warnings`, by kind.
+pub fn parse_cargo_audit(report: &str) -> anyhow::Result<Vec<Finding>> {
+    let report: Value = serde_json::from_str(report).context("unable to parse the report of cargo-audit")?;
+
+    let mut findings = Vec::new();
+    for entry in report["vulnerabilities"]["list"].as_array().into_iter().flatten() {
+        findings.push(finding(FindingKind::Vulnerability, entry));
+    }
+
+    for (kind, entries) in report["warnings"].as_object().into_iter().flatten() {
+        let kind = match kind.as_str() {
+            "unmaintained" => FindingKind::Unmaintained,
+            "unsound" => FindingKind::Unsound,
+            "yanked" => FindingKind::Yanked,
+            _ => continue,
+        };
+
+        for entry in entries.as_array().into_iter().flatten() {
+            findings.push(finding(kind, entry));
+        }
+    }
+
+    Ok(findings)
+}
+
+fn finding(kind: FindingKind, entry: &Value) -> Finding {
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    Finding {
+        kind,
+        package: text(&entry["package"]["name"]),
+        version: text(&entry["package"]["version"]),
+        advisory: entry["advisory"]["id"].as_str().map(ToString::to_string),
+        title: text(&entry["advisory"]["title"]),
+    }
+}
+
+/// Finds the dependencies whose licenses the policy doesn't allow, leaving out the workspace's own packages.
+///
+/// Licenses are read as SPDX expressions, such as `MIT OR Apache-2.0`, along with the older `MIT/Apache-2.0` form.
+#[must_use]
+pub fn license_findings(metadata: &Metadata, policy: &AuditPolicy) -> Vec<Finding> {
+    let allowed = policy.allowed_licenses();
+    if allowed.is_empty() {
+        return Vec::new();
+    }
+
+    let mut findings: Vec<_> = metadata
+        .packages
+        .iter()
+        .filter(|pkg| !metadata.workspace_members.contains(&pkg.id))
+        .filter_map(|pkg| {
+            let title = match pkg.license.as_deref() {
+                None => "no license is declared".to_string(),
+                Some(license) if !allows(license, allowed) => format!("'{license}' isn't allowed"),
+                Some(_) => return None,
+            };
+
+            Some(Finding {
+                kind: FindingKind::License,
+                package: pkg.name.to_string(),
+                version: pkg.version.to_string(),
+                advisory: None,
+                title,
+            })
+        })
+        .collect();
+
+    findings.sort_by(|x, y| (&x.package, &x.version).cmp(&(&y.package, &y.version)));
+    findings
+}
+
+/// Whether a license expression can be satisfied using only the allowed licenses.
+fn allows(expression: &str, allowed: &[String]) -> bool {
+    let spaced = expression.replace('(', " ( ").replace(')', " ) ").replace('/', " OR ");
+    let tokens: Vec<_> = spaced.split_whitespace().collect();
+    let mut pos = 0;
+    let satisfied = any_of(&tokens, &mut pos, allowed);
+
+    // expressions which can't be read entirely aren't trusted
+    satisfied && pos == tokens.len()
+}
+
+/// Reads licenses joined with `OR`, any of which must be allowed.
+fn any_of(tokens: &[&str], pos: &mut usize, allowed: &[String]) -> bool {
+    let mut satisfied = all_of(tokens, pos, allowed);
+    while tokens.get(*pos).is_some_and(|token| token.eq_ignore_ascii_case("OR")) {
+        *pos += 1;
+        satisfied |= all_of(tokens, pos, allowed);
+    }
+
+    satisfied
+}
+
+/// Reads licenses joined with `AND`, all of which must be allowed.
+fn all_of(tokens: &[&str], pos: &mut usize, allowed: &[String]) -> bool {
+    let mut satisfied = license(tokens, pos, allowed);
+    while tokens.get(*pos).is_some_and(|token| token.eq_ignore_ascii_case("AND")) {
+        *pos += 1;
+        satisfied &= license(tokens, pos, allowed);
+    }
+
+    satisfied
+}
+
+/// Reads a single license, possibly with an exception, or a parenthesized expression.
+fn license(tokens: &[&str], pos: &mut usize, allowed: &[String]) -> bool {
+    let Some(&token) = tokens.get(*pos) else {
+        return false;
+    };
+
+    *pos += 1;
+    if token == "(" {
+        let satisfied = any_of(tokens, pos, allowed);
+        if tokens.get(*pos) != Some(&")") {
+            return false;
+        }
+
+        *pos += 1;
+        return satisfied;
+    }
+
+    let is_allowed = |id: &str| allowed.iter().any(|license| license.eq_ignore_ascii_case(id));
+
+    // a license with an exception is allowed when either the license alone or the combination is
+    if tokens.get(*pos).is_some_and(|next| next.eq_ignore_ascii_case("WITH")) {
+        let exception = tokens.get(*pos + 1).copied().unwrap_or_default();
+        *pos += 2;
+        return is_allowed(token) || is_allowed(&format!("{token} WITH {exception}"));
+    }
+
+    is_allowed(token)
+}
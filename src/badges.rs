@@ -0,0 +1,95 @@
+use crate::long_paths;
+use crate::summary::{JobResult, JobStatus};
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// The machine-readable status written next to each badge.
+#[derive(Debug, Serialize)]
+struct BadgeStatus<'a> {
+    job: &'a str,
+    name: &'a str,
+    status: JobStatus,
+    steps: usize,
+    duration_secs: f64,
+    timestamp: String,
+}
+
+/// Writes a `<job-id>.svg` badge and a `<job-id>.json` status file for every job that ran.
+///
+/// Files for jobs which didn't run are left alone, so they keep reporting the last known status.
+pub fn write_badges(dir: &Path, written: DateTime<Local>, jobs: &[&JobResult]) -> anyhow::Result<()> {
+    fs::create_dir_all(long_paths::extended(dir)?).with_context(|| format!("unable to create badge directory {}", dir.display()))?;
+
+    let timestamp = written.to_rfc3339();
+    for job in jobs.iter().filter(|job| job.status != JobStatus::NotRun) {
+        let svg_path = dir.join(format!("{}.svg", job.id));
+        fs::write(long_paths::extended(&svg_path)?, render_svg(&job.name, job.status))
+            .with_context(|| format!("unable to write {}", svg_path.display()))?;
+
+        let status = BadgeStatus {
+            job: &job.id,
+            name: &job.name,
+            status: job.status,
+            steps: job.steps.len(),
+            duration_secs: job.duration.as_secs_f64(),
+            timestamp: timestamp.clone(),
+        };
+
+        let json_path = dir.join(format!("{}.json", job.id));
+        let json = serde_json::to_string_pretty(&status)?;
+        fs::write(long_paths::extended(&json_path)?, json).with_context(|| format!("unable to write {}", json_path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Renders a flat badge in the style popularized by shields.io.
+fn render_svg(label: &str, status: JobStatus) -> String {
+    let (message, color) = match status {
+        JobStatus::Succeeded => ("passing", "#4c1"),
+        JobStatus::Failed => ("failing", "#e05d44"),
+        JobStatus::FailedIgnored => ("failing (ignored)", "#dfb317"),
+        JobStatus::NotRun => ("not run", "#9f9f9f"),
+        JobStatus::Cancelled => ("cancelled", "#9f9f9f"),
+    };
+
+    let label = escape(label);
+    let label_width = text_width(&label);
+    let message_width = text_width(message);
+    let width = label_width + message_width;
+    let label_x = label_width / 2;
+    let message_x = label_width + message_width / 2;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+  <title>{label}: {message}</title>
+  <linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+  <clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="20" fill="#555"/>
+    <rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>
+    <rect width="{width}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x}" y="14">{label}</text>
+    <text x="{message_x}" y="14">{message}</text>
+  </g>
+</svg>
+"##
+    )
+}
+
+/// Approximates the rendered width of badge text, including padding.
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
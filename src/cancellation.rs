@@ -0,0 +1,41 @@
+use anyhow::{Context, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The directory within a run's temporary directory where requests to cancel its jobs are left, which only exists
+/// while the run's jobs are running.
+const REQUESTS_DIR: &str = "cancel";
+
+/// The temporary directory of a run, given its id.
+#[must_use]
+pub fn run_dir(target_dir: &Path, run_id: &str) -> PathBuf {
+    target_dir.join("ci-tmp").join(run_id)
+}
+
+/// Lets other processes ask the run with the given temporary directory to cancel its jobs.
+pub fn accept_requests(run_dir: &Path) -> anyhow::Result<()> {
+    let dir = run_dir.join(REQUESTS_DIR);
+    fs::create_dir_all(&dir).with_context(|| format!("unable to create {}", dir.display()))
+}
+
+/// Stops accepting requests to cancel jobs, once a run's jobs are done.
+pub fn stop_accepting_requests(run_dir: &Path) {
+    _ = fs::remove_dir_all(run_dir.join(REQUESTS_DIR));
+}
+
+/// Asks the run with the given temporary directory to cancel a job.
+pub fn request(run_dir: &Path, run_id: &str, job_id: &str) -> anyhow::Result<()> {
+    let dir = run_dir.join(REQUESTS_DIR);
+    if !dir.is_dir() {
+        return Err(anyhow!("run '{run_id}' isn't in progress"));
+    }
+
+    let path = dir.join(job_id);
+    fs::write(&path, "").with_context(|| format!("unable to write {}", path.display()))
+}
+
+/// Whether cancelling a job of the run with the given temporary directory was asked for.
+#[must_use]
+pub fn is_requested(run_dir: &Path, job_id: &str) -> bool {
+    run_dir.join(REQUESTS_DIR).join(job_id).exists()
+}
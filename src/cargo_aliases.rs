@@ -0,0 +1,125 @@
+use crate::host::Host;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Aliases cargo defines out of the box, along with what they expand to.
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("b", "build"),
+    ("c", "check"),
+    ("d", "doc"),
+    ("r", "run"),
+    ("rm", "remove"),
+    ("t", "test"),
+];
+
+/// How many times an alias expanding to another alias is followed, cargo gives up on cycles too.
+const MAX_EXPANSIONS: usize = 10;
+
+/// The cargo aliases visible from a workspace.
+#[derive(Debug, Default)]
+pub struct CargoAliases {
+    /// What each alias expands to, by name.
+    definitions: BTreeMap<String, Vec<String>>,
+
+    /// What the aliases defined in `CARGO_ALIAS_<NAME>` variables expand to, by `<NAME>`.
+    env_definitions: BTreeMap<String, Vec<String>>,
+}
+
+impl CargoAliases {
+    /// Collects the aliases defined in the cargo configuration files that apply to the workspace.
+    ///
+    /// Like cargo, this looks in `.cargo/config.toml` and `.cargo/config` in the workspace root and every one
+    /// of its parents, in the cargo home directory, and in `CARGO_ALIAS_<NAME>` environment variables.
+    /// Configuration files which can't be read or parsed are ignored, cargo will complain about those itself.
+    pub fn discover(host: &impl Host, workspace_root: &Path) -> Self {
+        let mut dirs: Vec<PathBuf> = workspace_root.ancestors().map(|dir| dir.join(".cargo")).collect();
+        if let Ok(cargo_home) = home::cargo_home() {
+            dirs.push(cargo_home);
+        }
+
+        // the closest definition of an alias wins, as it does with cargo
+        let mut definitions = BTreeMap::new();
+        for dir in dirs {
+            for file in ["config.toml", "config"] {
+                let path = dir.join(file);
+                if !host.is_file(&path) {
+                    continue;
+                }
+
+                let table = host.read_to_string(&path).ok().and_then(|text| text.parse::<toml::Table>().ok());
+                if let Some(table) = table
+                    && let Some(toml::Value::Table(aliases)) = table.get("alias")
+                {
+                    for (name, value) in aliases {
+                        _ = definitions.entry(name.clone()).or_insert_with(|| words(value));
+                    }
+                }
+            }
+        }
+
+        for (name, expansion) in BUILTIN_ALIASES {
+            _ = definitions
+                .entry((*name).to_string())
+                .or_insert_with(|| vec![(*expansion).to_string()]);
+        }
+
+        let env_definitions = host
+            .vars()
+            .filter_map(|(key, value)| {
+                let name = key.strip_prefix("CARGO_ALIAS_")?;
+                Some((name.to_string(), value.split_whitespace().map(ToString::to_string).collect()))
+            })
+            .collect();
+
+        Self {
+            definitions,
+            env_definitions,
+        }
+    }
+
+    /// Checks whether the given alias is defined.
+    pub fn contains(&self, name: &str) -> bool {
+        self.definition(name).is_some()
+    }
+
+    /// Expands the alias a cargo command line starts with, along with any alias that one expands to in turn, the way
+    /// cargo does. Returns `None` when the command line doesn't start with an alias.
+    pub fn expand(&self, args: &[String]) -> Option<Vec<String>> {
+        let (first, rest) = args.split_first()?;
+        let mut expanded = self.definition(first)?.to_vec();
+        for _ in 0..MAX_EXPANSIONS {
+            let Some((name, definition)) = expanded
+                .split_first()
+                .and_then(|(name, _)| self.definition(name).map(|definition| (name.clone(), definition)))
+            else {
+                break;
+            };
+
+            // an alias may share the name of the command it adds arguments to
+            if definition.first() == Some(&name) {
+                break;
+            }
+
+            _ = expanded.splice(..1, definition.iter().cloned());
+        }
+
+        expanded.extend(rest.iter().cloned());
+        Some(expanded)
+    }
+
+    fn definition(&self, name: &str) -> Option<&[String]> {
+        self.env_definitions
+            .get(&name.to_uppercase().replace('-', "_"))
+            .or_else(|| self.definitions.get(name))
+            .map(Vec::as_slice)
+    }
+}
+
+/// The words an alias expands to, which are given either as a single string or as an array of strings.
+fn words(value: &toml::Value) -> Vec<String> {
+    match value {
+        toml::Value::String(s) => s.split_whitespace().map(ToString::to_string).collect(),
+        toml::Value::Array(items) => items.iter().filter_map(|item| item.as_str().map(ToString::to_string)).collect(),
+        _ => Vec::new(),
+    }
+}
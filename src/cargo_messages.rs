@@ -0,0 +1,172 @@
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticLevel};
+use cargo_metadata::{Message, PackageId};
+use core::fmt::Write;
+
+/// Whether a command line asks cargo to report its messages as JSON.
+#[must_use]
+pub fn uses_json_messages(command: &str) -> bool {
+    let mut words = command.split_whitespace();
+    while let Some(word) = words.next() {
+        let format = match word.strip_prefix("--message-format") {
+            Some("") => words.next().unwrap_or_default(),
+            Some(value) => value.strip_prefix('=').unwrap_or_default(),
+            None => continue,
+        };
+
+        if format.starts_with("json") {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// An error reported by the compiler.
+#[derive(Debug)]
+struct CompilerError {
+    package: String,
+    code: Option<String>,
+    message: String,
+    location: Option<String>,
+}
+
+impl CompilerError {
+    fn headline(&self) -> String {
+        self.code.as_ref().map_or_else(
+            || format!("error: {}", self.message),
+            |code| format!("error[{code}]: {}", self.message),
+        )
+    }
+}
+
+/// The messages cargo reported as JSON, reduced to what's needed to understand a failure.
+#[derive(Debug, Default)]
+pub struct CargoMessages {
+    artifacts: usize,
+    errors: Vec<CompilerError>,
+    text: Vec<String>,
+}
+
+impl CargoMessages {
+    /// Parses the output of a cargo command, returning `None` when it holds no JSON messages.
+    #[must_use]
+    pub fn parse(stdout: &[u8]) -> Option<Self> {
+        let mut result = Self::default();
+        let mut records = 0;
+
+        for message in Message::parse_stream(stdout).map_while(Result::ok) {
+            match message {
+                Message::TextLine(line) => result.text.push(line),
+                Message::CompilerArtifact(_) => {
+                    records += 1;
+                    result.artifacts += 1;
+                }
+
+                Message::CompilerMessage(message) => {
+                    records += 1;
+                    if let Some(error) = compiler_error(&message.package_id, &message.message) {
+                        result.errors.push(error);
+                    }
+                }
+
+                _ => records += 1,
+            }
+        }
+
+        (records > 0).then_some(result)
+    }
+
+    /// The first error reported by the compiler, along with the package it was reported for.
+    #[must_use]
+    pub fn first_error(&self) -> Option<(&str, String)> {
+        self.errors.first().map(|error| (error.package.as_str(), error.headline()))
+    }
+
+    /// Summarizes the messages, listing the first error of every package along with the codes of all its errors, followed
+    /// by any output which wasn't a JSON message, such as the output of tests.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        if !self.errors.is_empty() {
+            let mut packages: Vec<&str> = Vec::new();
+            for error in &self.errors {
+                if !packages.contains(&error.package.as_str()) {
+                    packages.push(&error.package);
+                }
+            }
+
+            _ = writeln!(
+                out,
+                "cargo reported {} error(s) in {} package(s), after building {} crate(s)",
+                self.errors.len(),
+                packages.len(),
+                self.artifacts
+            );
+
+            for package in packages {
+                let errors: Vec<_> = self.errors.iter().filter(|error| error.package == package).collect();
+                let mut codes: Vec<_> = errors.iter().filter_map(|error| error.code.as_deref()).collect();
+                codes.sort_unstable();
+                codes.dedup();
+
+                if codes.is_empty() {
+                    _ = writeln!(out, "  {package}: {} error(s)", errors.len());
+                } else {
+                    _ = writeln!(out, "  {package}: {} error(s) [{}]", errors.len(), codes.join(", "));
+                }
+
+                let first = errors[0];
+                _ = writeln!(out, "    {}", first.headline());
+                if let Some(location) = &first.location {
+                    _ = writeln!(out, "      --> {location}");
+                }
+            }
+        }
+
+        for line in &self.text {
+            _ = writeln!(out, "{line}");
+        }
+
+        out
+    }
+}
+
+fn compiler_error(package_id: &PackageId, diagnostic: &Diagnostic) -> Option<CompilerError> {
+    if !matches!(diagnostic.level, DiagnosticLevel::Error | DiagnosticLevel::Ice) {
+        return None;
+    }
+
+    // rustc closes with a recap of how many errors it found, which says nothing new
+    if diagnostic.message.starts_with("aborting due to") {
+        return None;
+    }
+
+    let location = diagnostic
+        .spans
+        .iter()
+        .find(|span| span.is_primary)
+        .map(|span| format!("{}:{}:{}", span.file_name, span.line_start, span.column_start));
+
+    Some(CompilerError {
+        package: package_name(&package_id.repr),
+        code: diagnostic.code.as_ref().map(|code| code.code.clone()),
+        message: diagnostic.message.clone(),
+        location,
+    })
+}
+
+/// Extracts the name of a package from its ID, which is either `name version (source)` with older versions of cargo,
+/// or a URL of the form `source#version` or `source#name@version`, where the name is otherwise the last segment of
+/// the source's path.
+fn package_name(repr: &str) -> String {
+    if let Some((name, _)) = repr.split_once(' ') {
+        return name.to_string();
+    }
+
+    let (source, fragment) = repr.split_once('#').unwrap_or((repr, ""));
+    if let Some((name, _)) = fragment.split_once('@') {
+        return name.to_string();
+    }
+
+    source.trim_end_matches('/').rsplit('/').next().unwrap_or(repr).to_string()
+}
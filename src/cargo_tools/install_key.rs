@@ -46,7 +46,11 @@ impl TryFrom<RawInstallKey> for InstallKey {
             let source_str = source_parts.join(" ");
 
             if source_str.starts_with('(') && source_str.ends_with(')') {
-                source_str.strip_prefix('(').and_then(|s| s.strip_suffix(')')).unwrap_or(&source_str).to_string()
+                source_str
+                    .strip_prefix('(')
+                    .and_then(|s| s.strip_suffix(')'))
+                    .unwrap_or(&source_str)
+                    .to_string()
             } else {
                 source_str
             }
@@ -0,0 +1,189 @@
+use std::io;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// The pids of the running children leading a process group of their own, which Ctrl-C is forwarded to.
+static GROUPS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Makes a command start in a process group of its own, so [`kill_group`] also ends the processes it starts.
+///
+/// On Unix, the child leads a new process group. On Windows, it's started with `CREATE_NEW_PROCESS_GROUP` unless
+/// standard output is a terminal, so children of interactive runs still get Ctrl-C from the console, since
+/// [`kill_group`] ends the whole process tree regardless.
+pub fn set_process_group(cmd: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        _ = cmd.process_group(0);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::io::IsTerminal;
+        use std::os::windows::process::CommandExt;
+
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        if !io::stdout().is_terminal() {
+            _ = cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+        }
+    }
+}
+
+/// Forwards Ctrl-C to the process group of a child started with [`set_process_group`], until [`release_group`] is
+/// called once it exited.
+///
+/// A child leading a group of its own doesn't get the Ctrl-C pressed in the terminal. On Unix, the first call starts
+/// handling `SIGINT` instead: every tracked group gets it, what's left of them is killed a moment later, and then this
+/// process ends just as it would have without handling it.
+pub fn track_group(child: &Child) {
+    #[cfg(unix)]
+    {
+        use std::sync::Once;
+
+        static HANDLER: Once = Once::new();
+        HANDLER.call_once(interrupts::forward);
+    }
+
+    groups().push(child.id());
+}
+
+/// Stops forwarding Ctrl-C to the process group of the child with the given pid, once the child exited.
+pub fn release_group(pid: u32) {
+    groups().retain(|&tracked| tracked != pid);
+}
+
+fn groups() -> MutexGuard<'static, Vec<u32>> {
+    GROUPS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(unix)]
+mod interrupts {
+    use super::groups;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::time::Duration;
+    use std::thread;
+
+    /// How often the forwarding thread checks whether Ctrl-C was pressed.
+    const POLL: Duration = Duration::from_millis(50);
+
+    /// How long the process groups get to end after Ctrl-C before they're killed.
+    const GRACE: Duration = Duration::from_secs(1);
+
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    fn signal_groups(signal: libc::c_int) {
+        for pid in groups().iter().filter_map(|&pid| libc::pid_t::try_from(pid).ok()) {
+            // SAFETY: sending a signal has no memory safety requirements
+            _ = unsafe { libc::kill(-pid, signal) };
+        }
+    }
+
+    /// Handles `SIGINT` by sending it to the tracked process groups from a thread, since a signal handler can do little
+    /// more than set a flag.
+    pub fn forward() {
+        #[expect(clippy::fn_to_numeric_cast_any, reason = "libc takes signal handlers as addresses")]
+        let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        if unsafe { libc::signal(libc::SIGINT, handler) } == libc::SIG_ERR {
+            return;
+        }
+
+        _ = thread::spawn(|| {
+            while !INTERRUPTED.load(Ordering::SeqCst) {
+                thread::sleep(POLL);
+            }
+
+            signal_groups(libc::SIGINT);
+
+            // processes which ignore Ctrl-C, such as those started in the background by a shell script, are killed
+            thread::sleep(GRACE);
+            signal_groups(libc::SIGKILL);
+
+            // SAFETY: restoring the default disposition has no memory safety requirements
+            _ = unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+
+            // SAFETY: raising a signal has no memory safety requirements
+            _ = unsafe { libc::raise(libc::SIGINT) };
+        });
+    }
+}
+
+/// Kills a child along with the processes it started, such as the subprocesses of a test harness.
+///
+/// On Unix, this kills the child's process group, which only holds the processes it started when the child was made to
+/// lead a group of its own with [`set_process_group`]. On Windows, this kills the child's process tree. Either way, the
+/// child alone is killed when that fails.
+pub fn kill_group(child: &mut Child) -> io::Result<()> {
+    let pid = child.id().to_string();
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("taskkill");
+        _ = cmd.args(["/T", "/F", "/PID", &pid]);
+        cmd
+    } else {
+        let mut cmd = Command::new("kill");
+        _ = cmd.args(["-KILL", "--", &format!("-{pid}")]);
+        cmd
+    };
+
+    _ = cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    match cmd.status() {
+        Ok(status) if status.success() => Ok(()),
+        _ => child.kill(),
+    }
+}
+
+/// Rebuilds a command so its output goes to a pseudo-terminal, through the `script` utility, for programs which only
+/// show colors or progress when writing to a terminal. Everything the program writes, including to its standard
+/// error, then comes out on the standard output of the rebuilt command, which is piped.
+///
+/// The rebuilt command keeps the working directory of the command and the variables set or removed for it. Returns
+/// `None` on platforms without `script`, such as Windows.
+pub fn under_pty(cmd: &Command) -> Option<Command> {
+    let mut wrapped = Command::new("script");
+    if cfg!(target_os = "linux") {
+        // util-linux's script runs a command line through the shell, and only reports its status when asked to
+        let words: Vec<String> = core::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|word| posix_quote(&word.to_string_lossy()))
+            .collect();
+        _ = wrapped
+            .args(["--quiet", "--return", "--command"])
+            .arg(words.join(" "))
+            .arg("/dev/null");
+    } else if cfg!(unix) {
+        _ = wrapped.args(["-q", "/dev/null"]).arg(cmd.get_program()).args(cmd.get_args());
+    } else {
+        return None;
+    }
+
+    if let Some(dir) = cmd.get_current_dir() {
+        _ = wrapped.current_dir(dir);
+    }
+
+    for (name, value) in cmd.get_envs() {
+        match value {
+            Some(value) => _ = wrapped.env(name, value),
+            None => _ = wrapped.env_remove(name),
+        }
+    }
+
+    _ = wrapped.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    Some(wrapped)
+}
+
+fn posix_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | ':' | '=' | '@' | ','));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
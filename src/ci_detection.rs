@@ -0,0 +1,701 @@
+use crate::github_workflow::{PortableJob, StepCondition, Workflow};
+use crate::host::Host;
+use crate::toml_text::{key, string};
+use anyhow::{Context, anyhow};
+use core::fmt::Write;
+use core::iter;
+use core::str::Lines;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The top-level keys of a GitLab pipeline which configure the pipeline rather than define a job.
+const GITLAB_KEYWORDS: [&str; 10] = [
+    "default",
+    "include",
+    "stages",
+    "variables",
+    "workflow",
+    "image",
+    "services",
+    "cache",
+    "before_script",
+    "after_script",
+];
+
+/// The stages of a GitLab pipeline which doesn't declare its own.
+const GITLAB_DEFAULT_STAGES: [&str; 3] = ["build", "test", "deploy"];
+
+/// The keys of GitLab jobs which have no `cargo-ci` equivalent.
+const GITLAB_UNTRANSLATED_KEYS: [&str; 12] = [
+    "after_script",
+    "artifacts",
+    "cache",
+    "environment",
+    "extends",
+    "except",
+    "image",
+    "only",
+    "parallel",
+    "rules",
+    "services",
+    "variables",
+];
+
+/// Jenkins steps with no `cargo-ci` equivalent, which are worth pointing out when a stage uses them.
+const JENKINS_UNTRANSLATED_STEPS: [&str; 14] = [
+    "archiveArtifacts",
+    "build",
+    "docker",
+    "emailext",
+    "input",
+    "junit",
+    "powershell",
+    "publishHTML",
+    "pwsh",
+    "recordIssues",
+    "slackSend",
+    "stash",
+    "unstash",
+    "withCredentials",
+];
+
+/// A configuration mirroring the pipelines of the CI providers a repository already uses, as proposed by
+/// `cargo ci init --detect`.
+///
+/// The translation is best-effort: whatever has no equivalent is left out and annotated with a `NOTE` comment, so the
+/// proposal is a starting point to review rather than a faithful copy.
+#[derive(Debug, Default)]
+pub struct Proposal {
+    /// The files the pipelines were read from, relative to the repository root.
+    sources: Vec<String>,
+
+    stages: Vec<String>,
+    jobs: Vec<ProposedJob>,
+
+    /// Annotations about the pipelines as a whole.
+    notes: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct ProposedJob {
+    id: String,
+    name: Option<String>,
+    stage: Option<String>,
+    needs: Vec<String>,
+    os: Vec<&'static str>,
+    continue_on_error: bool,
+    steps: Vec<ProposedStep>,
+
+    /// Annotations about what of the job wasn't translated.
+    notes: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct ProposedStep {
+    name: Option<String>,
+    command: String,
+    working_directory: Option<String>,
+    os: Vec<&'static str>,
+}
+
+impl Proposal {
+    /// Whether no pipeline was found to translate.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Renders the proposal as the text of a configuration file.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "# Proposed by `cargo ci init --detect` from {}.\n\
+             # The translation is best-effort, review it along with the notes about what wasn't translated.\n",
+            self.sources.join(", ")
+        );
+
+        for note in &self.notes {
+            _ = writeln!(out, "# NOTE: {note}");
+        }
+
+        if !self.stages.is_empty() {
+            _ = writeln!(out, "\nstages = {}", string_array(&self.stages));
+        }
+
+        for job in &self.jobs {
+            _ = writeln!(out, "\n[jobs.{}]", key(&job.id));
+            for note in &job.notes {
+                _ = writeln!(out, "# NOTE: {note}");
+            }
+
+            if let Some(name) = &job.name {
+                _ = writeln!(out, "name = {}", string(name));
+            }
+
+            if let Some(stage) = &job.stage {
+                _ = writeln!(out, "stage = {}", string(stage));
+            }
+
+            if !job.needs.is_empty() {
+                _ = writeln!(out, "needs = {}", string_array(&job.needs));
+            }
+
+            if !job.os.is_empty() {
+                _ = writeln!(out, "os = {}", string_array(&job.os));
+            }
+
+            if job.continue_on_error {
+                out.push_str("continue_on_error = true\n");
+            }
+
+            out.push_str("steps = [\n");
+            for step in &job.steps {
+                _ = writeln!(out, "    {},", render_step(step));
+            }
+            out.push_str("]\n");
+        }
+
+        out
+    }
+}
+
+fn render_step(step: &ProposedStep) -> String {
+    let mut fields = Vec::new();
+    if let Some(name) = step.name.as_ref().filter(|name| **name != step.command) {
+        fields.push(format!("name = {}", string(name)));
+    }
+
+    if let Some(dir) = &step.working_directory {
+        fields.push(format!("working_directory = {}", string(dir)));
+    }
+
+    if !step.os.is_empty() {
+        fields.push(format!("os = {}", string_array(&step.os)));
+    }
+
+    if fields.is_empty() {
+        string(&step.command)
+    } else {
+        format!("{{ command = {}, {} }}", string(&step.command), fields.join(", "))
+    }
+}
+
+fn string_array(values: &[impl AsRef<str>]) -> String {
+    format!("[{}]", values.iter().map(|v| string(v.as_ref())).collect::<Vec<_>>().join(", "))
+}
+
+/// Looks for the pipelines of GitHub Actions, GitLab CI, and Jenkins in the closest directory at or above the
+/// workspace root which has any, and proposes a configuration mirroring them.
+pub fn detect<H: Host>(host: &H, workspace_root: &Path) -> anyhow::Result<Proposal> {
+    let mut proposal = Proposal::default();
+    let Some(repo_root) = workspace_root.ancestors().find(|dir| {
+        dir.join(".github").join("workflows").is_dir() || dir.join(".gitlab-ci.yml").is_file() || dir.join("Jenkinsfile").is_file()
+    }) else {
+        return Ok(proposal);
+    };
+
+    let workflows_dir = repo_root.join(".github").join("workflows");
+    if workflows_dir.is_dir() {
+        for file in workflow_files(&workflows_dir)? {
+            let text = host
+                .read_to_string(&file)
+                .with_context(|| format!("unable to read workflow {}", file.display()))?;
+            let jobs = Workflow::parse(&text)
+                .and_then(|workflow| workflow.portable_jobs())
+                .with_context(|| format!("invalid workflow {}", file.display()))?;
+
+            let source = relative(repo_root, &file);
+            add_github_jobs(&mut proposal, &source, jobs);
+            proposal.sources.push(source);
+        }
+    }
+
+    let gitlab_file = repo_root.join(".gitlab-ci.yml");
+    if host.is_file(&gitlab_file) {
+        let text = host
+            .read_to_string(&gitlab_file)
+            .with_context(|| format!("unable to read {}", gitlab_file.display()))?;
+        add_gitlab_jobs(&mut proposal, &text).with_context(|| format!("invalid pipeline {}", gitlab_file.display()))?;
+        proposal.sources.push(".gitlab-ci.yml".to_string());
+    }
+
+    let jenkins_file = repo_root.join("Jenkinsfile");
+    if host.is_file(&jenkins_file) {
+        let text = host
+            .read_to_string(&jenkins_file)
+            .with_context(|| format!("unable to read {}", jenkins_file.display()))?;
+        add_jenkins_jobs(&mut proposal, &text);
+        proposal.sources.push("Jenkinsfile".to_string());
+    }
+
+    if repo_root != workspace_root {
+        proposal.notes.push(format!(
+            "the pipelines run from {}, while steps run from the workspace root, {} below it",
+            repo_root.display(),
+            relative(repo_root, workspace_root)
+        ));
+    }
+
+    Ok(proposal)
+}
+
+fn workflow_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("unable to list the files in {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "yml" || ext == "yaml") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+impl Proposal {
+    /// Picks an id for a job which no other job has, prefixing the preferred id with where the job comes from when it's
+    /// taken.
+    fn unique_id(&self, preferred: &str, origin: &str) -> String {
+        let taken = |id: &str| self.jobs.iter().any(|job| job.id == id);
+        if !taken(preferred) {
+            return preferred.to_string();
+        }
+
+        let mut id = format!("{origin}-{preferred}");
+        let mut suffix = 2;
+        while taken(&id) {
+            id = format!("{origin}-{preferred}-{suffix}");
+            suffix += 1;
+        }
+
+        id
+    }
+
+    /// Adds the jobs of a pipeline, whose needs are given with the ids the pipeline uses, translating those into the
+    /// ids the jobs end up with. Jobs without any step are left out, along with the needs on them.
+    fn add_jobs(&mut self, origin: &str, jobs: Vec<(String, ProposedJob)>) {
+        let mut ids = HashMap::new();
+        let mut added = Vec::new();
+        for (original, mut job) in jobs {
+            if job.steps.is_empty() {
+                let mut note = format!("{origin}: job '{original}' wasn't translated, it has no steps which could be");
+                for job_note in &job.notes {
+                    _ = write!(note, "\n#   - {job_note}");
+                }
+
+                self.notes.push(note);
+                continue;
+            }
+
+            job.id = self.unique_id(&slug(&original), origin);
+            _ = ids.insert(original, job.id.clone());
+            self.jobs.push(job);
+            added.push(self.jobs.len() - 1);
+        }
+
+        for index in added {
+            let job = &mut self.jobs[index];
+            let mut needs = Vec::new();
+            for need in job.needs.drain(..) {
+                match ids.get(&need) {
+                    Some(id) => needs.push(id.clone()),
+                    None => job.notes.push(format!("needed '{need}', which wasn't translated")),
+                }
+            }
+
+            job.needs = needs;
+        }
+    }
+}
+
+/// Turns a name into a job id made of lowercase letters, digits, dashes, and underscores.
+fn slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() { "job".to_string() } else { slug.to_string() }
+}
+
+/// Splits a script into the commands it's made of, one per line, when every line is a command of its own. Scripts with
+/// continued lines or control structures are kept whole, and so are scripts with lines changing the directory or the
+/// environment of the lines after them, since every step runs in a shell of its own.
+fn script_commands(script: &str) -> Vec<String> {
+    let lines: Vec<&str> = script
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    let compound = lines.iter().any(|line| {
+        let first = line.split_whitespace().next().unwrap_or_default();
+        line.ends_with('\\')
+            || line.ends_with('|')
+            || line.ends_with("&&")
+            || line.ends_with("||")
+            || line.contains("<<")
+            || matches!(
+                first,
+                "if" | "then"
+                    | "else"
+                    | "elif"
+                    | "fi"
+                    | "for"
+                    | "while"
+                    | "until"
+                    | "do"
+                    | "done"
+                    | "case"
+                    | "esac"
+                    | "function"
+                    | "{"
+                    | "}"
+            )
+            || changes_shell_state(first)
+    });
+
+    if compound {
+        vec![script.trim().to_string()]
+    } else {
+        lines.into_iter().map(ToString::to_string).collect()
+    }
+}
+
+/// Whether a line starting with the given word changes the directory or the environment of the lines after it, such as
+/// `cd`, `export`, `source`, or a variable assignment.
+fn changes_shell_state(first: &str) -> bool {
+    let assignment = first
+        .split_once('=')
+        .is_some_and(|(name, _)| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+
+    assignment
+        || matches!(
+            first,
+            "cd" | "pushd" | "popd" | "export" | "unset" | "source" | "." | "set" | "shopt" | "alias" | "umask" | "ulimit"
+        )
+}
+
+/// The operating systems named by the labels of runners, which is none when any label doesn't name one.
+fn runner_os(labels: &[String]) -> Vec<&'static str> {
+    let mut os = Vec::new();
+    for label in labels {
+        let label = label.to_ascii_lowercase();
+        let named = if label.contains("windows") {
+            "windows"
+        } else if label.contains("macos") {
+            "macos"
+        } else if label.contains("ubuntu") || label.contains("linux") {
+            "linux"
+        } else {
+            return Vec::new();
+        };
+
+        if !os.contains(&named) {
+            os.push(named);
+        }
+    }
+
+    os
+}
+
+fn add_github_jobs(proposal: &mut Proposal, source: &str, jobs: Vec<PortableJob>) {
+    let origin = Path::new(source)
+        .file_stem()
+        .map_or_else(|| "github".to_string(), |stem| slug(&stem.to_string_lossy()));
+
+    let jobs = jobs
+        .into_iter()
+        .map(|job| {
+            let mut proposed = ProposedJob {
+                name: (job.name != job.id).then(|| job.name.clone()),
+                needs: job.needs,
+                os: runner_os(&job.runners),
+                ..ProposedJob::default()
+            };
+
+            if let Some(workflow) = &job.reusable_workflow {
+                proposed.notes.push(format!("calls the reusable workflow '{workflow}'"));
+            }
+
+            if let Some(condition) = &job.condition {
+                proposed.notes.push(format!("its condition, `{condition}`, wasn't translated"));
+            }
+
+            if !job.env.is_empty() {
+                proposed.notes.push(format!(
+                    "sets the environment variable(s) {}, which aren't set for its steps",
+                    job.env.join(", ")
+                ));
+            }
+
+            for step in job.steps {
+                let script = match step.script {
+                    Ok(Some(script)) => script,
+                    Ok(None) => continue,
+                    Err(reason) => {
+                        proposed.notes.push(format!("step '{}' wasn't translated, it {reason}", step.label));
+                        continue;
+                    }
+                };
+
+                let os = match step.shell.as_deref() {
+                    None | Some("bash" | "sh") => Vec::new(),
+                    Some("cmd") => vec!["windows"],
+                    Some(shell) => {
+                        proposed
+                            .notes
+                            .push(format!("step '{}' wasn't translated, it runs through '{shell}'", step.label));
+                        continue;
+                    }
+                };
+
+                match step.condition {
+                    StepCondition::Success => {}
+                    StepCondition::Always => proposed.notes.push(format!(
+                        "step '{}' ran even when earlier steps failed, but now only runs when they succeed",
+                        step.label
+                    )),
+                    StepCondition::Failure | StepCondition::Unknown => {
+                        proposed
+                            .notes
+                            .push(format!("step '{}' wasn't translated, its condition can't be", step.label));
+                        continue;
+                    }
+                }
+
+                let commands = script_commands(&script);
+                let named = commands.len() == 1;
+                proposed.steps.extend(commands.into_iter().map(|command| ProposedStep {
+                    name: step.name.clone().filter(|_| named),
+                    command,
+                    working_directory: step.working_directory.clone(),
+                    os: os.clone(),
+                }));
+            }
+
+            (job.id, proposed)
+        })
+        .collect();
+
+    proposal.add_jobs(&origin, jobs);
+}
+
+fn add_gitlab_jobs(proposal: &mut Proposal, text: &str) -> anyhow::Result<()> {
+    let pipeline: Mapping = serde_yaml::from_str(text).context("invalid pipeline")?;
+    let default = pipeline.get("default").cloned().unwrap_or(Value::Null);
+    let default_before_script = default.get("before_script").or_else(|| pipeline.get("before_script"));
+
+    let mut jobs = Vec::new();
+    let mut used_stages = Vec::new();
+    for (id, job) in &pipeline {
+        let Some(id) = id.as_str() else {
+            continue;
+        };
+
+        if GITLAB_KEYWORDS.contains(&id) || id.starts_with('.') || !job.is_mapping() {
+            continue;
+        }
+
+        let stage = job.get("stage").and_then(Value::as_str).unwrap_or("test").to_string();
+        if !used_stages.contains(&stage) {
+            used_stages.push(stage.clone());
+        }
+
+        let mut proposed = ProposedJob {
+            stage: Some(stage),
+            needs: job
+                .get("needs")
+                .and_then(Value::as_sequence)
+                .into_iter()
+                .flatten()
+                .filter_map(|need| need.as_str().or_else(|| need.get("job").and_then(Value::as_str)))
+                .map(ToString::to_string)
+                .collect(),
+            continue_on_error: job.get("allow_failure").and_then(Value::as_bool).unwrap_or(false),
+            ..ProposedJob::default()
+        };
+
+        if job.get("trigger").is_some() {
+            proposed.notes.push("triggers another pipeline".to_string());
+        }
+
+        if let Some(when) = job.get("when").and_then(Value::as_str).filter(|when| *when != "on_success") {
+            proposed
+                .notes
+                .push(format!("runs `when: {when}`, but now runs whenever the jobs before it succeed"));
+        }
+
+        let untranslated: Vec<_> = GITLAB_UNTRANSLATED_KEYS.iter().filter(|key| job.get(**key).is_some()).collect();
+        if !untranslated.is_empty() {
+            let keys: Vec<_> = untranslated.iter().map(|key| format!("`{key}`")).collect();
+            proposed.notes.push(format!("its {} weren't translated", keys.join(", ")));
+        }
+
+        let before_script = job.get("before_script").or(default_before_script);
+        for script in [before_script, job.get("script")].into_iter().flatten() {
+            let lines = match script {
+                Value::Sequence(lines) => lines.iter().filter_map(Value::as_str).collect(),
+                Value::String(line) => vec![line.as_str()],
+                _ => Vec::new(),
+            };
+
+            proposed
+                .steps
+                .extend(lines.into_iter().flat_map(script_commands).map(|command| ProposedStep {
+                    command,
+                    ..ProposedStep::default()
+                }));
+        }
+
+        jobs.push((id.to_string(), proposed));
+    }
+
+    let declared: Vec<String> = pipeline.get("stages").and_then(Value::as_sequence).map_or_else(
+        || GITLAB_DEFAULT_STAGES.iter().map(ToString::to_string).collect(),
+        |stages| stages.iter().filter_map(Value::as_str).map(ToString::to_string).collect(),
+    );
+
+    let ordered = iter::once(".pre".to_string()).chain(declared).chain([".post".to_string()]);
+    for stage in ordered.filter(|stage| used_stages.contains(stage)) {
+        if proposal.stages.contains(&stage) {
+            continue;
+        }
+
+        proposal.stages.push(stage);
+    }
+
+    if let Some(unknown) = used_stages.iter().find(|stage| !proposal.stages.contains(stage)) {
+        return Err(anyhow!("a job uses the stage '{unknown}', which isn't declared"));
+    }
+
+    proposal.add_jobs("gitlab", jobs);
+    Ok(())
+}
+
+fn add_jenkins_jobs(proposal: &mut Proposal, text: &str) {
+    let mut jobs: Vec<(String, ProposedJob)> = Vec::new();
+    let mut depth = 0_usize;
+    let mut dirs: Vec<(String, usize)> = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        let words = trimmed
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .next()
+            .unwrap_or_default();
+
+        if words == "stage"
+            && let Some(name) = quoted(trimmed)
+        {
+            let needs = jobs.last().map(|(id, _)| id.clone()).into_iter().collect();
+            jobs.push((
+                name.clone(),
+                ProposedJob {
+                    name: Some(name),
+                    needs,
+                    ..ProposedJob::default()
+                },
+            ));
+        } else if let Some((_, job)) = jobs.last_mut() {
+            match words {
+                "sh" | "bat" => {
+                    let Some(script) = jenkins_script(trimmed, &mut lines) else {
+                        job.notes.push(format!("`{trimmed}` wasn't translated"));
+                        continue;
+                    };
+
+                    if script.contains("${") && trimmed.contains('"') {
+                        job.notes
+                            .push("its scripts use Groovy interpolation, which wasn't translated".to_string());
+                    }
+
+                    let os = if words == "bat" { vec!["windows"] } else { Vec::new() };
+                    let working_directory = dirs.last().map(|(dir, _)| dir.clone());
+                    job.steps.extend(script_commands(&script).into_iter().map(|command| ProposedStep {
+                        command,
+                        working_directory: working_directory.clone(),
+                        os: os.clone(),
+                        ..ProposedStep::default()
+                    }));
+                }
+                "dir" => {
+                    if let Some(dir) = quoted(trimmed) {
+                        dirs.push((dir, depth + 1));
+                    }
+                }
+                "when" => job.notes.push("its `when` condition wasn't translated".to_string()),
+                "environment" => job.notes.push("its `environment` wasn't translated".to_string()),
+                "parallel" => proposal
+                    .notes
+                    .push("Jenkinsfile: stages running in parallel now run one after the other".to_string()),
+                _ if JENKINS_UNTRANSLATED_STEPS.contains(&words) => job.notes.push(format!("its `{words}` step wasn't translated")),
+                _ => {}
+            }
+        }
+
+        depth = (depth + trimmed.matches('{').count()).saturating_sub(trimmed.matches('}').count());
+        while dirs.last().is_some_and(|(_, dir_depth)| depth < *dir_depth) {
+            _ = dirs.pop();
+        }
+    }
+
+    for (_, job) in &mut jobs {
+        job.notes.dedup();
+    }
+
+    if jobs.is_empty() {
+        proposal
+            .notes
+            .push("Jenkinsfile: no stages were found, only declarative pipelines are translated".to_string());
+    }
+
+    proposal.add_jobs("jenkins", jobs);
+}
+
+/// The first single- or double-quoted string in a line.
+fn quoted(line: &str) -> Option<String> {
+    let quote = line.chars().find(|c| matches!(c, '\'' | '"'))?;
+    let (_, rest) = line.split_once(quote)?;
+    rest.split_once(quote).map(|(text, _)| text.to_string())
+}
+
+/// The script of a `sh` or `bat` step, reading further lines for scripts in triple quotes.
+fn jenkins_script(line: &str, lines: &mut Lines<'_>) -> Option<String> {
+    for delimiter in ["'''", "\"\"\""] {
+        if let Some((_, rest)) = line.split_once(delimiter) {
+            if let Some((script, _)) = rest.split_once(delimiter) {
+                return Some(script.to_string());
+            }
+
+            let mut script = rest.to_string();
+            for line in lines.by_ref() {
+                if let Some((last, _)) = line.split_once(delimiter) {
+                    script.push('\n');
+                    script.push_str(last);
+                    return Some(script);
+                }
+
+                script.push('\n');
+                script.push_str(line);
+            }
+
+            return None;
+        }
+    }
+
+    quoted(line)
+}
@@ -0,0 +1,106 @@
+use crate::diff::{diff_lines, render_diff};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The most diff lines reported for a single file.
+const MAX_DIFF_LINES: usize = 50;
+
+/// A difference between freshly generated files and the committed ones.
+#[derive(Debug)]
+pub enum Mismatch {
+    /// The generator produced a file which isn't committed.
+    Missing(PathBuf),
+
+    /// A committed file is no longer produced by the generator.
+    Stale(PathBuf),
+
+    /// A committed file differs from the generator's output, along with the lines which changed.
+    Changed(PathBuf, Vec<String>),
+}
+
+impl Mismatch {
+    /// Describes the mismatch as a list of lines, starting with a summary line.
+    pub fn describe(&self) -> Vec<String> {
+        match self {
+            Self::Missing(path) => vec![format!("'{}' is generated, but isn't committed", path.display())],
+            Self::Stale(path) => vec![format!("'{}' is committed, but is no longer generated", path.display())],
+            Self::Changed(path, diff) => {
+                let mut lines = vec![format!("'{}' differs from the generated version", path.display())];
+                lines.extend(diff.iter().map(|line| format!("    {line}")));
+                lines
+            }
+        }
+    }
+}
+
+/// Compares the files produced by a generator against the committed ones, returning every difference.
+///
+/// Diffs are from the committed file to the generated one, so `+` lines are what regenerating would add.
+pub fn compare(generated_dir: &Path, committed_dir: &Path) -> io::Result<Vec<Mismatch>> {
+    let generated = list_files(generated_dir)?;
+    let committed = if committed_dir.exists() {
+        list_files(committed_dir)?
+    } else {
+        BTreeMap::new()
+    };
+
+    let mut mismatches = Vec::new();
+    for (relative, generated_path) in &generated {
+        let Some(committed_path) = committed.get(relative) else {
+            mismatches.push(Mismatch::Missing(committed_dir.join(relative)));
+            continue;
+        };
+
+        let new = fs::read(generated_path)?;
+        let old = fs::read(committed_path)?;
+        if new == old {
+            continue;
+        }
+
+        let diff = match (String::from_utf8(old), String::from_utf8(new)) {
+            (Ok(old), Ok(new)) => {
+                let mut diff = render_diff(&diff_lines(&old, &new), true);
+                if diff.len() > MAX_DIFF_LINES {
+                    let omitted = diff.len() - MAX_DIFF_LINES;
+                    diff.truncate(MAX_DIFF_LINES);
+                    diff.push(format!("... {omitted} more changed line(s)"));
+                }
+
+                diff
+            }
+
+            _ => vec!["binary contents differ".to_string()],
+        };
+
+        mismatches.push(Mismatch::Changed(committed_path.clone(), diff));
+    }
+
+    for (relative, committed_path) in &committed {
+        if !generated.contains_key(relative) {
+            mismatches.push(Mismatch::Stale(committed_path.clone()));
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Lists all the files under a directory, keyed by their path relative to it.
+fn list_files(root: &Path) -> io::Result<BTreeMap<PathBuf, PathBuf>> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                _ = files.insert(relative.to_path_buf(), path.clone());
+            }
+        }
+    }
+
+    Ok(files)
+}
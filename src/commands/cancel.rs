@@ -0,0 +1,31 @@
+use crate::cancellation;
+use crate::config::Config;
+use crate::exit_codes::Failure;
+use crate::host::Host;
+use anyhow::anyhow;
+use cargo_metadata::Metadata;
+use clap::Parser;
+
+#[derive(Parser, Debug, Clone)]
+pub struct CancelArgs {
+    /// The id of the run, as printed when it starts
+    #[arg(value_name = "RUN_ID")]
+    run_id: String,
+
+    /// The job to cancel
+    #[arg(value_name = "JOB")]
+    job: String,
+}
+
+/// Asks a run in progress in another terminal to cancel one of its jobs, letting the rest of the run continue.
+pub fn cancel<H: Host>(args: &CancelArgs, host: &H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<()> {
+    if !cfg.jobs().iter().any(|(job_id, _)| job_id.as_str() == args.job) {
+        return Err(Failure::UnknownJob.tag(anyhow!("job '{}' is not defined in the [jobs] section", args.job)));
+    }
+
+    let run_dir = cancellation::run_dir(metadata.target_directory.as_std_path(), &args.run_id);
+    cancellation::request(&run_dir, &args.run_id, &args.job)?;
+
+    host.println(format!("Asked run '{}' to cancel job '{}'.", args.run_id, args.job));
+    Ok(())
+}
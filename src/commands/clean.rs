@@ -0,0 +1,86 @@
+use crate::commands::run::lock_target_dir;
+use crate::config::ByteSize;
+use crate::host::Host;
+use crate::long_paths;
+use crate::target_dir;
+use anyhow::{Context, anyhow};
+use cargo_metadata::Metadata;
+use clap::ArgAction;
+use clap::Parser;
+use std::fs;
+
+#[derive(Parser, Debug, Clone)]
+pub struct CleanArgs {
+    /// Remove the build artifacts of packages which are neither in the workspace nor among its dependencies anymore
+    #[arg(long, action = ArgAction::SetTrue)]
+    target_gc: bool,
+
+    /// List what would be removed without removing anything
+    #[arg(short = 'n', long, action = ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Fail right away if a run is in progress in this workspace, instead of waiting for it to finish
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_wait: bool,
+}
+
+/// Cleans up the target directory, given metadata which includes the workspace's dependencies.
+pub fn clean<H: Host>(args: &CleanArgs, host: &H, metadata: &Metadata) -> anyhow::Result<()> {
+    if !args.target_gc {
+        return Err(anyhow!(
+            "nothing to clean, use --target-gc to remove the artifacts of packages no longer in use"
+        ));
+    }
+
+    let target_dir = metadata.target_directory.as_std_path();
+    if !target_dir.is_dir() {
+        host.println("There is no target directory to clean.");
+        return Ok(());
+    }
+
+    // runs in progress may be building the very artifacts found stale, if the workspace changed since they started
+    let _lock = if args.dry_run {
+        None
+    } else {
+        Some(lock_target_dir(host, target_dir, args.no_wait)?)
+    };
+
+    let stale = target_dir::stale_artifacts(target_dir, metadata)?;
+    if stale.is_empty() {
+        host.println("No artifacts of packages which are no longer in use were found.");
+        return Ok(());
+    }
+
+    let mut freed = 0;
+    for path in &stale {
+        let size = if path.is_dir() {
+            target_dir::size(path)?
+        } else {
+            fs::metadata(long_paths::extended(path)?)?.len()
+        };
+
+        let shown = path.strip_prefix(target_dir).unwrap_or(path);
+        if args.dry_run {
+            host.println(format!("Would remove {} ({})", shown.display(), ByteSize::new(size)));
+        } else {
+            host.println(format!("Removing {} ({})", shown.display(), ByteSize::new(size)));
+            let extended = long_paths::extended(path)?;
+            if path.is_dir() {
+                fs::remove_dir_all(extended)
+            } else {
+                fs::remove_file(extended)
+            }
+            .with_context(|| format!("unable to remove {}", path.display()))?;
+        }
+
+        freed += size;
+    }
+
+    if args.dry_run {
+        host.println(format!("{} artifact(s) would free {}", stale.len(), ByteSize::new(freed)));
+    } else {
+        host.println(format!("Removed {} artifact(s), freeing {}", stale.len(), ByteSize::new(freed)));
+    }
+
+    Ok(())
+}
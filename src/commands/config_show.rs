@@ -0,0 +1,171 @@
+use crate::commands::run::passthrough_env_vars;
+use crate::config::EffectiveConfig;
+use crate::host::Host;
+use crate::pkg_data::{is_trusted, variables, variables_from};
+use crate::toml_text::{key, string};
+use cargo_metadata::{Metadata, Package};
+use clap::{Parser, Subcommand, ValueEnum};
+use core::fmt::Write;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+
+#[derive(Parser, Debug, Clone)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ConfigCommands {
+    /// Prints the configuration after merging overlays and platform-specific settings, noting where each entry came from.
+    Show(ShowArgs),
+}
+
+#[derive(Parser, Debug, Clone)]
+struct ShowArgs {
+    /// Output format.
+    #[arg(long, value_name = "FORMAT", default_value_t = Format::Toml, value_enum)]
+    format: Format,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Toml,
+    Json,
+}
+
+pub fn config<H: Host>(args: &ConfigArgs, host: &H, effective: &EffectiveConfig, metadata: &Metadata) -> anyhow::Result<()> {
+    let ConfigCommands::Show(show_args) = &args.command;
+
+    let packages: Vec<_> = metadata
+        .workspace_packages()
+        .into_iter()
+        .filter(|pkg| variables(pkg).next().is_some() || variables_from(pkg).is_some())
+        .collect();
+
+    // when nothing is listed, every environment variable is imported, and there's no point in dumping them all
+    let environment = (!effective.config().passthrough_env_variables().is_empty())
+        .then(|| passthrough_env_vars(host, effective.config()))
+        .map(|vars| vars.into_iter().collect::<BTreeMap<_, _>>());
+
+    let text = match show_args.format {
+        Format::Toml => render_toml(effective, metadata, &packages, environment.as_ref()),
+        Format::Json => serde_json::to_string_pretty(&json!({
+            "overlays": effective.overlays(),
+            "config": effective.document(),
+            "provenance": effective.provenance(),
+            "packages": packages
+                .iter()
+                .map(|pkg| {
+                    let vars: BTreeMap<_, _> = variables(pkg).collect();
+                    let trusted = is_trusted(pkg, metadata.workspace_root.as_std_path(), effective.config().trusted_packages());
+                    (
+                        pkg.name.to_string(),
+                        json!({ "variables": vars, "variables_from": variables_from(pkg), "trusted": trusted }),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>(),
+            "environment": environment,
+        }))?,
+    };
+
+    host.println(text.trim_end());
+    Ok(())
+}
+
+fn render_toml(
+    effective: &EffectiveConfig,
+    metadata: &Metadata,
+    packages: &[&Package],
+    environment: Option<&BTreeMap<String, String>>,
+) -> String {
+    let mut out = String::new();
+    out.push_str("# Effective cargo-ci configuration\n");
+    if !effective.overlays().is_empty() {
+        _ = writeln!(out, "# Overlays applied: {}", effective.overlays().join(", "));
+    }
+
+    render_table(&mut out, &[], effective.document(), effective.provenance());
+
+    if !packages.is_empty() {
+        out.push_str("\n# Package variables, from [package.metadata.ci] in each package's Cargo.toml:\n");
+        for pkg in packages {
+            if !is_trusted(pkg, metadata.workspace_root.as_std_path(), effective.config().trusted_packages()) {
+                _ = writeln!(out, "#   {}: ignored, since the package comes from outside the workspace", pkg.name);
+                continue;
+            }
+
+            for (name, value) in variables(pkg) {
+                _ = writeln!(out, "#   {}: {} = {}", pkg.name, key(name), string(value));
+            }
+
+            if let Some(command) = variables_from(pkg) {
+                _ = writeln!(out, "#   {}: more variables from the output of `{command}`", pkg.name);
+            }
+        }
+    }
+
+    match environment {
+        None => out.push_str("\n# Every environment variable is imported for expressions, since passthrough_env_variables is empty\n"),
+        Some(environment) => {
+            out.push_str("\n# Environment variables imported for expressions, per passthrough_env_variables:\n");
+            for (name, value) in environment {
+                _ = writeln!(out, "#   {} = {}", key(name), string(value));
+            }
+        }
+    }
+
+    out
+}
+
+/// Renders a table's plain values, followed by its sub-tables, each annotated with where it came from.
+fn render_table(out: &mut String, path: &[&str], table: &Map<String, Value>, provenance: &BTreeMap<String, String>) {
+    let source = |key: &str| {
+        let dotted = path.iter().copied().chain([key]).collect::<Vec<_>>().join(".");
+        provenance
+            .get(&dotted)
+            .map(|source| format!("  # from {source}"))
+            .unwrap_or_default()
+    };
+
+    // everything following a table header belongs to that table, so plain values must come first
+    for (name, value) in table.iter().filter(|(_, value)| !value.is_object() && !value.is_null()) {
+        _ = writeln!(out, "{} = {}{}", key(name), inline(value), source(name));
+    }
+
+    for (name, value) in table {
+        if let Value::Object(child) = value {
+            let child_path: Vec<_> = path.iter().copied().chain([name.as_str()]).collect();
+            let header = child_path.iter().map(|segment| key(segment)).collect::<Vec<_>>().join(".");
+            // tables holding nothing but other tables are implied by the headers of those tables
+            if child.is_empty() || child.values().any(|value| !value.is_object()) {
+                _ = writeln!(out, "\n[{header}]{}", source(name));
+            }
+            render_table(out, &child_path, child, provenance);
+        }
+    }
+}
+
+/// Renders a value on a single line.
+fn inline(value: &Value) -> String {
+    match value {
+        Value::Null => string(""),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => string(s),
+        Value::Array(items) => format!("[{}]", items.iter().map(inline).collect::<Vec<_>>().join(", ")),
+        Value::Object(table) => {
+            let entries: Vec<_> = table
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(name, value)| format!("{} = {}", key(name), inline(value)))
+                .collect();
+
+            if entries.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", entries.join(", "))
+            }
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use crate::color_modes::ColorModes;
+use crate::commands::run::{expression_env_vars, isolate_env, lower_priority, search_path, shell_command};
+use crate::config::{Config, shell_words};
+use crate::exit_codes::Failure;
+use crate::host::Host;
+use crate::log::Log;
+use crate::long_paths;
+use crate::messages::{Lang, Msg};
+use crate::outputter::Outputter;
+use crate::plan::{Plan, PlannedJob, PlannedStep};
+use crate::sharding::Shard;
+use crate::summary::{JobResult, JobStatus, PackageResult, RunSummary, StageResult, StepResult, StepStatus, print_summary};
+use anyhow::{Context, anyhow};
+use cargo_metadata::Metadata;
+use clap::Parser;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Parser, Debug, Clone)]
+pub struct ExecPlanArgs {
+    /// The plan file written by `cargo ci plan`.
+    #[arg(value_name = "PLAN")]
+    plan: PathBuf,
+
+    /// Only execute one slice of the plan's jobs, such as 1/3.
+    #[arg(long, value_name = "INDEX/COUNT")]
+    slice: Option<Shard>,
+
+    /// Send log output to the specified file.
+    #[arg(short = 'l', long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Number of log files to retain (default: 16).
+    #[arg(long, default_value_t = 16, value_name = "COUNT")]
+    log_file_retention_count: usize,
+
+    /// Colorize output.
+    #[arg(long, value_name = "WHEN", default_value_t = ColorModes::Auto, value_enum)]
+    color: ColorModes,
+
+    /// Language for step headings, captured output banners, statuses, and the summary; everything else is in English.
+    #[arg(long, value_name = "LANG", default_value_t = Lang::En, value_enum)]
+    lang: Lang,
+}
+
+/// Executes the jobs of a plan, or of one slice of it, in the order they appear in the plan.
+pub fn exec_plan<H: Host>(args: &ExecPlanArgs, host: &mut H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<()> {
+    let text = host
+        .read_to_string(&args.plan)
+        .with_context(|| format!("unable to read plan {}", args.plan.display()))?;
+    let plan: Plan = serde_json::from_str(&text).with_context(|| format!("invalid plan {}", args.plan.display()))?;
+
+    // jobs are dealt to slices round-robin, so every slice gets a similar share of a plan's jobs
+    let jobs: Vec<&PlannedJob> = plan
+        .jobs
+        .iter()
+        .enumerate()
+        .filter(|(position, _)| args.slice.is_none_or(|slice| slice.includes(*position)))
+        .map(|(_, job)| job)
+        .collect();
+
+    let started = host.local_time();
+    let log = Log::new(
+        metadata.target_directory.as_std_path(),
+        "exec-plan",
+        started,
+        args.log_file.as_deref(),
+        args.log_file_retention_count,
+    )?;
+
+    let run_id = format!("{}-{}", started.format("%Y%m%dT%H%M%S"), std::process::id());
+    let temp_dir = metadata.target_directory.as_std_path().join("ci-tmp").join(run_id);
+    fs::create_dir_all(long_paths::extended(&temp_dir)?)
+        .with_context(|| format!("unable to create temporary directory {}", temp_dir.display()))?;
+
+    // after this point, thia code takes care of error reporting itself
+    host.fail_silently();
+
+    let outputter = Outputter::new(&*host, &log, args.color, args.lang);
+    let workspace_root = metadata.workspace_root.as_std_path();
+
+    // steps get the variables of this machine's environment, platform, and repository, as they would in a run
+    let env_vars = expression_env_vars(&*host, cfg, metadata, None);
+    let start = host.now();
+
+    let mut outcome = Ok(());
+    let mut stage = StageResult::default();
+    for job in jobs {
+        if outcome.is_err() {
+            let mut steps = Vec::new();
+            for step in &job.steps {
+                note_step(&mut steps, step, StepStatus::NotRun);
+            }
+
+            stage.jobs.push(job_result(job, JobStatus::NotRun, Duration::ZERO, steps, None));
+            continue;
+        }
+
+        let job_start = host.now();
+        outputter.start_activity(&job.name);
+
+        let mut result = Ok(());
+        let mut steps = Vec::new();
+        for step in &job.steps {
+            let status = if result.is_ok() {
+                exec_step(host, cfg, &outputter, (workspace_root, &temp_dir), &env_vars, step).unwrap_or_else(|e| {
+                    result = Err(e);
+                    StepStatus::Failed
+                })
+            } else {
+                StepStatus::NotRun
+            };
+
+            note_step(&mut steps, step, status);
+        }
+
+        let status = match &result {
+            Ok(()) => {
+                outputter.complete_activity(Msg::RanSteps(job.steps.len()));
+                JobStatus::Succeeded
+            }
+            Err(_) if job.continue_on_error => {
+                outputter.complete_activity(Msg::Status(JobStatus::FailedIgnored));
+                JobStatus::FailedIgnored
+            }
+            Err(_) => {
+                outputter.complete_activity(Msg::Status(JobStatus::Failed));
+                JobStatus::Failed
+            }
+        };
+
+        let error = result.as_ref().err().map(|e| format!("{e:#}"));
+        stage
+            .jobs
+            .push(job_result(job, status, host.now().duration_since(job_start), steps, error));
+        if status == JobStatus::Failed {
+            outcome = result.map_err(|e| Failure::StepFailed.tag(e));
+        }
+    }
+
+    stage.duration = host.now().duration_since(start);
+    let summary = RunSummary {
+        stages: vec![stage],
+        warnings: Vec::new(),
+        tests: Vec::new(),
+        time_budget: None,
+    };
+
+    print_summary(&outputter, &summary, false);
+
+    if summary
+        .jobs()
+        .any(|job| matches!(job.status, JobStatus::Failed | JobStatus::FailedIgnored))
+    {
+        outputter.line(Msg::TempFilesKept(&temp_dir.display().to_string()));
+    } else if let Err(e) = long_paths::extended(&temp_dir).and_then(fs::remove_dir_all) {
+        outputter.line(format!("Unable to remove temporary directory {}: {e}", temp_dir.display()));
+    }
+
+    outcome
+}
+
+fn exec_step<H: Host>(
+    host: &H,
+    cfg: &Config,
+    outputter: &Outputter<H>,
+    (workspace_root, temp_dir): (&Path, &Path),
+    env_vars: &HashMap<String, String>,
+    step: &PlannedStep,
+) -> anyhow::Result<StepStatus> {
+    match &step.package {
+        Some(pkg) => outputter.message(Msg::StepForPackage(&step.name, pkg)),
+        None => outputter.message(Msg::Step(&step.name)),
+    }
+
+    // failures which will fall back to another command aren't fatal
+    let fatal = !step.continue_on_error;
+    let mut result = exec_command(
+        host,
+        cfg,
+        outputter,
+        (workspace_root, temp_dir, env_vars),
+        step,
+        None,
+        fatal && step.fallbacks.is_empty(),
+    );
+    for (index, fallback) in step.fallbacks.iter().enumerate() {
+        if result.is_ok() {
+            break;
+        }
+
+        outputter.message(format!("step '{}' failed, falling back to '{fallback}'", step.name));
+        let last = index + 1 == step.fallbacks.len();
+        result = exec_command(
+            host,
+            cfg,
+            outputter,
+            (workspace_root, temp_dir, env_vars),
+            step,
+            Some(fallback),
+            fatal && last,
+        );
+        if result.is_ok() {
+            outputter.message(format!("step '{}' succeeded with fallback '{fallback}'", step.name));
+        }
+    }
+
+    match result {
+        Ok(()) => Ok(StepStatus::Succeeded),
+        Err(_) if !fatal => Ok(StepStatus::FailedIgnored),
+        Err(e) => Err(e),
+    }
+}
+
+/// Runs the command of a planned step, or the given command line in its place, reporting any failure.
+fn exec_command<H: Host>(
+    host: &H,
+    cfg: &Config,
+    outputter: &Outputter<H>,
+    (workspace_root, temp_dir, env_vars): (&Path, &Path, &HashMap<String, String>),
+    step: &PlannedStep,
+    command: Option<&str>,
+    fatal: bool,
+) -> anyhow::Result<()> {
+    let target = step
+        .package
+        .as_ref()
+        .map_or_else(String::new, |pkg| format!(" for package '{pkg}'"));
+
+    let mut cmd = match (command, &step.cargo_alias) {
+        (Some(command), _) => shell_command(command),
+        (None, Some(alias)) => {
+            let mut c = Command::new("cargo");
+            _ = c.args(shell_words(alias));
+            c
+        }
+        (None, None) => shell_command(&step.command),
+    };
+
+    if step.background {
+        cmd = lower_priority(&cmd);
+    }
+
+    if step.isolated_env {
+        isolate_env(host, &mut cmd, &step.variables);
+    } else {
+        _ = cmd.envs(env_vars).envs(&step.variables);
+    }
+
+    _ = cmd.current_dir(workspace_root.join(&step.directory));
+    _ = cmd.env("CI_TEMP", temp_dir);
+    _ = cmd.stdout(Stdio::piped());
+    _ = cmd.stderr(Stdio::piped());
+
+    outputter.run_command(&cmd);
+
+    let started = search_path(host, workspace_root, &step.path).and_then(|path| {
+        if let Some(path) = path {
+            _ = cmd.env("PATH", path);
+        }
+
+        let directory = workspace_root.join(&step.directory);
+        if step.create_directory {
+            fs::create_dir_all(long_paths::extended(&directory)?).with_context(|| format!("unable to create {}", directory.display()))?;
+        }
+
+        long_paths::check_working_dir(&directory)?;
+        Ok(host.output(&mut cmd)?)
+    });
+
+    let output = match started {
+        Ok(output) => output,
+        Err(e) => {
+            outputter.command_error(format!("unable to start step: {e:#}"), None, None, fatal);
+            return Err(anyhow!("unable to start step '{}'{target}: {e:#}", step.name));
+        }
+    };
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    outputter.command_error(Msg::UnableToRunStep, Some(output.status), Some(&output), fatal);
+    let hints = outputter.report_hints(cfg.hints(), &output, fatal);
+    Err(anyhow!("unable to run step '{}'{target}: {}{hints}", step.name, output.status))
+}
+
+/// Notes how a planned execution of a step turned out, grouping the executions of a step for consecutive packages back
+/// into a single step.
+fn note_step(steps: &mut Vec<StepResult>, planned: &PlannedStep, status: StepStatus) {
+    let Some(package) = &planned.package else {
+        steps.push(StepResult {
+            name: planned.name.clone(),
+            status,
+            packages: Vec::new(),
+        });
+        return;
+    };
+
+    let mut step = match steps.pop() {
+        Some(step) if step.name == planned.name && !step.packages.is_empty() => step,
+        last => {
+            steps.extend(last);
+            StepResult::not_run(&planned.name)
+        }
+    };
+
+    // a step turns out as badly as it did for its worst package
+    let severity = |status| match status {
+        StepStatus::NotRun | StepStatus::Skipped => 0,
+        StepStatus::Succeeded => 1,
+        StepStatus::FailedIgnored => 2,
+        StepStatus::Failed => 3,
+    };
+
+    if severity(status) > severity(step.status) {
+        step.status = status;
+    }
+
+    step.packages.push(PackageResult::new(package.clone(), status));
+    steps.push(step);
+}
+
+fn job_result(job: &PlannedJob, status: JobStatus, duration: Duration, steps: Vec<StepResult>, error: Option<String>) -> JobResult {
+    JobResult {
+        id: job.id.clone(),
+        name: job.name.clone(),
+        status,
+        duration,
+        steps,
+        error,
+    }
+}
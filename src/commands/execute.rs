@@ -0,0 +1,46 @@
+use crate::commands::run::shell_command;
+use crate::executor::{PROTOCOL_VERSION, ReceivedRequest, Response};
+use crate::host::Host;
+use anyhow::{Context, anyhow};
+use clap::Parser;
+use std::process::Stdio;
+
+#[derive(Parser, Debug, Clone)]
+pub struct ExecuteArgs;
+
+/// Acts as the built-in local executor: reads a request of the executor protocol from the standard input, runs its
+/// command through the platform's shell from its directory with its variables, as steps without an executor run, and
+/// writes the response to the standard output.
+pub fn execute<H: Host>(_args: &ExecuteArgs, host: &H) -> anyhow::Result<()> {
+    let mut text = String::new();
+    loop {
+        let line = host.read_line().context("unable to read the request")?;
+        if line.is_empty() {
+            break;
+        }
+
+        text.push_str(&line);
+    }
+
+    let request: ReceivedRequest = serde_json::from_str(&text).context("unable to parse the request")?;
+    if request.protocol != PROTOCOL_VERSION {
+        return Err(anyhow!(
+            "the request uses version {} of the executor protocol, but only version {PROTOCOL_VERSION} is supported",
+            request.protocol
+        ));
+    }
+
+    let mut cmd = shell_command(&request.command);
+    _ = cmd
+        .current_dir(&request.directory)
+        .envs(&request.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let output = host
+        .output(&mut cmd)
+        .with_context(|| format!("unable to run '{}'", request.command))?;
+    host.println(serde_json::to_string(&Response::from(output))?);
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use crate::color_modes::ColorModes;
+use crate::commands::run::shell_command;
+use crate::config::{Config, JobId, Step};
+use crate::diff::{diff_lines, render_diff};
+use crate::exit_codes::Failure;
+use crate::git;
+use crate::host::Host;
+use crate::log::Log;
+use crate::messages::Lang;
+use crate::outputter::Outputter;
+use anyhow::{Context, anyhow};
+use cargo_metadata::Metadata;
+use cargo_metadata::camino::Utf8Path;
+use clap::ArgAction;
+use clap::Parser;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+#[derive(Parser, Debug, Clone)]
+pub struct FixArgs {
+    /// Names of the jobs whose fixable steps to run [default: all jobs]
+    jobs: Vec<String>,
+
+    /// Keep the changes without asking for confirmation
+    #[arg(short = 'y', long, action = ArgAction::SetTrue)]
+    yes: bool,
+
+    /// Send log output to the specified file.
+    #[arg(short = 'l', long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Number of log files to retain (default: 16).
+    #[arg(long, default_value_t = 16, value_name = "COUNT")]
+    log_file_retention_count: usize,
+
+    /// Colorize output.
+    #[arg(long, value_name = "WHEN", default_value_t = ColorModes::Auto, value_enum)]
+    color: ColorModes,
+
+    /// Language for step headings, captured output banners, statuses, and the summary; everything else is in English.
+    #[arg(long, value_name = "LANG", default_value_t = Lang::En, value_enum)]
+    lang: Lang,
+}
+
+/// Runs the fix commands of fixable steps, shows the resulting changes, and reverts them unless they are confirmed.
+pub fn fix<H: Host>(args: &FixArgs, host: &mut H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<()> {
+    let steps = select_steps(args, cfg)?;
+    if steps.is_empty() {
+        host.println("No fixable steps are defined for the selected jobs.");
+        return Ok(());
+    }
+
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let repo_root = git::toplevel(host, workspace_root)?;
+
+    // remember the content of files with uncommitted changes, so they can be restored exactly
+    let modified_before = git::modified_files(host, workspace_root)?;
+    let snapshots: BTreeMap<&String, Option<Vec<u8>>> = modified_before
+        .keys()
+        .map(|path| (path, fs::read(repo_root.join(path)).ok()))
+        .collect();
+
+    let log = Log::new(
+        metadata.target_directory.as_std_path(),
+        "fix",
+        host.local_time(),
+        args.log_file.as_deref(),
+        args.log_file_retention_count,
+    )?;
+
+    // after this point, thia code takes care of error reporting itself
+    host.fail_silently();
+
+    let outputter = Outputter::new(&*host, &log, args.color, args.lang);
+    let fixed = steps
+        .iter()
+        .try_for_each(|(job_id, step)| run_fix(&outputter, host, metadata, job_id, step));
+
+    // a fix which failed may have rewritten files halfway, which are put back as they were, along with what the fixes
+    // before it changed
+    if let Err(e) = fixed {
+        return match changes(host, workspace_root, &repo_root, &snapshots).and_then(|changes| revert(&repo_root, &changes)) {
+            Ok(count) => {
+                if count > 0 {
+                    outputter.line(format!("Reverted the changes to {count} file(s), since a fix failed."));
+                }
+                Err(e)
+            }
+            Err(revert_error) => Err(e.context(format!("unable to revert the changes made by the fixes: {revert_error:#}"))),
+        };
+    }
+
+    let changes = changes(host, workspace_root, &repo_root, &snapshots)?;
+    if changes.is_empty() {
+        outputter.line("No changes were needed.");
+        return Ok(());
+    }
+
+    for (path, original, current) in &changes {
+        let original = String::from_utf8_lossy(original.as_deref().unwrap_or_default());
+        let current = String::from_utf8_lossy(current.as_deref().unwrap_or_default());
+
+        outputter.line(format!("--- {path}"));
+        for line in render_diff(&diff_lines(&original, &current), true) {
+            outputter.line(line);
+        }
+    }
+
+    let keep = args.yes
+        || confirm(&outputter, host, changes.len()).or_else(|e| {
+            _ = revert(&repo_root, &changes)?;
+            Err(e)
+        })?;
+
+    if keep {
+        outputter.line(format!("Fixed {} file(s).", changes.len()));
+        return Ok(());
+    }
+
+    let count = revert(&repo_root, &changes)?;
+    outputter.line(format!("Reverted the changes to {count} file(s)."));
+    Ok(())
+}
+
+/// A file changed by the fixes, with its content before and after them, `None` when it didn't exist.
+type Change = (String, Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Finds the files the fixes changed, by comparing the files with uncommitted changes to their content before the fixes,
+/// or to their staged content for those which had no uncommitted changes then.
+fn changes(
+    host: &impl Host,
+    workspace_root: &Path,
+    repo_root: &Path,
+    snapshots: &BTreeMap<&String, Option<Vec<u8>>>,
+) -> anyhow::Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    for path in git::modified_files(host, workspace_root)?.into_keys() {
+        let original = match snapshots.get(&path) {
+            Some(snapshot) => snapshot.clone(),
+            None => Some(git::staged_content(host, workspace_root, &path)?),
+        };
+
+        let current = fs::read(repo_root.join(&path)).ok();
+        if current != original {
+            changes.push((path, original, current));
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Puts the changed files back as they were before the fixes, returning how many there were.
+fn revert(repo_root: &Path, changes: &[Change]) -> anyhow::Result<usize> {
+    for (path, original, _) in changes {
+        let full_path = repo_root.join(path);
+        original
+            .as_ref()
+            .map_or_else(|| fs::remove_file(&full_path), |content| fs::write(&full_path, content))
+            .with_context(|| format!("unable to revert {}", full_path.display()))?;
+    }
+
+    Ok(changes.len())
+}
+
+/// Finds the fixable steps of the selected jobs, ordered by job id.
+fn select_steps<'a>(args: &FixArgs, cfg: &'a Config) -> anyhow::Result<Vec<(&'a JobId, &'a Step)>> {
+    let mut jobs: Vec<_> = cfg.jobs().iter().collect();
+    jobs.sort_by_key(|(job_id, _)| job_id.as_str());
+
+    let unknown: Vec<_> = args
+        .jobs
+        .iter()
+        .filter(|name| !jobs.iter().any(|(job_id, _)| job_id.as_str() == name.as_str()))
+        .map(String::as_str)
+        .collect();
+
+    if !unknown.is_empty() {
+        return Err(Failure::UnknownJob.tag(anyhow!("invalid jobs specified: {}", unknown.join(", "))));
+    }
+
+    Ok(jobs
+        .into_iter()
+        .filter(|(job_id, _)| args.jobs.is_empty() || args.jobs.iter().any(|name| name == job_id.as_str()))
+        .flat_map(|(job_id, job)| {
+            job.steps()
+                .iter()
+                .filter(|step| step.fixable().is_some())
+                .map(move |step| (job_id, step))
+        })
+        .collect())
+}
+
+/// Runs the fix command of a step, once per workspace package for per-package steps.
+fn run_fix<H: Host>(outputter: &Outputter<H>, host: &H, metadata: &Metadata, job_id: &JobId, step: &Step) -> anyhow::Result<()> {
+    let Some(fixable) = step.fixable() else {
+        return Ok(());
+    };
+
+    let dirs: Vec<&Path> = if step.per_package() {
+        metadata
+            .workspace_packages()
+            .iter()
+            .filter_map(|pkg| pkg.manifest_path.parent())
+            .map(Utf8Path::as_std_path)
+            .collect()
+    } else {
+        vec![metadata.workspace_root.as_std_path()]
+    };
+
+    outputter.start_activity(format!("{job_id}: {}", step.name()));
+    for dir in dirs {
+        let mut cmd = shell_command(fixable.command());
+        _ = cmd.current_dir(dir).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        outputter.message(fixable.command());
+        outputter.run_command(&cmd);
+
+        let output = host
+            .output(&mut cmd)
+            .with_context(|| format!("unable to run '{}'", fixable.command()))?;
+        if !output.status.success() {
+            outputter.command_error("unable to fix", Some(output.status), Some(&output), true);
+            outputter.complete_activity("failed");
+            return Err(anyhow!("unable to run '{}': {}", fixable.command(), output.status));
+        }
+    }
+
+    outputter.complete_activity("done");
+    Ok(())
+}
+
+/// Asks whether to keep the changes, treating anything but an explicit yes as a no.
+fn confirm<H: Host>(outputter: &Outputter<H>, host: &H, count: usize) -> anyhow::Result<bool> {
+    outputter.line(format!("Keep the changes to {count} file(s)? [y/N]"));
+    let answer = host.read_line().context("unable to read the confirmation")?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
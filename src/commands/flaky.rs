@@ -0,0 +1,53 @@
+use crate::history::{DEFAULT_WINDOW, History};
+use crate::host::Host;
+use cargo_metadata::Metadata;
+use clap::Parser;
+
+#[derive(Parser, Debug, Clone)]
+pub struct FlakyArgs {
+    /// How many of each step's most recent runs to consider
+    #[arg(long, value_name = "RUNS", default_value_t = DEFAULT_WINDOW)]
+    runs: usize,
+
+    /// How many steps to list
+    #[arg(long, value_name = "COUNT", default_value_t = 10)]
+    top: usize,
+}
+
+pub fn flaky<H: Host>(args: &FlakyArgs, host: &H, metadata: &Metadata) -> anyhow::Result<()> {
+    let history = History::load(&History::path(metadata.target_directory.as_std_path()))?;
+    let mut stats = history.step_stats(args.runs);
+    if stats.is_empty() {
+        host.println("No run history yet, it is recorded by every run of the run subcommand.");
+        return Ok(());
+    }
+
+    stats.retain(|stats| stats.flips > 0);
+    if stats.is_empty() {
+        host.println("No step changed outcome over its recent runs.");
+        return Ok(());
+    }
+
+    // the steps whose outcome changes most often are the least trustworthy
+    stats.sort_by(|a, b| b.flips.cmp(&a.flips).then(b.failure_rate().total_cmp(&a.failure_rate())));
+
+    for stats in stats.iter().take(args.top) {
+        let (failing, streak) = stats.streak;
+        host.println(format!(
+            "{}: {}{}",
+            stats.job,
+            stats.step,
+            if stats.is_flaky() { " (flaky)" } else { "" }
+        ));
+        host.println(format!(
+            "  failed {} of {} run(s) ({:.0}%), flipped {} time(s), {} for the last {streak} run(s)",
+            stats.failures,
+            stats.runs,
+            stats.failure_rate() * 100.0,
+            stats.flips,
+            if failing { "failing" } else { "passing" }
+        ));
+    }
+
+    Ok(())
+}
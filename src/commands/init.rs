@@ -0,0 +1,255 @@
+use crate::ci_detection;
+use crate::config::{Config, parse_config};
+use crate::diff::{diff_lines, render_diff};
+use crate::git;
+use crate::host::Host;
+use anyhow::{Context, anyhow};
+use cargo_metadata::Metadata;
+use clap::ArgAction;
+use clap::Parser;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file of a template declaring the values projects fill in.
+const TEMPLATE_FILE: &str = "template.toml";
+
+#[derive(Parser, Debug, Clone)]
+pub struct InitArgs {
+    /// The template to instantiate, either a local directory or the URL of a git repository
+    #[arg(long, value_name = "SOURCE", required_unless_present = "detect")]
+    from: Option<String>,
+
+    /// Propose a configuration mirroring the pipelines of the CI providers the repository already uses
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["from", "values"])]
+    detect: bool,
+
+    /// Set a template parameter, instead of being prompted for it
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    values: Vec<(String, String)>,
+
+    /// Where to write the configuration [default: the template's configuration file name in the workspace root].
+    #[arg(short = 'o', long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Show the configuration without writing it
+    #[arg(short = 'n', long, action = ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Overwrite the output file if it already exists
+    #[arg(short = 'f', long, action = ArgAction::SetTrue)]
+    force: bool,
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid KEY=VALUE: no `=` found in `{s}`"))
+}
+
+/// The parameters a template declares in its `template.toml` file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Template {
+    #[serde(default)]
+    parameters: BTreeMap<String, Parameter>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Parameter {
+    prompt: Option<String>,
+    default: Option<String>,
+}
+
+pub fn init<H: Host>(args: &InitArgs, host: &H, metadata: &Metadata) -> anyhow::Result<()> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let (file_name, config) = match &args.from {
+        Some(from) => template_config(args, host, metadata, from)?,
+        None => detected_config(host, workspace_root)?,
+    };
+
+    let output = args.output.clone().unwrap_or_else(|| workspace_root.join(file_name));
+
+    let existing = fs::read_to_string(&output).unwrap_or_default();
+    if output.exists() && !args.force && !args.dry_run {
+        return Err(anyhow!("{} already exists, use --force to overwrite it", output.display()));
+    }
+
+    host.println(format!("--- {}", output.display()));
+    for line in render_diff(&diff_lines(&existing, &config), false) {
+        host.println(line);
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+
+    fs::write(&output, config).with_context(|| format!("unable to write {}", output.display()))?;
+    host.println(format!("Wrote {}", output.display()));
+    Ok(())
+}
+
+/// Instantiates a template, returning the name of its configuration file along with the resulting configuration.
+fn template_config<H: Host>(args: &InitArgs, host: &H, metadata: &Metadata, from: &str) -> anyhow::Result<(String, String)> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let source = Path::new(from);
+
+    let clone_dir = metadata.target_directory.as_std_path().join("ci-tmp").join("init-template");
+    let template_dir = if is_remote(from) {
+        if clone_dir.exists() {
+            fs::remove_dir_all(&clone_dir).with_context(|| format!("unable to remove {}", clone_dir.display()))?;
+        }
+
+        fs::create_dir_all(clone_dir.parent().unwrap_or(&clone_dir))
+            .with_context(|| format!("unable to create {}", clone_dir.display()))?;
+        git::shallow_clone(host, from, &clone_dir).with_context(|| format!("unable to fetch template '{from}'"))?;
+        clone_dir.clone()
+    } else if source.is_dir() {
+        source.to_path_buf()
+    } else {
+        return Err(anyhow!("template '{from}' is neither a directory nor a git repository URL"));
+    };
+
+    let result = instantiate_template(args, host, workspace_root, &template_dir);
+    if template_dir == clone_dir {
+        _ = fs::remove_dir_all(&clone_dir);
+    }
+
+    result
+}
+
+/// Proposes a configuration mirroring the pipelines of the CI providers the repository already uses.
+fn detected_config<H: Host>(host: &H, workspace_root: &Path) -> anyhow::Result<(String, String)> {
+    let proposal = ci_detection::detect(host, workspace_root)?;
+    if proposal.is_empty() {
+        return Err(anyhow!(
+            "no CI configuration found in {} or above, looked for .github/workflows, .gitlab-ci.yml, and Jenkinsfile",
+            workspace_root.display()
+        ));
+    }
+
+    let file_name = "ci.toml".to_string();
+    let config = proposal.render();
+    _ = parse_config::<Config>(&workspace_root.join(&file_name), &config)
+        .map_err(|e| anyhow!("unable to propose a valid configuration from the detected pipelines: {e:#}"))?;
+
+    Ok((file_name, config))
+}
+
+/// Whether a template source names a git repository rather than a local directory.
+fn is_remote(source: &str) -> bool {
+    source.contains("://") || source.starts_with("git@") || Path::new(source).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("git"))
+}
+
+/// Fills in the template's configuration file, returning its name along with the resulting configuration.
+fn instantiate_template<H: Host>(
+    args: &InitArgs,
+    host: &H,
+    workspace_root: &Path,
+    template_dir: &Path,
+) -> anyhow::Result<(String, String)> {
+    let (path, text) = Config::read_config(host, template_dir, None)?;
+
+    let template_file = template_dir.join(TEMPLATE_FILE);
+    let template: Template = if host.is_file(&template_file) {
+        let text = host
+            .read_to_string(&template_file)
+            .with_context(|| format!("unable to read {}", template_file.display()))?;
+        toml::from_str(&text).with_context(|| format!("unable to parse {}", template_file.display()))?
+    } else {
+        Template::default()
+    };
+
+    for (key, _) in &args.values {
+        if !template.parameters.contains_key(key) {
+            return Err(anyhow!("the template has no parameter named '{key}'"));
+        }
+    }
+
+    let values = parameter_values(args, host, &template)?;
+    let config = substitute(&text, &values)?;
+
+    // catch values which break the configuration now, rather than on the first run
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    _ = parse_config::<Config>(&workspace_root.join(&file_name), &config)
+        .map_err(|e| anyhow!("the instantiated template is not a valid configuration, check the values given for its parameters: {e:#}"))?;
+
+    Ok((file_name, config))
+}
+
+/// Determines the value of every parameter, from `--set` options, then by prompting, and finally from their defaults.
+fn parameter_values<H: Host>(args: &InitArgs, host: &H, template: &Template) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut values = BTreeMap::new();
+    for (name, parameter) in &template.parameters {
+        let given = args
+            .values
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone());
+        let value = match given {
+            Some(value) => value,
+            None if host.is_terminal() => prompt(host, name, parameter)?,
+            None => parameter
+                .default
+                .clone()
+                .ok_or_else(|| anyhow!("no value given for template parameter '{name}', use --set {name}=<VALUE>"))?,
+        };
+
+        _ = values.insert(name.clone(), value);
+    }
+
+    Ok(values)
+}
+
+fn prompt<H: Host>(host: &H, name: &str, parameter: &Parameter) -> anyhow::Result<String> {
+    let question = parameter.prompt.as_deref().unwrap_or(name);
+    loop {
+        match &parameter.default {
+            Some(default) => host.println(format!("{question} [{default}]:")),
+            None => host.println(format!("{question}:")),
+        }
+
+        let answer = host.read_line().context("unable to read the parameter value")?;
+        if answer.is_empty() {
+            return Err(anyhow!("no value given for template parameter '{name}', use --set {name}=<VALUE>"));
+        }
+
+        let answer = answer.trim();
+        if !answer.is_empty() {
+            return Ok(answer.to_string());
+        }
+
+        if let Some(default) = &parameter.default {
+            return Ok(default.clone());
+        }
+    }
+}
+
+/// Replaces the `{{ name }}` placeholders of a template with the values of its parameters.
+fn substitute(text: &str, values: &BTreeMap<String, String>) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((before, after)) = rest.split_once("{{") {
+        let (name, after) = after.split_once("}}").ok_or_else(|| {
+            anyhow!(
+                "unterminated placeholder in template: {{{{{}",
+                after.lines().next().unwrap_or_default()
+            )
+        })?;
+
+        let name = name.trim();
+        let value = values
+            .get(name)
+            .ok_or_else(|| anyhow!("the template uses '{name}', which is not declared in its {TEMPLATE_FILE}"))?;
+
+        result.push_str(before);
+        result.push_str(value);
+        rest = after;
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
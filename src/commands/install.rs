@@ -1,8 +1,10 @@
 use crate::color_modes::ColorModes;
-use crate::config::{Config, Tool, ToolId};
+use crate::config::{Config, Hints, Tool, ToolId};
 use crate::host::Host;
 use crate::log::Log;
+use crate::messages::Lang;
 use crate::outputter::Outputter;
+use anyhow::anyhow;
 use cargo_metadata::Metadata;
 use clap::Parser;
 use std::path::PathBuf;
@@ -10,6 +12,10 @@ use std::process::{Command, Stdio};
 
 #[derive(Parser, Debug, Clone)]
 pub struct InstallArgs {
+    /// Only install these tools or tool bundles, rather than every tool in the configuration.
+    #[arg(value_name = "TOOLS")]
+    tools: Vec<String>,
+
     /// Send log output to the specified file.
     #[arg(short = 'l', long, value_name = "FILE")]
     log_file: Option<PathBuf>,
@@ -21,12 +27,32 @@ pub struct InstallArgs {
     /// Colorize output.
     #[arg(long, value_name = "WHEN", default_value_t = ColorModes::Auto, value_enum)]
     color: ColorModes,
+
+    /// Language for step headings, captured output banners, statuses, and the summary; everything else is in English.
+    #[arg(long, value_name = "LANG", default_value_t = Lang::En, value_enum)]
+    lang: Lang,
 }
 
 pub fn install_tools<H: Host>(args: &InstallArgs, host: &mut H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<()> {
+    let tools: Vec<_> = if args.tools.is_empty() {
+        let mut tools: Vec<_> = cfg.tools().iter().map(|(tool_id, tool)| (tool_id.clone(), tool)).collect();
+        tools.sort_by(|x, y| x.0.cmp(&y.0));
+        tools
+    } else {
+        let names: Vec<_> = args.tools.iter().map(|name| ToolId::from(name.as_str())).collect();
+        cfg.resolve_tools(&names)
+            .into_iter()
+            .map(|tool_id| match cfg.tools().get(&tool_id) {
+                Some(tool) => Ok((tool_id, tool)),
+                None => Err(anyhow!("'{tool_id}' is neither a tool in the [tools] section nor a tool bundle")),
+            })
+            .collect::<anyhow::Result<_>>()?
+    };
+
     let log = Log::new(
         metadata.target_directory.as_std_path(),
         "install",
+        host.local_time(),
         args.log_file.as_deref(),
         args.log_file_retention_count,
     )?;
@@ -34,24 +60,22 @@ pub fn install_tools<H: Host>(args: &InstallArgs, host: &mut H, cfg: &Config, me
     // after this point, thia code takes care of error reporting itself
     host.fail_silently();
 
-    let outputter = Outputter::new(host, &log, args.color);
+    let outputter = Outputter::new(host, &log, args.color, args.lang);
     outputter.start_activity("Installing/Updating");
 
-    let mut tools: Vec<_> = cfg.tools().iter().collect();
-    tools.sort_by(|x, y| x.0.cmp(y.0));
-
     for (tool_id, tool) in &tools {
-        install_tool(host, tool_id, tool, &outputter)?;
+        install_tool(host, tool_id, tool, cfg.hints(), &outputter)?;
     }
 
     outputter.complete_activity(format!("installed or updated {} tool(s)", tools.len()));
     Ok(())
 }
 
-fn install_tool<H: Host>(host: &H, tool_id: &ToolId, tool: &Tool, outputter: &Outputter<H>) -> anyhow::Result<()> {
+/// Installs or updates a tool as described in the `[tools]` table.
+pub fn install_tool<H: Host>(host: &H, tool_id: &ToolId, tool: &Tool, hints: &Hints, outputter: &Outputter<H>) -> anyhow::Result<()> {
     let mut cmd = Command::new("cargo");
 
-    _ = cmd.current_dir(std::env::current_dir().unwrap_or_default());
+    _ = cmd.current_dir(host.current_dir().unwrap_or_default());
     _ = cmd.arg("install").arg(tool_id.to_string());
     _ = cmd.arg("--version").arg(tool.version().to_string());
 
@@ -87,6 +111,22 @@ fn install_tool<H: Host>(host: &H, tool_id: &ToolId, tool: &Tool, outputter: &Ou
         _ = cmd.arg("--root").arg(root);
     }
 
+    if !tool.features().is_empty() {
+        _ = cmd.arg("--features").arg(tool.features().join(","));
+    }
+
+    if tool.no_default_features() {
+        _ = cmd.arg("--no-default-features");
+    }
+
+    if tool.locked() {
+        _ = cmd.arg("--locked");
+    }
+
+    if let Some(profile) = tool.profile() {
+        _ = cmd.arg("--profile").arg(profile);
+    }
+
     _ = cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
     outputter.message(format!("{} {}", tool_id, tool.version()));
@@ -99,8 +139,9 @@ fn install_tool<H: Host>(host: &H, tool_id: &ToolId, tool: &Tool, outputter: &Ou
                     Ok(())
                 } else {
                     outputter.command_error("unable to install", Some(output.status), Some(&output), true);
+                    let hints = outputter.report_hints(hints, &output, true);
                     Err(anyhow::anyhow!(format!(
-                        "unable to install '{} {}': {}",
+                        "unable to install '{} {}': {}{hints}",
                         tool_id,
                         tool.version(),
                         output.status
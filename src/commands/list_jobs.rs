@@ -1,32 +1,199 @@
-use crate::config::Config;
+use crate::config::{Config, Job, JobId};
+use crate::excluded_crates;
+use crate::exit_codes::Failure;
 use crate::host::Host;
-use clap::ArgAction;
+use anyhow::anyhow;
+use cargo_metadata::Metadata;
 use clap::Parser;
+use clap::{ArgAction, ArgGroup};
 
 #[derive(Parser, Debug, Clone)]
+#[command(group(ArgGroup::new("tree").args(["needs_of", "reverse_needs"])))]
 pub struct ListJobArgs {
     /// Show the steps defined for each job
     #[arg(short = 's', long, action = ArgAction::SetTrue)]
     show_steps: bool,
+
+    /// Only list jobs whose ID or name contains this text
+    #[arg(long, value_name = "SUBSTRING", conflicts_with_all = ["needs_of", "reverse_needs"])]
+    filter: Option<String>,
+
+    /// Show the tree of jobs the given job needs
+    #[arg(long, value_name = "JOB", conflicts_with = "reverse_needs")]
+    needs_of: Option<String>,
+
+    /// Show the tree of jobs which need the given job
+    #[arg(long, value_name = "JOB")]
+    reverse_needs: Option<String>,
+
+    /// Limit how many levels of a tree are shown
+    #[arg(long, value_name = "LEVELS", requires = "tree")]
+    depth: Option<usize>,
+
+    /// Also list the crates under the workspace root which aren't members of the workspace, which takes running cargo
+    /// metadata
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["filter", "tree"])]
+    excluded_crates: bool,
 }
 
-pub fn list_jobs<H: Host>(args: &ListJobArgs, host: &H, cfg: &Config) {
+impl ListJobArgs {
+    /// Whether listing takes knowing about the workspace's packages, rather than just reading the configuration.
+    #[must_use]
+    pub const fn needs_metadata(&self) -> bool {
+        self.excluded_crates
+    }
+}
+
+/// Lists the jobs of the configuration, along with the crates outside of the workspace when their metadata is given.
+pub fn list_jobs<H: Host>(args: &ListJobArgs, host: &H, cfg: &Config, metadata: Option<&Metadata>) -> anyhow::Result<()> {
     if cfg.jobs().is_empty() {
         host.println("No jobs defined in the workspace configuration.");
-        return;
+        return Ok(());
+    }
+
+    if let Some(job_id) = &args.needs_of {
+        let (root, _) = find_job(cfg, job_id)?;
+        print_tree(args, host, cfg, root, 0, &|job_id| {
+            cfg.jobs()
+                .get_job(job_id)
+                .map(|job| sorted(job.needs().iter().collect()))
+                .unwrap_or_default()
+        });
+        return Ok(());
+    }
+
+    if let Some(job_id) = &args.reverse_needs {
+        let (root, _) = find_job(cfg, job_id)?;
+        print_tree(args, host, cfg, root, 0, &|job_id| {
+            sorted(
+                cfg.jobs()
+                    .iter()
+                    .filter(|(_, job)| job.needs().contains(job_id))
+                    .map(|(id, _)| id)
+                    .collect(),
+            )
+        });
+        return Ok(());
     }
 
-    for (job_id, job) in cfg.jobs().iter() {
+    let filter = args.filter.as_deref().map(str::to_lowercase);
+    let mut jobs: Vec<_> = cfg
+        .jobs()
+        .iter()
+        .filter(|(job_id, job)| filter.as_deref().is_none_or(|filter| matches(job_id, job, filter)))
+        .collect();
+    jobs.sort_by_key(|(job_id, _)| *job_id);
+
+    if jobs.is_empty() {
+        host.println("No jobs match the filter.");
+        return Ok(());
+    }
+
+    for (job_id, job) in jobs {
         host.println(job_id.as_str());
+        print_steps(args, host, job, "  ");
+    }
+
+    if let Some(metadata) = metadata {
+        print_excluded_crates(host, cfg, metadata)?;
+    }
+
+    Ok(())
+}
+
+/// Lists the crates under the workspace root which aren't members of the workspace, and whether any job covers them.
+fn print_excluded_crates<H: Host>(host: &H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<()> {
+    let (excluded, warnings) = excluded_crates::find(host, metadata)?;
+    for warning in warnings {
+        host.eprintln(format!("WARNING: {warning}"));
+    }
+
+    if excluded.is_empty() {
+        return Ok(());
+    }
+
+    let mut including: Vec<_> = cfg
+        .jobs()
+        .iter()
+        .filter(|(_, job)| job.include_excluded())
+        .map(|(job_id, _)| job_id)
+        .collect();
+    including.sort();
+    let including = including.iter().map(|job_id| format!("'{job_id}'")).collect::<Vec<_>>().join(", ");
+
+    host.println("");
+    host.println("Crates outside of the workspace:");
+    for excluded in excluded {
+        let coverage = if !excluded.exclusion.can_be_included() {
+            "not covered, cargo refuses to build it".to_string()
+        } else if including.is_empty() {
+            "not covered, set include_excluded = true on a job to cover it".to_string()
+        } else {
+            format!("covered by {including}")
+        };
+
+        host.println(format!(
+            "  {} ({}), {}, {coverage}",
+            excluded.name,
+            excluded.dir.display(),
+            excluded.exclusion.description()
+        ));
+    }
+
+    Ok(())
+}
+
+fn find_job<'a>(cfg: &'a Config, job_id: &str) -> anyhow::Result<(&'a JobId, &'a Job)> {
+    cfg.jobs()
+        .iter()
+        .find(|(id, _)| id.as_str() == job_id)
+        .ok_or_else(|| Failure::UnknownJob.tag(anyhow!("job '{job_id}' is not defined in the [jobs] section")))
+}
+
+fn matches(job_id: &JobId, job: &Job, filter: &str) -> bool {
+    job_id.as_str().to_lowercase().contains(filter) || job.name().is_some_and(|name| name.to_lowercase().contains(filter))
+}
+
+fn sorted(mut job_ids: Vec<&JobId>) -> Vec<&JobId> {
+    job_ids.sort();
+    job_ids
+}
+
+/// Prints a job and, recursively, the jobs related to it, indenting each level.
+fn print_tree<'a, H: Host>(
+    args: &ListJobArgs,
+    host: &H,
+    cfg: &'a Config,
+    job_id: &'a JobId,
+    level: usize,
+    children: &dyn Fn(&JobId) -> Vec<&'a JobId>,
+) {
+    let indent = "  ".repeat(level);
+    host.println(format!("{indent}{job_id}"));
+
+    if let Some(job) = cfg.jobs().get_job(job_id) {
+        print_steps(args, host, job, &format!("{indent}  - "));
+    }
+
+    if args.depth.is_some_and(|depth| level >= depth) {
+        return;
+    }
+
+    for child in children(job_id) {
+        print_tree(args, host, cfg, child, level + 1, children);
+    }
+}
+
+fn print_steps<H: Host>(args: &ListJobArgs, host: &H, job: &Job, prefix: &str) {
+    if !args.show_steps {
+        return;
+    }
 
-        if args.show_steps {
-            for step in job.steps() {
-                if let Some(id) = step.id() {
-                    host.println(format!("  {}", id.as_str()));
-                } else {
-                    host.println(format!("  {}", step.name()));
-                }
-            }
+    for step in job.steps() {
+        if let Some(id) = step.id() {
+            host.println(format!("{prefix}{}", id.as_str()));
+        } else {
+            host.println(format!("{prefix}{}", step.name()));
         }
     }
 }
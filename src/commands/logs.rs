@@ -0,0 +1,118 @@
+use crate::config::Config;
+use crate::host::Host;
+use crate::log;
+use crate::log_search::{self, LogEntry};
+use crate::long_paths;
+use anyhow::{Context, anyhow};
+use cargo_metadata::Metadata;
+use clap::Parser;
+use regex::Regex;
+use std::fs;
+
+#[derive(Parser, Debug, Clone)]
+pub struct LogsArgs {
+    /// Only search the log of this run, by its id as printed when it starts, or the start of it, such as its date
+    #[arg(long, value_name = "RUN_ID")]
+    run: Option<String>,
+
+    /// Only show the lines written for this job, by its id or its name
+    #[arg(long, value_name = "JOB")]
+    job: Option<String>,
+
+    /// Only show the lines matching this regular expression
+    #[arg(long, value_name = "REGEX")]
+    grep: Option<Regex>,
+}
+
+/// Searches the logs of past runs, newest first, showing the matching lines with the job, step, and package they were
+/// written for.
+///
+/// Logs name jobs by the name shown while they run, which the configuration, when there's one, maps job ids to.
+pub fn logs<H: Host>(args: &LogsArgs, host: &H, cfg: Option<&Config>, metadata: &Metadata) -> anyhow::Result<()> {
+    let job_names: Vec<(&str, &str)> = cfg
+        .map(|cfg| {
+            cfg.jobs()
+                .iter()
+                .map(|(job_id, job)| (job_id.as_str(), job.name().unwrap_or(job_id.as_str())))
+                .collect()
+        })
+        .unwrap_or_default();
+    let known: Vec<&str> = job_names.iter().map(|(_, name)| *name).collect();
+    let job = args
+        .job
+        .as_deref()
+        .map(|job| job_names.iter().find(|(job_id, _)| *job_id == job).map_or(job, |(_, name)| *name));
+
+    let log_dir = log::log_dir(metadata.target_directory.as_std_path());
+    let mut searched = 0;
+    let mut matched = 0;
+
+    for (_, path) in log::logs(&log_dir, "run-") {
+        let text = fs::read_to_string(long_paths::extended(&path)?).with_context(|| format!("unable to read {}", path.display()))?;
+        let run_id = log::run_id(&path, &text).unwrap_or_else(|| path.display().to_string());
+        if let Some(run) = &args.run
+            && !run_id.starts_with(run.as_str())
+        {
+            continue;
+        }
+
+        searched += 1;
+        let entries: Vec<LogEntry> = log_search::entries(&text, &known)
+            .into_iter()
+            .filter(|entry| matches(args, job, entry))
+            .collect();
+        if entries.is_empty() {
+            continue;
+        }
+
+        host.println(format!("Run {run_id} ({}):", path.display()));
+        for entry in &entries {
+            host.println(format!("  {}", describe(entry)));
+        }
+
+        matched += entries.len();
+    }
+
+    if searched == 0 {
+        return Err(args.run.as_ref().map_or_else(
+            || anyhow!("no logs of past runs in {}", log_dir.display()),
+            |run| anyhow!("no log of run '{run}' in {}", log_dir.display()),
+        ));
+    }
+
+    if matched == 0 {
+        host.println(format!("No matching lines in the logs of {searched} run(s)."));
+    }
+
+    Ok(())
+}
+
+fn matches(args: &LogsArgs, job: Option<&str>, entry: &LogEntry) -> bool {
+    if let Some(job) = job
+        && entry.job.as_deref() != Some(job)
+    {
+        return false;
+    }
+
+    args.grep.as_ref().is_none_or(|grep| grep.is_match(&entry.message))
+}
+
+/// A line of a log, such as `line 12 [ERROR] test, step 'clippy', package 'foo': unable to run step`.
+fn describe(entry: &LogEntry) -> String {
+    let mut context = Vec::new();
+    if let Some(job) = &entry.job {
+        context.push(job.clone());
+    }
+    if let Some(step) = &entry.step {
+        context.push(format!("step '{step}'"));
+    }
+    if let Some(package) = &entry.package {
+        context.push(format!("package '{package}'"));
+    }
+
+    if context.is_empty() {
+        format!("line {} [{}] {}", entry.line, entry.level, entry.message)
+    } else {
+        format!("line {} [{}] {}: {}", entry.line, entry.level, context.join(", "), entry.message)
+    }
+}
@@ -0,0 +1,291 @@
+use crate::diff::{diff_lines, render_diff};
+use crate::host::Host;
+use crate::toml_text::{key, string};
+use anyhow::{Context, anyhow};
+use cargo_metadata::Metadata;
+use clap::ArgAction;
+use clap::Parser;
+use core::fmt::Write;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug, Clone)]
+pub struct MigrateArgs {
+    /// Where to write the migrated configuration [default: ci.toml in the workspace root].
+    #[arg(short = 'o', long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Show the migrated configuration without writing it
+    #[arg(short = 'n', long, action = ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Overwrite the output file if it already exists
+    #[arg(short = 'f', long, action = ArgAction::SetTrue)]
+    force: bool,
+}
+
+/// The configuration format once read from `[workspace.metadata.ci]`.
+#[derive(Debug, Default, Deserialize)]
+struct LegacyConfig {
+    #[serde(default)]
+    default_jobs: Vec<String>,
+
+    #[serde(default)]
+    passthrough_env_variables: Vec<String>,
+
+    #[serde(default)]
+    tools: BTreeMap<String, serde_json::Value>,
+
+    #[serde(default)]
+    jobs: BTreeMap<String, LegacyJob>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyJob {
+    name: Option<String>,
+
+    #[serde(default, alias = "depends_on")]
+    needs: Vec<String>,
+
+    #[serde(default)]
+    steps: Vec<LegacyStep>,
+
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LegacyStep {
+    Simple(String),
+
+    Extended {
+        #[serde(alias = "run")]
+        command: String,
+        name: Option<String>,
+        id: Option<String>,
+
+        #[serde(default)]
+        modifiers: Vec<String>,
+    },
+}
+
+/// What a list of legacy modifiers translates to in the current schema.
+#[derive(Debug, Default)]
+struct Modifiers {
+    conditions: Vec<String>,
+    continue_on_error: bool,
+    per_package: bool,
+    variables: BTreeMap<String, String>,
+    unknown: Vec<String>,
+}
+
+impl Modifiers {
+    fn parse(modifiers: &[String]) -> Self {
+        let mut result = Self::default();
+        for modifier in modifiers {
+            let modifier = modifier.trim();
+            if let Some(expr) = modifier.strip_prefix("when:") {
+                result.conditions.push(expr.trim().to_string());
+            } else if let Some((key, value)) = modifier.strip_prefix("var:").and_then(|kv| kv.split_once('=')) {
+                _ = result.variables.insert(key.trim().to_string(), value.trim().to_string());
+            } else {
+                match modifier {
+                    "per-package" | "per_package" => result.per_package = true,
+                    "continue-on-error" | "continue_on_error" | "allow-failure" => result.continue_on_error = true,
+                    _ => result.unknown.push(modifier.to_string()),
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Combines all conditions into a single `if` expression.
+    fn condition(&self) -> Option<String> {
+        match self.conditions.len() {
+            0 => None,
+            1 => Some(self.conditions[0].clone()),
+            _ => Some(self.conditions.iter().map(|c| format!("({c})")).collect::<Vec<_>>().join(" && ")),
+        }
+    }
+}
+
+pub fn migrate<H: Host>(args: &MigrateArgs, host: &H, metadata: &Metadata) -> anyhow::Result<()> {
+    let legacy = metadata
+        .workspace_metadata
+        .get("ci")
+        .ok_or_else(|| anyhow!("no legacy configuration found in [workspace.metadata.ci]"))?;
+
+    let legacy = LegacyConfig::deserialize(legacy).context("unable to parse legacy configuration in [workspace.metadata.ci]")?;
+
+    let mut warnings = Vec::new();
+    let migrated = render(&legacy, &mut warnings);
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| metadata.workspace_root.as_std_path().join("ci.toml"));
+
+    let existing = fs::read_to_string(&output).unwrap_or_default();
+    if output.exists() && !args.force && !args.dry_run {
+        return Err(anyhow!("{} already exists, use --force to overwrite it", output.display()));
+    }
+
+    host.println(format!("--- {}", output.display()));
+    for line in render_diff(&diff_lines(&existing, &migrated), false) {
+        host.println(line);
+    }
+
+    for warning in &warnings {
+        host.eprintln(format!("WARNING: {warning}"));
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+
+    fs::write(&output, migrated).with_context(|| format!("unable to write {}", output.display()))?;
+    host.println(format!(
+        "Wrote {}, you can now remove [workspace.metadata.ci] from Cargo.toml",
+        output.display()
+    ));
+
+    Ok(())
+}
+
+fn render(legacy: &LegacyConfig, warnings: &mut Vec<String>) -> String {
+    let mut out = String::from("# Migrated from [workspace.metadata.ci] by `cargo ci migrate`.\n");
+
+    if !legacy.default_jobs.is_empty() {
+        _ = writeln!(out, "\ndefault_jobs = {}", string_array(&legacy.default_jobs));
+    }
+
+    if !legacy.passthrough_env_variables.is_empty() {
+        _ = writeln!(
+            out,
+            "\npassthrough_env_variables = {}",
+            string_array(&legacy.passthrough_env_variables)
+        );
+    }
+
+    if !legacy.tools.is_empty() {
+        out.push_str("\n[tools]\n");
+        for (tool, value) in &legacy.tools {
+            match value {
+                serde_json::Value::String(version) => _ = writeln!(out, "{} = {}", key(tool), string(version)),
+                serde_json::Value::Object(fields) => {
+                    let fields: Vec<_> = fields
+                        .iter()
+                        .filter_map(|(k, v)| v.as_str().map(|v| format!("{} = {}", key(k), string(v))))
+                        .collect();
+                    _ = writeln!(out, "{} = {{ {} }}", key(tool), fields.join(", "));
+                }
+                _ => warnings.push(format!("tool '{tool}' has an unsupported definition and was not migrated")),
+            }
+        }
+    }
+
+    for (job_id, job) in &legacy.jobs {
+        let modifiers = Modifiers::parse(&job.modifiers);
+        for unknown in &modifiers.unknown {
+            warnings.push(format!("job '{job_id}': unknown modifier '{unknown}' was not migrated"));
+        }
+
+        _ = writeln!(out, "\n[jobs.{}]", key(job_id));
+
+        if let Some(name) = &job.name {
+            _ = writeln!(out, "name = {}", string(name));
+        }
+
+        if !job.needs.is_empty() {
+            _ = writeln!(out, "needs = {}", string_array(&job.needs));
+        }
+
+        if let Some(condition) = modifiers.condition() {
+            _ = writeln!(out, "if = {}", string(&condition));
+        }
+
+        if modifiers.continue_on_error {
+            out.push_str("continue_on_error = true\n");
+        }
+
+        if !modifiers.variables.is_empty() {
+            _ = writeln!(out, "variables = {}", inline_table(&modifiers.variables));
+        }
+
+        out.push_str("steps = [\n");
+        for step in &job.steps {
+            _ = writeln!(out, "    {},", render_step(job_id, step, modifiers.per_package, warnings));
+        }
+        out.push_str("]\n");
+    }
+
+    out
+}
+
+fn render_step(job_id: &str, step: &LegacyStep, per_package: bool, warnings: &mut Vec<String>) -> String {
+    let (command, name, id, modifiers) = match step {
+        LegacyStep::Simple(command) => (command, None, None, Modifiers::default()),
+        LegacyStep::Extended {
+            command,
+            name,
+            id,
+            modifiers,
+        } => (command, name.as_ref(), id.as_ref(), Modifiers::parse(modifiers)),
+    };
+
+    for unknown in &modifiers.unknown {
+        warnings.push(format!(
+            "job '{job_id}', step '{command}': unknown modifier '{unknown}' was not migrated"
+        ));
+    }
+
+    let mut fields = Vec::new();
+    if let Some(name) = name {
+        fields.push(format!("name = {}", string(name)));
+    }
+
+    if let Some(id) = id {
+        fields.push(format!("id = {}", string(id)));
+    }
+
+    if let Some(condition) = modifiers.condition() {
+        fields.push(format!("if = {}", string(&condition)));
+    }
+
+    if modifiers.continue_on_error {
+        fields.push("continue_on_error = true".to_string());
+    }
+
+    if per_package || modifiers.per_package {
+        fields.push("per_package = true".to_string());
+    }
+
+    if !modifiers.variables.is_empty() {
+        fields.push(format!("variables = {}", inline_table(&modifiers.variables)));
+    }
+
+    if fields.is_empty() {
+        string(command)
+    } else {
+        format!("{{ command = {}, {} }}", string(command), fields.join(", "))
+    }
+}
+
+fn string_array(values: &[String]) -> String {
+    format!("[{}]", values.iter().map(|v| string(v)).collect::<Vec<_>>().join(", "))
+}
+
+fn inline_table(values: &BTreeMap<String, String>) -> String {
+    format!(
+        "{{ {} }}",
+        values
+            .iter()
+            .map(|(k, v)| format!("{} = {}", key(k), string(v)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
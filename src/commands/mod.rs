@@ -1,7 +1,35 @@
+mod cancel;
+mod clean;
+mod config_show;
+mod exec_plan;
+mod execute;
+mod fix;
+mod flaky;
+mod init;
 mod install;
 mod list_jobs;
+mod logs;
+mod migrate;
+mod plan;
 mod run;
+mod simulate;
+mod test;
+mod validate;
 
-pub use install::{InstallArgs, install_tools};
+pub use cancel::{CancelArgs, cancel};
+pub use clean::{CleanArgs, clean};
+pub use config_show::{ConfigArgs, config};
+pub use exec_plan::{ExecPlanArgs, exec_plan};
+pub use execute::{ExecuteArgs, execute};
+pub use fix::{FixArgs, fix};
+pub use flaky::{FlakyArgs, flaky};
+pub use init::{InitArgs, init};
+pub use install::{InstallArgs, install_tool, install_tools};
 pub use list_jobs::{ListJobArgs, list_jobs};
-pub use run::{RunArgs, run_jobs};
+pub use logs::{LogsArgs, logs};
+pub use migrate::{MigrateArgs, migrate};
+pub use plan::{PlanArgs, plan};
+pub use run::{RunArgs, run_jobs, run_workspaces};
+pub use simulate::{SimulateArgs, simulate};
+pub use test::{TestArgs, test};
+pub use validate::{ValidateArgs, validate};
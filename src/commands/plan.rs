@@ -0,0 +1,46 @@
+use crate::commands::run::{RunArgs, plan_jobs};
+use crate::config::Config;
+use crate::host::Host;
+use crate::platform::Platform;
+use anyhow::Context;
+use cargo_metadata::Metadata;
+use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug, Clone)]
+pub struct PlanArgs {
+    /// Write the plan to the specified file instead of standard output.
+    #[arg(short = 'o', long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Evaluate conditions as if running on another platform, given as `os=<OS>,arch=<ARCH>`, leaving out the
+    /// environment variables of this machine.
+    #[arg(long = "for", value_name = "PLATFORM")]
+    platform: Option<Platform>,
+
+    #[command(flatten)]
+    run_args: RunArgs,
+}
+
+impl PlanArgs {
+    #[must_use]
+    pub const fn run_args(&self) -> &RunArgs {
+        &self.run_args
+    }
+}
+
+/// Writes the fully resolved execution plan of the selected jobs as JSON.
+pub fn plan<H: Host>(args: &PlanArgs, host: &H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<()> {
+    let plan = plan_jobs(&args.run_args, host, cfg, metadata, args.platform.as_ref())?;
+    let json = serde_json::to_string_pretty(&plan)?;
+
+    if let Some(path) = &args.output {
+        fs::write(path, json).with_context(|| format!("unable to write plan to {}", path.display()))?;
+        host.println(format!("Wrote a plan of {} job(s) to {}", plan.jobs.len(), path.display()));
+    } else {
+        host.println(json);
+    }
+
+    Ok(())
+}
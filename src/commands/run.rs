@@ -1,331 +1,4616 @@
+use crate::artifact_store::ArtifactStore;
+use crate::audit;
+use crate::badges::write_badges;
+use crate::cancellation;
+use crate::cargo_aliases::CargoAliases;
+use crate::cargo_messages::{CargoMessages, uses_json_messages};
+use crate::cargo_tools::CargoTools;
+use crate::codegen;
 use crate::color_modes::ColorModes;
-use crate::config::{Config, Job, JobId, Step};
-use crate::host::Host;
-use crate::log::Log;
+use crate::commands::install_tool;
+use crate::config::{ByteSize, Config, Consumes, Job, JobId, Os, RetryStrategy, StageId, Step, StepKind, ToolId, WorkingDirectoryMode};
+use crate::debug_failures;
+use crate::diagnostics::Diagnostic;
+use crate::excluded_crates;
+use crate::executor;
+use crate::exit_codes::Failure;
+use crate::expressions::{Conditional, VariableSource};
+use crate::feature_unification::{self, FeatureDivergence};
+use crate::fingerprint::Fingerprint;
+use crate::freshness::{self, StaleFile};
+use crate::git;
+use crate::github_pr;
+use crate::heartbeat::{self, Heartbeat, Waited};
+use crate::history::{DEFAULT_WINDOW, History, RunRecord, StepRecord};
+use crate::host::{Host, SpawnOptions};
+use crate::keyed_locks::KeyedLocks;
+use crate::live_status::{self, LiveStatus};
+use crate::lockfile_changes;
+use crate::log::{self, Log};
+use crate::long_paths;
+use crate::memory_budget::MemoryBudget;
+use crate::messages::{Lang, Localize, Msg};
+use crate::network_isolation;
 use crate::outputter::Outputter;
-use crate::pkg_data::variables;
-use anyhow::anyhow;
-use cargo_metadata::{Metadata, Package};
+use crate::pkg_data::{is_trusted, parse_variables, step_command, untrusted_package_warnings, variables, variables_from};
+use crate::plan::{Plan, PlannedJob, PlannedStep};
+use crate::platform::Platform;
+use crate::required_targets::RequiredTargets;
+use crate::result_cache::ResultCache;
+use crate::run_lock::RunLock;
+use crate::run_progress::RunProgress;
+use crate::sarif::{self, Finding};
+use crate::semver_checks;
+use crate::sharding::{Shard, ShardDurations};
+use crate::shared_results::{SharedResult, SharedResults};
+use crate::shuffle;
+use crate::status_board::{PLAIN_STATUS_INTERVAL_SECS, StatusBoard};
+use crate::step_outcomes::{StepOutcome, StepOutcomes};
+use crate::summary::{
+    JobResult, JobStatus, PackageResult, RunSummary, StageResult, StepResult, StepStatus, format_duration, print_summary, render_markdown,
+};
+use crate::target_dir;
+use crate::telemetry::{ActiveSpan, SpanId, Telemetry};
+use crate::terminal_notifications;
+use crate::test_results::{self, TestResult};
+use crate::token_bucket::TokenBucket;
+use anyhow::{Context, anyhow};
+use cargo_metadata::{Metadata, MetadataCommand, Package, PackageId};
+use chrono::{DateTime, Local};
 use clap::ArgAction;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use core::error::Error;
+use core::fmt::Write as _;
+use core::mem;
 use core::str::FromStr;
-use std::collections::{HashMap, HashSet};
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError, mpsc};
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+/// How many lines from the end of the log of a run which never finished are shown on request.
+const UNFINISHED_LOG_TAIL: usize = 20;
+
+/// How long steps still running when the run goes over its time budget may take to finish before they're killed.
+const TIME_BUDGET_GRACE: Duration = Duration::from_secs(30);
+
+/// How many times its expected duration, or its usual duration, a step must take to be flagged as slow.
+const SLOW_STEP_FACTOR: u32 = 2;
+
+/// How long a step must take to be flagged as slow compared to its usual duration, so quick steps which merely
+/// fluctuate aren't flagged.
+const MIN_SLOW_STEP_DURATION: Duration = Duration::from_secs(10);
 
 #[derive(Parser, Debug, Default, Clone)]
+#[expect(clippy::struct_excessive_bools, reason = "Command-line flags are naturally bools")]
 pub struct RunArgs {
     /// Names of the jobs to run
     jobs: Vec<String>,
 
-    /// Show steps to execute without executing them
-    #[arg(short = 'n', long, action = ArgAction::SetTrue)]
-    dry_run: bool,
+    /// Run the jobs in the workspace in this directory, using its own configuration; may be repeated to run them in
+    /// several workspaces, with a combined summary
+    #[arg(long, value_name = "DIR")]
+    workspace_dir: Vec<PathBuf>,
+
+    /// Apply the options of a named run from the [runs] section; options given explicitly take precedence
+    #[arg(long = "run", value_name = "NAME")]
+    named_run: Option<String>,
+
+    /// Show steps to execute without executing them
+    #[arg(short = 'n', long, action = ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Resolve the configuration and packages as they were at the given git revision, without checking it out
+    #[arg(long, value_name = "REV")]
+    at: Option<String>,
+
+    /// Run the jobs in a temporary git worktree of HEAD, or of the given revision, with a target directory of their
+    /// own, so that uncommitted changes can't affect them
+    #[arg(
+        long,
+        value_name = "REV",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "HEAD",
+        conflicts_with_all = ["at", "workspace_dir"]
+    )]
+    isolated: Option<String>,
+
+    /// Package to run jobs on (see `cargo help pkgid`)
+    #[arg(short = 'p', long, value_name = "SPEC")]
+    package: Vec<String>,
+
+    /// Only process the given shard of the packages of sharded steps (e.g. 2/4)
+    #[arg(long, value_name = "INDEX/COUNT")]
+    shard: Option<Shard>,
+
+    /// Split packages into shards by how long they took according to a file saved with `--save-shard-durations`, rather
+    /// than round-robin, so shards finish at similar times
+    #[arg(long, value_name = "FILE", requires = "shard")]
+    balance_shards: Option<PathBuf>,
+
+    /// Save how long the sharded steps of the selected jobs took for each package in earlier runs to a file, for
+    /// `--balance-shards`
+    #[arg(long, value_name = "FILE")]
+    save_shard_durations: Option<PathBuf>,
+
+    /// Run every step which runs a command for each package, or once for the workspace, regardless of how the step is
+    /// configured
+    #[arg(long, value_enum, value_name = "SCOPE")]
+    scope: Option<StepScope>,
+
+    /// Skip jobs which passed the last time they ran, provided the configuration, the files of the packages, and the
+    /// toolchain haven't changed since
+    #[arg(long, action = ArgAction::SetTrue)]
+    since_last_green: bool,
+
+    /// Run pure jobs even when they passed before on the same git trees, and don't cache their results, nor reuse the
+    /// results of steps setting `reuse_result` which another job of the run already ran
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_cache: bool,
+
+    /// Skip the steps, and the packages of per-package steps, which succeeded in the last run that didn't complete
+    #[arg(long, action = ArgAction::SetTrue)]
+    resume: bool,
+
+    /// Define a variable. Prefix its name with a job or step id and a dot to only apply it to that job or step.
+    #[arg(short = 'v', long, visible_alias = "var", value_parser = parse_key_val::<String, String>, value_name = "[SCOPE.]VAR=VALUE")]
+    variable: Vec<(String, String)>,
+
+    /// Warn about dependencies whose features differ between per-package and workspace builds
+    #[arg(long, action = ArgAction::SetTrue)]
+    check_feature_unification: bool,
+
+    /// Fail right away when another run in this workspace holds the lock, instead of waiting for it to finish
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "no_lock")]
+    no_wait: bool,
+
+    /// Don't lock the workspace, allowing this run to proceed concurrently with other runs
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_lock: bool,
+
+    /// Run destructive steps without asking for confirmation
+    #[arg(long, action = ArgAction::SetTrue)]
+    allow_destructive: bool,
+
+    /// Install the tools jobs require which aren't installed at the version the [tools] table asks for
+    #[arg(long, action = ArgAction::SetTrue)]
+    install_tools: bool,
+
+    /// Answer yes to the questions of steps which ask for confirmation before running
+    #[arg(short = 'y', long, action = ArgAction::SetTrue)]
+    yes: bool,
+
+    /// Run packages in a random order, to uncover tests which depend on other packages running first.
+    /// A seed reproduces a specific order; one is picked and printed when omitted.
+    #[arg(long, value_name = "SEED", num_args = 0..=1, require_equals = true, conflicts_with = "shuffle_seed")]
+    #[expect(clippy::option_option, reason = "Distinguishes --shuffle from --shuffle <SEED>")]
+    shuffle: Option<Option<u64>>,
+
+    /// Run packages in the random order produced by the given seed, as printed by an earlier run with --shuffle
+    #[arg(long, value_name = "SEED")]
+    shuffle_seed: Option<u64>,
+
+    /// Explain how each `if` condition was evaluated
+    #[arg(long, action = ArgAction::SetTrue)]
+    explain_conditions: bool,
+
+    /// Report a heartbeat, with the elapsed and CPU time, after a step goes this many seconds without output, and then
+    /// every as many seconds until it produces more; 0 disables heartbeats
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    heartbeat: u64,
+
+    /// Report a step as possibly stalled once it goes this many seconds without output
+    #[arg(long, value_name = "SECS")]
+    stall_timeout: Option<u64>,
+
+    /// Kill steps reported as possibly stalled, failing them
+    #[arg(long, action = ArgAction::SetTrue, requires = "stall_timeout")]
+    kill_stalled: bool,
+
+    /// Help investigate crashes: give steps `RUST_BACKTRACE` and `RUST_LOG`, let steps with `ulimit_core = true` write
+    /// core dumps, and point to the backtraces and core dumps of failed steps
+    #[arg(long, action = ArgAction::SetTrue)]
+    debug_failures: bool,
+
+    /// Show the command line of each step; repeat to also list the variables each step receives and where they came from
+    #[arg(long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Send log output to the specified file.
+    #[arg(short = 'l', long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Write a Markdown summary of the run to the specified file.
+    #[arg(long, value_name = "FILE")]
+    summary_file: Option<PathBuf>,
+
+    /// Show how every step turned out for every package in the summary.
+    #[arg(long)]
+    summary_packages: bool,
+
+    /// Post the Markdown summary of the run as a comment, updated by later runs, on the branch's pull request.
+    #[arg(long, value_name = "TARGET", value_enum)]
+    post_summary: Option<SummaryTarget>,
+
+    /// Export OpenTelemetry spans for the run, its jobs, steps, and packages to the given OTLP/HTTP endpoint.
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+
+    /// Serve the live status of the run as JSON over HTTP on this local port, for IDE extensions; 0 picks a free port
+    #[arg(long, value_name = "PORT")]
+    status_port: Option<u16>,
+
+    /// Write the diagnostics of steps with `sarif = true` to the specified SARIF file [default: target/ci.sarif].
+    #[arg(long, value_name = "FILE")]
+    sarif_file: Option<PathBuf>,
+
+    /// Write job status badges to the specified directory [default: target/ci-badges].
+    #[arg(long, value_name = "DIR")]
+    badges_dir: Option<PathBuf>,
+
+    /// Number of log files to retain (default: 16).
+    #[arg(long, default_value_t = 16, value_name = "COUNT")]
+    log_file_retention_count: usize,
+
+    /// Colorize output.
+    #[arg(long, value_name = "WHEN", default_value_t = ColorModes::Auto, value_enum)]
+    color: ColorModes,
+
+    /// Language for step headings, captured output banners, statuses, and the summary; everything else is in English.
+    #[arg(long, value_name = "LANG", default_value_t = Lang::En, value_enum)]
+    lang: Lang,
+}
+
+/// Where the steps run, as forced with `--scope`.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum StepScope {
+    /// Once for every package, in the package's directory
+    Package,
+
+    /// Once for the workspace, from its root
+    Workspace,
+}
+
+/// Where the summary of a run is posted with `--post-summary`.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum SummaryTarget {
+    /// The open GitHub pull request of the checked out branch, using the token in `GITHUB_TOKEN` or `GH_TOKEN`
+    GithubPr,
+}
+
+impl RunArgs {
+    /// The names of the jobs to run, as given on the command line.
+    #[must_use]
+    pub fn jobs(&self) -> &[String] {
+        &self.jobs
+    }
+
+    /// The specs of the packages to run jobs on, as given on the command line.
+    #[must_use]
+    pub fn packages(&self) -> &[String] {
+        &self.package
+    }
+
+    /// The same arguments, running the given jobs instead.
+    #[must_use]
+    pub fn with_jobs(&self, jobs: Vec<String>) -> Self {
+        Self { jobs, ..self.clone() }
+    }
+
+    /// The git revision whose configuration and packages to use, when not those of the checkout.
+    #[must_use]
+    pub fn at(&self) -> Option<&str> {
+        self.at.as_deref()
+    }
+
+    /// The git revision to check out in a worktree of its own for the run, with `--isolated`.
+    #[must_use]
+    pub fn isolated(&self) -> Option<&str> {
+        self.isolated.as_deref()
+    }
+
+    #[must_use]
+    pub const fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// The workspaces to run jobs in, when not just the current one.
+    #[must_use]
+    pub fn workspace_dirs(&self) -> &[PathBuf] {
+        &self.workspace_dir
+    }
+
+    /// Returns an iterator over the unscoped variables defined in the command line arguments.
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &str)> + Clone {
+        self.variable
+            .iter()
+            .filter(|(k, _)| !k.contains('.'))
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns an iterator over the command-line variables that apply to a job.
+    ///
+    /// Variables scoped to the job come after the unscoped ones, so they take precedence.
+    pub fn job_variables<'a>(&'a self, job_id: &'a JobId) -> impl Iterator<Item = (&'a str, &'a str)> + Clone {
+        self.variables().chain(self.scoped_variables(job_id.as_str()))
+    }
+
+    /// Returns an iterator over the command-line variables that apply to a step of a job.
+    ///
+    /// Variables scoped to the step come after those scoped to the job, so they take precedence.
+    pub fn step_variables<'a>(&'a self, job_id: &'a JobId, step: &'a Step) -> impl Iterator<Item = (&'a str, &'a str)> + Clone {
+        self.job_variables(job_id)
+            .chain(step.id().into_iter().flat_map(|step_id| self.scoped_variables(step_id.as_str())))
+    }
+
+    /// Applies the named run selected with `--run`, if any, beneath the options given explicitly.
+    fn resolve_named_run(&self, cfg: &Config) -> anyhow::Result<Cow<'_, Self>> {
+        let Some(name) = &self.named_run else {
+            return Ok(Cow::Borrowed(self));
+        };
+
+        let run = cfg
+            .named_run(name)
+            .ok_or_else(|| Failure::UnknownJob.tag(anyhow!("run '{name}' is not defined in the [runs] section")))?;
+
+        let mut args = self.clone();
+        if args.jobs.is_empty() {
+            args.jobs = run.jobs().iter().map(ToString::to_string).collect();
+        }
+
+        if args.package.is_empty() {
+            args.package = run.packages().to_vec();
+        }
+
+        // later variables take precedence, so those from the command line go last
+        args.variable = run
+            .variables()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .chain(self.variable.iter().cloned())
+            .collect();
+
+        args.allow_destructive |= run.allow_destructive();
+        args.check_feature_unification |= run.check_feature_unification();
+        args.explain_conditions |= run.explain_conditions();
+        Ok(Cow::Owned(args))
+    }
+
+    /// How to watch over steps which go quiet.
+    const fn heartbeat(&self) -> Heartbeat {
+        Heartbeat {
+            interval: if self.heartbeat == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(self.heartbeat))
+            },
+            stall_after: match self.stall_timeout {
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => None,
+            },
+            kill_stalled: self.kill_stalled,
+            deadline: None,
+            cancellable: false,
+            overdue_after: None,
+        }
+    }
+
+    /// The seed to shuffle packages with, or `None` when packages keep their usual order.
+    fn shuffle_seed(&self, host: &impl Host) -> Option<u64> {
+        self.shuffle_seed.or_else(|| {
+            self.shuffle
+                .map(|seed| seed.unwrap_or_else(|| shuffle::seed_from_time(host.local_time())))
+        })
+    }
+
+    /// Ensures every scoped variable refers to a job or step that exists.
+    fn check_variable_scopes(&self, cfg: &Config) -> anyhow::Result<()> {
+        for (key, _) in &self.variable {
+            let Some((scope, _)) = key.split_once('.') else {
+                continue;
+            };
+
+            let known = cfg.jobs().iter().any(|(job_id, job)| {
+                job_id.as_str() == scope || job.steps().iter().any(|step| step.id().is_some_and(|id| id.as_str() == scope))
+            });
+
+            if !known {
+                return Err(Failure::UnknownJob.tag(anyhow!(
+                    "variable '{key}' is scoped to '{scope}', which is neither a job nor a step id"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn scoped_variables<'a>(&'a self, scope: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> + Clone {
+        self.variable.iter().filter_map(move |(k, v)| match k.split_once('.') {
+            Some((s, name)) if s == scope => Some((name, v.as_str())),
+            _ => None,
+        })
+    }
+}
+
+/// Parse a single key-value pair
+#[expect(clippy::string_slice, reason = "Necessary for parsing KEY=VALUE")]
+fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+    U: FromStr,
+    U::Err: Error + Send + Sync + 'static,
+{
+    let pos = s.find('=').ok_or_else(|| format!("invalid KEY=value: no '=' found in `{s}`"))?;
+    Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
+}
+
+/// State shared by every job and step executed during a run.
+struct RunContext<'a, H> {
+    args: &'a RunArgs,
+    host: &'a H,
+    cfg: &'a Config,
+    metadata: &'a Metadata,
+    packages: &'a [&'a Package],
+
+    /// The packages of the crates excluded from the workspace and of nested workspaces, which the per-package steps of
+    /// jobs with `include_excluded` also process.
+    excluded_packages: &'a [&'a Package],
+
+    env_vars: &'a HashMap<String, String>,
+
+    /// Variables defined by the `variables_from` commands of packages.
+    package_variables: &'a PackageVariables,
+
+    /// Scratch space for this run, exposed to steps as `CI_TEMP`.
+    temp_dir: &'a Path,
+
+    /// Held while running network-heavy steps from rate-limited jobs, so they never overlap.
+    network_lock: Mutex<()>,
+
+    /// Held while asking whether to run a destructive step, so concurrent jobs don't ask at the same time.
+    prompt_lock: Mutex<()>,
+
+    /// Held while running steps with a `lock` key, so steps sharing a key never overlap.
+    step_locks: KeyedLocks,
+
+    /// Held while installing a tool, by the tool's name, so concurrent jobs don't install it at the same time. These are
+    /// apart from `step_locks` so a step holding its `lock` can install a tool of the same name.
+    tool_locks: KeyedLocks,
+
+    /// The memory reserved by running steps with a `memory_hint`, so they don't start together when memory is short.
+    memory: MemoryBudget,
+
+    /// How step commands turned out, so jobs running the very same command reuse the result.
+    shared_results: SharedResults,
+
+    /// Collects the spans exported with `--otlp-endpoint`.
+    telemetry: &'a Telemetry,
+
+    /// The span covering the whole run, which job spans nest within.
+    run_span: SpanId,
+
+    /// Used to give every `codegen-check` step its own scratch directory.
+    codegen_runs: AtomicUsize,
+
+    /// Used to give the raw output saved for every failed step its own files.
+    raw_outputs: AtomicUsize,
+
+    /// Jobs which are skipped because they passed the last time they ran with the same inputs.
+    passed_jobs: HashSet<&'a JobId>,
+
+    /// The digest of the committed trees pure jobs depend on, when caching their results is possible.
+    trees: Option<String>,
+
+    /// Pure jobs which are skipped because they passed before on the same trees.
+    cached_jobs: HashSet<&'a JobId>,
+
+    /// How the steps which ran turned out, added to the run history once the run completes.
+    step_records: Mutex<Vec<StepRecord>>,
+
+    /// The outcomes of the tests run by steps, reported in the summary.
+    test_results: Mutex<Vec<TestResult>>,
+
+    /// The diagnostics of steps with `sarif = true`, written to a SARIF file once the run completes.
+    sarif_findings: Mutex<Vec<Finding>>,
+
+    /// When the run's time budget runs out, after which no more steps are started.
+    deadline: Option<Instant>,
+
+    /// Whether the time budget cut the run short, by keeping steps from starting or killing them.
+    out_of_time: AtomicBool,
+
+    /// The jobs which were cancelled with `cargo ci cancel`, whose dependents don't run.
+    cancelled_jobs: Mutex<HashSet<JobId>>,
+
+    /// How the jobs which finished so far turned out, for the jobs consuming their artifacts.
+    job_statuses: Mutex<HashMap<JobId, JobStatus>>,
+
+    /// The steps and packages which succeeded so far, including those of the run resumed with `--resume`.
+    progress: Mutex<RunProgress>,
+
+    /// What the run is up to, as served by the status endpoint.
+    live_status: Arc<Mutex<LiveStatus>>,
+
+    /// Whether the terminal's title shows what the run is up to, as asked for by the `[notifications]` table.
+    show_title: bool,
+
+    /// The platform conditions and operating system restrictions are evaluated for.
+    platform: Platform,
+
+    /// Whether steps with `network = false` can be kept off the network on this system.
+    network_isolation: bool,
+}
+
+impl<'a, H: Host> RunContext<'a, H> {
+    /// Points cargo at the run's target directory, which runs with `--isolated` keep apart from the checkout's, even
+    /// when `CARGO_TARGET_DIR` says otherwise.
+    fn use_target_dir(&self, cmd: &mut Command) {
+        if self.args.isolated.is_some() {
+            _ = cmd.env("CARGO_TARGET_DIR", self.metadata.target_directory.as_std_path());
+        }
+    }
+
+    fn env_vars(&self) -> impl Iterator<Item = (&'a str, &'a str)> + use<'a, H> {
+        self.env_vars.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The variables of a package, from its metadata followed by those from its `variables_from` command.
+    ///
+    /// Packages which aren't trusted have no variables.
+    fn package_variables(&self, pkg: &'a Package) -> impl Iterator<Item = (&'a str, &'a str)> + use<'a, H> {
+        let trusted = is_trusted(pkg, self.metadata.workspace_root.as_std_path(), self.cfg.trusted_packages());
+        variables(pkg).filter(move |_| trusted).chain(
+            self.package_variables
+                .get(&pkg.id)
+                .into_iter()
+                .flatten()
+                .map(|(k, v)| (k.as_str(), v.as_str())),
+        )
+    }
+
+    /// The command a step runs for a package, which the package's `[package.metadata.ci.overrides]` table may replace
+    /// or add arguments to when the package is trusted.
+    fn step_command(&self, step: &Step, pkg: Option<&Package>) -> Option<String> {
+        let (pkg, id) = (pkg?, step.id()?);
+        if !is_trusted(pkg, self.metadata.workspace_root.as_std_path(), self.cfg.trusted_packages()) {
+            return None;
+        }
+
+        step_command(pkg, id.as_str(), &step.command())
+    }
+
+    /// Whether the run's time budget ran out, in which case whatever was about to start doesn't and the run is
+    /// marked as cut short.
+    fn out_of_time(&self) -> bool {
+        let out_of_time = self.deadline.is_some_and(|deadline| self.host.now() >= deadline);
+        if out_of_time {
+            self.out_of_time.store(true, Ordering::Relaxed);
+        }
+
+        out_of_time
+    }
+
+    /// Whether cancelling a job was asked for with `cargo ci cancel`.
+    fn cancel_requested(&self, job_id: &JobId) -> bool {
+        !self.args.dry_run && cancellation::is_requested(self.temp_dir, job_id.as_str())
+    }
+
+    fn out_of_time_error(&self) -> anyhow::Error {
+        let budget = self.cfg.max_run_duration().map(|budget| budget.to_string()).unwrap_or_default();
+        anyhow!("the run went over its time budget of {budget}")
+    }
+
+    /// The command an invocation of a step runs in place of the step's own, for batches of packages and packages which
+    /// override the step's command.
+    fn invocation_command(&self, step: &Step, invocation: &Invocation<'_>) -> Option<String> {
+        if invocation.batch.is_empty() {
+            self.step_command(step, invocation.pkg)
+        } else {
+            Some(batch_command(&step.command(), &invocation.batch))
+        }
+    }
+
+    /// The command lines an invocation of a step falls back to when its command fails, for each batch of packages,
+    /// which packages replacing the step's command don't have.
+    fn fallback_commands(&self, step: &Step, invocation: &Invocation<'_>) -> Vec<String> {
+        if !invocation.batch.is_empty() {
+            return step
+                .fallbacks()
+                .iter()
+                .map(|fallback| batch_command(fallback, &invocation.batch))
+                .collect();
+        }
+
+        if self.step_command(step, invocation.pkg).is_some() {
+            return Vec::new();
+        }
+
+        step.fallbacks().to_vec()
+    }
+
+    /// Delays a network-heavy step as needed to honor its job's rate limit.
+    ///
+    /// The returned guard serializes network-heavy steps across concurrently running jobs and must be held while the step runs.
+    fn throttle_network(&self, outputter: &Outputter<H>, step: &Step, bucket: &mut Option<TokenBucket>) -> Option<MutexGuard<'_, ()>> {
+        let bucket = bucket.as_mut()?;
+        if !step.is_network_heavy() {
+            return None;
+        }
+
+        let guard = self.network_lock.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let delay = bucket.take(self.host.now());
+        if !delay.is_zero() {
+            outputter.message(format!("waiting {} to honor the network rate limit", format_duration(delay)));
+            self.host.sleep(delay);
+        }
+
+        Some(guard)
+    }
+
+    /// Whether a step runs for every package, as `--scope` forces for the steps which run a command, except in jobs
+    /// with a working directory of their own, whose steps always run once in it.
+    fn per_package(&self, job: &Job, step: &Step) -> bool {
+        match self.args.scope {
+            _ if job.working_directory().is_some() => false,
+            Some(scope) if step.kind() == StepKind::Command => scope == StepScope::Package,
+            _ => step.per_package(),
+        }
+    }
+
+    fn progress(&self) -> MutexGuard<'_, RunProgress> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Notes that a step succeeded, for all of its packages or only for the given ones, saving the progress right away
+    /// so that `--resume` picks up from there even when the run is killed.
+    fn record_progress(&self, outputter: &Outputter<H>, job_id: &JobId, step: usize, packages: Option<&[&str]>) {
+        if self.args.dry_run || packages.is_some_and(<[&str]>::is_empty) {
+            return;
+        }
+
+        let mut progress = self.progress();
+        match packages {
+            Some(packages) => {
+                for package in packages {
+                    progress.complete_package(job_id.as_str(), step, package);
+                }
+            }
+            None => progress.complete_step(job_id.as_str(), step),
+        }
+
+        if let Err(e) = progress.save(&RunProgress::path(self.metadata.target_directory.as_std_path())) {
+            outputter.message(format!("WARNING: {e:#}, so --resume won't know about it"));
+        }
+    }
+
+    /// Whether a step succeeded for all the given packages in the run resumed with `--resume`, in which case it's
+    /// skipped for them.
+    fn resumed_packages(&self, outputter: &Outputter<H>, job_id: &JobId, (index, step): (usize, &Step), packages: &[&str]) -> bool {
+        let progress = self.progress();
+        let resumed = !packages.is_empty()
+            && packages
+                .iter()
+                .all(|package| progress.package_completed(job_id.as_str(), index, package));
+
+        if resumed {
+            outputter.message(format!(
+                "step '{}' skipped for {} because it succeeded in the resumed run",
+                step.name(),
+                packages.join(", ")
+            ));
+        }
+
+        resumed
+    }
+
+    fn live_status(&self) -> MutexGuard<'_, LiveStatus> {
+        self.live_status.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Shows what the run is up to in the terminal's title, when it's asked to.
+    fn update_title(&self) {
+        if self.show_title {
+            let title = self.live_status().title();
+            terminal_notifications::set_title(self.host, &title);
+        }
+    }
+
+    /// Notes how a step turned out, for the run history and the status endpoint. Sharded steps also note how long they
+    /// took for each package, which `--save-shard-durations` saves.
+    fn record_step(&self, job_id: &JobId, step: &Step, result: &anyhow::Result<StepOutcome>, started: Instant, packages: &[PackageResult]) {
+        let failed = match result {
+            Ok(StepOutcome::Skipped) => {
+                self.live_status().finish_step(job_id.as_str(), step.name(), StepStatus::Skipped);
+                return;
+            }
+            Ok(StepOutcome::Success) => false,
+            Ok(StepOutcome::Failure) | Err(_) => true,
+        };
+
+        let status = if failed { StepStatus::Failed } else { StepStatus::Succeeded };
+        self.live_status().finish_step(job_id.as_str(), step.name(), status);
+
+        if self.args.dry_run {
+            return;
+        }
+
+        self.step_records.lock().unwrap_or_else(PoisonError::into_inner).push(StepRecord {
+            job: job_id.to_string(),
+            step: shard_step_key(step),
+            failed,
+            duration_ms: u64::try_from(self.host.now().duration_since(started).as_millis()).ok(),
+            package_durations_ms: packages
+                .iter()
+                .filter(|_| step.shards().is_some())
+                .filter_map(|result| Some((result.package.clone(), u64::try_from(result.duration?.as_millis()).ok()?)))
+                .collect(),
+        });
+    }
+
+    /// Evaluates a condition, explaining the outcome when requested on the command line.
+    fn evaluate_conditional<'v>(
+        &self,
+        outputter: &Outputter<H>,
+        description: &str,
+        conditional: &Conditional,
+        variables: impl IntoIterator<Item = (&'v str, &'v str, VariableSource)>,
+    ) -> anyhow::Result<bool> {
+        let variables: Vec<_> = variables.into_iter().collect();
+        let result = conditional.evaluate(variables.iter().map(|(k, v, _)| (k, v)));
+
+        if self.args.explain_conditions {
+            explain_conditional(outputter, description, conditional, &variables, &result);
+        }
+
+        result
+    }
+}
+
+fn explain_conditional<H: Host>(
+    outputter: &Outputter<H>,
+    description: &str,
+    conditional: &Conditional,
+    variables: &[(&str, &str, VariableSource)],
+    result: &anyhow::Result<bool>,
+) {
+    let outcome = match result {
+        Ok(value) => value.to_string(),
+        Err(e) => format!("error: {e:#}"),
+    };
+
+    let Some(expr) = conditional.expression() else {
+        outputter.detail(format!("{description}: constant => {outcome}"));
+        return;
+    };
+
+    outputter.detail(format!("{description}: `{}` => {outcome}", expr.text()));
+    for name in expr.variable_identifiers() {
+        // later definitions take precedence over earlier ones
+        match variables.iter().rev().find(|(k, _, _)| *k == name) {
+            Some((_, value, source)) => outputter.detail(format!("  {name} = '{value}' (from {source})")),
+            None => outputter.detail(format!("  {name} is not defined")),
+        }
+    }
+}
+
+/// Labels each variable with where it came from.
+fn tagged<'v>(
+    source: VariableSource,
+    variables: impl Iterator<Item = (&'v str, &'v str)>,
+) -> impl Iterator<Item = (&'v str, &'v str, VariableSource)> {
+    variables.map(move |(k, v)| (k, v, source))
+}
+
+/// A run of the jobs of one workspace, whose summary still needs to be reported.
+struct WorkspaceRun {
+    log: Log,
+    summary: RunSummary,
+    outcome: anyhow::Result<()>,
+    started: DateTime<Local>,
+    temp_dir: PathBuf,
+}
+
+pub fn run_jobs<H: Host>(args: &RunArgs, host: &mut H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<()> {
+    let run = run_workspace(args, host, cfg, metadata)?;
+    let outputter = Outputter::new(&*host, &run.log, args.color, args.lang);
+    print_summary(&outputter, &run.summary, args.summary_packages);
+
+    let keep_temp_dir = clean_up_temp_dir(args, &outputter, &run.summary, &run.temp_dir);
+    write_summary_file(args, &*host, &outputter, &run, metadata.workspace_root.as_std_path(), keep_temp_dir);
+    notify_completion(args, &*host, cfg, &run.summary, run.outcome.is_ok(), run.started);
+    run.outcome
+}
+
+/// Runs the same jobs in several workspaces, one after the other, each with its own configuration, and reports a
+/// single summary covering all of them.
+///
+/// Workspaces are labeled with the given names, which prefix the names of their jobs and stages in the summary.
+pub fn run_workspaces<H: Host>(args: &RunArgs, host: &mut H, workspaces: &[(String, Config, Metadata)]) -> anyhow::Result<()> {
+    let started = host.local_time();
+    let mut combined = RunSummary::default();
+    let mut outcome = Ok(());
+    let mut last_run = None;
+
+    for (label, cfg, metadata) in workspaces {
+        host.println(format!("Workspace '{label}' ({})", metadata.workspace_root));
+        let run = match run_workspace(args, host, cfg, metadata) {
+            Ok(run) => run,
+            Err(e) => {
+                combined.warnings.push(format!("workspace '{label}' didn't run: {e:#}"));
+                outcome = Err(Failure::carry(
+                    &e,
+                    Diagnostic::carry(&e, anyhow!("unable to run the jobs of workspace '{label}': {e:#}")),
+                ));
+                continue;
+            }
+        };
+
+        _ = clean_up_temp_dir(
+            args,
+            &Outputter::new(&*host, &run.log, args.color, args.lang),
+            &run.summary,
+            &run.temp_dir,
+        );
+
+        combined.stages.extend(run.summary.stages.iter().cloned().map(|mut stage| {
+            stage.name = stage.name.map(|name| format!("{label}: {name}"));
+            for job in &mut stage.jobs {
+                job.name = format!("{label}: {}", job.name);
+            }
+
+            stage
+        }));
+        combined
+            .warnings
+            .extend(run.summary.warnings.iter().map(|warning| format!("{label}: {warning}")));
+        combined.tests.extend(run.summary.tests.iter().cloned().map(|mut test| {
+            test.binary = Some(test.binary.map_or_else(|| label.clone(), |binary| format!("{label}: {binary}")));
+            test
+        }));
+
+        if let Err(e) = &run.outcome
+            && outcome.is_ok()
+        {
+            outcome = Err(Failure::carry(e, Diagnostic::carry(e, anyhow!("{label}: {e:#}"))));
+        }
+
+        last_run = Some(run);
+    }
+
+    // the combined summary goes to the log of the last workspace, along with its own output
+    let (Some(mut run), Some((_, cfg, metadata))) = (last_run, workspaces.last()) else {
+        for warning in &combined.warnings {
+            host.eprintln(format!("WARNING: {warning}"));
+        }
+
+        return outcome;
+    };
+
+    run.summary = combined;
+    let outputter = Outputter::new(&*host, &run.log, args.color, args.lang);
+    print_summary(&outputter, &run.summary, args.summary_packages);
+    write_summary_file(args, &*host, &outputter, &run, metadata.workspace_root.as_std_path(), false);
+    notify_completion(args, &*host, cfg, &run.summary, outcome.is_ok(), started);
+    outcome
+}
+
+/// Lets the user know the run is over through the terminal, as set in the `[notifications]` table.
+fn notify_completion<H: Host>(args: &RunArgs, host: &H, cfg: &Config, summary: &RunSummary, succeeded: bool, started: DateTime<Local>) {
+    if args.dry_run || !host.is_terminal() {
+        return;
+    }
+
+    let elapsed = (host.local_time() - started).to_std().unwrap_or_default();
+    terminal_notifications::notify_completion(host, cfg.notifications(), summary, succeeded, elapsed);
+}
+
+/// Serializes runs in the same workspace, since concurrent builds trample on each other in the target directory.
+///
+/// Holding the lock also means no other run is going on, so a run whose log says it never finished must have been
+/// killed or have crashed, which is reported before this run starts.
+fn lock_workspace<H: Host>(args: &RunArgs, host: &H, metadata: &Metadata) -> anyhow::Result<Option<RunLock>> {
+    if args.dry_run || args.no_lock {
+        return Ok(None);
+    }
+
+    let target_dir = metadata.target_directory.as_std_path();
+    let lock = lock_target_dir(host, target_dir, args.no_wait)?;
+
+    if args.log_file.is_none() {
+        report_unfinished_run(host, target_dir);
+    }
+
+    Ok(Some(lock))
+}
+
+/// Takes the lock keeping runs in a workspace from happening concurrently, along with anything else changing its
+/// target directory underneath them.
+pub fn lock_target_dir<H: Host>(host: &H, target_dir: &Path, no_wait: bool) -> anyhow::Result<RunLock> {
+    RunLock::acquire(&target_dir.join("cargo-ci.lock"), no_wait, || {
+        host.eprintln("Waiting for another cargo-ci run in this workspace to finish...");
+    })
+}
+
+/// Reports the previous run when it never finished, offering to show the end of its log, and records it in the run
+/// history as crashed.
+fn report_unfinished_run<H: Host>(host: &H, target_dir: &Path) {
+    let Some(path) = log::unfinished(target_dir, "run") else {
+        return;
+    };
+
+    host.eprintln(format!(
+        "WARNING: the previous run never finished, it was killed or crashed, see its log at {}",
+        path.display()
+    ));
+
+    if host.is_terminal() {
+        host.eprintln("Show the end of its log? [y/N]");
+        let answer = host.read_line().unwrap_or_default();
+        if matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+            match log::tail(&path, UNFINISHED_LOG_TAIL) {
+                Ok(lines) => lines.iter().for_each(|line| host.eprintln(format!("  {line}"))),
+                Err(e) => host.eprintln(format!("Unable to read {}: {e}", path.display())),
+            }
+        }
+    }
+
+    // the log was last written to when the run stopped
+    let stopped: Option<DateTime<Local>> = fs::metadata(&path).and_then(|meta| meta.modified()).ok().map(Into::into);
+    let record = RunRecord {
+        timestamp: stopped.unwrap_or_else(|| host.local_time()).to_rfc3339(),
+        crashed: true,
+        ..RunRecord::default()
+    };
+
+    if let Err(e) = log::note_unfinished(&path) {
+        host.eprintln(format!("WARNING: unable to mark {} as unfinished: {e}", path.display()));
+    }
+
+    if let Err(e) = History::append(&History::path(target_dir), record) {
+        host.eprintln(format!("WARNING: unable to record the unfinished run in the run history: {e:#}"));
+    }
+}
+
+/// Runs the selected jobs of a workspace, leaving it to the caller to report the outcome.
+fn run_workspace<H: Host>(args: &RunArgs, host: &mut H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<WorkspaceRun> {
+    let args = &*args.resolve_named_run(cfg)?;
+    let jobs = select_jobs(args, cfg)?;
+    args.check_variable_scopes(cfg)?;
+    save_shard_durations(args, cfg, metadata, &jobs)?;
+    let (packages, shuffle_seed) = select_shuffled_packages(args, &*host, metadata)?;
+    let excluded_workspaces = load_excluded_workspaces(args, &*host, cfg, metadata, &jobs)?;
+
+    let package_variables = load_package_variables(&*host, cfg, metadata, &packages)?;
+
+    let env_vars = expression_env_vars(&*host, cfg, metadata, None);
+
+    let _lock = lock_workspace(args, &*host, metadata)?;
+
+    let started = host.local_time();
+    let log_prefix = if args.dry_run { "dry-run" } else { "run" };
+    let log = Log::new(
+        metadata.target_directory.as_std_path(),
+        log_prefix,
+        started,
+        args.log_file.as_deref(),
+        args.log_file_retention_count,
+    )?;
+
+    let run_id = format!("{}-{}", started.format("%Y%m%dT%H%M%S"), std::process::id());
+    let temp_dir = create_temp_dir(args, metadata, &run_id)?;
+
+    let telemetry = Telemetry::new(args.otlp_endpoint.is_some() && !args.dry_run, started);
+    let run_span = telemetry.start(None, "cargo ci run", started);
+
+    let mut summary = RunSummary::default();
+    let fingerprint = run_fingerprint(args, &*host, cfg, metadata);
+    let variables = input_variables(&*host, cfg, &package_variables);
+    let inputs = digest_inputs(args, &fingerprint, cfg, metadata, (&packages, &variables), &mut summary);
+    let passed_jobs = passed_jobs(args, cfg, metadata, inputs.as_deref(), &jobs)?;
+    let trees = digest_trees(args, &*host, cfg, metadata, &fingerprint, &packages, &mut summary);
+    let cached_jobs = cached_jobs(cfg, metadata, trees.as_deref(), &jobs)?;
+
+    let network_isolation = check_network_isolation(&*host, cfg, &jobs, &mut summary);
+
+    // after this point, thia code takes care of error reporting itself
+    host.fail_silently();
+
+    let ctx = RunContext {
+        args,
+        host: &*host,
+        cfg,
+        metadata,
+        packages: &packages,
+        excluded_packages: &excluded_packages(&excluded_workspaces),
+        env_vars: &env_vars,
+        package_variables: &package_variables,
+        temp_dir: &temp_dir,
+        network_lock: Mutex::new(()),
+        prompt_lock: Mutex::new(()),
+        step_locks: KeyedLocks::default(),
+        tool_locks: KeyedLocks::default(),
+        memory: MemoryBudget::detect(&*host),
+        shared_results: SharedResults::default(),
+        telemetry: &telemetry,
+        run_span: run_span.id(),
+        codegen_runs: AtomicUsize::new(0),
+        raw_outputs: AtomicUsize::new(0),
+        passed_jobs,
+        trees,
+        cached_jobs,
+        step_records: Mutex::new(Vec::new()),
+        test_results: Mutex::new(Vec::new()),
+        sarif_findings: Mutex::new(Vec::new()),
+        deadline: run_deadline(args, &*host, cfg),
+        out_of_time: AtomicBool::new(false),
+        cancelled_jobs: Mutex::new(HashSet::new()),
+        job_statuses: Mutex::new(HashMap::new()),
+        progress: Mutex::new(load_progress(args, cfg, metadata, &mut summary)),
+        live_status: Arc::new(Mutex::new(new_live_status(cfg, &run_id, &jobs))),
+        show_title: cfg.notifications().title() && host.is_terminal() && !args.dry_run,
+        platform: Platform::current(),
+        network_isolation,
+    };
+
+    let outputter = Outputter::new(ctx.host, &log, args.color, args.lang);
+
+    report_shuffle_seed(&outputter, shuffle_seed);
+    report_run_id(args, &outputter, &run_id, jobs.len());
+    serve_live_status(&ctx, &log, &outputter);
+
+    summary.warnings.extend(untrusted_package_warnings(
+        &packages,
+        metadata.workspace_root.as_std_path(),
+        cfg.trusted_packages(),
+    ));
+
+    if args.check_feature_unification {
+        summary.warnings.extend(check_feature_unification(&ctx, &outputter, &jobs));
+    }
+
+    let mut outcome = Ok(());
+    run_stages(&ctx, &outputter, &jobs, &mut summary, &mut outcome);
+
+    cancellation::stop_accepting_requests(&temp_dir);
+    finish_run(&ctx, &jobs, &mut summary, &mut outcome);
+    export_spans(args, &*host, &telemetry, (run_span, &temp_dir), &outcome, &mut summary);
+
+    if !args.dry_run {
+        update_history(&ctx, fingerprint, inputs, &mut summary);
+        write_status_badges(&ctx, &mut summary);
+    }
+
+    Ok(WorkspaceRun {
+        log,
+        summary,
+        outcome,
+        started,
+        temp_dir,
+    })
+}
+
+/// Runs the jobs stage by stage, adding how each stage went to the summary. The terminal's title shows what the run
+/// is up to meanwhile, when it's asked to, and gets back the title it had before once the jobs are done.
+fn run_stages<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    jobs: &[&JobId],
+    summary: &mut RunSummary,
+    outcome: &mut anyhow::Result<()>,
+) {
+    if ctx.show_title {
+        terminal_notifications::save_title(ctx.host);
+        ctx.update_title();
+    }
+
+    for (stage, stage_jobs) in group_by_stage(ctx.cfg, jobs) {
+        let start = ctx.host.now();
+
+        // jobs within a declared stage run concurrently, everything else runs one job at a time
+        let job_results = if stage.is_some() {
+            run_concurrently(ctx, outputter, &stage_jobs, outcome)
+        } else {
+            run_sequentially(ctx, outputter, &stage_jobs, outcome)
+        };
+
+        summary.stages.push(StageResult {
+            name: stage.map(ToString::to_string),
+            duration: ctx.host.now().duration_since(start),
+            jobs: job_results,
+        });
+    }
+
+    if ctx.show_title {
+        terminal_notifications::restore_title(ctx.host);
+    }
+}
+
+/// Gathers what the jobs left behind into the summary once they're all done.
+fn finish_run<H: Host>(ctx: &RunContext<'_, H>, jobs: &[&JobId], summary: &mut RunSummary, outcome: &mut anyhow::Result<()>) {
+    cache_results(ctx, summary);
+    check_cancelled_jobs(summary, outcome);
+    if outcome.is_ok()
+        && !ctx.args.dry_run
+        && let Err(e) = RunProgress::remove(&RunProgress::path(ctx.metadata.target_directory.as_std_path()))
+    {
+        summary.warnings.push(format!("{e:#}"));
+    }
+
+    summary.tests = mem::take(&mut *ctx.test_results.lock().unwrap_or_else(PoisonError::into_inner));
+    summary.time_budget = ctx.cfg.max_run_duration().filter(|_| ctx.out_of_time.load(Ordering::Relaxed));
+    write_sarif_file(ctx, jobs, summary);
+}
+
+/// Loads the progress of the last run to pick up from with `--resume`, or else starts afresh, forgetting that progress.
+fn load_progress(args: &RunArgs, cfg: &Config, metadata: &Metadata, summary: &mut RunSummary) -> RunProgress {
+    let path = RunProgress::path(metadata.target_directory.as_std_path());
+    let fresh = RunProgress::new(cfg.text());
+    if !args.resume {
+        if !args.dry_run
+            && let Err(e) = RunProgress::remove(&path)
+        {
+            summary.warnings.push(format!("{e:#}"));
+        }
+
+        return fresh;
+    }
+
+    match RunProgress::load(&path) {
+        Ok(Some(progress)) if progress.matches_config(cfg.text()) => progress,
+        Ok(Some(_)) => {
+            summary
+                .warnings
+                .push("the configuration changed since the last run, so --resume ran every step".to_string());
+            fresh
+        }
+        Ok(None) => {
+            summary
+                .warnings
+                .push("there was no unfinished run to resume, so --resume ran every step".to_string());
+            fresh
+        }
+        Err(e) => {
+            summary.warnings.push(format!("{e:#}, so --resume ran every step"));
+            fresh
+        }
+    }
+}
+
+fn new_live_status(cfg: &Config, run_id: &str, jobs: &[&JobId]) -> LiveStatus {
+    LiveStatus::new(
+        run_id,
+        jobs.iter().map(|job_id| {
+            let job = cfg.jobs().get_job(job_id).expect("job not found");
+            let steps = job.steps().iter().map(|step| step.name().to_string()).collect();
+            (job_id.as_str(), job.name().unwrap_or(job_id.as_str()), steps)
+        }),
+    )
+}
+
+/// Starts the status endpoint asked for on the command line, if any.
+fn serve_live_status<H: Host>(ctx: &RunContext<'_, H>, log: &Log, outputter: &Outputter<H>) {
+    let Some(port) = ctx.args.status_port.filter(|_| !ctx.args.dry_run) else {
+        return;
+    };
+
+    match live_status::serve(port, &ctx.live_status, log.tail()) {
+        Ok(address) => outputter.line(format!("Serving the status of the run at http://{address}/status")),
+        Err(e) => outputter.line(format!("Unable to serve the status of the run on port {port}: {e}")),
+    }
+}
+
+/// Fails a run in which jobs were cancelled, once the rest of the run is done, unless it failed for another reason.
+fn check_cancelled_jobs(summary: &RunSummary, outcome: &mut anyhow::Result<()>) {
+    let cancelled: Vec<_> = summary
+        .jobs()
+        .filter(|job| job.status == JobStatus::Cancelled)
+        .map(|job| format!("'{}'", job.id))
+        .collect();
+
+    if !cancelled.is_empty() && outcome.is_ok() {
+        *outcome = Err(Failure::Cancelled.tag(anyhow!("job(s) {} were cancelled", cancelled.join(", "))));
+    }
+}
+
+fn write_status_badges<H: Host>(ctx: &RunContext<'_, H>, summary: &mut RunSummary) {
+    let badges_dir = ctx
+        .args
+        .badges_dir
+        .clone()
+        .unwrap_or_else(|| ctx.metadata.target_directory.as_std_path().join("ci-badges"));
+    if let Err(e) = write_badges(&badges_dir, ctx.host.local_time(), &summary.jobs().collect::<Vec<_>>()) {
+        summary.warnings.push(format!("unable to write status badges: {e:#}"));
+    }
+}
+
+/// Writes the diagnostics of the steps with `sarif = true` to the run's SARIF file, when any of the selected jobs has
+/// such steps, so a run which found nothing leaves an empty file rather than a stale one.
+fn write_sarif_file<H: Host>(ctx: &RunContext<'_, H>, jobs: &[&JobId], summary: &mut RunSummary) {
+    let has_sarif_steps = jobs
+        .iter()
+        .filter_map(|job_id| ctx.cfg.jobs().get_job(job_id))
+        .any(|job| job.steps().iter().any(Step::sarif));
+    if ctx.args.dry_run || !has_sarif_steps {
+        return;
+    }
+
+    let path = ctx
+        .args
+        .sarif_file
+        .clone()
+        .unwrap_or_else(|| ctx.metadata.target_directory.as_std_path().join("ci.sarif"));
+    let findings = mem::take(&mut *ctx.sarif_findings.lock().unwrap_or_else(PoisonError::into_inner));
+    if let Err(e) = sarif::write(&path, ctx.metadata.workspace_root.as_std_path(), &findings) {
+        summary.warnings.push(format!("unable to write SARIF file: {e:#}"));
+    }
+}
+
+/// Writes the Markdown summary of the run to the file given with `--summary-file`, and posts it where `--post-summary`
+/// asks to, going by the repository `workspace_root` is in.
+fn write_summary_file<H: Host>(
+    args: &RunArgs,
+    host: &H,
+    outputter: &Outputter<H>,
+    run: &WorkspaceRun,
+    workspace_root: &Path,
+    keep_temp_dir: bool,
+) {
+    let markdown = || {
+        render_markdown(
+            &run.summary,
+            &run.started.format("%Y-%m-%d %H:%M:%S").to_string(),
+            run.log.path(),
+            keep_temp_dir.then_some(run.temp_dir.as_path()),
+            args.summary_packages,
+        )
+    };
+
+    if let Some(path) = &args.summary_file
+        && let Err(e) = fs::write(path, markdown())
+    {
+        outputter.line(format!("Unable to write summary file {}: {e}", path.display()));
+    }
+
+    // a dry run has nothing to report to reviewers
+    if args.post_summary == Some(SummaryTarget::GithubPr) && !args.dry_run {
+        let scratch_dir = run.temp_dir.parent().unwrap_or(&run.temp_dir);
+        match github_pr::post_summary(host, workspace_root, scratch_dir, &markdown()) {
+            Ok(url) => outputter.line(format!("Posted the summary to {url}")),
+            Err(e) => outputter.line(format!("Unable to post the summary to the pull request: {e:#}")),
+        }
+    }
+}
+
+/// Adds how the steps of the run turned out to the run history, warning about the steps which look flaky and about
+/// changes to the environment since the previous run.
+fn update_history<H: Host>(ctx: &RunContext<'_, H>, fingerprint: Fingerprint, inputs: Option<String>, summary: &mut RunSummary) {
+    let records = mem::take(&mut *ctx.step_records.lock().unwrap_or_else(PoisonError::into_inner));
+    if records.is_empty() {
+        return;
+    }
+
+    let ran: Vec<_> = records.iter().map(|record| (record.job.clone(), record.step.clone())).collect();
+    let path = History::path(ctx.metadata.target_directory.as_std_path());
+
+    // a history which can't be read is reported once appending to it fails too
+    let previous = History::load(&path).ok();
+    if let Some(previous) = previous.as_ref().and_then(History::last_fingerprint) {
+        let changes = fingerprint.changes_since(previous);
+        if !changes.is_empty() {
+            summary.warnings.push(format!(
+                "the environment changed since the previous run ({}), so outcomes recorded by earlier runs may not hold anymore",
+                changes.join(", ")
+            ));
+        }
+    }
+
+    let passed = summary
+        .jobs()
+        .filter(|job| job.status == JobStatus::Succeeded)
+        .map(|job| job.id.clone())
+        .chain(ctx.passed_jobs.iter().map(ToString::to_string))
+        .collect();
+
+    let run = RunRecord {
+        timestamp: ctx.host.local_time().to_rfc3339(),
+        steps: records,
+        fingerprint: Some(fingerprint),
+        inputs,
+        passed,
+        target_size: check_target_dir_budget(ctx, previous.as_ref(), summary),
+        crashed: false,
+    };
+
+    let history = match History::append(&path, run) {
+        Ok(history) => history,
+        Err(e) => {
+            summary.warnings.push(format!("unable to update the run history: {e:#}"));
+            return;
+        }
+    };
+
+    if let Some(previous) = &previous {
+        check_slow_steps(ctx, previous, &history, summary);
+    }
+
+    for stats in history.step_stats(DEFAULT_WINDOW) {
+        if stats.is_flaky() && ran.contains(&(stats.job.clone(), stats.step.clone())) {
+            summary.warnings.push(format!(
+                "step '{}' of job '{}' looks flaky, its outcome flipped {} time(s) over its last {} run(s), see `cargo ci flaky`",
+                stats.step, stats.job, stats.flips, stats.runs
+            ));
+        }
+    }
+}
+
+/// Flags the steps of the run which took much longer than they're expected to, or than they usually do according to the
+/// runs before this one.
+fn check_slow_steps<H: Host>(ctx: &RunContext<'_, H>, previous: &History, history: &History, summary: &mut RunSummary) {
+    for record in history.last_run_steps() {
+        let Some(duration) = record.duration_ms.map(Duration::from_millis) else {
+            continue;
+        };
+
+        let expected = ctx
+            .cfg
+            .jobs()
+            .iter()
+            .find(|(job_id, _)| job_id.as_str() == record.job)
+            .and_then(|(_, job)| {
+                job.steps().iter().find(|step| {
+                    step.id()
+                        .map_or_else(|| step.name() == record.step, |id| id.as_str() == record.step)
+                })
+            })
+            .and_then(Step::expected_duration);
+
+        let (job, step, took) = (&record.job, &record.step, format_duration(duration));
+        if let Some(expected) = expected
+            && duration > expected.duration() * SLOW_STEP_FACTOR
+        {
+            summary.warnings.push(format!(
+                "step '{step}' of job '{job}' took {took}, more than {SLOW_STEP_FACTOR} times its expected duration of {expected}"
+            ));
+        } else if let Some(typical) = previous.typical_duration(job, step, DEFAULT_WINDOW)
+            && duration > typical * SLOW_STEP_FACTOR
+            && duration >= MIN_SLOW_STEP_DURATION
+        {
+            summary.warnings.push(format!(
+                "step '{step}' of job '{job}' took {took}, more than {SLOW_STEP_FACTOR} times as long as it usually does ({})",
+                format_duration(typical)
+            ));
+        }
+    }
+}
+
+/// Measures the target directory once the jobs are done, warning when it takes up more space than its budget allows.
+fn check_target_dir_budget<H: Host>(ctx: &RunContext<'_, H>, previous: Option<&History>, summary: &mut RunSummary) -> Option<u64> {
+    let size = match target_dir::size(ctx.metadata.target_directory.as_std_path()) {
+        Ok(size) => size,
+        Err(e) => {
+            summary.warnings.push(format!("unable to measure the target directory: {e:#}"));
+            return None;
+        }
+    };
+
+    if let Some(budget) = ctx.cfg.target_dir_budget()
+        && size > budget.bytes()
+    {
+        let growth = previous
+            .and_then(History::last_target_size)
+            .filter(|previous| size > *previous)
+            .map_or_else(String::new, |previous| {
+                format!(", up {} since the previous run", ByteSize::new(size - previous))
+            });
+
+        summary.warnings.push(format!(
+            "the target directory takes up {}{growth}, over its budget of {budget}, see `cargo ci clean --target-gc` or `cargo clean`",
+            ByteSize::new(size)
+        ));
+    }
+
+    Some(size)
+}
+
+/// Takes the fingerprint of the environment the jobs run in.
+fn run_fingerprint<H: Host>(args: &RunArgs, host: &H, cfg: &Config, metadata: &Metadata) -> Fingerprint {
+    Fingerprint::compute(
+        host,
+        metadata.workspace_root.as_std_path(),
+        cfg,
+        cfg.variables().chain(args.variable.iter().map(|(k, v)| (k.as_str(), v.as_str()))),
+    )
+}
+
+/// Digests the inputs of the run for `--since-last-green`, which is all they're used for, warning when that isn't
+/// possible.
+fn digest_inputs(
+    args: &RunArgs,
+    fingerprint: &Fingerprint,
+    cfg: &Config,
+    metadata: &Metadata,
+    (packages, variables): (&[&Package], &BTreeMap<String, String>),
+    summary: &mut RunSummary,
+) -> Option<String> {
+    if !args.since_last_green {
+        return None;
+    }
+
+    match fingerprint.digest_inputs(cfg, metadata, packages, variables) {
+        Ok(inputs) => Some(inputs),
+        Err(e) => {
+            summary.warnings.push(format!(
+                "unable to digest the inputs of the run, so --since-last-green can't skip its jobs: {e:#}"
+            ));
+            None
+        }
+    }
+}
+
+/// The variables a run's outcome depends on which its fingerprint leaves out: those let through from the environment,
+/// and those computed by the `variables_from` commands of packages.
+fn input_variables<H: Host>(host: &H, cfg: &Config, package_variables: &PackageVariables) -> BTreeMap<String, String> {
+    let mut variables: BTreeMap<String, String> = passthrough_env_vars(host, cfg)
+        .into_iter()
+        .map(|(name, value)| (format!("environment {name}"), value))
+        .collect();
+
+    for (id, package) in package_variables {
+        variables.extend(package.iter().map(|(name, value)| (format!("package {id} {name}"), value.clone())));
+    }
+
+    variables
+}
+
+/// Finds the jobs which `--since-last-green` skips: those which passed the last time they ran, in a run with the same
+/// inputs, and whose needed jobs are skipped too.
+fn passed_jobs<'a>(
+    args: &RunArgs,
+    cfg: &Config,
+    metadata: &Metadata,
+    inputs: Option<&str>,
+    jobs: &[&'a JobId],
+) -> anyhow::Result<HashSet<&'a JobId>> {
+    let mut skipped = HashSet::new();
+    let (true, Some(inputs)) = (args.since_last_green, inputs) else {
+        return Ok(skipped);
+    };
+
+    let history = History::load(&History::path(metadata.target_directory.as_std_path()))?;
+    let passed = history.passed_jobs(inputs);
+
+    // jobs are sorted so that the jobs they need come first
+    for &job_id in jobs {
+        let job = cfg.jobs().get_job(job_id).expect("job not found");
+        if passed.contains(job_id.as_str()) && job.needs().iter().all(|needed| skipped.contains(needed) || !jobs.contains(&needed)) {
+            _ = skipped.insert(job_id);
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Digests the committed trees pure jobs depend on, unless caching their results is turned off or there are no pure
+/// jobs, warning when that isn't possible.
+fn digest_trees<H: Host>(
+    args: &RunArgs,
+    host: &H,
+    cfg: &Config,
+    metadata: &Metadata,
+    fingerprint: &Fingerprint,
+    packages: &[&Package],
+    summary: &mut RunSummary,
+) -> Option<String> {
+    if args.no_cache || args.dry_run || !cfg.jobs().iter().any(|(_, job)| job.pure()) {
+        return None;
+    }
+
+    fingerprint.digest_trees(host, cfg, metadata, packages).unwrap_or_else(|e| {
+        summary.warnings.push(format!(
+            "unable to digest the git trees of the workspace, so pure jobs can't be cached: {e:#}"
+        ));
+        None
+    })
+}
+
+/// Finds the pure jobs which passed before on the same trees.
+fn cached_jobs<'a>(cfg: &Config, metadata: &Metadata, trees: Option<&str>, jobs: &[&'a JobId]) -> anyhow::Result<HashSet<&'a JobId>> {
+    let Some(trees) = trees else {
+        return Ok(HashSet::new());
+    };
+
+    let cache = ResultCache::load(&ResultCache::path(metadata.target_directory.as_std_path()))?;
+    Ok(jobs
+        .iter()
+        .copied()
+        .filter(|job_id| cfg.jobs().get_job(job_id).expect("job not found").pure() && cache.passed(job_id.as_str(), trees))
+        .collect())
+}
+
+/// Remembers the pure jobs which passed during the run, so that running them again on the same trees is skipped.
+fn cache_results<H: Host>(ctx: &RunContext<'_, H>, summary: &mut RunSummary) {
+    let Some(trees) = &ctx.trees else {
+        return;
+    };
+
+    let passed: Vec<_> = summary
+        .jobs()
+        .filter(|job| job.status == JobStatus::Succeeded)
+        .filter(|job| ctx.cfg.jobs().iter().any(|(job_id, x)| job_id.as_str() == job.id && x.pure()))
+        .map(|job| job.id.clone())
+        .collect();
+
+    if passed.is_empty() {
+        return;
+    }
+
+    let path = ResultCache::path(ctx.metadata.target_directory.as_std_path());
+    let result = ResultCache::load(&path).and_then(|mut cache| {
+        for job_id in &passed {
+            cache.record(job_id, trees);
+        }
+
+        cache.save(&path)
+    });
+
+    if let Err(e) = result {
+        summary.warnings.push(format!("unable to cache the results of pure jobs: {e:#}"));
+    }
+}
+
+/// When the run must wind down, given its time budget.
+fn run_deadline<H: Host>(args: &RunArgs, host: &H, cfg: &Config) -> Option<Instant> {
+    cfg.max_run_duration()
+        .filter(|_| !args.dry_run)
+        .map(|budget| host.now() + budget.duration())
+}
+
+/// Creates the temporary directory of the run, which also takes requests to cancel its jobs.
+fn create_temp_dir(args: &RunArgs, metadata: &Metadata, run_id: &str) -> anyhow::Result<PathBuf> {
+    let temp_dir = cancellation::run_dir(metadata.target_directory.as_std_path(), run_id);
+    if !args.dry_run {
+        fs::create_dir_all(long_paths::extended(&temp_dir)?)
+            .with_context(|| format!("unable to create temporary directory {}", temp_dir.display()))?;
+        cancellation::accept_requests(&temp_dir)?;
+    }
+
+    Ok(temp_dir)
+}
+
+/// Sends the spans of the run to the OTLP endpoint given on the command line, if any, through a file in the run's
+/// temporary directory.
+fn export_spans<H: Host>(
+    args: &RunArgs,
+    host: &H,
+    telemetry: &Telemetry,
+    (run_span, temp_dir): (ActiveSpan, &Path),
+    outcome: &anyhow::Result<()>,
+    summary: &mut RunSummary,
+) {
+    let Some(endpoint) = &args.otlp_endpoint else {
+        return;
+    };
+
+    if args.dry_run {
+        return;
+    }
+
+    telemetry.finish(run_span, host.local_time(), outcome.as_ref().err().map(|e| format!("{e:#}")));
+    if let Err(e) = telemetry.export(host, endpoint, &temp_dir.join("spans.json")) {
+        summary.warnings.push(format!("unable to export spans: {e:#}"));
+    }
+}
+
+/// Removes the run's scratch directory, unless the run failed, returning whether the directory was kept.
+fn clean_up_temp_dir<H: Host>(args: &RunArgs, outputter: &Outputter<H>, summary: &RunSummary, temp_dir: &Path) -> bool {
+    if args.dry_run {
+        return false;
+    }
+
+    // keep the scratch files of failed runs around, since they often help figuring out what went wrong
+    let keep_temp_dir = summary
+        .jobs()
+        .any(|job| matches!(job.status, JobStatus::Failed | JobStatus::FailedIgnored));
+
+    if keep_temp_dir {
+        outputter.line(Msg::TempFilesKept(&temp_dir.display().to_string()));
+    } else if let Err(e) = long_paths::extended(temp_dir).and_then(fs::remove_dir_all) {
+        outputter.line(format!("Unable to remove temporary directory {}: {e}", temp_dir.display()));
+    }
+
+    keep_temp_dir
+}
+
+/// Resolves the jobs selected by the arguments into a plan, evaluating conditions without running anything.
+///
+/// Given another platform, conditions are evaluated as if running there, leaving out the environment of this one.
+pub fn plan_jobs<H: Host>(
+    args: &RunArgs,
+    host: &H,
+    cfg: &Config,
+    metadata: &Metadata,
+    platform: Option<&Platform>,
+) -> anyhow::Result<Plan> {
+    let args = &*args.resolve_named_run(cfg)?;
+    let jobs = select_jobs(args, cfg)?;
+    args.check_variable_scopes(cfg)?;
+    save_shard_durations(args, cfg, metadata, &jobs)?;
+    let (packages, shuffle_seed) = select_shuffled_packages(args, host, metadata)?;
+    let excluded_workspaces = load_excluded_workspaces(args, host, cfg, metadata, &jobs)?;
+
+    let package_variables = load_package_variables(host, cfg, metadata, &packages)?;
+    let env_vars = expression_env_vars(host, cfg, metadata, platform);
+
+    let log = Log::new(
+        metadata.target_directory.as_std_path(),
+        "plan",
+        host.local_time(),
+        args.log_file.as_deref(),
+        args.log_file_retention_count,
+    )?;
+
+    let passed_jobs = if args.since_last_green {
+        let inputs = run_fingerprint(args, host, cfg, metadata).digest_inputs(
+            cfg,
+            metadata,
+            &packages,
+            &input_variables(host, cfg, &package_variables),
+        )?;
+        passed_jobs(args, cfg, metadata, Some(&inputs), &jobs)?
+    } else {
+        HashSet::new()
+    };
+
+    let temp_dir = metadata.target_directory.as_std_path().join("ci-tmp");
+    let telemetry = Telemetry::new(false, host.local_time());
+    let ctx = RunContext {
+        args,
+        host,
+        cfg,
+        metadata,
+        packages: &packages,
+        excluded_packages: &excluded_packages(&excluded_workspaces),
+        env_vars: &env_vars,
+        package_variables: &package_variables,
+        temp_dir: &temp_dir,
+        network_lock: Mutex::new(()),
+        prompt_lock: Mutex::new(()),
+        step_locks: KeyedLocks::default(),
+        tool_locks: KeyedLocks::default(),
+        memory: MemoryBudget::default(),
+        shared_results: SharedResults::default(),
+        telemetry: &telemetry,
+        run_span: telemetry.start(None, "cargo ci plan", host.local_time()).id(),
+        codegen_runs: AtomicUsize::new(0),
+        raw_outputs: AtomicUsize::new(0),
+        passed_jobs,
+        trees: None,
+        cached_jobs: HashSet::new(),
+        step_records: Mutex::new(Vec::new()),
+        test_results: Mutex::new(Vec::new()),
+        sarif_findings: Mutex::new(Vec::new()),
+        deadline: None,
+        out_of_time: AtomicBool::new(false),
+        cancelled_jobs: Mutex::new(HashSet::new()),
+        job_statuses: Mutex::new(HashMap::new()),
+        progress: Mutex::new(RunProgress::default()),
+        live_status: Arc::default(),
+        show_title: false,
+        platform: platform.cloned().unwrap_or_else(Platform::current),
+        network_isolation: false,
+    };
+
+    let outputter = Outputter::new(host, &log, args.color, args.lang);
+    let mut plan = Plan::default();
+
+    report_shuffle_seed(&outputter, shuffle_seed);
+
+    for job_id in group_by_stage(cfg, &jobs).into_iter().flat_map(|(_, stage_jobs)| stage_jobs) {
+        let job = cfg.jobs().get_job(job_id).expect("job not found");
+        let job_name = job.name().unwrap_or(job_id.as_str());
+        outputter.start_activity(job_name);
+
+        if !ctx.platform.runs_on(job.os()) {
+            outputter.complete_activity(unsupported_os(job.os()));
+            continue;
+        }
+
+        if ctx.passed_jobs.contains(job_id) {
+            outputter.complete_activity(PASSED_BEFORE);
+            continue;
+        }
+
+        let continue_on_error = job
+            .continue_on_error()
+            .evaluate(ctx.env_vars().chain(cfg.variables()).chain(args.job_variables(job_id)))?;
+
+        let steps = plan_steps(&ctx, &outputter, job_id, job)?;
+        outputter.complete_activity(format!("planned {} step(s)", steps.len()));
+        plan.jobs.push(PlannedJob {
+            id: job_id.to_string(),
+            name: job_name.to_string(),
+            continue_on_error,
+            steps,
+        });
+    }
+
+    Ok(plan)
+}
+
+/// Plans the executions of the steps of a job.
+fn plan_steps<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, job_id: &JobId, job: &Job) -> anyhow::Result<Vec<PlannedStep>> {
+    let workspace_root = ctx.metadata.workspace_root.as_std_path();
+    let mut steps = Vec::new();
+    let mut outcomes = StepOutcomes::default();
+    for step in job.steps() {
+        check_plannable(ctx.args, job_id, step)?;
+
+        // plans run unattended, so there's nobody to confirm destructive steps
+        if step.destructive() && !ctx.args.allow_destructive {
+            outputter.message(format!(
+                "step '{}' left out because it is destructive, use --allow-destructive to include it",
+                step.name()
+            ));
+            outcomes.record(step, StepOutcome::Skipped);
+            continue;
+        }
+
+        // the plan can't know how steps will turn out, so it assumes every step that runs succeeds
+        let (invocations, _) = step_invocations(ctx, outputter, job_id, job, step, &outcomes)?;
+        outcomes.record(
+            step,
+            if invocations.is_empty() {
+                StepOutcome::Skipped
+            } else {
+                StepOutcome::Success
+            },
+        );
+
+        for invocation in invocations {
+            let pkg = invocation.pkg;
+            let variables = step_run_variables(ctx, job_id, job, step, pkg);
+            let directory = step_working_directory(ctx, (job_id, job, step), pkg, &variables, false)
+                .map_err(|e| anyhow!("unable to plan step '{}': {e:#}", step.name()))?;
+            let overridden = ctx.invocation_command(step, &invocation);
+            let fallbacks = ctx.fallback_commands(step, &invocation);
+            steps.push(PlannedStep {
+                name: step.name().to_string(),
+                package: pkg.map(|pkg| pkg.name.to_string()),
+                directory: directory.strip_prefix(workspace_root).unwrap_or(&directory).to_path_buf(),
+                cargo_alias: step.cargo_alias().filter(|_| overridden.is_none()).map(ToString::to_string),
+                command: overridden.unwrap_or_else(|| step.command().into_owned()),
+                fallbacks,
+                path: step_path(job, step),
+                variables: declared_variables(&variables),
+                isolated_env: !step.inherits_env(),
+                create_directory: step.working_directory_mode() == WorkingDirectoryMode::CreateIfMissing,
+                background: job.background(),
+                continue_on_error: invocation.continue_on_error,
+            });
+        }
+    }
+
+    Ok(steps)
+}
+
+/// The variables computed by the `variables_from` commands of packages.
+type PackageVariables = HashMap<PackageId, Vec<(String, String)>>;
+
+/// Runs the `variables_from` commands of trusted packages, collecting the variables they define.
+fn load_package_variables<H: Host>(host: &H, cfg: &Config, metadata: &Metadata, packages: &[&Package]) -> anyhow::Result<PackageVariables> {
+    let mut result = HashMap::new();
+    for pkg in packages {
+        let Some(command) = variables_from(pkg) else {
+            continue;
+        };
+
+        if !is_trusted(pkg, metadata.workspace_root.as_std_path(), cfg.trusted_packages()) {
+            continue;
+        }
+
+        let mut cmd = shell_command(command);
+        _ = cmd
+            .current_dir(pkg.manifest_path.parent().expect("should have a valid parent"))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let output = host
+            .output(&mut cmd)
+            .with_context(|| format!("unable to run variables_from command '{command}' of package '{}'", pkg.name))?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "variables_from command '{command}' of package '{}' failed, {}: {}",
+                pkg.name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let vars = parse_variables(&String::from_utf8_lossy(&output.stdout))
+            .with_context(|| format!("invalid output from variables_from command '{command}' of package '{}'", pkg.name))?;
+        _ = result.insert(pkg.id.clone(), vars);
+    }
+
+    Ok(result)
+}
+
+/// Collects the environment variables which the configuration lets through to expressions.
+pub fn passthrough_env_vars<H: Host>(host: &H, cfg: &Config) -> HashMap<String, String> {
+    let mut env_vars = HashMap::new();
+    for (key, value) in host.vars() {
+        if cfg!(windows) {
+            if cfg.passthrough_env_variables().iter().any(|v| v.eq_ignore_ascii_case(&key)) {
+                _ = env_vars.insert(key, value);
+            }
+        } else if cfg.passthrough_env_variables().contains(&key) {
+            _ = env_vars.insert(key, value);
+        }
+    }
+
+    env_vars
+}
+
+/// The environment variables visible to expressions, along with the variables describing the state of the git
+/// repository and the platform.
+///
+/// Given another platform, the environment of this one is left out.
+pub fn expression_env_vars<H: Host>(host: &H, cfg: &Config, metadata: &Metadata, platform: Option<&Platform>) -> HashMap<String, String> {
+    let mut env_vars = if platform.is_some() {
+        HashMap::new()
+    } else {
+        passthrough_env_vars(host, cfg)
+    };
+
+    // outside of a git repository, or without git, there's simply nothing to describe
+    if cfg.git_variables()
+        && let Ok(variables) = git::variables(host, metadata.workspace_root.as_std_path())
+    {
+        env_vars.extend(variables);
+    }
+
+    env_vars.extend(platform.map_or_else(Platform::current, Clone::clone).variables());
+    env_vars
+}
+
+/// Looks for feature unification differences that could hide breakage when jobs run steps on a per-package basis.
+fn check_feature_unification<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, jobs: &[&JobId]) -> Vec<String> {
+    let per_package = jobs
+        .iter()
+        .filter_map(|job_id| ctx.cfg.jobs().get_job(job_id))
+        .any(|job| job.steps().iter().any(Step::per_package));
+
+    if !per_package {
+        return Vec::new();
+    }
+
+    outputter.start_activity("Feature unification");
+    outputter.message("comparing per-package and workspace features");
+
+    match feature_unification::analyze(ctx.host, ctx.metadata.workspace_root.as_std_path(), ctx.packages) {
+        Ok(divergences) => {
+            outputter.complete_activity(format!("found {} divergence(s)", divergences.len()));
+            divergences.iter().map(FeatureDivergence::describe).collect()
+        }
+
+        Err(e) => {
+            outputter.complete_activity("unable to analyze");
+            vec![format!("unable to check feature unification: {e:#}")]
+        }
+    }
+}
+
+/// Splits the jobs to run into groups, one per declared stage in declaration order, followed by the jobs without a stage.
+fn group_by_stage<'a>(cfg: &'a Config, jobs: &[&'a JobId]) -> Vec<(Option<&'a StageId>, Vec<&'a JobId>)> {
+    let stage_of = |job_id: &JobId| cfg.jobs().get_job(job_id).and_then(Job::stage);
+
+    cfg.stages()
+        .iter()
+        .map(Some)
+        .chain(core::iter::once(None))
+        .map(|stage| {
+            (
+                stage,
+                jobs.iter().copied().filter(|job_id| stage_of(job_id) == stage).collect::<Vec<_>>(),
+            )
+        })
+        .filter(|(_, stage_jobs)| !stage_jobs.is_empty())
+        .collect()
+}
+
+fn run_sequentially<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    jobs: &[&JobId],
+    outcome: &mut anyhow::Result<()>,
+) -> Vec<JobResult> {
+    jobs.iter()
+        .map(|&job_id| {
+            if outcome.is_err() {
+                return not_run(ctx.cfg, job_id);
+            }
+
+            let (result, job_outcome) = execute_job(ctx, outputter, job_id);
+            if let Err(e) = job_outcome {
+                *outcome = Err(e);
+            }
+
+            result
+        })
+        .collect()
+}
+
+/// Runs jobs on separate threads, starting each one as soon as the jobs it needs have finished.
+///
+/// What each running job is doing is shown on a status board, which is kept current on a separate thread.
+///
+/// Once a job fails, no new jobs are started, but the ones already running are allowed to complete.
+fn run_concurrently<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    jobs: &[&JobId],
+    outcome: &mut anyhow::Result<()>,
+) -> Vec<JobResult> {
+    let mut pending = jobs.to_vec();
+    let mut finished = HashSet::new();
+    let mut results = Vec::new();
+    let board = Arc::new(StatusBoard::new(ctx.host.is_terminal() && !ctx.args.dry_run));
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        let mut in_flight = 0;
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let board = &board;
+        _ = scope.spawn(move || show_status(ctx.host, board, &stopped));
+
+        loop {
+            if outcome.is_ok() {
+                let (ready, blocked): (Vec<&JobId>, Vec<&JobId>) = pending.iter().partition(|&&job_id| {
+                    let job = ctx.cfg.jobs().get_job(job_id).expect("job not found");
+                    job.needs()
+                        .iter()
+                        .all(|needed| !jobs.contains(&needed) || finished.contains(needed))
+                });
+
+                pending = blocked;
+                for job_id in ready {
+                    let sender = sender.clone();
+                    let job_outputter = outputter.fork_onto(board);
+                    _ = scope.spawn(move || {
+                        _ = sender.send((job_id, execute_job(ctx, &job_outputter, job_id)));
+                    });
+                    in_flight += 1;
+                }
+            }
+
+            if in_flight == 0 {
+                break;
+            }
+
+            let Ok((job_id, (result, job_outcome))) = receiver.recv() else {
+                break;
+            };
+
+            in_flight -= 1;
+            _ = finished.insert(job_id);
+            results.push((job_id, result));
+
+            if let Err(e) = job_outcome
+                && outcome.is_ok()
+            {
+                *outcome = Err(e);
+            }
+        }
+
+        drop(stop);
+    });
+
+    board.finish();
+    results.extend(pending.into_iter().map(|job_id| (job_id, not_run(ctx.cfg, job_id))));
+    results.sort_by_key(|(job_id, _)| jobs.iter().position(|x| x == job_id));
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Keeps the status board current until told to stop: redrawn every second on a terminal, or otherwise printed as plain
+/// text every now and then.
+fn show_status<H: Host>(host: &H, board: &StatusBoard, stop: &mpsc::Receiver<()>) {
+    let plain_interval = Duration::from_secs(PLAIN_STATUS_INTERVAL_SECS);
+    let mut last_plain = host.now();
+    while stop.recv_timeout(Duration::from_secs(1)) == Err(RecvTimeoutError::Timeout) {
+        let now = host.now();
+        if board.is_interactive() {
+            board.refresh(now);
+            continue;
+        }
+
+        if now.duration_since(last_plain) < plain_interval {
+            continue;
+        }
+
+        last_plain = now;
+        let lines = board.lines(now);
+        if !lines.is_empty() {
+            board.print_above(now, || {
+                host.println(format!("Still running {} job(s):", lines.len()));
+                for line in &lines {
+                    host.println(format!("  {line}"));
+                }
+            });
+        }
+    }
+}
+
+fn not_run(cfg: &Config, job_id: &JobId) -> JobResult {
+    let job = cfg.jobs().get_job(job_id).expect("job not found");
+    JobResult {
+        id: job_id.to_string(),
+        name: job.name().unwrap_or(job_id.as_str()).to_string(),
+        status: JobStatus::NotRun,
+        duration: Duration::ZERO,
+        steps: not_run_steps(job),
+        error: None,
+    }
+}
+
+fn not_run_steps(job: &Job) -> Vec<StepResult> {
+    job.steps().iter().map(|step| StepResult::not_run(step.name())).collect()
+}
+
+/// Runs a single job, returning its result along with an error if the failure should stop the run.
+fn execute_job<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, job_id: &JobId) -> (JobResult, anyhow::Result<()>) {
+    let job = ctx.cfg.jobs().get_job(job_id).expect("job not found");
+    let job_name = job.name().unwrap_or(job_id.as_str());
+    let start = ctx.host.now();
+    let mut span = ctx.telemetry.start(Some(ctx.run_span), job_name, ctx.host.local_time());
+    span.attribute("cargo_ci.job.id", job_id.as_str());
+
+    outputter.start_activity(job_name);
+    outputter.set_quiet(job.background());
+
+    // we evaluate that up here even when there is no error, so that the expression gets validated eagerly
+    let continue_on_error = job
+        .continue_on_error()
+        .evaluate(ctx.env_vars().chain(ctx.cfg.variables()).chain(ctx.args.job_variables(job_id)));
+
+    let cancelled_need = job
+        .needs()
+        .iter()
+        .find(|need| ctx.cancelled_jobs.lock().unwrap_or_else(PoisonError::into_inner).contains(*need));
+
+    let mut steps = not_run_steps(job);
+    let (status, outcome) = match continue_on_error {
+        Ok(_) if !ctx.platform.runs_on(job.os()) => {
+            outputter.complete_activity(unsupported_os(job.os()));
+            (JobStatus::NotRun, Ok(()))
+        }
+
+        Ok(_) if ctx.passed_jobs.contains(job_id) => {
+            outputter.complete_activity(PASSED_BEFORE);
+            (JobStatus::NotRun, Ok(()))
+        }
+
+        Ok(_) if ctx.cached_jobs.contains(job_id) => {
+            outputter.complete_activity(PASSED_ON_SAME_TREES);
+            (JobStatus::NotRun, Ok(()))
+        }
+
+        Ok(_) if ctx.out_of_time() => {
+            outputter.complete_activity(OUT_OF_TIME);
+            (JobStatus::NotRun, Ok(()))
+        }
+
+        Ok(_) if let Some(need) = cancelled_need => {
+            outputter.complete_activity(format!("not run, it needs job '{need}', which was cancelled"));
+            (JobStatus::NotRun, Ok(()))
+        }
+
+        Ok(_) if ctx.cancel_requested(job_id) => {
+            outputter.complete_activity(Msg::Status(JobStatus::Cancelled));
+            (JobStatus::Cancelled, Ok(()))
+        }
+
+        Ok(continue_on_error) => {
+            ctx.live_status().start_job(job_id.as_str());
+            ctx.update_title();
+            let result = consume_artifacts(ctx, outputter, job)
+                .and_then(|()| run_job(ctx, outputter, job_id, job, span.id(), &mut steps))
+                .and_then(|()| store_artifacts(ctx, job_id));
+            if result.is_err() && ctx.cancel_requested(job_id) {
+                outputter.set_quiet(false);
+                outputter.complete_activity(Msg::Status(JobStatus::Cancelled));
+                (JobStatus::Cancelled, Ok(()))
+            } else if result.is_ok() {
+                outputter.complete_activity(Msg::RanSteps(job.steps().len()));
+                (JobStatus::Succeeded, Ok(()))
+            } else if continue_on_error {
+                outputter.set_quiet(false);
+                outputter.complete_activity(Msg::Status(JobStatus::FailedIgnored));
+                (JobStatus::FailedIgnored, Ok(()))
+            } else {
+                outputter.set_quiet(false);
+                outputter.complete_activity(Msg::Status(JobStatus::Failed));
+                (JobStatus::Failed, result.map_err(|e| Failure::StepFailed.tag(e)))
+            }
+        }
+
+        Err(e) => {
+            outputter.set_quiet(false);
+            outputter.complete_activity(Msg::Status(JobStatus::Failed));
+            (JobStatus::Failed, Err(e))
+        }
+    };
+
+    outputter.set_quiet(false);
+    if status == JobStatus::Cancelled {
+        _ = ctx
+            .cancelled_jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(job_id.clone());
+    }
+
+    _ = ctx
+        .job_statuses
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(job_id.clone(), status);
+
+    let result = JobResult {
+        id: job_id.to_string(),
+        name: job_name.to_string(),
+        status,
+        duration: ctx.host.now().duration_since(start),
+        steps,
+        error: outcome.as_ref().err().map(|e| format!("{e:#}")),
+    };
+
+    ctx.live_status().finish_job(&result);
+    ctx.update_title();
+    span.attribute("cargo_ci.job.status", Msg::Status(status).localize(Lang::En));
+    ctx.telemetry.finish(span, ctx.host.local_time(), result.error.clone());
+
+    (result, outcome)
+}
+
+/// Ensures the artifacts a job consumes are in the workspace before it starts, restoring the missing ones from the
+/// artifact store.
+///
+/// The job producing them must have succeeded, either in this run or in an earlier one which left its artifacts in the
+/// store.
+fn consume_artifacts<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, job: &Job) -> anyhow::Result<()> {
+    let Some(consumes) = job.consumes().filter(|_| !ctx.args.dry_run) else {
+        return Ok(());
+    };
+
+    let from = consumes.from();
+    let store = ArtifactStore::new(ctx.metadata.target_directory.as_std_path());
+    let status = ctx.job_statuses.lock().unwrap_or_else(PoisonError::into_inner).get(from).copied();
+
+    match status {
+        Some(JobStatus::Succeeded) => {}
+        Some(JobStatus::NotRun) | None if store.has(from.as_str()) => {}
+        Some(JobStatus::NotRun) | None => {
+            return Err(anyhow!(
+                "job '{from}' hasn't succeeded in this run or an earlier one, so there are no artifacts to consume from it"
+            ));
+        }
+        Some(_) => return Err(anyhow!("job '{from}' didn't succeed, so its artifacts can't be consumed")),
+    }
+
+    let workspace_root = ctx.metadata.workspace_root.as_std_path();
+    for artifact in consumes.artifacts() {
+        if workspace_root.join(artifact).exists() {
+            continue;
+        }
+
+        if !store.restore(from.as_str(), workspace_root, artifact)? {
+            return Err(anyhow!(
+                "artifact '{}' of job '{from}' doesn't exist, and isn't in the artifact store either",
+                artifact.display()
+            ));
+        }
+
+        outputter.message(format!(
+            "restored artifact '{}' of job '{from}' from the artifact store",
+            artifact.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Keeps a copy of the artifacts other jobs consume from a job which just succeeded.
+fn store_artifacts<H: Host>(ctx: &RunContext<'_, H>, job_id: &JobId) -> anyhow::Result<()> {
+    let artifacts: Vec<&Path> = ctx
+        .cfg
+        .jobs()
+        .iter()
+        .filter_map(|(_, job)| job.consumes())
+        .filter(|consumes| consumes.from() == job_id)
+        .flat_map(Consumes::artifacts)
+        .map(PathBuf::as_path)
+        .collect();
+
+    if artifacts.is_empty() || ctx.args.dry_run {
+        return Ok(());
+    }
+
+    ArtifactStore::new(ctx.metadata.target_directory.as_std_path()).store(
+        job_id.as_str(),
+        ctx.metadata.workspace_root.as_std_path(),
+        &artifacts,
+    )
+}
+
+/// Explains why a job doesn't start once the run's time budget ran out.
+const OUT_OF_TIME: &str = "not run, the run went over its time budget";
+
+/// Explains why a `pure` job is skipped, its success being cached for the git trees it covers.
+const PASSED_ON_SAME_TREES: &str = "skipped, passed before on the same git trees";
+
+/// Explains why `--since-last-green` skips a job.
+const PASSED_BEFORE: &str = "skipped, passed the last time it ran and nothing changed since";
+
+/// Explains why a job or step restricted to some operating systems doesn't run on this one.
+fn unsupported_os(os: &[Os]) -> String {
+    let names: Vec<_> = os.iter().map(|os| os.as_str()).collect();
+    format!("skipped, not supported on this OS (runs on {})", names.join(", "))
+}
+
+/// One execution of a step, either for a single package, for a batch of packages, or once for the whole workspace.
+struct Invocation<'a> {
+    pkg: Option<&'a Package>,
+
+    /// The packages a step with `batch_packages` runs for at once, from the workspace root.
+    batch: Vec<&'a Package>,
+
+    continue_on_error: bool,
+}
+
+impl<'a> Invocation<'a> {
+    /// The packages this invocation runs for, which is none of them when it runs once for the whole workspace.
+    fn packages(&self) -> impl Iterator<Item = &'a Package> + '_ {
+        self.pkg.into_iter().chain(self.batch.iter().copied())
+    }
+}
+
+/// Determines how a step of a job executes, evaluating the job and step conditions for every selected package.
+///
+/// Also returns the packages the step was meant for but which it skips, such as those whose conditions don't hold.
+fn step_invocations<'a, H: Host>(
+    ctx: &RunContext<'a, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    job: &Job,
+    step: &Step,
+    outcomes: &StepOutcomes,
+) -> anyhow::Result<(Vec<Invocation<'a>>, Vec<&'a Package>)> {
+    let RunContext { args, cfg, packages, .. } = *ctx;
+    let env_vars = || ctx.env_vars();
+
+    if !ctx.platform.runs_on(step.os()) {
+        outputter.message(format!("step '{}' {}", step.name(), unsupported_os(step.os())));
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let step_packages = step_packages(ctx, outputter, job_id, job, step)?;
+    let required_targets = step
+        .cargo_subcommand()
+        .and_then(|subcommand| RequiredTargets::for_subcommand(&subcommand))
+        .filter(|_| ctx.per_package(job, step));
+
+    let mut packages_to_process = Vec::new();
+    let mut skipped_packages = Vec::new();
+    for &pkg in &step_packages {
+        if !ctx.evaluate_conditional(
+            outputter,
+            &format!("job condition for package '{}'", pkg.name),
+            job.conditional(),
+            tagged(VariableSource::Environment, env_vars())
+                .chain(tagged(VariableSource::Workspace, cfg.variables()))
+                .chain(tagged(VariableSource::Package, ctx.package_variables(pkg)))
+                .chain(tagged(VariableSource::CommandLine, args.job_variables(job_id))),
+        )? {
+            outputter.message(Msg::PackageSkippedByJob(&pkg.name));
+            skipped_packages.push(pkg);
+            continue;
+        }
+
+        if !ctx.evaluate_conditional(
+            outputter,
+            &format!("condition of step '{}' for package '{}'", step.name(), pkg.name),
+            step.conditional(),
+            tagged(VariableSource::Environment, env_vars())
+                .chain(tagged(VariableSource::Workspace, cfg.variables()))
+                .chain(tagged(VariableSource::Job, job.variables()))
+                .chain(tagged(VariableSource::Package, ctx.package_variables(pkg)))
+                .chain(tagged(VariableSource::CommandLine, args.step_variables(job_id, step)))
+                .chain(tagged(VariableSource::StepOutcome, outcomes.variables())),
+        )? {
+            outputter.message(Msg::PackageSkippedByStep(&pkg.name));
+            skipped_packages.push(pkg);
+            continue;
+        }
+
+        if let Some(required) = required_targets
+            && !required.present_in(pkg)
+        {
+            outputter.message(Msg::PackageSkippedNoTargets(&pkg.name, required.as_str()));
+            skipped_packages.push(pkg);
+            continue;
+        }
+
+        // there's no published version to compare unpublished packages against
+        if step.kind() == StepKind::SemverCheck && step.baseline().is_none() && pkg.publish.as_ref().is_some_and(Vec::is_empty) {
+            outputter.message(format!("package '{}' skipped, it isn't published", pkg.name));
+            skipped_packages.push(pkg);
+            continue;
+        }
+
+        packages_to_process.push(pkg);
+    }
+
+    if packages_to_process.len() == packages.len() && !ctx.per_package(job, step) {
+        // we evaluate that up here even when there is no error, so that the expression gets validated eagerly
+        let continue_on_error = step.continue_on_error().evaluate(
+            env_vars()
+                .chain(cfg.variables())
+                .chain(job.variables())
+                .chain(args.step_variables(job_id, step)),
+        )?;
+
+        return Ok((
+            vec![Invocation {
+                pkg: None,
+                batch: Vec::new(),
+                continue_on_error,
+            }],
+            skipped_packages,
+        ));
+    }
+
+    let invocations = packages_to_process
+        .into_iter()
+        .map(|pkg| {
+            // we evaluate that up here even when there is no error, so that the expression gets validated eagerly
+            let continue_on_error = if ctx.per_package(job, step) {
+                step.continue_on_error().evaluate(
+                    env_vars()
+                        .chain(cfg.variables())
+                        .chain(job.variables())
+                        .chain(ctx.package_variables(pkg))
+                        .chain(args.step_variables(job_id, step)),
+                )?
+            } else {
+                step.continue_on_error().evaluate(
+                    env_vars()
+                        .chain(cfg.variables())
+                        .chain(job.variables())
+                        .chain(args.step_variables(job_id, step)),
+                )?
+            };
+
+            Ok(Invocation {
+                pkg: Some(pkg),
+                batch: Vec::new(),
+                continue_on_error,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok((batch_invocations(ctx, step, invocations), skipped_packages))
+}
+
+/// Groups the packages of a step with `batch_packages` into batches, each run with a single command.
+///
+/// Packages only share a batch when they agree on `continue_on_error`, and those which override the step's command
+/// keep running on their own.
+fn batch_invocations<'a, H: Host>(ctx: &RunContext<'a, H>, step: &Step, invocations: Vec<Invocation<'a>>) -> Vec<Invocation<'a>> {
+    let Some(size) = step.batch_packages() else {
+        return invocations;
+    };
+
+    let mut batched: Vec<Invocation<'a>> = Vec::new();
+    let mut open: [Option<usize>; 2] = [None, None];
+    for invocation in invocations {
+        // packages of excluded crates and nested workspaces can't be selected from the workspace root
+        let Some(pkg) = invocation
+            .pkg
+            .filter(|pkg| ctx.step_command(step, Some(pkg)).is_none() && ctx.metadata.workspace_members.contains(&pkg.id))
+        else {
+            batched.push(invocation);
+            continue;
+        };
+
+        let slot = usize::from(invocation.continue_on_error);
+        match open[slot] {
+            Some(index) if batched[index].batch.len() < size => batched[index].batch.push(pkg),
+            _ => {
+                open[slot] = Some(batched.len());
+                batched.push(Invocation {
+                    pkg: None,
+                    batch: vec![pkg],
+                    continue_on_error: invocation.continue_on_error,
+                });
+            }
+        }
+    }
+
+    batched
+}
+
+/// Adds a `-p` option for every package to a cargo command, right after its subcommand so they come before any `--`.
+fn batch_command(command: &str, packages: &[&Package]) -> String {
+    let options = packages.iter().fold(String::new(), |mut options, pkg| {
+        _ = write!(options, " -p {}", pkg.name);
+        options
+    });
+
+    let mut batched = String::new();
+    let mut seen_cargo = false;
+    let mut inserted = false;
+    for piece in command.split_inclusive(char::is_whitespace) {
+        let word = piece.trim();
+        if !inserted && !word.is_empty() && seen_cargo && !word.starts_with('+') && !word.starts_with('-') {
+            batched.push_str(word);
+            batched.push_str(&options);
+            batched.push_str(piece.strip_prefix(word).unwrap_or_default());
+            inserted = true;
+            continue;
+        }
+
+        seen_cargo |= !word.is_empty();
+        batched.push_str(piece);
+    }
+
+    batched
+}
+
+/// Runs the steps of a job, noting how each of them turned out in the given results, which start out as not run.
+fn run_job<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    job: &Job,
+    job_span: SpanId,
+    step_results: &mut [StepResult],
+) -> anyhow::Result<()> {
+    let RunContext { args, .. } = *ctx;
+    let mut network_bucket = job.network_rate_limit().map(TokenBucket::new);
+    let mut needs_prebuild = job.prebuild_dependencies();
+
+    let modified_before = if job.ensure_clean() && !args.dry_run {
+        Some(modified_files(ctx)?)
+    } else {
+        None
+    };
+
+    if !args.dry_run {
+        install_job_tools(ctx, outputter, job)?;
+    }
+
+    let mut outcomes = StepOutcomes::default();
+    for (index, (step, step_result)) in job.steps().iter().zip(step_results).enumerate() {
+        if ctx.out_of_time() {
+            return Err(ctx.out_of_time_error().context(format!("step '{}' wasn't started", step.name())));
+        }
+
+        if ctx.cancel_requested(job_id) {
+            return Err(anyhow!("job '{job_id}' was cancelled before step '{}'", step.name()));
+        }
+
+        if ctx.progress().step_completed(job_id.as_str(), index) {
+            outputter.message(format!("step '{}' skipped because it succeeded in the resumed run", step.name()));
+            outcomes.record(step, StepOutcome::Success);
+            step_result.status = StepStatus::Skipped;
+            continue;
+        }
+
+        ctx.live_status().start_step(job_id.as_str(), step.name());
+        ctx.update_title();
+
+        // errors while getting a step ready fail the job just like the step failing would
+        step_result.status = StepStatus::Failed;
+
+        let (invocations, skipped_packages) = step_invocations(ctx, outputter, job_id, job, step, &outcomes)?;
+        step_result.packages = invocations
+            .iter()
+            .flat_map(Invocation::packages)
+            .map(|pkg| (pkg, StepStatus::NotRun))
+            .chain(skipped_packages.into_iter().map(|pkg| (pkg, StepStatus::Skipped)))
+            .map(|(pkg, status)| PackageResult::new(pkg.name.as_str(), status))
+            .collect();
+
+        if step.destructive() && !invocations.is_empty() && !args.dry_run && !allow_destructive(ctx, outputter, job_id, step)? {
+            outputter.message(format!(
+                "step '{}' skipped because it is destructive, use --allow-destructive to run it",
+                step.name()
+            ));
+            outcomes.record(step, StepOutcome::Skipped);
+            step_result.status = StepStatus::Skipped;
+            continue;
+        }
+
+        if let Some(question) = step.confirm()
+            && !invocations.is_empty()
+            && !args.dry_run
+        {
+            confirm_step(ctx, outputter, job_id, step, question)?;
+        }
+
+        if needs_prebuild && ctx.per_package(job, step) && !invocations.is_empty() {
+            let packages: Vec<_> = invocations.iter().flat_map(Invocation::packages).collect();
+            prebuild_dependencies(ctx, outputter, &packages)?;
+            needs_prebuild = false;
+        }
+
+        let mut step_span = ctx.telemetry.start(Some(job_span), step.name(), ctx.host.local_time());
+        if let Some(id) = step.id() {
+            step_span.attribute("cargo_ci.step.id", id.as_str());
+        }
+
+        let step_start = ctx.host.now();
+        let result = run_invocations(
+            ctx,
+            outputter,
+            job_id,
+            job,
+            (index, step),
+            invocations,
+            step_result,
+            &mut network_bucket,
+            &mut step_span,
+        );
+        ctx.record_step(job_id, step, &result, step_start, &step_result.packages);
+        if matches!(result, Ok(StepOutcome::Success)) {
+            ctx.record_progress(outputter, job_id, index, None);
+        }
+
+        step_result.status = match &result {
+            Ok(StepOutcome::Success) => StepStatus::Succeeded,
+            Ok(StepOutcome::Failure) => StepStatus::FailedIgnored,
+            Ok(StepOutcome::Skipped) => StepStatus::Skipped,
+            Err(_) => StepStatus::Failed,
+        };
+        let error = match &result {
+            Ok(StepOutcome::Failure) => Some("failed, but continued due to continue_on_error".to_string()),
+            Ok(_) => None,
+            Err(e) => Some(format!("{e:#}")),
+        };
+
+        if let Ok(outcome) = result {
+            outcomes.record(step, outcome);
+            step_span.attribute("cargo_ci.step.outcome", outcome.as_str());
+        }
+
+        ctx.telemetry.finish(step_span, ctx.host.local_time(), error);
+        _ = result?;
+    }
+
+    if let Some(modified_before) = modified_before {
+        check_clean(ctx, outputter, &modified_before)?;
+    }
+
+    Ok(())
+}
+
+/// Runs every invocation of a step, returning how the step turned out.
+#[expect(clippy::too_many_arguments, reason = "All of these are needed to run the step")]
+fn run_invocations<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    job: &Job,
+    (step_index, step): (usize, &Step),
+    invocations: Vec<Invocation<'_>>,
+    step_result: &mut StepResult,
+    network_bucket: &mut Option<TokenBucket>,
+    step_span: &mut ActiveSpan,
+) -> anyhow::Result<StepOutcome> {
+    let args = ctx.args;
+
+    // dry runs assume every step that would run succeeds
+    let mut outcome = if invocations.is_empty() {
+        StepOutcome::Skipped
+    } else {
+        StepOutcome::Success
+    };
+
+    if !args.dry_run && !invocations.is_empty() {
+        check_freshness(ctx, outputter, step)?;
+    }
+
+    let shared = (Mutex::new(network_bucket), Mutex::new(step_span));
+    let parallel_packages = step.parallel_packages().unwrap_or(1).min(invocations.len());
+    if !args.dry_run && parallel_packages > 1 {
+        let mut first_error = None;
+        for invoked in run_invocations_in_parallel(
+            ctx,
+            outputter,
+            (job_id, job),
+            (step_index, step),
+            invocations,
+            parallel_packages,
+            &shared,
+        ) {
+            match invoked.and_then(|invoked| note_invoked(step_result, outcome, invoked)) {
+                Ok(noted) => outcome = noted,
+                Err(e) => _ = first_error.get_or_insert(e),
+            }
+        }
+
+        return first_error.map_or(Ok(outcome), Err);
+    }
+
+    for invocation in invocations {
+        let invoked = run_invocation(ctx, outputter, (job_id, job), (step_index, step), &invocation, &shared)?;
+        outcome = note_invoked(step_result, outcome, invoked)?;
+    }
+
+    Ok(outcome)
+}
+
+/// How a step turned out for an invocation.
+struct Invoked<'a> {
+    /// The packages the invocation ran for.
+    names: Vec<&'a str>,
+
+    /// The package the invocation ran for on its own, along with how long it took, when it ran.
+    duration: Option<(&'a str, Duration)>,
+
+    status: StepStatus,
+
+    /// Why the step failed, when the failure should fail the step.
+    error: Option<anyhow::Error>,
+}
+
+/// The token bucket limiting the step's network-heavy commands, and the span of the step, shared by its invocations.
+type SharedInvocationState<'s> = (Mutex<&'s mut Option<TokenBucket>>, Mutex<&'s mut ActiveSpan>);
+
+/// Notes how a step turned out for an invocation in the step's result, returning the step's outcome so far, or the error
+/// failing the step.
+fn note_invoked(step_result: &mut StepResult, outcome: StepOutcome, invoked: Invoked<'_>) -> anyhow::Result<StepOutcome> {
+    for result in step_result
+        .packages
+        .iter_mut()
+        .filter(|result| invoked.names.contains(&result.package.as_str()))
+    {
+        result.status = invoked.status;
+    }
+
+    if let Some((package, duration)) = invoked.duration
+        && let Some(result) = step_result.packages.iter_mut().find(|result| result.package == package)
+    {
+        result.duration = Some(duration);
+    }
+
+    if let Some(e) = invoked.error {
+        return Err(e);
+    }
+
+    Ok(match invoked.status {
+        StepStatus::Failed | StepStatus::FailedIgnored => StepOutcome::Failure,
+        _ => outcome,
+    })
+}
+
+/// Runs a step for several packages at once, each package getting its own line on the status board, until every
+/// invocation has run or one of them fails the step.
+///
+/// Returns how the step turned out for the invocations which ran, in no particular order.
+fn run_invocations_in_parallel<'a, H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    (job_id, job): (&JobId, &Job),
+    (step_index, step): (usize, &Step),
+    invocations: Vec<Invocation<'a>>,
+    parallel_packages: usize,
+    shared: &SharedInvocationState<'_>,
+) -> Vec<anyhow::Result<Invoked<'a>>> {
+    let job_name = job.name().unwrap_or(job_id.as_str());
+    let names: Vec<_> = invocations
+        .iter()
+        .flat_map(Invocation::packages)
+        .map(|pkg| pkg.name.as_str())
+        .collect();
+    outputter.message(Msg::StepForPackages(step.name(), &names.join(", ")));
+
+    // jobs running one at a time have no status board yet
+    let own_board = outputter
+        .board()
+        .is_none()
+        .then(|| Arc::new(StatusBoard::new(ctx.host.is_terminal())));
+    let board = outputter.board().or(own_board.as_ref()).expect("status board missing");
+
+    let pending = Mutex::new(invocations.into_iter());
+    let invoked = Mutex::new(Vec::new());
+    let failed = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let (stop_status, stopped) = mpsc::channel::<()>();
+        if own_board.is_some() {
+            _ = scope.spawn(move || show_status(ctx.host, board, &stopped));
+        }
+
+        for _ in 0..parallel_packages {
+            let package_outputter = outputter.fork_onto(board);
+            let (pending, invoked, failed) = (&pending, &invoked, &failed);
+            _ = scope.spawn(move || {
+                while !failed.load(Ordering::Relaxed) {
+                    let Some(invocation) = pending.lock().unwrap_or_else(PoisonError::into_inner).next() else {
+                        break;
+                    };
+
+                    let names: Vec<_> = invocation.packages().map(|pkg| pkg.name.as_str()).collect();
+                    package_outputter.start_activity(format!("{job_name} [{}]", names.join(", ")));
+                    let result = run_invocation(ctx, &package_outputter, (job_id, job), (step_index, step), &invocation, shared);
+                    match &result {
+                        Ok(result) if result.error.is_none() => package_outputter.complete_activity(Msg::StepStatus(result.status)),
+                        _ => {
+                            failed.store(true, Ordering::Relaxed);
+                            package_outputter.complete_activity(Msg::StepStatus(StepStatus::Failed));
+                        }
+                    }
+
+                    invoked.lock().unwrap_or_else(PoisonError::into_inner).push(result);
+                }
+            });
+        }
+
+        drop(stop_status);
+    });
+
+    if let Some(board) = own_board {
+        board.finish();
+    }
+
+    invoked.into_inner().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Runs a step for an invocation, failing when the step can't even start for it.
+fn run_invocation<'a, H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    (job_id, job): (&JobId, &Job),
+    (step_index, step): (usize, &Step),
+    invocation: &Invocation<'a>,
+    (network_bucket, step_span): &SharedInvocationState<'_>,
+) -> anyhow::Result<Invoked<'a>> {
+    let args = ctx.args;
+    let (pkg, continue_on_error) = (invocation.pkg, invocation.continue_on_error);
+    if !args.dry_run && ctx.out_of_time() {
+        return Err(ctx
+            .out_of_time_error()
+            .context(format!("step '{}' wasn't started for every package", step.name())));
+    }
+
+    if ctx.cancel_requested(job_id) {
+        return Err(anyhow!("job '{job_id}' was cancelled during step '{}'", step.name()));
+    }
+
+    let names: Vec<_> = invocation.packages().map(|pkg| pkg.name.as_str()).collect();
+    let invoked = |names, status| Invoked {
+        names,
+        duration: None,
+        status,
+        error: None,
+    };
+
+    if ctx.resumed_packages(outputter, job_id, (step_index, step), &names) {
+        return Ok(invoked(names, StepStatus::Skipped));
+    }
+
+    match (pkg, names.len()) {
+        (Some(pkg), _) => outputter.message(Msg::StepForPackage(step.name(), &pkg.name)),
+        (None, 0) => outputter.message(Msg::Step(step.name())),
+        (None, _) => outputter.message(Msg::StepForPackages(step.name(), &names.join(", "))),
+    }
+
+    if args.dry_run {
+        preview_invocation(ctx, outputter, (job_id, job, step), invocation);
+        return Ok(invoked(names, StepStatus::Succeeded));
+    }
+
+    let variables = step_run_variables(ctx, job_id, job, step, pkg);
+    let directory = step_working_directory(ctx, (job_id, job, step), pkg, &variables, true)
+        .with_context(|| format!("unable to start step '{}'", step.name()))?;
+    let overridden = ctx.invocation_command(step, invocation);
+    let mut cmd = build_command(ctx, job, step, overridden.as_deref(), &directory, &variables)?;
+    if args.verbose >= 1 {
+        outputter.detail(format!("running {}> {cmd:?}", directory.display()));
+    }
+
+    if args.verbose >= 2 {
+        explain_variables(outputter, &variables);
+    }
+
+    let step_span_id = step_span.lock().unwrap_or_else(PoisonError::into_inner).id();
+    let mut package_span = (!names.is_empty()).then(|| {
+        let mut span = ctx.telemetry.start(Some(step_span_id), names.join(", "), ctx.host.local_time());
+        span.attribute("cargo_ci.package", names.join(","));
+        span
+    });
+
+    let attributes = [
+        ("process.command_line", overridden.unwrap_or_else(|| step.command().into_owned())),
+        ("process.working_directory", directory.display().to_string()),
+    ];
+    for (key, value) in attributes {
+        match package_span.as_mut() {
+            Some(span) => span.attribute(key, value),
+            None => step_span.lock().unwrap_or_else(PoisonError::into_inner).attribute(key, value),
+        }
+    }
+
+    let key = shared_result_key(ctx, step, invocation, &cmd, &variables);
+    let started = ctx.host.now();
+    let e = run_shared(ctx, outputter, job_id, step, key, || {
+        let _network_guard = ctx.throttle_network(outputter, step, &mut network_bucket.lock().unwrap_or_else(PoisonError::into_inner));
+        run_with_fallbacks(ctx, outputter, (job_id, job, step), invocation, &mut cmd, (&directory, &variables))
+    });
+
+    if let Some(span) = package_span {
+        ctx.telemetry
+            .finish(span, ctx.host.local_time(), e.as_ref().err().map(|e| format!("{e:#}")));
+    }
+
+    let duration = pkg.map(|pkg| (pkg.name.as_str(), ctx.host.now().duration_since(started)));
+    if e.is_ok() {
+        ctx.record_progress(outputter, job_id, step_index, Some(&names));
+    }
+
+    Ok(match e {
+        Ok(()) => Invoked {
+            duration,
+            ..invoked(names, StepStatus::Succeeded)
+        },
+        Err(_) if continue_on_error => Invoked {
+            duration,
+            ..invoked(names, StepStatus::FailedIgnored)
+        },
+        Err(e) => Invoked {
+            duration,
+            error: Some(e),
+            ..invoked(names, StepStatus::Failed)
+        },
+    })
+}
+
+/// Ensures the generated files a step requires are up to date before it runs, first running the step's `regenerate`
+/// command when they aren't and it has one.
+fn check_freshness<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, step: &Step) -> anyhow::Result<()> {
+    let rules = step.requires_fresh();
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let workspace_root = ctx.metadata.workspace_root.as_std_path();
+    let check = || {
+        freshness::stale_files(workspace_root, rules)
+            .with_context(|| format!("unable to check the generated files of step '{}'", step.name()))
+    };
+
+    let stale = check()?;
+    if stale.is_empty() {
+        return Ok(());
+    }
+
+    let Some(command) = step.regenerate() else {
+        return Err(stale_files_error(outputter, step, &stale));
+    };
+
+    outputter.message(format!("regenerating {} stale file(s) for step '{}'", stale.len(), step.name()));
+    let mut cmd = shell_command(command);
+    _ = cmd.current_dir(workspace_root).stdout(Stdio::piped()).stderr(Stdio::piped());
+    outputter.run_command(&cmd);
+
+    let output = ctx.host.output(&mut cmd).with_context(|| format!("unable to run '{command}'"))?;
+    if !output.status.success() {
+        outputter.command_error("unable to regenerate files", Some(output.status), Some(&output), true);
+        return Err(anyhow!(
+            "unable to regenerate the stale files of step '{}': {}",
+            step.name(),
+            output.status
+        ));
+    }
+
+    let stale = check()?;
+    if stale.is_empty() {
+        Ok(())
+    } else {
+        Err(stale_files_error(outputter, step, &stale))
+    }
+}
+
+/// Reports the stale generated files of a step, returning the error failing the step.
+fn stale_files_error<H: Host>(outputter: &Outputter<H>, step: &Step, stale: &[StaleFile]) -> anyhow::Error {
+    for file in stale {
+        outputter.detail(file.describe());
+    }
+
+    anyhow!(
+        "generated files of step '{}' are stale, {} file(s) are out of date",
+        step.name(),
+        stale.len()
+    )
+}
+
+/// Makes the command running a step, or the given command line in its place, ready to start.
+fn build_command<H: Host>(
+    ctx: &RunContext<'_, H>,
+    job: &Job,
+    step: &Step,
+    command: Option<&str>,
+    directory: &Path,
+    variables: &[(&str, &str, VariableSource)],
+) -> anyhow::Result<Command> {
+    let mut cmd = make_command(
+        step,
+        command,
+        directory,
+        (
+            job.background(),
+            ctx.args.debug_failures && step.ulimit_core(),
+            ctx.network_isolation && !step.network(),
+        ),
+        variables.iter().map(|(k, v, _)| (*k, *v)),
+    );
+    if !step.inherits_env() {
+        isolate_env(ctx.host, &mut cmd, &declared_variables(variables));
+    }
+
+    // a step given a target directory of its own keeps it
+    if !variables.iter().any(|(k, _, _)| *k == "CARGO_TARGET_DIR") {
+        ctx.use_target_dir(&mut cmd);
+    }
+
+    // variables the step is given, or inherits, keep their value
+    if ctx.args.debug_failures {
+        for (name, value) in debug_failures::VARIABLES {
+            let declared = variables.iter().any(|(k, _, _)| *k == name);
+            let inherited = step.inherits_env() && ctx.host.vars().any(|(k, _)| k == name);
+            if !declared && !inherited {
+                _ = cmd.env(name, value);
+            }
+        }
+    }
+
+    // directories are checked as each step starts, since an earlier step may be what creates them
+    if let Some(path) = search_path(ctx.host, ctx.metadata.workspace_root.as_std_path(), &step_path(job, step))
+        .with_context(|| format!("unable to start step '{}'", step.name()))?
+    {
+        _ = cmd.env("PATH", path);
+    }
+
+    Ok(cmd)
+}
+
+/// Identifies what an invocation of a step runs: its command line and fallbacks, directory, packages, and variables.
+/// Returns `None` for steps which don't opt into `reuse_result`, for those whose result can't be reused by other jobs,
+/// because they do more than run a command or because they change things, and when `--no-cache` is given.
+fn shared_result_key<H: Host>(
+    ctx: &RunContext<'_, H>,
+    step: &Step,
+    invocation: &Invocation<'_>,
+    cmd: &Command,
+    variables: &[(&str, &str, VariableSource)],
+) -> Option<String> {
+    if ctx.args.no_cache || !step.reuse_result() || step.kind() != StepKind::Command || step.destructive() || step.sarif() {
+        return None;
+    }
+
+    let mut key = command_preview(cmd, !step.inherits_env());
+    for fallback in ctx.fallback_commands(step, invocation) {
+        _ = write!(key, "\n|| {fallback}");
+    }
+
+    for pkg in invocation.packages() {
+        _ = write!(key, "\npackage {}", pkg.id);
+    }
+
+    let mut variables: Vec<_> = variables.iter().map(|(name, value, _)| (*name, *value)).collect();
+    variables.sort_unstable();
+    for (name, value) in variables {
+        _ = write!(key, "\n{name}={value}");
+    }
+
+    Some(key)
+}
+
+/// Runs a step command, unless another job of this run already ran the very same command, in which case how it turned
+/// out then is reused. Jobs running the same command concurrently wait for the first one to finish.
+fn run_shared<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    step: &Step,
+    key: Option<String>,
+    run: impl FnOnce() -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let Some(key) = key else {
+        return run();
+    };
+
+    let lock = ctx.shared_results.lock(&key);
+    let _guard = match lock.try_lock() {
+        Ok(guard) => guard,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            outputter.message("waiting for another job running the same command");
+            lock.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    };
+
+    if let Some(previous) = ctx.shared_results.get(&key) {
+        outputter.message(format!("reused result from job '{}'", previous.job_id));
+        return match previous.error {
+            None => Ok(()),
+            Some(e) => Err(anyhow!(
+                "step '{}' failed in job '{}', whose result was reused: {e}",
+                step.name(),
+                previous.job_id
+            )),
+        };
+    }
+
+    let result = run();
+    ctx.shared_results.record(
+        key,
+        SharedResult {
+            job_id: job_id.to_string(),
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+        },
+    );
+    result
+}
+
+/// Runs an invocation of a step, trying the fallback commands of the step in order when its command fails, until one
+/// of them succeeds.
+fn run_with_fallbacks<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    (job_id, job, step): (&JobId, &Job, &Step),
+    invocation: &Invocation<'_>,
+    cmd: &mut Command,
+    (directory, variables): (&Path, &[(&str, &str, VariableSource)]),
+) -> anyhow::Result<()> {
+    let (pkg, continue_on_error) = (invocation.pkg, invocation.continue_on_error);
+    let fallbacks = ctx.fallback_commands(step, invocation);
+
+    // failures which will fall back to another command aren't fatal
+    let mut result = run_with_retries(ctx, outputter, job_id, step, pkg, cmd, continue_on_error || !fallbacks.is_empty());
+    for (index, fallback) in fallbacks.iter().enumerate() {
+        if result.is_ok() || ctx.cancel_requested(job_id) || ctx.out_of_time() {
+            break;
+        }
+
+        outputter.message(format!("step '{}' failed, falling back to '{fallback}'", step.name()));
+        let mut cmd = build_command(ctx, job, step, Some(fallback), directory, variables)?;
+        if ctx.args.verbose >= 1 {
+            outputter.detail(format!("running {}> {cmd:?}", directory.display()));
+        }
+
+        let last = index + 1 == fallbacks.len();
+        result = run_with_retries(ctx, outputter, job_id, step, pkg, &mut cmd, continue_on_error || !last);
+        if result.is_ok() {
+            outputter.message(format!("step '{}' succeeded with fallback '{fallback}'", step.name()));
+        }
+    }
+
+    result
+}
+
+/// Shows what an invocation of a step would run, quoted for the shell along with its working directory and the
+/// variables set for it, so it can be pasted into a terminal to run it by hand. Steps running a cargo alias also show
+/// what cargo expands the alias to.
+fn preview_invocation<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    (job_id, job, step): (&JobId, &Job, &Step),
+    invocation: &Invocation<'_>,
+) {
+    let variables = step_run_variables(ctx, job_id, job, step, invocation.pkg);
+    let overridden = ctx.invocation_command(step, invocation);
+    let directory = match step_working_directory(ctx, (job_id, job, step), invocation.pkg, &variables, false) {
+        Ok(directory) => directory,
+        Err(e) => {
+            outputter.detail(format!("unable to show the command: {e:#}"));
+            return;
+        }
+    };
+
+    match build_command(ctx, job, step, overridden.as_deref(), &directory, &variables) {
+        Ok(mut cmd) => {
+            _ = cmd.env("CI_TEMP", ctx.temp_dir);
+            outputter.detail(format!("$ {}", command_preview(&cmd, !step.inherits_env())));
+        }
+        Err(e) => outputter.detail(format!("unable to show the command: {e:#}")),
+    }
+
+    if let Some(args) = step.cargo_alias_args().filter(|_| overridden.is_none())
+        && let Some(expanded) = CargoAliases::discover(ctx.host, &directory).expand(&args)
+    {
+        let words: Vec<String> = expanded.iter().map(|arg| shell_quote(arg)).collect();
+        outputter.detail(format!("# cargo expands this to: cargo {}", words.join(" ")));
+    }
+}
+
+/// A command line which runs a command the way `cargo-ci` does, in its working directory and with the variables set
+/// for it, in the syntax of the shell which runs steps. An `isolated` command gets none of the other variables of the
+/// environment it's run in.
+fn command_preview(cmd: &Command, isolated: bool) -> String {
+    let quote = |s: &OsStr| shell_quote(&s.to_string_lossy());
+    let mut words = Vec::new();
+
+    if let Some(dir) = cmd.get_current_dir() {
+        words.push(if cfg!(windows) { "cd /d".to_string() } else { "cd".to_string() });
+        words.push(quote(dir.as_os_str()));
+        words.push("&&".to_string());
+    }
+
+    let vars = cmd.get_envs().filter_map(|(name, value)| Some((name.to_string_lossy(), value?)));
+    if cfg!(windows) {
+        words.extend(vars.map(|(name, value)| format!("set \"{name}={}\" &&", value.to_string_lossy())));
+    } else {
+        let vars: Vec<_> = vars.map(|(name, value)| format!("{name}={}", quote(value))).collect();
+        if isolated {
+            words.push("env -i".to_string());
+        } else if !vars.is_empty() {
+            words.push("env".to_string());
+        }
+
+        words.extend(vars);
+    }
+
+    words.push(quote(cmd.get_program()));
+    words.extend(cmd.get_args().map(quote));
+    words.join(" ")
+}
+
+/// The variables a step receives when it runs, labeled with where they came from, from lowest to highest precedence.
+fn step_run_variables<'v, H: Host>(
+    ctx: &RunContext<'v, H>,
+    job_id: &'v JobId,
+    job: &'v Job,
+    step: &'v Step,
+    pkg: Option<&'v Package>,
+) -> Vec<(&'v str, &'v str, VariableSource)> {
+    let pkg = pkg.filter(|_| ctx.per_package(job, step));
+
+    tagged(VariableSource::Environment, ctx.env_vars())
+        .chain(tagged(VariableSource::Workspace, ctx.cfg.variables()))
+        .chain(tagged(VariableSource::Job, job.variables()))
+        .chain(tagged(VariableSource::Step, step.variables()))
+        .chain(tagged(
+            VariableSource::Package,
+            pkg.into_iter().flat_map(|pkg| ctx.package_variables(pkg)),
+        ))
+        .chain(tagged(VariableSource::CommandLine, ctx.args.step_variables(job_id, step)))
+        .collect()
+}
+
+/// The variables declared for a step in the configuration or on the command line, leaving out those imported from the
+/// environment.
+fn declared_variables(variables: &[(&str, &str, VariableSource)]) -> BTreeMap<String, String> {
+    variables
+        .iter()
+        .filter(|(_, _, source)| *source != VariableSource::Environment)
+        .map(|(k, v, _)| ((*k).to_string(), (*v).to_string()))
+        .collect()
+}
+
+/// Keeps a command from inheriting the environment `cargo-ci` runs in, giving it only the given variables, along with
+/// `PATH` and whatever else the platform can't do without.
+pub fn isolate_env<H: Host>(host: &H, cmd: &mut Command, variables: &BTreeMap<String, String>) {
+    // Windows processes misbehave without SystemRoot, and names aren't case-sensitive there
+    let essential = |name: &str| name.eq_ignore_ascii_case("PATH") || (cfg!(windows) && name.eq_ignore_ascii_case("SystemRoot"));
+
+    _ = cmd.env_clear();
+    for (name, value) in host.vars().filter(|(name, _)| essential(name)) {
+        _ = cmd.env(name, value);
+    }
+
+    _ = cmd.envs(variables);
+}
+
+/// Lists the variables a step receives, along with where each came from and which definitions it overrides.
+fn explain_variables<H: Host>(outputter: &Outputter<H>, variables: &[(&str, &str, VariableSource)]) {
+    let mut names: Vec<_> = variables.iter().map(|(k, _, _)| *k).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    outputter.detail(format!("{} variable(s):", names.len()));
+    for name in names {
+        // later definitions take precedence over earlier ones
+        let mut definitions = variables.iter().rev().filter(|(k, _, _)| *k == name);
+        let Some((_, value, source)) = definitions.next() else {
+            continue;
+        };
+
+        let overridden: Vec<_> = definitions.map(|(_, _, source)| source.to_string()).collect();
+        if overridden.is_empty() {
+            outputter.detail(format!("  {name} = '{value}' (from {source})"));
+        } else {
+            outputter.detail(format!(
+                "  {name} = '{value}' (from {source}, overriding {})",
+                overridden.join(", ")
+            ));
+        }
+    }
+}
+
+/// Determines whether a destructive step may run, asking for confirmation when running interactively.
+fn allow_destructive<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, job_id: &JobId, step: &Step) -> anyhow::Result<bool> {
+    if ctx.args.allow_destructive {
+        return Ok(true);
+    }
+
+    // the question goes to stdout while the answer comes from stdin, so both need to be a terminal
+    if !ctx.host.is_terminal() || !ctx.host.stdin_is_terminal() {
+        return Ok(false);
+    }
+
+    let _guard = ctx.prompt_lock.lock().unwrap_or_else(PoisonError::into_inner);
+    outputter.line(format!("Step '{}' of job '{job_id}' is destructive. Run it? [y/N]", step.name()));
+    let answer = ctx.host.read_line().context("unable to read the confirmation")?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Fails for steps which can't be part of a plan.
+fn check_plannable(args: &RunArgs, job_id: &JobId, step: &Step) -> anyhow::Result<()> {
+    if step.kind() != StepKind::Command {
+        return Err(anyhow!(
+            "step '{}' of job '{job_id}' is not a plain command, so it can't be part of a plan",
+            step.name()
+        ));
+    }
+
+    if let Some(name) = step.executor() {
+        return Err(anyhow!(
+            "step '{}' of job '{job_id}' runs through executor '{name}', which a plan can't express",
+            step.name()
+        ));
+    }
+
+    if !step.network() {
+        return Err(anyhow!(
+            "step '{}' of job '{job_id}' is kept off the network, which depends on the system running it, so it can't be part of a plan",
+            step.name()
+        ));
+    }
+
+    if step.working_directory_mode() == WorkingDirectoryMode::Temp {
+        return Err(anyhow!(
+            "step '{}' of job '{job_id}' runs in a temporary directory, which only exists during a run, so it can't be part of a plan",
+            step.name()
+        ));
+    }
+
+    if !step.requires_fresh().is_empty() {
+        return Err(anyhow!(
+            "step '{}' of job '{job_id}' requires fresh generated files, which a plan can't check, so it can't be part of a plan",
+            step.name()
+        ));
+    }
+
+    if !step.retry_strategies().is_empty() {
+        return Err(anyhow!(
+            "step '{}' of job '{job_id}' has retry strategies, which a plan can't carry out, so it can't be part of a plan",
+            step.name()
+        ));
+    }
+
+    if let Some(question) = step.confirm()
+        && !args.yes
+    {
+        return Err(anyhow!(
+            "step '{}' of job '{job_id}' asks for confirmation (\"{question}\"), which a plan can't do, use --yes to include it",
+            step.name()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Asks whether to go ahead with a step which wants confirmation, failing when the answer is no or when there's nobody
+/// to ask.
+fn confirm_step<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    step: &Step,
+    question: &str,
+) -> anyhow::Result<()> {
+    if ctx.args.yes {
+        return Ok(());
+    }
+
+    if !ctx.host.is_terminal() || !ctx.host.stdin_is_terminal() {
+        return Err(anyhow!(
+            "step '{}' of job '{job_id}' asks for confirmation (\"{question}\") but the run isn't interactive, use --yes to confirm it",
+            step.name()
+        ));
+    }
+
+    let _guard = ctx.prompt_lock.lock().unwrap_or_else(PoisonError::into_inner);
+    outputter.line(format!("{question} [y/N]"));
+    let answer = ctx.host.read_line().context("unable to read the confirmation")?;
+    if matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(Failure::Cancelled.tag(anyhow!("step '{}' of job '{job_id}' wasn't confirmed", step.name())))
+    }
+}
+
+/// The directory a step runs in, which is the job's `working_directory` when it sets one, with variables interpolated,
+/// the package's directory for steps run for a package, or the workspace root otherwise.
+fn step_directory<H: Host>(
+    ctx: &RunContext<'_, H>,
+    job: &Job,
+    pkg: Option<&Package>,
+    variables: &[(&str, &str, VariableSource)],
+) -> anyhow::Result<PathBuf> {
+    let workspace_root = ctx.metadata.workspace_root.as_std_path();
+    if let Some(working_directory) = job.working_directory() {
+        let relative = interpolate(working_directory, variables).context("unable to resolve the working directory of the job")?;
+
+        // the configuration only holds relative directories, but variables can still make them absolute
+        if Path::new(&relative).is_absolute() {
+            return Err(anyhow!(
+                "the working directory of the job, '{working_directory}', is '{relative}', which must be relative to the workspace root"
+            ));
+        }
+
+        return Ok(normalize_path(&workspace_root.join(relative)));
+    }
+
+    Ok(pkg.map_or_else(
+        || workspace_root.to_path_buf(),
+        |pkg| {
+            pkg.manifest_path
+                .parent()
+                .expect("should have a valid parent")
+                .as_std_path()
+                .to_path_buf()
+        },
+    ))
+}
+
+/// Resolves the `.` and `..` components of a path without looking at the file system, so directories outside the
+/// workspace are shown as such.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                _ = normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// The directory a step runs in, which is its `working_directory` when it sets one, with variables interpolated.
+///
+/// The directory is checked or created as the step's working directory mode says, unless `prepare` is false, as when
+/// the step is only planned.
+fn step_working_directory<H: Host>(
+    ctx: &RunContext<'_, H>,
+    (job_id, job, step): (&JobId, &Job, &Step),
+    pkg: Option<&Package>,
+    variables: &[(&str, &str, VariableSource)],
+    prepare: bool,
+) -> anyhow::Result<PathBuf> {
+    let base = step_directory(ctx, job, pkg, variables)?;
+    if prepare && job.working_directory().is_some() && !base.is_dir() {
+        return Err(anyhow!("the working directory of the job, {}, doesn't exist", base.display()));
+    }
+
+    let Some(working_directory) = step.working_directory() else {
+        return Ok(base);
+    };
+
+    let relative = interpolate(working_directory, variables).context("unable to resolve the working directory")?;
+    let mode = step.working_directory_mode();
+    let directory = match mode {
+        WorkingDirectoryMode::Temp => temp_working_directory(ctx, (job_id, job, step), pkg, &relative)?,
+        WorkingDirectoryMode::MustExist | WorkingDirectoryMode::CreateIfMissing => base.join(relative),
+    };
+
+    if !prepare {
+        return Ok(directory);
+    }
+
+    match mode {
+        WorkingDirectoryMode::MustExist if !directory.is_dir() => {
+            return Err(anyhow!(
+                "working directory {} doesn't exist, use 'create_working_directory = true' to create it",
+                directory.display()
+            ));
+        }
+
+        WorkingDirectoryMode::Temp if directory.exists() => {
+            fs::remove_dir_all(long_paths::extended(&directory)?).with_context(|| format!("unable to clear {}", directory.display()))?;
+        }
+
+        _ => {}
+    }
+
+    fs::create_dir_all(long_paths::extended(&directory)?).with_context(|| format!("unable to create {}", directory.display()))?;
+    Ok(directory)
+}
+
+/// Where a step with `working_directory_mode = "temp"` runs, within the run's temporary directory.
+///
+/// Since the directory is cleared before the step runs, it must stay within the temporary directory, and every job,
+/// step, and package gets one of its own, so invocations running concurrently don't clear each other's.
+fn temp_working_directory<H: Host>(
+    ctx: &RunContext<'_, H>,
+    (job_id, job, step): (&JobId, &Job, &Step),
+    pkg: Option<&Package>,
+    relative: &str,
+) -> anyhow::Result<PathBuf> {
+    let relative = Path::new(relative);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(anyhow!(
+            "temporary working directory '{}' must be a relative path which stays within the run's temporary directory",
+            relative.display()
+        ));
+    }
+
+    let step_index = job.steps().iter().position(|s| core::ptr::eq(s, step)).unwrap_or_default();
+    let mut directory = ctx.temp_dir.join("work").join(job_id.as_str()).join(step_index.to_string());
+    if let Some(pkg) = pkg {
+        directory.push(pkg.name.as_str());
+    }
+
+    Ok(directory.join(relative))
+}
+
+/// Replaces every `${NAME}` in a setting with the value of the variable, failing for variables which aren't defined.
+fn interpolate(text: &str, variables: &[(&str, &str, VariableSource)]) -> anyhow::Result<String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some((before, after)) = rest.split_once("${") {
+        let (name, remaining) = after
+            .split_once('}')
+            .ok_or_else(|| anyhow!("'{text}' has an unterminated variable reference"))?;
+
+        // later definitions take precedence over earlier ones
+        let (_, value, _) = variables
+            .iter()
+            .rev()
+            .find(|(k, _, _)| *k == name)
+            .ok_or_else(|| anyhow!("'{text}' refers to variable '{name}', which isn't defined"))?;
+
+        result.push_str(before);
+        result.push_str(value);
+        rest = remaining;
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// The tracked files with uncommitted changes, along with their `git status` code and the hash of their content, which
+/// is missing for deleted files.
+fn modified_files<H: Host>(ctx: &RunContext<'_, H>) -> anyhow::Result<BTreeMap<String, (String, Option<String>)>> {
+    let root = ctx.metadata.workspace_root.as_std_path();
+    let statuses = git::modified_files(ctx.host, root)?;
+    let paths: Vec<_> = statuses.keys().map(String::as_str).collect();
+    let mut hashes = git::content_hashes(ctx.host, root, &paths)?;
+
+    Ok(statuses
+        .into_iter()
+        .map(|(path, status)| {
+            let hash = hashes.remove(&path);
+            (path, (status, hash))
+        })
+        .collect())
+}
+
+/// Fails if tracked files were modified since the given snapshot was taken, listing the files which changed.
+///
+/// Files which were already modified when the snapshot was taken count as changed when either their status or their
+/// content is different now.
+fn check_clean<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    modified_before: &BTreeMap<String, (String, Option<String>)>,
+) -> anyhow::Result<()> {
+    let modified_after = modified_files(ctx)?;
+
+    let changed: Vec<_> = modified_after
+        .iter()
+        .filter(|(path, state)| modified_before.get(*path) != Some(*state))
+        .map(|(path, _)| path.as_str())
+        .collect();
+
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    for path in &changed {
+        outputter.detail(format!("modified '{path}'"));
+    }
+
+    Err(anyhow!(
+        "the job modified {} tracked file(s): {}",
+        changed.len(),
+        changed.join(", ")
+    ))
+}
+
+/// Builds the dependencies of the given packages once from the workspace root, so the ones they share are compiled a
+/// single time instead of once by every per-package step. The workspace's own packages are left to the steps.
+fn prebuild_dependencies<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, packages: &[&Package]) -> anyhow::Result<()> {
+    outputter.message("pre-building shared dependencies");
+
+    if ctx.args.dry_run {
+        return Ok(());
+    }
+
+    // the metadata the run works from leaves out dependencies, so the resolved graph is loaded here
+    let mut metadata = MetadataCommand::new();
+    _ = metadata.current_dir(ctx.metadata.workspace_root.as_std_path());
+    let metadata = crate::read_metadata(ctx.host, &metadata).context("unable to list the dependencies to pre-build")?;
+
+    let dependencies = external_dependencies(&metadata, packages);
+    if dependencies.is_empty() {
+        return Ok(());
+    }
+
+    let mut cmd = Command::new("cargo");
+    _ = cmd.current_dir(ctx.metadata.workspace_root.as_std_path()).arg("build");
+    ctx.use_target_dir(&mut cmd);
+    for spec in dependencies {
+        _ = cmd.arg("--package").arg(spec);
+    }
+
+    _ = cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    outputter.run_command(&cmd);
+
+    match ctx.host.output(&mut cmd) {
+        Ok(output) if output.status.success() => Ok(()),
+
+        Ok(output) => {
+            outputter.command_error("unable to pre-build dependencies", Some(output.status), Some(&output), true);
+            let hints = outputter.report_hints(ctx.cfg.hints(), &output, true);
+            Err(anyhow!("unable to pre-build dependencies: {}{hints}", output.status))
+        }
+
+        Err(e) => {
+            outputter.command_error(format!("unable to start dependency pre-build: {e}"), None, None, true);
+            Err(anyhow!("unable to start dependency pre-build: {e}"))
+        }
+    }
+}
+
+/// The package specs of the dependencies from outside the workspace which the given packages use directly, or through
+/// other workspace packages.
+fn external_dependencies(metadata: &Metadata, packages: &[&Package]) -> BTreeSet<String> {
+    let mut pending: Vec<_> = metadata
+        .workspace_members
+        .iter()
+        .filter(|id| packages.iter().any(|pkg| pkg.id == **id))
+        .collect();
+    let mut covered = HashSet::new();
+    let mut dependencies = BTreeSet::new();
+    while let Some(id) = pending.pop() {
+        if !covered.insert(id) {
+            continue;
+        }
+
+        let node = metadata
+            .resolve
+            .as_ref()
+            .and_then(|resolve| resolve.nodes.iter().find(|node| node.id == *id));
+        for dep in node.into_iter().flat_map(|node| &node.dependencies) {
+            if metadata.workspace_members.contains(dep) {
+                pending.push(dep);
+            } else {
+                let pkg = &metadata[dep];
+                _ = dependencies.insert(format!("{}@{}", pkg.name, pkg.version));
+            }
+        }
+    }
+
+    dependencies
+}
+
+/// Runs a step, retrying it after each of its retry strategies in turn for as long as it fails.
+fn run_with_retries<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    step: &Step,
+    pkg: Option<&Package>,
+    cmd: &mut Command,
+    continue_on_error: bool,
+) -> anyhow::Result<()> {
+    let strategies = step.retry_strategies();
+
+    // failures which will be retried aren't fatal
+    let mut result = run_step(ctx, outputter, job_id, step, pkg, cmd, continue_on_error || !strategies.is_empty());
+    for (index, &strategy) in strategies.iter().enumerate() {
+        if result.is_ok() {
+            break;
+        }
+
+        outputter.message(format!("retrying step '{}' ({})", step.name(), strategy.as_str()));
+        prepare_retry(ctx, outputter, strategy, pkg)?;
+
+        let last = index + 1 == strategies.len();
+        result = run_step(ctx, outputter, job_id, step, pkg, cmd, continue_on_error || !last);
+        if result.is_ok() {
+            outputter.message(format!("step '{}' succeeded once retried ({})", step.name(), strategy.as_str()));
+        }
+    }
+
+    result
+}
+
+/// Cleans up whatever a retry strategy calls for before a failed step is retried.
+fn prepare_retry<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    strategy: RetryStrategy,
+    pkg: Option<&Package>,
+) -> anyhow::Result<()> {
+    match strategy {
+        RetryStrategy::Plain => Ok(()),
+        RetryStrategy::CleanIncremental => remove_incremental_data(ctx.metadata.target_directory.as_std_path()),
+        RetryStrategy::CleanPackage => {
+            let packages = pkg.map_or_else(|| ctx.packages.to_vec(), |pkg| vec![pkg]);
+            clean_packages(ctx, outputter, &packages)
+        }
+    }
+}
+
+/// Removes the incremental compilation data of every profile, including the profiles of cross-compilation targets.
+fn remove_incremental_data(target_dir: &Path) -> anyhow::Result<()> {
+    let subdirs = |dir: &Path| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_dir())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    // profiles live in target/<profile>, or in target/<triple>/<profile> when cross-compiling
+    for profile_dir in subdirs(target_dir)
+        .iter()
+        .flat_map(|dir| subdirs(dir).into_iter().chain([dir.clone()]))
+    {
+        let incremental = profile_dir.join("incremental");
+        if incremental.is_dir() {
+            fs::remove_dir_all(long_paths::extended(&incremental)?)
+                .with_context(|| format!("unable to remove {}", incremental.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the build artifacts of the given packages with `cargo clean`.
+fn clean_packages<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, packages: &[&Package]) -> anyhow::Result<()> {
+    let mut cmd = Command::new("cargo");
+    _ = cmd.current_dir(ctx.metadata.workspace_root.as_std_path()).arg("clean");
+    ctx.use_target_dir(&mut cmd);
+    for pkg in packages {
+        _ = cmd.arg("--package").arg(pkg.name.as_str());
+    }
+
+    _ = cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    outputter.run_command(&cmd);
+
+    match ctx.host.output(&mut cmd) {
+        Ok(output) if output.status.success() => Ok(()),
+
+        Ok(output) => {
+            outputter.command_error("unable to clean packages", Some(output.status), Some(&output), true);
+            Err(anyhow!("unable to clean packages before retrying: {}", output.status))
+        }
+
+        Err(e) => {
+            outputter.command_error(format!("unable to start cargo clean: {e}"), None, None, true);
+            Err(anyhow!("unable to start cargo clean: {e}"))
+        }
+    }
+}
+
+/// Determines the packages a step applies to, honoring the shard selected on the command line.
+fn step_packages<'a, H: Host>(
+    ctx: &RunContext<'a, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    job: &Job,
+    step: &Step,
+) -> anyhow::Result<Vec<&'a Package>> {
+    let excluded = if job.include_excluded() { ctx.excluded_packages } else { &[] };
+    let packages: Vec<_> = ctx.packages.iter().chain(excluded).copied().collect();
+    let (Some(shards), Some(shard)) = (step.shards(), ctx.args.shard) else {
+        return Ok(packages);
+    };
+
+    if shards != shard.count() {
+        return Err(anyhow!(
+            "step '{}' is split into {shards} shard(s), but shard {shard} was requested",
+            step.name()
+        ));
+    }
+
+    let selected = if let Some(path) = &ctx.args.balance_shards {
+        balanced_shard(ctx, outputter, (job_id, step), shard, &packages, path)?
+    } else {
+        shard.select(&packages)
+    };
+
+    outputter.message(format!(
+        "step '{}' processing shard {shard}, {} of {} package(s)",
+        step.name(),
+        selected.len(),
+        packages.len()
+    ));
+
+    Ok(selected)
+}
+
+/// Selects the packages of a shard by how long the step took for each according to the given shard durations file.
+/// Dry runs show the packages every shard gets, along with how long each shard is expected to take.
+fn balanced_shard<'a, H: Host>(
+    ctx: &RunContext<'a, H>,
+    outputter: &Outputter<H>,
+    (job_id, step): (&JobId, &Step),
+    shard: Shard,
+    packages: &[&'a Package],
+    path: &Path,
+) -> anyhow::Result<Vec<&'a Package>> {
+    let durations = ShardDurations::load(path)?.get(job_id.as_str(), &shard_step_key(step));
+
+    if ctx.args.dry_run {
+        for (index, (members, expected)) in shard.balance(packages, &durations).iter().enumerate() {
+            let names: Vec<&str> = members.iter().map(|pkg| pkg.name.as_str()).collect();
+            outputter.detail(format!(
+                "shard {}/{}, expected to take {}: {}",
+                index + 1,
+                shard.count(),
+                format_duration(*expected),
+                names.join(", ")
+            ));
+        }
+    }
+
+    Ok(shard.select_balanced(packages, &durations))
+}
+
+/// Identifies a sharded step in the run history and in shard durations files.
+fn shard_step_key(step: &Step) -> String {
+    step.id().map_or_else(|| step.name().to_string(), ToString::to_string)
+}
+
+/// Saves how long the sharded steps of the given jobs took for each package in earlier runs, when asked to with
+/// `--save-shard-durations`.
+fn save_shard_durations(args: &RunArgs, cfg: &Config, metadata: &Metadata, jobs: &[&JobId]) -> anyhow::Result<()> {
+    let Some(path) = &args.save_shard_durations else {
+        return Ok(());
+    };
+
+    let history = History::load(&History::path(metadata.target_directory.as_std_path()))?;
+    let mut durations = ShardDurations::default();
+    for job_id in jobs {
+        let Some(job) = cfg.jobs().get_job(job_id) else {
+            continue;
+        };
+
+        for step in job.steps().iter().filter(|step| step.shards().is_some()) {
+            let key = shard_step_key(step);
+            durations.insert(
+                job_id.as_str(),
+                &key,
+                &history.package_durations(job_id.as_str(), &key, DEFAULT_WINDOW),
+            );
+        }
+    }
+
+    durations.save(path)
+}
+
+/// Runs a step according to its kind, reporting any failure.
+fn run_step<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    step: &Step,
+    pkg: Option<&Package>,
+    cmd: &mut Command,
+    continue_on_error: bool,
+) -> anyhow::Result<()> {
+    _ = cmd.env("CI_TEMP", ctx.temp_dir);
+
+    let diagnostic = || Diagnostic::Execution {
+        job: job_id.to_string(),
+        step: step.name().to_string(),
+        package: pkg.map(|pkg| pkg.name.to_string()),
+    };
+
+    if let Some(dir) = cmd.get_current_dir()
+        && let Err(e) = long_paths::check_working_dir(dir)
+    {
+        outputter.command_error(format!("unable to start step: {e}"), None, None, !continue_on_error);
+        return Err(diagnostic().attach(anyhow!("unable to start step '{}': {e}", step.name())));
+    }
+
+    let lock = step.lock().map(|key| (key, ctx.step_locks.get(key)));
+    let _guard = lock.as_ref().map(|(key, lock)| match lock.try_lock() {
+        Ok(guard) => guard,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            outputter.message(format!("waiting for lock '{key}'"));
+            lock.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    });
+
+    let _reservation = step.memory_hint().map(|hint| {
+        ctx.memory.try_reserve(hint.bytes()).unwrap_or_else(|| {
+            outputter.message(format!("waiting for {hint} of memory to be free"));
+            ctx.memory.reserve(hint.bytes())
+        })
+    });
+
+    let result = match step.kind() {
+        StepKind::Command => spawn_step(ctx, outputter, job_id, step, pkg, cmd, continue_on_error),
+        StepKind::CodegenCheck => check_codegen(ctx, outputter, job_id, step, pkg, cmd, continue_on_error),
+        StepKind::SemverCheck => install_cargo_tool(ctx, outputter, "cargo-semver-checks")
+            .and_then(|()| spawn_step(ctx, outputter, job_id, step, pkg, cmd, continue_on_error)),
+        StepKind::Audit => install_cargo_tool(ctx, outputter, "cargo-audit")
+            .and_then(|()| audit_dependencies(ctx, outputter, step, cmd, continue_on_error)),
+        StepKind::UpdateCheck => check_updates(ctx, outputter, step, cmd, continue_on_error),
+    };
+
+    result.map_err(|e| diagnostic().attach(e))
+}
 
-    /// Package to run jobs on (see `cargo help pkgid`)
-    #[arg(short = 'p', long, value_name = "SPEC")]
-    package: Vec<String>,
+/// Reports which dependencies `cargo update` would change in `Cargo.lock`, and for steps with `locked = true`, fails
+/// when the lockfile is out of sync with the manifests.
+fn check_updates<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    step: &Step,
+    cmd: &mut Command,
+    continue_on_error: bool,
+) -> anyhow::Result<()> {
+    let fatal = !continue_on_error;
+    outputter.run_command(cmd);
 
-    /// Define a variable.
-    #[arg(short = 'v', long, value_parser = parse_key_val::<String, String>, value_name = "VAR=VALUE")]
-    variable: Vec<(String, String)>,
+    let output = match ctx.host.output(cmd) {
+        Ok(output) => output,
+        Err(e) => {
+            outputter.command_error(format!("unable to start step: {e}"), None, None, fatal);
+            return Err(anyhow!("unable to start step '{}': {e}", step.name()));
+        }
+    };
 
-    /// Send log output to the specified file.
-    #[arg(short = 'l', long, value_name = "FILE")]
-    log_file: Option<PathBuf>,
+    if !output.status.success() {
+        outputter.command_error(Msg::UnableToRunStep, Some(output.status), Some(&output), fatal);
+        let hints = outputter.report_hints(ctx.cfg.hints(), &output, fatal);
+        return Err(anyhow!("unable to run step '{}': {}{hints}", step.name(), output.status));
+    }
 
-    /// Number of log files to retain (default: 16).
-    #[arg(long, default_value_t = 16, value_name = "COUNT")]
-    log_file_retention_count: usize,
+    // cargo reports the changes on its standard error
+    let changes = lockfile_changes::parse(&String::from_utf8_lossy(&output.stderr));
+    if changes.is_empty() {
+        outputter.message("no dependency would change");
+    } else {
+        outputter.message(format!("{} dependency change(s) available:", changes.len()));
+        for change in &changes {
+            outputter.detail(change.to_string());
+        }
+    }
 
-    /// Colorize output.
-    #[arg(long, value_name = "WHEN", default_value_t = ColorModes::Auto, value_enum)]
-    color: ColorModes,
-}
+    if !step.locked() {
+        return Ok(());
+    }
 
-impl RunArgs {
-    /// Returns an iterator over the variables defined in the command line arguments.
-    pub fn variables(&self) -> impl Iterator<Item = (&str, &str)> {
-        self.variable.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    let mut metadata = MetadataCommand::new();
+    _ = metadata.other_options(vec!["--locked".to_string()]);
+    if let Some(dir) = cmd.get_current_dir() {
+        _ = metadata.current_dir(dir);
+    }
+
+    let mut cargo = metadata.cargo_command();
+    _ = cargo.stdout(Stdio::null()).stderr(Stdio::piped());
+    let output = ctx.host.output(&mut cargo).context("unable to run cargo metadata")?;
+    if output.status.success() {
+        return Ok(());
     }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let error = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("error: "))
+        .unwrap_or_else(|| stderr.trim());
+    outputter.detail(error);
+    Err(anyhow!(
+        "step '{}' found Cargo.lock out of sync with the manifests: {error}",
+        step.name()
+    ))
 }
 
-/// Parse a single key-value pair
-#[expect(clippy::string_slice, reason = "Necessary for parsing KEY=VALUE")]
-fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
-where
-    T: FromStr,
-    T::Err: Error + Send + Sync + 'static,
-    U: FromStr,
-    U::Err: Error + Send + Sync + 'static,
-{
-    let pos = s.find('=').ok_or_else(|| format!("invalid KEY=value: no '=' found in `{s}`"))?;
-    Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
+/// Audits the workspace's dependencies for advisories with cargo-audit, and for licenses against the allowed ones,
+/// failing according to the `[audit]` policy.
+fn audit_dependencies<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    step: &Step,
+    cmd: &mut Command,
+    continue_on_error: bool,
+) -> anyhow::Result<()> {
+    let fatal = !continue_on_error;
+    outputter.run_command(cmd);
+
+    let output = match ctx.host.output(cmd) {
+        Ok(output) => output,
+        Err(e) => {
+            outputter.command_error(format!("unable to start step: {e}"), None, None, fatal);
+            return Err(anyhow!("unable to start step '{}': {e}", step.name()));
+        }
+    };
+
+    // cargo-audit fails when it finds vulnerabilities, so the outcome comes from its report rather than its exit status
+    let mut findings = match audit::parse_cargo_audit(&String::from_utf8_lossy(&output.stdout)) {
+        Ok(findings) => findings,
+        Err(e) if output.status.success() => return Err(e.context(format!("step '{}' didn't produce a report", step.name()))),
+        Err(_) => {
+            outputter.command_error(Msg::UnableToRunStep, Some(output.status), Some(&output), fatal);
+            let hints = outputter.report_hints(ctx.cfg.hints(), &output, fatal);
+            return Err(anyhow!("unable to run step '{}': {}{hints}", step.name(), output.status));
+        }
+    };
+
+    let policy = ctx.cfg.audit();
+    if !policy.allowed_licenses().is_empty() {
+        let mut metadata = MetadataCommand::new();
+        _ = metadata.current_dir(ctx.metadata.workspace_root.as_std_path());
+        let mut cargo = metadata.cargo_command();
+        _ = cargo.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let output = ctx.host.output(&mut cargo).context("unable to obtain cargo metadata")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "unable to obtain cargo metadata: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let stdout = String::from_utf8(output.stdout).context("cargo metadata produced invalid UTF-8")?;
+        let metadata = MetadataCommand::parse(stdout).context("unable to parse cargo metadata")?;
+        findings.extend(audit::license_findings(&metadata, policy));
+    }
+
+    let (failures, passes): (Vec<_>, Vec<_>) = findings.iter().partition(|finding| finding.fails(policy));
+    for finding in &passes {
+        outputter.detail(format!("tolerated by the audit policy: {finding}"));
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    for finding in &failures {
+        outputter.detail(finding.to_string());
+    }
+
+    let failures: Vec<_> = failures.iter().map(ToString::to_string).collect();
+    Err(anyhow!(
+        "step '{}' found {} problem(s) with dependencies: {}",
+        step.name(),
+        failures.len(),
+        failures.join("; ")
+    ))
 }
 
-pub fn run_jobs<H: Host>(args: &RunArgs, host: &mut H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<()> {
-    let jobs = select_jobs(args, cfg)?;
-    let packages = select_packages(args, metadata)?;
+/// Installs the tools a job requires, with tool bundles expanded, unless they're already installed at the version the
+/// `[tools]` table asks for. Without `--install-tools`, a missing tool fails the job instead.
+fn install_job_tools<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, job: &Job) -> anyhow::Result<()> {
+    for tool_id in ctx.cfg.resolve_tools(job.tools()) {
+        let Some(tool) = ctx.cfg.tools().get(&tool_id) else {
+            continue;
+        };
 
-    let mut env_vars = HashMap::new();
-    for (key, value) in host.vars() {
-        if cfg!(windows) {
-            if cfg.passthrough_env_variables().iter().any(|v| v.eq_ignore_ascii_case(&key)) {
-                _ = env_vars.insert(key, value);
-            }
-        } else if cfg.passthrough_env_variables().contains(&key) {
-            _ = env_vars.insert(key, value);
+        // concurrent jobs would otherwise install it at the same time
+        let name = tool_id.to_string();
+        let lock = ctx.tool_locks.get(&name);
+        let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let installed = CargoTools::read()
+            .ok()
+            .is_some_and(|tools| tools.get_install(&name).is_some_and(|(key, _)| key.version() == tool.version()));
+        if installed {
+            continue;
+        }
+
+        if !ctx.args.install_tools {
+            return Err(Diagnostic::Environment.attach(anyhow!(
+                "tool '{tool_id}' isn't installed at version {}, install it with 'cargo ci install {tool_id}' or pass --install-tools",
+                tool.version()
+            )));
         }
+
+        install_tool(ctx.host, &tool_id, tool, ctx.cfg.hints(), outputter)?;
     }
 
-    let log_prefix = if args.dry_run { "dry-run" } else { "run" };
-    let log = Log::new(
-        metadata.target_directory.as_std_path(),
-        log_prefix,
-        args.log_file.as_deref(),
-        args.log_file_retention_count,
-    )?;
+    Ok(())
+}
 
-    // after this point, thia code takes care of error reporting itself
-    host.fail_silently();
+/// Installs a cargo subcommand such as `cargo-audit` unless it's already installed, as the `[tools]` table says when
+/// it lists it, or at its latest version otherwise.
+fn install_cargo_tool<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, tool: &str) -> anyhow::Result<()> {
+    install_missing_tool(ctx, outputter, tool).map_err(|e| Diagnostic::Environment.attach(e))
+}
 
-    let outputter = Outputter::new(host, &log, args.color);
+fn install_missing_tool<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, tool: &str) -> anyhow::Result<()> {
+    // concurrent jobs would otherwise install it at the same time
+    let lock = ctx.tool_locks.get(tool);
+    let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
 
-    let env_vars = || env_vars.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+    let subcommand = tool.strip_prefix("cargo-").unwrap_or(tool);
+    let mut probe = Command::new("cargo");
+    _ = probe.args([subcommand, "--version"]).stdout(Stdio::null()).stderr(Stdio::null());
+    if ctx.host.output(&mut probe).is_ok_and(|output| output.status.success()) {
+        return Ok(());
+    }
 
-    for job_id in jobs {
-        let job = cfg.jobs().get_job(job_id).expect("job not found");
-        let job_name = job.name().unwrap_or(job_id.as_str());
+    let tool_id = ToolId::from(tool);
+    if let Some(tool) = ctx.cfg.tools().get(&tool_id) {
+        return install_tool(ctx.host, &tool_id, tool, ctx.cfg.hints(), outputter);
+    }
 
-        outputter.start_activity(job_name);
+    outputter.message(format!("installing {tool}"));
+    let mut cmd = Command::new("cargo");
+    _ = cmd
+        .args(["install", "--locked", tool])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    outputter.run_command(&cmd);
 
-        // we evaluate that up here even when there is no error, so that the expression gets validated eagerly
-        let continue_on_error = job
-            .continue_on_error()
-            .evaluate(env_vars().chain(cfg.variables()).chain(args.variables()))?;
+    match ctx.host.output(&mut cmd) {
+        Ok(output) if output.status.success() => Ok(()),
+
+        Ok(output) => {
+            outputter.command_error(format!("unable to install {tool}"), Some(output.status), Some(&output), true);
+            let hints = outputter.report_hints(ctx.cfg.hints(), &output, true);
+            Err(anyhow!("unable to install {tool}: {}{hints}", output.status))
+        }
+
+        Err(e) => {
+            outputter.command_error(format!("unable to start 'cargo install': {e}"), None, None, true);
+            Err(anyhow!("unable to start 'cargo install': {e}"))
+        }
+    }
+}
 
-        let result = run_job(args, host, metadata, &packages, &env_vars, &outputter, cfg, job);
+/// Runs a code generator into a scratch directory, exposed to it as `CODEGEN_OUT`, and fails if the generated
+/// files differ from the committed ones.
+fn check_codegen<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    job_id: &JobId,
+    step: &Step,
+    pkg: Option<&Package>,
+    cmd: &mut Command,
+    continue_on_error: bool,
+) -> anyhow::Result<()> {
+    let run = ctx.codegen_runs.fetch_add(1, Ordering::Relaxed);
+    let out_dir = ctx.temp_dir.join(format!("codegen-{run}"));
 
-        if result.is_ok() {
-            outputter.complete_activity(format!("ran {0} step(s)", job.steps().len()));
-        } else if continue_on_error {
-            outputter.complete_activity("failed, but ignored");
-        } else {
-            outputter.complete_activity("failed");
-            return result;
+    if out_dir.exists() {
+        fs::remove_dir_all(long_paths::extended(&out_dir)?).with_context(|| format!("unable to clear {}", out_dir.display()))?;
+    }
+
+    fs::create_dir_all(long_paths::extended(&out_dir)?).with_context(|| format!("unable to create {}", out_dir.display()))?;
+    _ = cmd.env("CODEGEN_OUT", &out_dir);
+
+    let committed_dir = cmd
+        .get_current_dir()
+        .unwrap_or_else(|| ctx.metadata.workspace_root.as_std_path())
+        .join(step.generated().unwrap_or_else(|| Path::new(".")));
+
+    let result = spawn_step(ctx, outputter, job_id, step, pkg, cmd, continue_on_error).and_then(|()| {
+        codegen::compare(&out_dir, &committed_dir).with_context(|| format!("unable to compare generated files for step '{}'", step.name()))
+    });
+
+    _ = long_paths::extended(&out_dir).and_then(fs::remove_dir_all);
+
+    let mismatches = result?;
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+
+    for mismatch in &mismatches {
+        for line in mismatch.describe() {
+            outputter.detail(line);
         }
     }
 
-    Ok(())
+    let target = pkg.map_or_else(String::new, |pkg| format!(" for package '{}'", pkg.name));
+    Err(anyhow!(
+        "generated files of step '{}'{target} are out of date, {} file(s) differ",
+        step.name(),
+        mismatches.len()
+    ))
 }
 
-#[expect(clippy::too_many_lines, reason = "Necessary for job execution")]
-#[expect(clippy::too_many_arguments, reason = "Necessary for job execution")]
-fn run_job<'a, H: Host, F, I>(
-    args: &'a RunArgs,
-    host: &H,
-    metadata: &Metadata,
-    packages: &'a [&Package],
-    env_vars: &'a F,
+/// Runs a step's command to completion, reporting any failure.
+fn spawn_step<H: Host>(
+    ctx: &RunContext<'_, H>,
     outputter: &Outputter<H>,
-    cfg: &'a Config,
-    job: &'a Job,
-) -> anyhow::Result<()>
-where
-    F: Fn() -> I,
-    I: Iterator<Item = (&'a str, &'a str)> + Clone,
-{
-    for step in job.steps() {
-        let mut packages_to_process = HashSet::new();
-        for pkg in packages {
-            if !job
-                .conditional()
-                .evaluate(env_vars().chain(cfg.variables()).chain(variables(pkg)).chain(args.variables()))?
+    job_id: &JobId,
+    step: &Step,
+    pkg: Option<&Package>,
+    cmd: &mut Command,
+    continue_on_error: bool,
+) -> anyhow::Result<()> {
+    let target = pkg.map_or_else(String::new, |pkg| format!(" for package '{}'", pkg.name));
+    let started = SystemTime::from(ctx.host.local_time());
+
+    outputter.run_command(cmd);
+    outputter.set_output_encoding(step.output_encoding());
+    outputter.set_output_filters(step.output_filters());
+
+    let report = |message: String| outputter.message(message);
+    let heartbeat = Heartbeat {
+        deadline: ctx.deadline.map(|deadline| deadline + TIME_BUDGET_GRACE),
+        cancellable: true,
+        overdue_after: step.expected_duration().map(|expected| expected.duration() * SLOW_STEP_FACTOR),
+        ..ctx.args.heartbeat()
+    };
+    let cancelled = || ctx.cancel_requested(job_id);
+
+    match start_step(ctx, step, pkg, cmd) {
+        Ok(child) => {
+            match heartbeat::wait(ctx.host, child, step.name(), heartbeat, report, cancelled)
+                .and_then(|waited| executor_output(step, waited))
             {
-                outputter.message(format!("Package '{}' skipped due to job-level condition", pkg.name));
-                continue;
-            }
+                Ok(Waited::Cancelled) => Err(anyhow!(
+                    "step '{}'{target} was killed, since job '{job_id}' was cancelled",
+                    step.name()
+                )),
 
-            if !step.conditional().evaluate(
-                env_vars()
-                    .chain(cfg.variables())
-                    .chain(job.variables())
-                    .chain(variables(pkg))
-                    .chain(args.variables()),
-            )? {
-                outputter.message(format!("Package '{}' skipped due to step-level condition", pkg.name));
-                continue;
-            }
+                Ok(Waited::OutOfTime(output)) => {
+                    ctx.out_of_time.store(true, Ordering::Relaxed);
+                    outputter.command_error(Msg::UnableToRunStep, Some(output.status), Some(&output), !continue_on_error);
+                    Err(ctx
+                        .out_of_time_error()
+                        .context(format!("step '{}'{target} was killed", step.name())))
+                }
 
-            _ = packages_to_process.insert(pkg);
-        }
-
-        if packages_to_process.len() != packages.len() || step.per_package() {
-            for pkg in packages_to_process {
-                // we evaluate that up here even when there is no error, so that the expression gets validated eagerly
-                let continue_on_error = if step.per_package() {
-                    step.continue_on_error().evaluate(
-                        env_vars()
-                            .chain(cfg.variables())
-                            .chain(job.variables())
-                            .chain(variables(pkg))
-                            .chain(args.variables()),
-                    )?
-                } else {
-                    step.continue_on_error()
-                        .evaluate(env_vars().chain(cfg.variables()).chain(job.variables()).chain(args.variables()))?
-                };
-
-                outputter.message(format!("step '{}' for package '{}'", step.name(), pkg.name));
-
-                if args.dry_run {
-                    continue;
+                Ok(Waited::Killed(output, silence)) => {
+                    outputter.command_error(Msg::UnableToRunStep, Some(output.status), Some(&output), !continue_on_error);
+                    Err(anyhow!(
+                        "step '{}'{target} was killed after producing no output for {}",
+                        step.name(),
+                        format_duration(silence)
+                    ))
                 }
 
-                let mut cmd = if step.per_package() {
-                    make_command(
-                        step,
-                        pkg.manifest_path.parent().expect("should have a valid parent").as_std_path(),
-                        env_vars()
-                            .chain(cfg.variables())
-                            .chain(job.variables())
-                            .chain(variables(pkg))
-                            .chain(step.variables())
-                            .chain(args.variables()),
-                    )
-                } else {
-                    make_command(
-                        step,
-                        pkg.manifest_path.parent().expect("should have a valid parent").as_std_path(),
-                        env_vars()
-                            .chain(cfg.variables())
-                            .chain(job.variables())
-                            .chain(step.variables())
-                            .chain(args.variables()),
-                    )
-                };
-
-                outputter.run_command(&cmd);
-
-                let e = match host.spawn(&mut cmd) {
-                    Ok(child) => match child.wait_with_output() {
-                        Ok(output) => {
-                            if output.status.success() {
-                                Ok(())
-                            } else {
-                                outputter.command_error("unable to run step", Some(output.status), Some(&output), !continue_on_error);
-                                Err(anyhow::anyhow!(format!(
-                                    "unable to run step '{}' for package '{}': {}",
-                                    step.name(),
-                                    pkg.name,
-                                    output.status
-                                )))
-                            }
-                        }
+                Ok(Waited::Exited(output)) => {
+                    record_test_results(ctx, step, &output);
+                    record_sarif_findings(ctx, step, &output);
+                    if output.status.success() {
+                        Ok(())
+                    } else {
+                        let messages = cargo_messages(ctx, outputter, step, &output);
+                        let summarized = messages.as_ref().map(|messages| Output {
+                            status: output.status,
+                            stdout: messages.summary().into_bytes(),
+                            stderr: output.stderr.clone(),
+                        });
 
-                        Err(e) => {
-                            outputter.command_error(format!("unable to wait for step: {e}"), None, None, !continue_on_error);
-                            Err(anyhow::anyhow!(format!(
-                                "unable to wait for step '{}' for package '{}': {e}",
-                                step.name(),
-                                pkg.name
-                            )))
-                        }
-                    },
+                        outputter.command_error(
+                            Msg::UnableToRunStep,
+                            Some(output.status),
+                            Some(summarized.as_ref().unwrap_or(&output)),
+                            !continue_on_error,
+                        );
+                        save_raw_output(ctx, outputter, &output);
+                        let hints = outputter.report_hints(ctx.cfg.hints(), &output, !continue_on_error);
+                        let first_error = messages
+                            .as_ref()
+                            .and_then(CargoMessages::first_error)
+                            .map(|(package, error)| format!(" ({error}, in package '{package}')"))
+                            .or_else(|| semver_violations(outputter, step, &output))
+                            .unwrap_or_default();
+                        let debug_aids = debug_aids(ctx, outputter, (step, pkg), cmd, &output, started);
 
-                    Err(e) => {
-                        outputter.command_error(format!("unable to start step: {e}"), None, None, !continue_on_error);
-                        Err(anyhow::anyhow!(format!(
-                            "unable to start step '{}' for package '{}': {e}",
+                        Err(anyhow!(
+                            "unable to run step '{}'{target}: {}{first_error}{hints}{debug_aids}",
                             step.name(),
-                            pkg.name
-                        )))
+                            output.status
+                        ))
                     }
-                };
-
-                if e.is_ok() || continue_on_error {
-                    continue;
                 }
 
-                e?;
+                Err(e) => {
+                    outputter.command_error(format!("unable to wait for step: {e}"), None, None, !continue_on_error);
+                    Err(anyhow!("unable to wait for step '{}'{target}: {e}", step.name()))
+                }
             }
-        } else {
-            // we evaluate that up here even when there is no error, so that the expression gets validated eagerly
-            let continue_on_error = step
-                .continue_on_error()
-                .evaluate(env_vars().chain(cfg.variables()).chain(job.variables()).chain(args.variables()))?;
+        }
 
-            outputter.message(format!("step '{}'", step.name()));
+        Err(e) => {
+            outputter.command_error(format!("unable to start step: {e}"), None, None, !continue_on_error);
+            Err(anyhow!("unable to start step '{}'{target}: {e}", step.name()))
+        }
+    }
+}
 
-            if args.dry_run {
-                continue;
-            }
+/// Starts a step's command, or hands the step to its executor when it has one.
+///
+/// Executors get a JSON request describing the step on their standard input, and run from the workspace root.
+fn start_step<H: Host>(ctx: &RunContext<'_, H>, step: &Step, pkg: Option<&Package>, cmd: &mut Command) -> io::Result<Child> {
+    // a step in a process group of its own can be killed along with what it started, and Ctrl-C is forwarded to it
+    let options = SpawnOptions {
+        process_group: true,
+        pty: step.pty(),
+    };
 
-            let mut cmd = make_command(
-                step,
-                metadata.workspace_root.as_std_path(),
-                env_vars()
-                    .chain(cfg.variables())
-                    .chain(job.variables())
-                    .chain(step.variables())
-                    .chain(args.variables()),
-            );
-            outputter.run_command(&cmd);
-
-            let e = match host.spawn(&mut cmd) {
-                Ok(child) => match child.wait_with_output() {
-                    Ok(output) => {
-                        if output.status.success() {
-                            Ok(())
-                        } else {
-                            outputter.command_error("unable to run step", Some(output.status), Some(&output), !continue_on_error);
-                            Err(anyhow::anyhow!(format!("unable to run step '{}': {}", step.name(), output.status)))
-                        }
-                    }
+    let Some(name) = step.executor() else {
+        return ctx.host.spawn_with(cmd, options);
+    };
 
-                    Err(e) => {
-                        outputter.command_error(format!("unable to wait for step: {e}"), None, None, !continue_on_error);
-                        Err(anyhow::anyhow!(format!("unable to wait for step '{}': {e}", step.name())))
-                    }
-                },
+    let command = ctx.step_command(step, pkg).map_or_else(|| step.command(), Cow::Owned);
+    let request = executor::Request::new(step.name(), pkg.map(|pkg| pkg.name.as_str()), &command, cmd);
 
-                Err(e) => {
-                    outputter.command_error(format!("unable to start step: {e}"), None, None, !continue_on_error);
-                    Err(anyhow::anyhow!(format!("unable to start step '{}': {e}", step.name())))
-                }
-            };
+    let mut executor = shell_command(ctx.cfg.executor(name).expect("executor not found"));
+    _ = executor
+        .current_dir(ctx.metadata.workspace_root.as_std_path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
 
-            if e.is_ok() || continue_on_error {
-                continue;
-            }
+    // an executor which exits without reading the request still gets to explain itself through its response
+    let mut child = ctx.host.spawn_with(&mut executor, SpawnOptions { pty: false, ..options })?;
+    if let Some(stdin) = child.stdin.take()
+        && let Err(e) = serde_json::to_writer(stdin, &request)
+        && e.io_error_kind() != Some(io::ErrorKind::BrokenPipe)
+    {
+        _ = child.kill();
+        _ = child.wait();
+        ctx.host.release_group(child.id());
+        return Err(io::Error::other(format!("unable to send the step to executor '{name}': {e}")));
+    }
+
+    Ok(child)
+}
+
+/// Replaces what an executor wrote with the output of the step it ran, for steps which have an executor.
+fn executor_output(step: &Step, waited: Waited) -> io::Result<Waited> {
+    let (Some(name), Waited::Exited(output)) = (step.executor(), &waited) else {
+        return Ok(waited);
+    };
+
+    executor::step_output(output)
+        .map(Waited::Exited)
+        .map_err(|e| io::Error::other(format!("executor '{name}' failed: {e:#}")))
+}
 
-            e?;
+/// Lists the semver violations reported by a failed `semver-check` step, returning them in a form suitable for
+/// appending to an error message.
+fn semver_violations<H: Host>(outputter: &Outputter<H>, step: &Step, output: &Output) -> Option<String> {
+    if step.kind() != StepKind::SemverCheck {
+        return None;
+    }
+
+    let encoding = step.output_encoding();
+    let text = format!("{}\n{}", encoding.decode(&output.stdout), encoding.decode(&output.stderr));
+    let violations = semver_checks::parse(&text);
+    if violations.is_empty() {
+        return None;
+    }
+
+    for violation in &violations {
+        for line in violation.describe() {
+            outputter.detail(line);
         }
     }
 
-    Ok(())
+    let items: Vec<_> = violations
+        .iter()
+        .flat_map(|violation| violation.items.iter().map(move |item| format!("{}: {item}", violation.lint)))
+        .collect();
+    Some(format!(" ({} semver violation(s): {})", violations.len(), items.join("; ")))
+}
+
+/// Points to the backtraces a failed step printed and the core dumps its processes wrote, with `--debug-failures`,
+/// returning them in a form suitable for appending to an error message.
+fn debug_aids<H: Host>(
+    ctx: &RunContext<'_, H>,
+    outputter: &Outputter<H>,
+    (step, pkg): (&Step, Option<&Package>),
+    cmd: &Command,
+    output: &Output,
+    started: SystemTime,
+) -> String {
+    if !ctx.args.debug_failures {
+        return String::new();
+    }
+
+    let mut aids = Vec::new();
+    if debug_failures::printed_backtrace(output) {
+        aids.push(format!(
+            "backtrace in the step's output, logged to {}",
+            outputter.log_path().display()
+        ));
+    }
+
+    if step.ulimit_core() {
+        // tests run in the directory of their package
+        let packages: Vec<&Package> = pkg.map_or_else(|| ctx.packages.to_vec(), |pkg| vec![pkg]);
+        let dirs: Vec<&Path> = cmd
+            .get_current_dir()
+            .into_iter()
+            .chain(packages.iter().filter_map(|pkg| pkg.manifest_path.parent()).map(AsRef::as_ref))
+            .collect();
+
+        aids.extend(debug_failures::core_dump_location(ctx.host, &dirs, started));
+    }
+
+    for aid in &aids {
+        outputter.detail(aid);
+    }
+
+    aids.iter().map(|aid| format!(" ({aid})")).collect::<Vec<_>>().concat()
+}
+
+/// Picks the outcomes of individual tests out of a step's output, for the summary.
+fn record_test_results<H: Host>(ctx: &RunContext<'_, H>, step: &Step, output: &Output) {
+    let encoding = step.output_encoding();
+    let results = test_results::parse(&encoding.decode(&output.stdout), &encoding.decode(&output.stderr));
+    if !results.is_empty() {
+        ctx.test_results.lock().unwrap_or_else(PoisonError::into_inner).extend(results);
+    }
+}
+
+/// Collects the diagnostics of a step with `sarif = true`, from its JSON messages or, for `cargo fmt`, from the files
+/// it would reformat.
+fn record_sarif_findings<H: Host>(ctx: &RunContext<'_, H>, step: &Step, output: &Output) {
+    if !step.sarif() {
+        return;
+    }
+
+    let workspace_root = ctx.metadata.workspace_root.as_std_path();
+    let findings = if uses_json_messages(&step.command()) {
+        sarif::from_cargo_messages(&output.stdout, workspace_root)
+    } else {
+        sarif::from_rustfmt_check(&step.output_encoding().decode(&output.stdout), workspace_root)
+    };
+
+    ctx.sarif_findings.lock().unwrap_or_else(PoisonError::into_inner).extend(findings);
+}
+
+/// Parses the JSON messages of a failed cargo step, saving them to the temporary directory since only a summary of
+/// them is shown.
+fn cargo_messages<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, step: &Step, output: &Output) -> Option<CargoMessages> {
+    if step.cargo_subcommand().is_none() || !uses_json_messages(&step.command()) {
+        return None;
+    }
+
+    let messages = CargoMessages::parse(&output.stdout)?;
+    let index = ctx.raw_outputs.fetch_add(1, Ordering::Relaxed);
+    let path = ctx.temp_dir.join(format!("cargo-messages-{index}.json"));
+    match long_paths::extended(&path).and_then(|extended| fs::write(extended, &output.stdout)) {
+        Ok(()) => outputter.detail(format!("cargo messages saved to {}", path.display())),
+        Err(e) => outputter.detail(format!("unable to save cargo messages to {}: {e}", path.display())),
+    }
+
+    Some(messages)
+}
+
+/// Keeps the exact bytes of a failed step's output when they aren't valid UTF-8, since the decoded text which is shown
+/// and logged may have lost information.
+fn save_raw_output<H: Host>(ctx: &RunContext<'_, H>, outputter: &Outputter<H>, output: &Output) {
+    let streams = [("stdout", &output.stdout), ("stderr", &output.stderr)];
+    if streams.iter().all(|(_, bytes)| core::str::from_utf8(bytes).is_ok()) {
+        return;
+    }
+
+    let index = ctx.raw_outputs.fetch_add(1, Ordering::Relaxed);
+    for (name, bytes) in streams.into_iter().filter(|(_, bytes)| !bytes.is_empty()) {
+        let path = ctx.temp_dir.join(format!("raw-output-{index}.{name}"));
+        match long_paths::extended(&path).and_then(|extended| fs::write(extended, bytes)) {
+            Ok(()) => outputter.detail(format!("raw {name} saved to {}", path.display())),
+            Err(e) => outputter.detail(format!("unable to save raw {name} to {}: {e}", path.display())),
+        }
+    }
+}
+
+/// Creates a command which runs the given command line through the platform's shell.
+pub fn shell_command(command_line: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        _ = cmd.arg("/C").arg(command_line);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        _ = cmd.arg("-c").arg(command_line);
+        cmd
+    }
 }
 
-fn make_command<'a>(step: &Step, directory: &Path, _variables: impl Iterator<Item = (&'a str, &'a str)>) -> Command {
-    let mut cmd = if cfg!(windows) {
-        let mut c = Command::new("cmd");
-        _ = c.arg("/C").arg(step.command());
-        c
+/// Quotes an argument for the shell which runs steps, unless it doesn't need it.
+pub fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | ':' | '=' | '@' | ','));
+    if plain {
+        arg.to_string()
+    } else if cfg!(windows) {
+        format!("\"{}\"", arg.replace('"', "\"\""))
     } else {
-        let mut c = Command::new("sh");
-        _ = c.arg("-c").arg(step.command());
-        c
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Makes a command run with the lowest CPU and I/O priority the platform offers without special privileges, so it
+/// gets out of the way of interactive work.
+///
+/// On Unix the command runs through `nice`, and through `ionice` where it's available. Since the command is rebuilt,
+/// this must be called before anything else is set on it.
+#[cfg(not(windows))]
+pub fn lower_priority(cmd: &Command) -> Command {
+    let mut lowered = Command::new("sh");
+    _ = lowered
+        .arg("-c")
+        .arg(r#"ionice -c 3 -p $$ 2>/dev/null; exec nice -n 19 "$0" "$@""#)
+        .arg(cmd.get_program())
+        .args(cmd.get_args());
+    lowered
+}
+
+/// Makes a command run with the lowest CPU and I/O priority the platform offers without special privileges, so it
+/// gets out of the way of interactive work.
+///
+/// On Windows the command runs in the below-normal priority class. Since the command is rebuilt, this must be called
+/// before anything else is set on it.
+#[cfg(windows)]
+pub fn lower_priority(cmd: &Command) -> Command {
+    use std::os::windows::process::CommandExt;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+
+    let mut lowered = Command::new(cmd.get_program());
+    _ = lowered.args(cmd.get_args()).creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+    lowered
+}
+
+/// The directories a step adds to `PATH`, with the step's own ahead of its job's.
+fn step_path(job: &Job, step: &Step) -> Vec<PathBuf> {
+    step.path().iter().chain(job.path()).cloned().collect()
+}
+
+/// Builds the `PATH` for a step which adds directories to it, placing them, relative to the workspace root, ahead of the
+/// inherited entries.
+///
+/// Returns `None` when there's nothing to add, and fails when one of the directories doesn't exist.
+pub fn search_path<H: Host>(host: &H, workspace_root: &Path, entries: &[PathBuf]) -> anyhow::Result<Option<OsString>> {
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let dir = workspace_root.join(entry);
+        if !dir.is_dir() {
+            return Err(anyhow!("directory '{}' listed in 'path' doesn't exist", entry.display()));
+        }
+
+        dirs.push(dir);
+    }
+
+    // the variable is spelled 'Path' on Windows, where names aren't case-sensitive
+    if let Some((_, inherited)) = host.vars().find(|(name, _)| name.eq_ignore_ascii_case("PATH")) {
+        dirs.extend(env::split_paths(&inherited));
+    }
+
+    env::join_paths(dirs)
+        .map(Some)
+        .context("unable to add the directories listed in 'path' to PATH")
+}
+
+/// Builds the command for a step, with `overridden` being the command a package runs in place of the step's own.
+fn make_command<'a>(
+    step: &Step,
+    overridden: Option<&str>,
+    directory: &Path,
+    (background, core_dumps, offline): (bool, bool, bool),
+    variables: impl Iterator<Item = (&'a str, &'a str)>,
+) -> Command {
+    // cargo resolves aliases itself, using the configuration visible from the step's directory
+    let mut cmd = match (overridden, step.cargo_alias_args()) {
+        (Some(command), _) => shell_command(command),
+        (None, Some(args)) => {
+            let mut c = Command::new("cargo");
+            _ = c.args(args);
+            c
+        }
+        (None, None) => shell_command(&step.command()),
     };
 
-    // TODO: figure out what to do with environment variables
-    _ = cmd.current_dir(directory); // .env_clear().envs(variables);
+    if core_dumps {
+        cmd = debug_failures::enable_core_dumps(&cmd);
+    }
+
+    if offline {
+        cmd = network_isolation::disable_network(&cmd);
+    }
+
+    if background {
+        cmd = lower_priority(&cmd);
+    }
+
+    // later definitions of a variable take precedence, as they come from more specific sources
+    _ = cmd.current_dir(directory).envs(variables);
     _ = cmd.stdout(Stdio::piped());
     _ = cmd.stderr(Stdio::piped());
 
     cmd
 }
 
+/// Checks whether the steps with `network = false` of the jobs to run can be kept off the network, warning that they'll
+/// reach it anyway when the system doesn't allow it. The check is skipped when no such step is going to run.
+fn check_network_isolation<H: Host>(host: &H, cfg: &Config, jobs: &[&JobId], summary: &mut RunSummary) -> bool {
+    let offline_steps: Vec<String> = jobs
+        .iter()
+        .filter_map(|job_id| cfg.jobs().get_job(job_id).map(|job| (job_id, job)))
+        .flat_map(|(job_id, job)| {
+            job.steps()
+                .iter()
+                .filter(|step| !step.network())
+                .map(move |step| format!("'{}' of job '{job_id}'", step.name()))
+        })
+        .collect();
+    if offline_steps.is_empty() {
+        return false;
+    }
+
+    let Some(reason) = network_isolation::unsupported_reason(host) else {
+        return true;
+    };
+
+    summary.warnings.push(format!(
+        "step(s) {} ran with network access even though they set 'network = false', since {reason}",
+        offline_steps.join(", ")
+    ));
+    false
+}
+
 fn select_jobs<'a>(args: &RunArgs, cfg: &'a Config) -> anyhow::Result<Vec<&'a JobId>> {
     if cfg.jobs().is_empty() {
         return Err(anyhow!("no jobs are defined in configuration"));
@@ -350,7 +4635,7 @@ fn select_jobs<'a>(args: &RunArgs, cfg: &'a Config) -> anyhow::Result<Vec<&'a Jo
         }
 
         if !unknown_jobs.is_empty() {
-            return Err(anyhow!("invalid jobs specified: {}", unknown_jobs.join(", ")));
+            return Err(Failure::UnknownJob.tag(anyhow!("invalid jobs specified: {}", unknown_jobs.join(", "))));
         }
 
         jobs_to_run
@@ -375,6 +4660,78 @@ fn select_jobs<'a>(args: &RunArgs, cfg: &'a Config) -> anyhow::Result<Vec<&'a Jo
     Ok(cfg.jobs().topological_sort(&jobs_to_run))
 }
 
+/// Loads the metadata of the crates excluded from the workspace and of nested workspaces, when any of the jobs covers
+/// them. Crates which are neither members nor excluded are left out, since cargo refuses to build them, and so are all
+/// of them when packages are selected on the command line.
+fn load_excluded_workspaces<H: Host>(
+    args: &RunArgs,
+    host: &H,
+    cfg: &Config,
+    metadata: &Metadata,
+    jobs: &[&JobId],
+) -> anyhow::Result<Vec<Metadata>> {
+    let included = jobs
+        .iter()
+        .any(|job_id| cfg.jobs().get_job(job_id).is_some_and(Job::include_excluded));
+    if !included || !args.package.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (excluded, warnings) = excluded_crates::find(host, metadata)?;
+    for warning in warnings {
+        host.eprintln(format!("WARNING: {warning}"));
+    }
+
+    excluded
+        .into_iter()
+        .filter(|excluded| excluded.exclusion.can_be_included())
+        .map(|excluded| {
+            let mut cmd = MetadataCommand::new();
+            _ = cmd
+                .manifest_path(metadata.workspace_root.as_std_path().join(&excluded.dir).join("Cargo.toml"))
+                .no_deps();
+            crate::read_metadata(host, &cmd).with_context(|| format!("unable to load excluded crate '{}'", excluded.name))
+        })
+        .collect()
+}
+
+fn excluded_packages(workspaces: &[Metadata]) -> Vec<&Package> {
+    workspaces.iter().flat_map(Metadata::workspace_packages).collect()
+}
+
+/// Selects the packages to process, shuffling them when requested, and returns the seed used to shuffle them.
+fn select_shuffled_packages<'a>(
+    args: &RunArgs,
+    host: &impl Host,
+    metadata: &'a Metadata,
+) -> anyhow::Result<(Vec<&'a Package>, Option<u64>)> {
+    let mut packages = select_packages(args, metadata)?;
+    let seed = args.shuffle_seed(host);
+    if let Some(seed) = seed {
+        shuffle::shuffle(&mut packages, seed);
+    }
+
+    Ok((packages, seed))
+}
+
+/// Tells how to cancel a job of the run, which only matters when there's more than one job.
+fn report_run_id<H: Host>(args: &RunArgs, outputter: &Outputter<H>, run_id: &str, job_count: usize) {
+    if !args.dry_run && job_count > 1 {
+        outputter.line(format!(
+            "Run {run_id} started, use `cargo ci cancel {run_id} <JOB>` to cancel one of its jobs"
+        ));
+    }
+}
+
+/// Tells the user how to reproduce the order of shuffled packages.
+fn report_shuffle_seed<H: Host>(outputter: &Outputter<H>, seed: Option<u64>) {
+    if let Some(seed) = seed {
+        outputter.line(format!(
+            "Shuffling packages with seed {seed}, use --shuffle-seed {seed} to reproduce this order"
+        ));
+    }
+}
+
 fn select_packages<'a>(args: &RunArgs, metadata: &'a Metadata) -> anyhow::Result<Vec<&'a Package>> {
     let mut result = Vec::new();
 
@@ -0,0 +1,306 @@
+use crate::color_modes::ColorModes;
+use crate::exit_codes::Failure;
+use crate::github_workflow::{JobMapping, LocalAction, LocalStep, StepCondition, Workflow, WorkflowJob};
+use crate::host::Host;
+use crate::log::Log;
+use crate::messages::{Lang, Msg};
+use crate::outputter::Outputter;
+use anyhow::{Context, anyhow};
+use cargo_metadata::Metadata;
+use clap::{ArgAction, Parser, ValueEnum};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Parser, Debug, Clone)]
+pub struct SimulateArgs {
+    /// The CI provider whose workflows to simulate
+    #[arg(long, value_name = "PROVIDER", default_value_t = Provider::Github, value_enum)]
+    provider: Provider,
+
+    /// Only simulate this workflow file, rather than every workflow of the repository
+    #[arg(short = 'w', long, value_name = "FILE")]
+    workflow: Option<PathBuf>,
+
+    /// Only simulate the workflow jobs with these ids
+    #[arg(short = 'j', long = "job", value_name = "ID")]
+    jobs: Vec<String>,
+
+    /// Report how each job maps to this machine without running anything
+    #[arg(short = 'n', long, action = ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Send log output to the specified file
+    #[arg(short = 'l', long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Number of log files to retain
+    #[arg(long, default_value_t = 16, value_name = "COUNT")]
+    log_file_retention_count: usize,
+
+    /// Colorize output
+    #[arg(long, value_name = "WHEN", default_value_t = ColorModes::Auto, value_enum)]
+    color: ColorModes,
+
+    /// Language for step headings, captured output banners, statuses, and the summary; everything else is in English
+    #[arg(long, value_name = "LANG", default_value_t = Lang::En, value_enum)]
+    lang: Lang,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    /// GitHub Actions, whose workflows live in `.github/workflows`
+    Github,
+}
+
+/// How simulating a workflow job turned out.
+enum Outcome {
+    Passed,
+    Failed,
+    WouldRun(usize),
+    Skipped(String),
+    Unmappable(String),
+    NotRun(String),
+}
+
+/// Runs the parts of a repository's CI workflows which can run on this machine, reporting which jobs were simulated,
+/// which were skipped, and which have no local equivalent.
+pub fn simulate<H: Host>(args: &SimulateArgs, host: &mut H, metadata: &Metadata) -> anyhow::Result<()> {
+    let Provider::Github = args.provider;
+
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let workflows_dir = workspace_root
+        .ancestors()
+        .map(|dir| dir.join(".github").join("workflows"))
+        .find(|dir| dir.is_dir())
+        .ok_or_else(|| anyhow!("no .github/workflows directory found in {} or above", workspace_root.display()))?;
+
+    // workflows run from the root of the repository, which may be above the workspace
+    let repo_root = workflows_dir.ancestors().nth(2).unwrap_or(workspace_root).to_path_buf();
+
+    let files = match &args.workflow {
+        Some(file) => vec![file.clone()],
+        None => workflow_files(&workflows_dir)?,
+    };
+
+    let mut workflows = Vec::new();
+    for file in files {
+        let text = host
+            .read_to_string(&file)
+            .with_context(|| format!("unable to read workflow {}", file.display()))?;
+        let workflow = Workflow::parse(&text).with_context(|| format!("invalid workflow {}", file.display()))?;
+        let name = workflow.name.clone().unwrap_or_else(|| file.display().to_string());
+        let jobs = workflow.jobs().with_context(|| format!("invalid workflow {}", file.display()))?;
+        workflows.push((name, jobs));
+    }
+
+    if let Some(unknown) = args
+        .jobs
+        .iter()
+        .find(|id| !workflows.iter().flat_map(|(_, jobs)| jobs).any(|job| job.id == **id))
+    {
+        return Err(Failure::UnknownJob.tag(anyhow!("no workflow has a job with id '{unknown}'")));
+    }
+
+    let started = host.local_time();
+    let log = Log::new(
+        metadata.target_directory.as_std_path(),
+        "simulate",
+        started,
+        args.log_file.as_deref(),
+        args.log_file_retention_count,
+    )?;
+
+    // after this point, this code takes care of error reporting itself
+    host.fail_silently();
+
+    let outputter = Outputter::new(&*host, &log, args.color, args.lang);
+
+    let report: Vec<_> = workflows
+        .iter()
+        .map(|(workflow, jobs)| (workflow, simulate_workflow(args, &*host, &outputter, &repo_root, jobs)))
+        .collect();
+
+    print_report(&outputter, &report);
+
+    let failed = report
+        .iter()
+        .flat_map(|(_, outcomes)| outcomes)
+        .filter(|(_, outcome)| matches!(outcome, Outcome::Failed))
+        .count();
+    if failed > 0 {
+        return Err(Failure::StepFailed.tag(anyhow!("{failed} simulated job(s) failed")));
+    }
+
+    Ok(())
+}
+
+fn simulate_workflow<'a, H: Host>(
+    args: &SimulateArgs,
+    host: &H,
+    outputter: &Outputter<H>,
+    repo_root: &Path,
+    jobs: &'a [WorkflowJob],
+) -> Vec<(&'a WorkflowJob, Outcome)> {
+    let selected = |job: &WorkflowJob| args.jobs.is_empty() || args.jobs.contains(&job.id);
+    let ordered = in_needs_order(jobs);
+
+    let mut outcomes: Vec<(&WorkflowJob, Outcome)> = Vec::new();
+    for job in ordered.iter().copied().filter(|job| selected(job)) {
+        let failed_need = job.needs.iter().find(|need| {
+            outcomes
+                .iter()
+                .any(|(other, outcome)| other.id == **need && !matches!(outcome, Outcome::Passed | Outcome::WouldRun(_)))
+        });
+
+        let outcome = match (&job.mapping, failed_need) {
+            (_, Some(need)) => Outcome::NotRun(format!("it needs '{need}', which didn't pass")),
+            (JobMapping::Skipped(reason), None) => Outcome::Skipped(reason.clone()),
+            (JobMapping::Unmappable(reason), None) => Outcome::Unmappable(reason.clone()),
+            (JobMapping::Runnable(steps), None) if args.dry_run => Outcome::WouldRun(steps.len()),
+            (JobMapping::Runnable(steps), None) => run_job(host, outputter, repo_root, job, steps),
+        };
+
+        outcomes.push((job, outcome));
+    }
+
+    // jobs left out of the order are part of a cycle of needs
+    for job in jobs
+        .iter()
+        .filter(|job| selected(job) && !ordered.iter().any(|other| other.id == job.id))
+    {
+        outcomes.push((job, Outcome::Unmappable("its needs form a cycle".to_string())));
+    }
+
+    outcomes
+}
+
+fn workflow_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("unable to list the files in {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "yml" || ext == "yaml") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Orders jobs so each comes after the jobs it needs, otherwise keeping the order they're written in. Jobs whose needs
+/// form a cycle are left out.
+fn in_needs_order(jobs: &[WorkflowJob]) -> Vec<&WorkflowJob> {
+    let mut ordered: Vec<&WorkflowJob> = Vec::new();
+    while let Some(job) = jobs.iter().find(|job| {
+        !ordered.iter().any(|done| done.id == job.id)
+            && job
+                .needs
+                .iter()
+                .all(|need| ordered.iter().any(|done| done.id == *need) || !jobs.iter().any(|other| other.id == *need))
+    }) {
+        ordered.push(job);
+    }
+
+    ordered
+}
+
+fn run_job<H: Host>(host: &H, outputter: &Outputter<H>, repo_root: &Path, job: &WorkflowJob, steps: &[LocalStep]) -> Outcome {
+    outputter.start_activity(&job.name);
+
+    let mut failed = false;
+    let mut ran = 0;
+    for step in steps {
+        let runs = match step.condition {
+            StepCondition::Success => !failed,
+            StepCondition::Failure => failed,
+            StepCondition::Always => true,
+            StepCondition::Unknown => false,
+        };
+
+        let (shell, script, working_directory, env) = match &step.action {
+            LocalAction::Run {
+                shell,
+                script,
+                working_directory,
+                env,
+            } if runs => (shell, script, working_directory, env),
+            LocalAction::Skip(reason) if runs => {
+                outputter.message(format!("step '{}' skipped, {reason}", step.name));
+                continue;
+            }
+            _ => continue,
+        };
+
+        outputter.message(Msg::Step(&step.name));
+        ran += 1;
+
+        let directory = working_directory
+            .as_ref()
+            .map_or_else(|| repo_root.to_path_buf(), |dir| repo_root.join(dir));
+        let mut cmd = Command::new(&shell[0]);
+        _ = cmd.args(&shell[1..]).arg(script);
+        _ = cmd.envs(env);
+        _ = cmd
+            .env("CI", "true")
+            .env("GITHUB_ACTIONS", "true")
+            .env("GITHUB_WORKSPACE", repo_root);
+        _ = cmd.current_dir(&directory);
+        _ = cmd.stdout(Stdio::piped());
+        _ = cmd.stderr(Stdio::piped());
+
+        outputter.run_command(&cmd);
+        match host.output(&mut cmd) {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => {
+                outputter.command_error(Msg::UnableToRunStep, Some(output.status), Some(&output), true);
+                failed = true;
+            }
+            Err(e) => {
+                outputter.command_error(format!("unable to start step: {e}"), None, None, true);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        outputter.complete_activity(Msg::Status(crate::summary::JobStatus::Failed));
+        Outcome::Failed
+    } else {
+        outputter.complete_activity(Msg::RanSteps(ran));
+        Outcome::Passed
+    }
+}
+
+fn print_report<H: Host>(outputter: &Outputter<H>, report: &[(&String, Vec<(&WorkflowJob, Outcome)>)]) {
+    for (workflow, outcomes) in report {
+        outputter.line(format!("\nWorkflow '{workflow}':"));
+        for (job, outcome) in outcomes {
+            let text = match outcome {
+                Outcome::Passed => "simulated, passed".to_string(),
+                Outcome::Failed => "simulated, failed".to_string(),
+                Outcome::WouldRun(steps) => format!("would be simulated, {steps} step(s)"),
+                Outcome::Skipped(reason) => format!("skipped, {reason}"),
+                Outcome::Unmappable(reason) => format!("unmappable, {reason}"),
+                Outcome::NotRun(reason) => format!("not run, {reason}"),
+            };
+
+            outputter.line(format!("  job '{}': {text}", job.id));
+        }
+    }
+
+    let count = |matches: fn(&Outcome) -> bool| {
+        report
+            .iter()
+            .flat_map(|(_, outcomes)| outcomes)
+            .filter(|(_, outcome)| matches(outcome))
+            .count()
+    };
+
+    outputter.line(format!(
+        "\n{} job(s) simulated, {} skipped, {} unmappable",
+        count(|outcome| matches!(outcome, Outcome::Passed | Outcome::Failed | Outcome::WouldRun(_))),
+        count(|outcome| matches!(outcome, Outcome::Skipped(_) | Outcome::NotRun(_))),
+        count(|outcome| matches!(outcome, Outcome::Unmappable(_))),
+    ));
+}
@@ -0,0 +1,94 @@
+use crate::commands::run::{RunArgs, run_jobs, shell_quote};
+use crate::config::{Config, Job, JobId};
+use crate::host::Host;
+use anyhow::{Context, anyhow};
+use cargo_metadata::Metadata;
+use clap::{ArgAction, Parser, ValueEnum};
+use serde_json::json;
+use std::process::{Command, Stdio};
+
+/// The ID of the job which runs the tests, chosen so it's unlikely to clash with a configured job.
+const TEST_JOB: &str = "cargo-ci-test";
+
+#[derive(Parser, Debug, Clone)]
+pub struct TestArgs {
+    /// Which test runner to use
+    #[arg(long, value_name = "RUNNER", default_value_t = TestRunner::Auto, value_enum)]
+    runner: TestRunner,
+
+    /// Run the tests of every package separately, from the package's directory
+    #[arg(long, action = ArgAction::SetTrue)]
+    per_package: bool,
+
+    #[command(flatten)]
+    run_args: RunArgs,
+
+    /// Arguments passed to the test runner, such as test name filters
+    #[arg(last = true, value_name = "ARGS")]
+    runner_args: Vec<String>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum TestRunner {
+    /// cargo-nextest when it's installed, `cargo test` otherwise
+    Auto,
+    Nextest,
+    Cargo,
+}
+
+/// Runs the workspace's tests as a job of its own, so they get the same package selection, variables, and reporting as
+/// any other job, without needing a configuration file.
+pub fn test<H: Host>(args: &TestArgs, host: &mut H, mut cfg: Config, metadata: &Metadata) -> anyhow::Result<()> {
+    if !args.run_args.jobs().is_empty() {
+        return Err(anyhow!(
+            "the test subcommand doesn't take job names, pass arguments for the test runner after '--' instead"
+        ));
+    }
+
+    let nextest = match args.runner {
+        TestRunner::Nextest => true,
+        TestRunner::Cargo => false,
+        TestRunner::Auto => has_nextest(&*host),
+    };
+
+    let command = test_command(args, nextest);
+    let job = serde_json::from_value::<Job>(json!({
+        "name": "test",
+        "steps": [{
+            "name": if nextest { "cargo nextest run" } else { "cargo test" },
+            "command": command,
+            "per_package": args.per_package,
+        }],
+    }))
+    .context("unable to define the test job")?;
+
+    cfg.add_job(JobId::from(TEST_JOB), job);
+    run_jobs(&args.run_args.with_jobs(vec![TEST_JOB.to_string()]), host, &cfg, metadata)
+}
+
+/// Builds the command line which runs the tests.
+///
+/// Steps which run once for the whole workspace pass the selected packages along, while per-package steps already run
+/// in the directory of each selected package.
+fn test_command(args: &TestArgs, nextest: bool) -> String {
+    let mut words = vec![if nextest { "cargo nextest run" } else { "cargo test" }.to_string()];
+    if !args.per_package {
+        if args.run_args.packages().is_empty() {
+            words.push("--workspace".to_string());
+        } else {
+            for spec in args.run_args.packages() {
+                words.push(format!("--package {}", shell_quote(spec)));
+            }
+        }
+    }
+
+    words.extend(args.runner_args.iter().map(|arg| shell_quote(arg)));
+    words.join(" ")
+}
+
+/// Whether cargo-nextest is installed.
+fn has_nextest(host: &impl Host) -> bool {
+    let mut cmd = Command::new("cargo");
+    _ = cmd.args(["nextest", "--version"]).stdout(Stdio::null()).stderr(Stdio::null());
+    host.output(&mut cmd).is_ok_and(|output| output.status.success())
+}
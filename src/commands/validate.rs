@@ -0,0 +1,247 @@
+use crate::cargo_aliases::CargoAliases;
+use crate::config::{Config, Job, Step, ToolId};
+use crate::excluded_crates::{self, Exclusion};
+use crate::host::Host;
+use crate::pkg_data::{overridden_steps, untrusted_package_warnings};
+use anyhow::anyhow;
+use cargo_metadata::Metadata;
+use clap::ArgAction;
+use clap::Parser;
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser, Debug, Clone)]
+pub struct ValidateArgs {
+    /// Fail when validation produces any warnings
+    #[arg(long, action = ArgAction::SetTrue)]
+    strict: bool,
+
+    /// Also check the workspace's packages against the configuration, which takes running cargo metadata
+    #[arg(long, action = ArgAction::SetTrue)]
+    packages: bool,
+}
+
+impl ValidateArgs {
+    /// Whether validating takes knowing about the workspace's packages, rather than just reading the configuration.
+    #[must_use]
+    pub const fn needs_metadata(&self) -> bool {
+        self.packages
+    }
+}
+
+/// Reports problems with the configuration which don't prevent it from loading.
+///
+/// Errors which make the configuration unusable are already reported when it is loaded, so this only
+/// needs to look for things that are likely mistakes. The workspace's packages are only checked when their metadata is
+/// given.
+pub fn validate<H: Host>(
+    args: &ValidateArgs,
+    host: &H,
+    cfg: &Config,
+    workspace_root: &Path,
+    metadata: Option<&Metadata>,
+) -> anyhow::Result<()> {
+    let mut warnings = check_cargo_aliases(host, cfg, workspace_root);
+    warnings.extend(check_paths(cfg, workspace_root));
+    if let Some(metadata) = metadata {
+        warnings.extend(check_trusted_packages(cfg, metadata));
+        warnings.extend(check_step_overrides(cfg, metadata));
+        warnings.extend(check_excluded_crates(host, cfg, metadata)?);
+    }
+
+    for warning in &warnings {
+        host.eprintln(format!("WARNING: {warning}"));
+    }
+
+    if warnings.is_empty() {
+        host.println("The configuration is valid.");
+        Ok(())
+    } else if args.strict {
+        Err(anyhow!("validation produced {} warning(s)", warnings.len()))
+    } else {
+        host.println(format!("The configuration is valid, with {} warning(s).", warnings.len()));
+        Ok(())
+    }
+}
+
+/// Commands built into cargo, which steps can run without defining an alias or installing anything.
+const BUILTIN_COMMANDS: &[&str] = &[
+    "add",
+    "bench",
+    "build",
+    "check",
+    "clean",
+    "config",
+    "doc",
+    "fetch",
+    "fix",
+    "generate-lockfile",
+    "help",
+    "info",
+    "init",
+    "install",
+    "locate-project",
+    "login",
+    "logout",
+    "metadata",
+    "new",
+    "owner",
+    "package",
+    "pkgid",
+    "publish",
+    "read-manifest",
+    "remove",
+    "report",
+    "run",
+    "rustc",
+    "rustdoc",
+    "search",
+    "test",
+    "tree",
+    "uninstall",
+    "update",
+    "vendor",
+    "verify-project",
+    "version",
+    "yank",
+];
+
+/// Looks for steps that run a cargo alias which the workspace's cargo configuration doesn't define.
+///
+/// This covers `cargo_alias` steps, and `command` steps running a cargo subcommand which is neither built into cargo,
+/// a tool listed in the configuration, nor installed as a `cargo-<NAME>` program.
+fn check_cargo_aliases<H: Host>(host: &H, cfg: &Config, workspace_root: &Path) -> Vec<String> {
+    let aliases = CargoAliases::discover(host, workspace_root);
+
+    let mut jobs: Vec<_> = cfg.jobs().iter().collect();
+    jobs.sort_by_key(|(job_id, _)| job_id.as_str());
+
+    let mut warnings = Vec::new();
+    for (job_id, job) in jobs {
+        for step in job.steps() {
+            if let Some(alias) = step.cargo_alias_args().and_then(|args| args.into_iter().next()) {
+                if !aliases.contains(&alias) {
+                    warnings.push(format!(
+                        "step '{step}' in job '{job_id}' runs cargo alias '{alias}', which isn't defined in the workspace's cargo configuration"
+                    ));
+                }
+            } else if let Some(subcommand) = step.cargo_subcommand()
+                && !BUILTIN_COMMANDS.contains(&subcommand.as_str())
+                && !aliases.contains(&subcommand)
+                && cfg.tools().get(&ToolId::from(format!("cargo-{subcommand}").as_str())).is_none()
+                && !is_installed(host, workspace_root, job, step, &subcommand)
+            {
+                warnings.push(format!(
+                    "step '{step}' in job '{job_id}' runs 'cargo {subcommand}', which is neither a cargo command, an installed subcommand, nor an alias defined in the workspace's cargo configuration"
+                ));
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Whether cargo finds an external `cargo-<NAME>` program for a subcommand, which it looks for in the step's `PATH`
+/// and in the cargo home directory.
+fn is_installed<H: Host>(host: &H, workspace_root: &Path, job: &Job, step: &Step, subcommand: &str) -> bool {
+    let program = format!("cargo-{subcommand}{}", env::consts::EXE_SUFFIX);
+
+    let mut dirs: Vec<PathBuf> = step.path().iter().chain(job.path()).map(|dir| workspace_root.join(dir)).collect();
+    if let Some((_, inherited)) = host.vars().find(|(name, _)| name.eq_ignore_ascii_case("PATH")) {
+        dirs.extend(env::split_paths(&inherited));
+    }
+
+    if let Ok(cargo_home) = home::cargo_home() {
+        dirs.push(cargo_home.join("bin"));
+    }
+
+    dirs.iter().any(|dir| host.is_file(&dir.join(&program)))
+}
+
+/// Looks for packages from outside the workspace whose variables are ignored, and for trusted packages which don't exist.
+fn check_trusted_packages(cfg: &Config, metadata: &Metadata) -> Vec<String> {
+    let packages = metadata.workspace_packages();
+    let mut warnings = untrusted_package_warnings(&packages, metadata.workspace_root.as_std_path(), cfg.trusted_packages());
+
+    let mut trusted: Vec<_> = cfg.trusted_packages().iter().collect();
+    trusted.sort();
+    for name in trusted {
+        if !packages.iter().any(|pkg| pkg.name.as_str() == name) {
+            warnings.push(format!("trusted package '{name}' is not a member of the workspace"));
+        }
+    }
+
+    warnings
+}
+
+/// Looks for packages which override the command of a step that no job has.
+fn check_step_overrides(cfg: &Config, metadata: &Metadata) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for pkg in metadata.workspace_packages() {
+        for step_id in overridden_steps(pkg) {
+            let known = cfg
+                .jobs()
+                .iter()
+                .any(|(_, job)| job.steps().iter().any(|step| step.id().is_some_and(|id| id.as_str() == step_id)));
+            if !known {
+                warnings.push(format!(
+                    "package '{}' overrides the command of step '{step_id}', but no step has this id",
+                    pkg.name
+                ));
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Looks for crates under the workspace root which no job covers: those which are neither members of the workspace nor
+/// excluded from it, and nested workspaces when no job has `include_excluded = true`. Crates which the workspace
+/// excludes on purpose are left alone.
+fn check_excluded_crates<H: Host>(host: &H, cfg: &Config, metadata: &Metadata) -> anyhow::Result<Vec<String>> {
+    let included = cfg.jobs().iter().any(|(_, job)| job.include_excluded());
+    let (excluded, mut warnings) = excluded_crates::find(host, metadata)?;
+    warnings.extend(
+        excluded
+            .into_iter()
+            .filter(|excluded| match excluded.exclusion {
+                Exclusion::Excluded => false,
+                Exclusion::NestedWorkspace => !included,
+                Exclusion::Stray => true,
+            })
+            .map(|excluded| {
+                format!(
+                    "crate '{}' in '{}' is {}, so no job covers it",
+                    excluded.name,
+                    excluded.dir.display(),
+                    excluded.exclusion.description()
+                )
+            }),
+    );
+
+    Ok(warnings)
+}
+
+/// Looks for directories listed in `path` which don't exist, at least not before any steps run.
+fn check_paths(cfg: &Config, workspace_root: &Path) -> Vec<String> {
+    let mut jobs: Vec<_> = cfg.jobs().iter().collect();
+    jobs.sort_by_key(|(job_id, _)| job_id.as_str());
+
+    let mut warnings = Vec::new();
+    for (job_id, job) in jobs {
+        for dir in job.path().iter().filter(|dir| !workspace_root.join(dir).is_dir()) {
+            warnings.push(format!("job '{job_id}' adds '{}' to PATH, which doesn't exist", dir.display()));
+        }
+
+        for step in job.steps() {
+            for dir in step.path().iter().filter(|dir| !workspace_root.join(dir).is_dir()) {
+                warnings.push(format!(
+                    "step '{step}' in job '{job_id}' adds '{}' to PATH, which doesn't exist",
+                    dir.display()
+                ));
+            }
+        }
+    }
+
+    warnings
+}
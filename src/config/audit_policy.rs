@@ -0,0 +1,36 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// How `audit` steps judge the workspace's dependencies, as set in the `[audit]` table.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditPolicy {
+    #[serde(default)]
+    ignore: HashSet<String>,
+
+    #[serde(default)]
+    allowed_licenses: Vec<String>,
+
+    #[serde(default)]
+    deny_warnings: bool,
+}
+
+impl AuditPolicy {
+    /// Whether an advisory, such as `RUSTSEC-2020-0071`, was looked into and deemed harmless.
+    #[must_use]
+    pub fn ignores(&self, advisory: &str) -> bool {
+        self.ignore.contains(advisory)
+    }
+
+    /// The licenses dependencies may be released under, where an empty list means licenses aren't checked.
+    #[must_use]
+    pub fn allowed_licenses(&self) -> &[String] {
+        &self.allowed_licenses
+    }
+
+    /// Whether warnings about unmaintained, unsound, or yanked dependencies fail the step, as vulnerabilities do.
+    #[must_use]
+    pub const fn deny_warnings(&self) -> bool {
+        self.deny_warnings
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::{Context, anyhow};
+use core::fmt;
+use serde::{Deserialize, Deserializer, de};
+
+/// An amount of disk space or memory, written as `"<number><unit>"`, such as `"20GB"` or `"512 MiB"`.
+///
+/// The unit is one of `B`, `KB`, `MB`, `GB`, or `TB`, which are powers of 1000, or `KiB`, `MiB`, `GiB`, or `TiB`, which
+/// are powers of 1024, as are the shorthands `K`, `M`, `G`, and `T` often used for memory, such as `"2G"`. Units are
+/// case-insensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(u64);
+
+const UNITS: [(&str, u64); 13] = [
+    ("b", 1),
+    ("kb", 1_000),
+    ("mb", 1_000_000),
+    ("gb", 1_000_000_000),
+    ("tb", 1_000_000_000_000),
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("tib", 1 << 40),
+    ("k", 1 << 10),
+    ("m", 1 << 20),
+    ("g", 1 << 30),
+    ("t", 1 << 40),
+];
+
+impl ByteSize {
+    #[must_use]
+    pub const fn new(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    #[must_use]
+    pub const fn bytes(self) -> u64 {
+        self.0
+    }
+}
+
+impl core::str::FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let unit_start = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(unit_start);
+
+        let number: f64 = number
+            .parse()
+            .with_context(|| format!("invalid size '{s}', expected a number followed by a unit such as 'GB'"))?;
+        let unit = unit.trim().to_ascii_lowercase();
+        let (_, multiplier) = UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .ok_or_else(|| anyhow!("invalid unit in size '{s}', expected one of B, KB, MB, GB, TB, KiB, MiB, GiB, TiB, K, M, G, or T"))?;
+
+        #[expect(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss,
+            reason = "Sizes are far from where precision matters"
+        )]
+        let bytes = (number * *multiplier as f64).round() as u64;
+        Ok(Self(bytes))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    #[expect(clippy::cast_precision_loss, reason = "Sizes are only shown with two decimals")]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = [("TiB", 1_u64 << 40), ("GiB", 1 << 30), ("MiB", 1 << 20), ("KiB", 1 << 10)]
+            .into_iter()
+            .find(|(_, multiplier)| self.0 >= *multiplier);
+
+        match shown {
+            Some((unit, multiplier)) => write!(f, "{:.2} {unit}", self.0 as f64 / multiplier as f64),
+            None => write!(f, "{} B", self.0),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
@@ -1,19 +1,38 @@
-use crate::config::Tools;
-use crate::config::{JobId, Jobs};
+use crate::config::includes::{Included, merge_includes};
+use crate::config::{AuditPolicy, ByteSize, Hints, NamedRun, Notifications, Overlay, TimeSpan, ToolId, Tools};
+use crate::config::{Job, JobId, Jobs, LOCAL_EXECUTOR, StageId};
+use crate::diagnostics::{config_error, line_column};
+use crate::host::Host;
 use anyhow::{Context, Result, anyhow};
+use cargo_metadata::Metadata;
 use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
-use std::fs;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Default, Deserialize)]
 #[serde(try_from = "RawConfig")]
 pub struct Config {
     tools: Tools,
+    tool_bundles: BTreeMap<ToolId, Vec<ToolId>>,
     jobs: Jobs,
     passthrough_env_variables: HashSet<String>,
+    trusted_packages: HashSet<String>,
     default_jobs: HashSet<JobId>,
+    stages: Vec<StageId>,
     variables: HashMap<String, String>,
+    hints: Hints,
+    overlays: BTreeMap<String, Overlay>,
+    runs: BTreeMap<String, NamedRun>,
+    audit: AuditPolicy,
+    notifications: Notifications,
+    executors: BTreeMap<String, String>,
+    target_dir_budget: Option<ByteSize>,
+    max_run_duration: Option<TimeSpan>,
+    git_variables: Option<bool>,
+    text: String,
+    applied_overlays: Vec<String>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -22,12 +41,18 @@ struct RawConfig {
     #[serde(default)]
     tools: Tools,
 
+    #[serde(default)]
+    tool_bundles: BTreeMap<ToolId, Vec<ToolId>>,
+
     #[serde(default)]
     jobs: Jobs,
 
     #[serde(default)]
     default_jobs: HashSet<JobId>,
 
+    #[serde(default)]
+    stages: Vec<StageId>,
+
     #[serde(default)]
     passthrough_env_variables: HashSet<String>,
 
@@ -40,8 +65,38 @@ struct RawConfig {
     #[serde(default)]
     passthrough_env_variables_macos: HashSet<String>,
 
+    #[serde(default)]
+    trusted_packages: HashSet<String>,
+
     #[serde(default)]
     variables: HashMap<String, String>,
+
+    #[serde(default)]
+    hints: Hints,
+
+    #[serde(default)]
+    overlays: BTreeMap<String, Overlay>,
+
+    #[serde(default)]
+    runs: BTreeMap<String, NamedRun>,
+
+    #[serde(default)]
+    audit: AuditPolicy,
+
+    #[serde(default)]
+    notifications: Notifications,
+
+    #[serde(default)]
+    executors: BTreeMap<String, String>,
+
+    #[serde(default)]
+    target_dir_budget: Option<ByteSize>,
+
+    #[serde(default)]
+    max_run_duration: Option<TimeSpan>,
+
+    #[serde(default)]
+    git_variables: Option<bool>,
 }
 
 impl TryFrom<RawConfig> for Config {
@@ -54,6 +109,12 @@ impl TryFrom<RawConfig> for Config {
             }
         }
 
+        check_stages(&raw_config.stages, &raw_config.jobs)?;
+        check_overlays(&raw_config.overlays, &raw_config.jobs, &raw_config.tools)?;
+        check_tool_bundles(&raw_config.tool_bundles, &raw_config.tools, &raw_config.jobs)?;
+        check_runs(&raw_config.runs, &raw_config.jobs)?;
+        check_executors(&raw_config.executors, &raw_config.jobs)?;
+
         let mut passthrough_env_variables = raw_config.passthrough_env_variables;
         if cfg!(target_os = "windows") {
             passthrough_env_variables.extend(raw_config.passthrough_env_variables_windows);
@@ -65,77 +126,514 @@ impl TryFrom<RawConfig> for Config {
 
         Ok(Self {
             tools: raw_config.tools,
+            tool_bundles: raw_config.tool_bundles,
             jobs: raw_config.jobs,
             passthrough_env_variables,
+            trusted_packages: raw_config.trusted_packages,
             default_jobs: raw_config.default_jobs,
+            stages: raw_config.stages,
             variables: raw_config.variables,
+            hints: raw_config.hints,
+            overlays: raw_config.overlays,
+            runs: raw_config.runs,
+            audit: raw_config.audit,
+            notifications: raw_config.notifications,
+            executors: raw_config.executors,
+            target_dir_budget: raw_config.target_dir_budget,
+            max_run_duration: raw_config.max_run_duration,
+            git_variables: raw_config.git_variables,
+            text: String::new(),
+            applied_overlays: Vec::new(),
         })
     }
 }
 
+/// Ensures stages are unique, that jobs only reference declared stages, and that no job needs a job from a later stage.
+fn check_stages(stages: &[StageId], jobs: &Jobs) -> Result<()> {
+    let mut seen = HashSet::new();
+    for stage in stages {
+        if !seen.insert(stage) {
+            return Err(anyhow!("stage '{stage}' is declared more than once"));
+        }
+    }
+
+    // jobs without a stage run after all declared stages
+    let stage_index = |stage: Option<&StageId>| stage.map_or(Some(stages.len()), |s| stages.iter().position(|x| x == s));
+
+    for (job_id, job) in jobs.iter() {
+        let Some(index) = stage_index(job.stage()) else {
+            return Err(anyhow!(
+                "job '{job_id}' is in stage '{}', but that stage is not declared in 'stages'",
+                job.stage().map(StageId::as_str).unwrap_or_default()
+            ));
+        };
+
+        for needed_job_id in job.needs() {
+            let needed_index = jobs.get_job(needed_job_id).and_then(|needed| stage_index(needed.stage()));
+            if needed_index.is_some_and(|needed_index| needed_index > index) {
+                return Err(anyhow!("job '{job_id}' needs job '{needed_job_id}', which runs in a later stage"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensures overlays only refer to jobs and tools defined in the base configuration.
+fn check_overlays(overlays: &BTreeMap<String, Overlay>, jobs: &Jobs, tools: &Tools) -> Result<()> {
+    for (name, overlay) in overlays {
+        for job_id in overlay.default_jobs().into_iter().flatten() {
+            if jobs.get_job(job_id).is_none() {
+                return Err(anyhow!(
+                    "overlay '{name}' has default job '{job_id}', which is not defined in the [jobs] section"
+                ));
+            }
+        }
+
+        for tool_id in overlay.tools().keys() {
+            if tools.get(tool_id).is_none() {
+                return Err(anyhow!(
+                    "overlay '{name}' sets the version of tool '{tool_id}', which is not defined in the [tools] section"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensures tool bundles and the tools jobs require only refer to tools or other bundles, that bundles aren't named like
+/// tools, and that no bundle ends up including itself.
+fn check_tool_bundles(bundles: &BTreeMap<ToolId, Vec<ToolId>>, tools: &Tools, jobs: &Jobs) -> Result<()> {
+    let is_defined = |tool_id: &ToolId| tools.get(tool_id).is_some() || bundles.contains_key(tool_id);
+
+    for (name, members) in bundles {
+        if tools.get(name).is_some() {
+            return Err(anyhow!("tool bundle '{name}' has the same name as a tool in the [tools] section"));
+        }
+
+        if let Some(member) = members.iter().find(|member| !is_defined(member)) {
+            return Err(anyhow!(
+                "tool bundle '{name}' includes '{member}', which is neither a tool in the [tools] section nor a tool bundle"
+            ));
+        }
+
+        // following bundles from this one must never lead back to it
+        let mut pending: Vec<&ToolId> = members.iter().collect();
+        let mut visited = HashSet::new();
+        while let Some(member) = pending.pop() {
+            if member == name {
+                return Err(anyhow!("tool bundle '{name}' includes itself"));
+            }
+
+            if visited.insert(member) {
+                pending.extend(bundles.get(member).into_iter().flatten());
+            }
+        }
+    }
+
+    for (job_id, job) in jobs.iter() {
+        if let Some(tool_id) = job.tools().iter().find(|tool_id| !is_defined(tool_id)) {
+            return Err(anyhow!(
+                "job '{job_id}' requires tool '{tool_id}', which is neither a tool in the [tools] section nor a tool bundle"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensures steps only use executors defined in the configuration, and that the built-in executor isn't redefined.
+fn check_executors(executors: &BTreeMap<String, String>, jobs: &Jobs) -> Result<()> {
+    if executors.contains_key(LOCAL_EXECUTOR) {
+        return Err(anyhow!(
+            "executor '{LOCAL_EXECUTOR}' is built in and can't be defined in the [executors] section"
+        ));
+    }
+
+    for (job_id, job) in jobs.iter() {
+        for step in job.steps() {
+            if let Some(name) = step.executor()
+                && !executors.contains_key(name)
+            {
+                return Err(anyhow!(
+                    "step '{step}' in job '{job_id}' uses executor '{name}', which is not defined in the [executors] section"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensures named runs only refer to jobs defined in the configuration.
+fn check_runs(runs: &BTreeMap<String, NamedRun>, jobs: &Jobs) -> Result<()> {
+    for (name, run) in runs {
+        for job_id in run.jobs() {
+            if jobs.get_job(job_id).is_none() {
+                return Err(anyhow!(
+                    "run '{name}' has job '{job_id}', which is not defined in the [jobs] section"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 impl Config {
-    pub fn load(workspace_root: &Path, config_path: Option<&PathBuf>) -> Result<Self> {
-        let (ci_path, text) = Self::read_config(workspace_root, config_path)?;
+    /// Loads the configuration and applies the given overlays to it, in order.
+    ///
+    /// When no overlays are given and the `CI` environment variable indicates a CI environment, the `ci` overlay is
+    /// applied if it is defined.
+    pub fn load(
+        host: &impl Host,
+        workspace_root: &Path,
+        config_path: Option<&PathBuf>,
+        overlays: &[String],
+        offline: bool,
+    ) -> Result<Self> {
+        let (ci_path, text) = Self::read_config(host, workspace_root, config_path)?;
+        let (mut cfg, _, _) = Self::parse(host, &ci_path, &text, offline)?;
 
-        let extension = ci_path.extension().and_then(|s| s.to_str()).unwrap_or("");
-        match extension {
-            "toml" => toml::from_str(&text).map_err(Into::into),
-            "yml" | "yaml" => serde_yaml::from_str(&text).map_err(Into::into),
-            "json" => serde_json::from_str(&text).map_err(Into::into),
-            _ => Err(anyhow!("unsupported configuration file extension: {extension}")),
+        for name in cfg.overlays_to_apply(host, overlays) {
+            cfg.apply_overlay(&name)?;
+        }
+
+        Ok(cfg)
+    }
+
+    /// The names of the overlays to apply: the given ones or, when none are given, the `ci` overlay if it is defined
+    /// and we're running in a CI environment.
+    pub fn overlays_to_apply(&self, host: &impl Host, overlays: &[String]) -> Vec<String> {
+        if !overlays.is_empty() {
+            return overlays.to_vec();
+        }
+
+        if is_ci(host) && self.overlays.contains_key(CI_OVERLAY) {
+            vec![CI_OVERLAY.to_string()]
+        } else {
+            Vec::new()
         }
     }
 
-    #[expect(clippy::similar_names, reason = "Yep, indeed")]
-    fn read_config(workspace_root: &Path, config_path: Option<&PathBuf>) -> Result<(PathBuf, String)> {
+    pub fn apply_overlay(&mut self, name: &str) -> Result<()> {
+        let Some(overlay) = self.overlays.get(name) else {
+            return Err(anyhow!("overlay '{name}' is not defined in the [overlays] section"));
+        };
+
+        self.variables
+            .extend(overlay.variables().iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        if let Some(default_jobs) = overlay.default_jobs() {
+            self.default_jobs.clone_from(default_jobs);
+        }
+
+        for (tool_id, version) in overlay.tools() {
+            if let Some(tool) = self.tools.get_mut(tool_id) {
+                tool.set_version(version.clone());
+            }
+        }
+
+        if let Some(max_run_duration) = overlay.max_run_duration() {
+            self.max_run_duration = Some(max_run_duration);
+        }
+
+        self.applied_overlays.push(name.to_string());
+        Ok(())
+    }
+
+    /// Looks for the configuration file in the workspace root.
+    #[must_use]
+    pub fn find(host: &impl Host, workspace_root: &Path) -> Option<PathBuf> {
+        ["ci.toml", "ci.yml", "ci.yaml", "ci.json"]
+            .into_iter()
+            .map(|name| workspace_root.join(name))
+            .find(|path| host.is_file(path))
+    }
+
+    pub fn read_config(host: &impl Host, workspace_root: &Path, config_path: Option<&PathBuf>) -> Result<(PathBuf, String)> {
         let path = if let Some(path) = config_path {
             path.clone()
         } else {
-            let yml = workspace_root.join("ci.yml");
-            let yaml = workspace_root.join("ci.yaml");
-            let json = workspace_root.join("ci.json");
-            let toml = workspace_root.join("ci.toml");
-
-            if toml.exists() {
-                toml
-            } else if yml.exists() {
-                yml
-            } else if yaml.exists() {
-                yaml
-            } else if json.exists() {
-                json
-            } else {
-                return Err(anyhow!(
-                    "no configuration file found (looked for ci.toml, ci.yml, ci.yaml, and ci.json)"
-                ));
-            }
+            Self::find(host, workspace_root)
+                .ok_or_else(|| anyhow!("no configuration file found (looked for ci.toml, ci.yml, ci.yaml, and ci.json)"))?
         };
 
-        let text = fs::read_to_string(&path).with_context(|| format!("Reading cargo-ci configuration from {}", path.display()))?;
+        let text = host
+            .read_to_string(&path)
+            .with_context(|| format!("Reading cargo-ci configuration from {}", path.display()))?;
         Ok((path, text))
     }
 
+    /// Parses a configuration file along with the files it includes, returning the document they were merged into and
+    /// the included files too.
+    ///
+    /// When offline, included URLs are only read from the cache.
+    pub fn parse(host: &impl Host, path: &Path, text: &str, offline: bool) -> Result<(Self, Map<String, Value>, Vec<Included>)> {
+        let Value::Object(mut document) = parse_config(path, text)? else {
+            return Err(anyhow!("the configuration in {} is not a table", path.display()));
+        };
+
+        // parsing the file itself gives better error messages, so the merged document is only used when needed
+        let has_includes = document.contains_key("include");
+        let included = merge_includes(host, path, &mut document, offline)?;
+        let mut cfg: Self = if has_includes {
+            serde_path_to_error::deserialize(Value::Object(document.clone()))
+                .map_err(|e| config_error(path, text, Some(e.path()), &e.inner().to_string(), None))
+                .context("invalid configuration once includes are merged")?
+        } else {
+            parse_config(path, text)?
+        };
+
+        // the included files are part of the configuration's text, so that changing them invalidates cached results
+        cfg.text = core::iter::once(text)
+            .chain(included.iter().map(|included| included.text.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok((cfg, document, included))
+    }
+
+    /// Expands the jobs with `foreach_features` into one job per feature of the named workspace package, with the ID
+    /// `<job>-<feature>`. The `default` feature and those listed in `skip_features` are left out. The original job keeps
+    /// no steps of its own and needs all of its instances, so that running it runs them all.
+    pub fn expand_feature_jobs(&mut self, metadata: &Metadata) -> Result<()> {
+        let templates: Vec<(JobId, String)> = self
+            .jobs
+            .iter()
+            .filter_map(|(job_id, job)| job.foreach_features().map(|name| (job_id.clone(), name.to_string())))
+            .collect();
+
+        for (job_id, name) in templates {
+            let pkg = metadata
+                .workspace_packages()
+                .into_iter()
+                .find(|pkg| pkg.name.as_str() == name)
+                .ok_or_else(|| {
+                    anyhow!("job '{job_id}' runs for every feature of package '{name}', which is not a member of the workspace")
+                })?;
+
+            let template = self.jobs.get_job(&job_id).expect("job not found");
+            let mut features: Vec<&String> = pkg
+                .features
+                .keys()
+                .filter(|feature| *feature != "default" && !template.skip_features().contains(feature))
+                .collect();
+            features.sort();
+
+            let mut instances = Vec::new();
+            for feature in features {
+                let instance_id = JobId::from(format!("{job_id}-{feature}").as_str());
+                if self.jobs.get_job(&instance_id).is_some() {
+                    return Err(anyhow!(
+                        "job '{job_id}' runs for feature '{feature}' as job '{instance_id}', which is already defined"
+                    ));
+                }
+
+                instances.push((instance_id, template.feature_instance(feature)));
+            }
+
+            let instance_ids = instances.iter().map(|(instance_id, _)| instance_id.clone()).collect();
+            for (instance_id, instance) in instances {
+                self.jobs.insert(instance_id, instance);
+            }
+
+            if let Some(template) = self.jobs.get_job_mut(&job_id) {
+                template.gather_instances(instance_ids);
+            }
+        }
+
+        Ok(())
+    }
+
     #[must_use]
     pub const fn tools(&self) -> &Tools {
         &self.tools
     }
 
+    /// Expands tool bundles into the tools they include, recursively, keeping the tools in the order they're first
+    /// mentioned and leaving out repeats.
+    #[must_use]
+    pub fn resolve_tools<'a>(&'a self, names: impl IntoIterator<Item = &'a ToolId>) -> Vec<ToolId> {
+        let mut resolved: Vec<ToolId> = Vec::new();
+        let mut pending: Vec<&ToolId> = names.into_iter().collect();
+        pending.reverse();
+
+        while let Some(name) = pending.pop() {
+            match self.tool_bundles.get(name) {
+                Some(members) => pending.extend(members.iter().rev()),
+                None if !resolved.contains(name) => resolved.push(name.clone()),
+                None => {}
+            }
+        }
+
+        resolved
+    }
+
     #[must_use]
     pub const fn jobs(&self) -> &Jobs {
         &self.jobs
     }
 
+    /// Adds a job, replacing any job with the same ID.
+    pub fn add_job(&mut self, id: JobId, job: Job) {
+        self.jobs.insert(id, job);
+    }
+
     #[must_use]
     pub const fn passthrough_env_variables(&self) -> &HashSet<String> {
         &self.passthrough_env_variables
     }
 
+    /// Packages from outside the workspace whose `[package.metadata.ci]` variables are used anyway.
+    #[must_use]
+    pub const fn trusted_packages(&self) -> &HashSet<String> {
+        &self.trusted_packages
+    }
+
     #[must_use]
     pub const fn default_jobs(&self) -> &HashSet<JobId> {
         &self.default_jobs
     }
 
+    #[must_use]
+    pub fn stages(&self) -> &[StageId] {
+        &self.stages
+    }
+
+    #[must_use]
+    pub const fn hints(&self) -> &Hints {
+        &self.hints
+    }
+
+    /// How large the target directory may grow before runs warn about it.
+    #[must_use]
+    pub const fn target_dir_budget(&self) -> Option<ByteSize> {
+        self.target_dir_budget
+    }
+
+    /// How long a run may take before it stops starting steps and winds down.
+    #[must_use]
+    pub const fn max_run_duration(&self) -> Option<TimeSpan> {
+        self.max_run_duration
+    }
+
+    /// Whether runs probe git for the variables describing the state of the repository, which they do unless disabled.
+    #[must_use]
+    pub fn git_variables(&self) -> bool {
+        self.git_variables.unwrap_or(true)
+    }
+
+    /// The command line of an executor defined in the `[executors]` table.
+    #[must_use]
+    pub fn executor(&self, name: &str) -> Option<&str> {
+        self.executors.get(name).map(String::as_str)
+    }
+
+    /// The overlays applied to the configuration, in order.
+    #[must_use]
+    pub fn applied_overlays(&self) -> &[String] {
+        &self.applied_overlays
+    }
+
+    /// The text the configuration was loaded from, before any overlays were applied.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// How `audit` steps judge the workspace's dependencies.
+    #[must_use]
+    pub const fn audit(&self) -> &AuditPolicy {
+        &self.audit
+    }
+
+    /// How runs notify users through the terminal.
+    #[must_use]
+    pub const fn notifications(&self) -> &Notifications {
+        &self.notifications
+    }
+
     pub fn variables(&self) -> impl Iterator<Item = (&str, &str)> {
         self.variables.iter().map(|(k, v)| (k.as_str(), v.as_str()))
     }
+
+    #[must_use]
+    pub fn overlay(&self, name: &str) -> Option<&Overlay> {
+        self.overlays.get(name)
+    }
+
+    #[must_use]
+    pub fn named_run(&self, name: &str) -> Option<&NamedRun> {
+        self.runs.get(name)
+    }
+}
+
+/// Parses a configuration file in the format given by its extension.
+///
+/// Errors are located in the file by line, column, and the path of the offending key, as far as the format allows.
+pub fn parse_config<T: DeserializeOwned>(path: &Path, text: &str) -> Result<T> {
+    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+    match extension {
+        "toml" => {
+            let locate = |error: &toml::de::Error| error.span().map(|span| line_column(text, span.start));
+            let deserializer = toml::Deserializer::parse(text).map_err(|e| config_error(path, text, None, e.message(), locate(&e)))?;
+            serde_path_to_error::deserialize(deserializer).map_err(|e| {
+                // checks of the whole document are located at its start, which says nothing
+                let position = locate(e.inner()).filter(|_| e.path().iter().next().is_some());
+                config_error(path, text, Some(e.path()), e.inner().message(), position)
+            })
+        }
+
+        "yml" | "yaml" => serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(text)).map_err(|e| {
+            let position = e.inner().location().map(|location| (location.line(), location.column()));
+            let message = without_position(&e.inner().to_string(), position);
+
+            // the YAML parser starts its messages with the path of the key it was reading, which errors already show
+            let key = e.path().to_string();
+            let message = message
+                .split_once(": ")
+                .filter(|(prefix, _)| key.starts_with(prefix))
+                .map_or(message.as_str(), |(_, rest)| rest);
+            config_error(path, text, Some(e.path()), message, position)
+        }),
+
+        "json" => {
+            let mut deserializer = serde_json::Deserializer::from_str(text);
+            let locate = |error: &serde_json::Error| Some((error.line(), error.column())).filter(|(line, _)| *line > 0);
+            let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+                let position = locate(e.inner());
+                config_error(
+                    path,
+                    text,
+                    Some(e.path()),
+                    &without_position(&e.inner().to_string(), position),
+                    position,
+                )
+            })?;
+            deserializer.end().map_err(|e| {
+                let position = locate(&e);
+                config_error(path, text, None, &without_position(&e.to_string(), position), position)
+            })?;
+            Ok(value)
+        }
+
+        _ => Err(anyhow!("unsupported configuration file extension: {extension}")),
+    }
+}
+
+/// Removes the position YAML and JSON parsers add to their messages, since errors show it before the message.
+fn without_position(message: &str, position: Option<(usize, usize)>) -> String {
+    position.map_or_else(
+        || message.to_string(),
+        |(line, column)| message.replace(&format!(" at line {line} column {column}"), ""),
+    )
+}
+
+const CI_OVERLAY: &str = "ci";
+
+/// Determines whether we're running in a CI environment, which most CI services indicate by setting `CI`.
+fn is_ci(host: &impl Host) -> bool {
+    host.vars()
+        .any(|(k, v)| k == "CI" && !v.is_empty() && !v.eq_ignore_ascii_case("false") && v != "0")
 }
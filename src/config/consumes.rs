@@ -0,0 +1,34 @@
+use crate::config::JobId;
+use serde::Deserialize;
+use std::path::{Component, PathBuf};
+
+/// Files produced by another job which a job needs before it starts.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Consumes {
+    from: JobId,
+    artifacts: Vec<PathBuf>,
+}
+
+impl Consumes {
+    /// The job producing the artifacts.
+    #[must_use]
+    pub const fn from(&self) -> &JobId {
+        &self.from
+    }
+
+    /// The files or directories the job consumes, relative to the workspace root.
+    #[must_use]
+    pub fn artifacts(&self) -> &[PathBuf] {
+        &self.artifacts
+    }
+
+    /// The first artifact which isn't a path within the workspace, if any.
+    #[must_use]
+    pub fn escaping_artifact(&self) -> Option<&PathBuf> {
+        self.artifacts.iter().find(|path| {
+            path.components()
+                .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+        })
+    }
+}
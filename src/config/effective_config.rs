@@ -0,0 +1,201 @@
+use crate::config::{Config, Overlay};
+use crate::host::Host;
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const PASSTHROUGH: &str = "passthrough_env_variables";
+const PLATFORM_PASSTHROUGH: [&str; 3] = [
+    "passthrough_env_variables_windows",
+    "passthrough_env_variables_linux",
+    "passthrough_env_variables_macos",
+];
+
+/// The configuration once overlays and platform-specific settings are applied, as a plain document which records where
+/// each of its entries came from.
+#[derive(Debug)]
+pub struct EffectiveConfig {
+    config: Config,
+    document: Map<String, Value>,
+    provenance: BTreeMap<String, String>,
+    overlays: Vec<String>,
+}
+
+impl EffectiveConfig {
+    /// Loads the configuration like [`Config::load`] does, keeping track of where everything came from.
+    pub fn load(
+        host: &impl Host,
+        workspace_root: &Path,
+        config_path: Option<&PathBuf>,
+        overlays: &[String],
+        offline: bool,
+    ) -> Result<Self> {
+        let (path, text) = Config::read_config(host, workspace_root, config_path)?;
+        let (mut config, document, included) = Config::parse(host, &path, &text, offline)?;
+
+        let file = path.strip_prefix(workspace_root).unwrap_or(&path).display().to_string();
+        let mut provenance = document_provenance(&document, &file);
+        for included in &included {
+            provenance.extend(document_provenance(&included.entries, &format!("include '{}'", included.source)));
+        }
+
+        let applied = config.overlays_to_apply(host, overlays);
+        let mut effective = Self {
+            provenance,
+            document,
+            overlays: Vec::new(),
+            config: Config::default(),
+        };
+
+        effective.merge_passthrough(&config, &file);
+
+        for name in &applied {
+            config.apply_overlay(name)?;
+            let source = if overlays.is_empty() {
+                format!("overlay '{name}', applied since CI is set")
+            } else {
+                format!("overlay '{name}'")
+            };
+
+            if let Some(overlay) = config.overlay(name) {
+                effective.merge_overlay(overlay, &source);
+            }
+        }
+
+        effective.config = config;
+        effective.overlays = applied;
+        Ok(effective)
+    }
+
+    /// The configuration itself.
+    #[must_use]
+    pub const fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The merged configuration, as it could be written in a configuration file.
+    #[must_use]
+    pub const fn document(&self) -> &Map<String, Value> {
+        &self.document
+    }
+
+    /// Where entries of the document came from, keyed by their dotted path.
+    #[must_use]
+    pub const fn provenance(&self) -> &BTreeMap<String, String> {
+        &self.provenance
+    }
+
+    /// The names of the overlays which were applied, in order.
+    #[must_use]
+    pub fn overlays(&self) -> &[String] {
+        &self.overlays
+    }
+
+    /// Replaces the platform-specific lists of environment variables with the one list which applies on this platform.
+    fn merge_passthrough(&mut self, config: &Config, file: &str) {
+        let platform_key = if cfg!(target_os = "windows") {
+            Some(PLATFORM_PASSTHROUGH[0])
+        } else if cfg!(target_os = "linux") {
+            Some(PLATFORM_PASSTHROUGH[1])
+        } else if cfg!(target_os = "macos") {
+            Some(PLATFORM_PASSTHROUGH[2])
+        } else {
+            None
+        };
+
+        let mut sources = Vec::new();
+        for key in core::iter::once(PASSTHROUGH).chain(PLATFORM_PASSTHROUGH) {
+            _ = self.provenance.remove(key);
+            if self.document.remove(key).is_some() && (key == PASSTHROUGH || Some(key) == platform_key) {
+                sources.push(key);
+            }
+        }
+
+        if sources.is_empty() {
+            return;
+        }
+
+        let mut names: Vec<_> = config.passthrough_env_variables().iter().cloned().collect();
+        names.sort();
+        _ = self
+            .document
+            .insert(PASSTHROUGH.to_string(), names.into_iter().map(Value::String).collect());
+        _ = self
+            .provenance
+            .insert(PASSTHROUGH.to_string(), format!("{file} ({})", sources.join(" + ")));
+    }
+
+    fn merge_overlay(&mut self, overlay: &Overlay, source: &str) {
+        if !overlay.variables().is_empty() {
+            let variables = table_entry(&mut self.document, "variables");
+            for (name, value) in overlay.variables() {
+                _ = variables.insert(name.clone(), Value::String(value.clone()));
+                _ = self.provenance.insert(format!("variables.{name}"), source.to_string());
+            }
+        }
+
+        if let Some(default_jobs) = overlay.default_jobs() {
+            let mut jobs: Vec<_> = default_jobs.iter().map(ToString::to_string).collect();
+            jobs.sort();
+            _ = self
+                .document
+                .insert("default_jobs".to_string(), jobs.into_iter().map(Value::String).collect());
+            _ = self.provenance.insert("default_jobs".to_string(), source.to_string());
+        }
+
+        if let Some(max_run_duration) = overlay.max_run_duration() {
+            _ = self
+                .document
+                .insert("max_run_duration".to_string(), Value::String(max_run_duration.to_string()));
+            _ = self.provenance.insert("max_run_duration".to_string(), source.to_string());
+        }
+
+        if overlay.tools().is_empty() {
+            return;
+        }
+
+        let tools = table_entry(&mut self.document, "tools");
+        for (tool_id, version) in overlay.tools() {
+            let version = Value::String(version.to_string());
+            match tools.get_mut(&tool_id.to_string()) {
+                Some(Value::Object(tool)) => {
+                    _ = tool.insert("version".to_string(), version);
+                    _ = self.provenance.insert(format!("tools.{tool_id}.version"), source.to_string());
+                }
+
+                Some(tool) => {
+                    *tool = version;
+                    _ = self.provenance.insert(format!("tools.{tool_id}"), source.to_string());
+                }
+
+                None => {}
+            }
+        }
+    }
+}
+
+/// Attributes every top-level entry of the document, and every entry of its tables, to the configuration file.
+fn document_provenance(document: &Map<String, Value>, file: &str) -> BTreeMap<String, String> {
+    let mut provenance = BTreeMap::new();
+    for (key, value) in document {
+        _ = provenance.insert(key.clone(), file.to_string());
+        if let Value::Object(table) = value {
+            for child in table.keys() {
+                _ = provenance.insert(format!("{key}.{child}"), file.to_string());
+            }
+        }
+    }
+
+    provenance
+}
+
+/// Gets a table of the document, creating it if needed.
+fn table_entry<'a>(document: &'a mut Map<String, Value>, key: &str) -> &'a mut Map<String, Value> {
+    let entry = document.entry(key).or_insert_with(|| Value::Object(Map::new()));
+    if !entry.is_object() {
+        *entry = Value::Object(Map::new());
+    }
+
+    entry.as_object_mut().expect("entry was just made a table")
+}
@@ -0,0 +1,16 @@
+use serde::Deserialize;
+
+/// How to automatically fix the problems a check step reports.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixable {
+    command: String,
+}
+
+impl Fixable {
+    /// The command which fixes the problems, such as `cargo fmt` for a `cargo fmt --check` step.
+    #[must_use]
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+}
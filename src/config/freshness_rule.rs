@@ -0,0 +1,90 @@
+use anyhow::anyhow;
+use core::fmt;
+use serde::{Deserialize, Deserializer, de};
+use std::path::{Component, Path};
+
+/// A mapping from source files to the files generated from them, written as `"<sources>:<generated>"`, such as
+/// `"proto/*.proto:generated/*.rs"`.
+///
+/// Both sides are paths relative to the workspace root. The file name of the sources may contain a single `*`, which
+/// matches any part of a file name. A `*` in the generated path stands for the same part of the name, so every source
+/// file has a generated counterpart of its own. Without one, every source file maps to the same generated file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreshnessRule {
+    sources: String,
+    generated: String,
+}
+
+impl FreshnessRule {
+    /// The pattern matching the source files.
+    #[must_use]
+    pub fn sources(&self) -> &str {
+        &self.sources
+    }
+
+    /// The path of the files generated from the sources, where `*` stands for what it matched in a source's name.
+    #[must_use]
+    pub fn generated(&self) -> &str {
+        &self.generated
+    }
+}
+
+impl core::str::FromStr for FreshnessRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sources, generated) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("invalid freshness rule '{s}', expected '<sources>:<generated>'"))?;
+        let (sources, generated) = (sources.trim(), generated.trim());
+
+        if sources.is_empty() || generated.is_empty() {
+            return Err(anyhow!("invalid freshness rule '{s}', expected '<sources>:<generated>'"));
+        }
+
+        for path in [sources, generated] {
+            let escapes = Path::new(path)
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+            if escapes {
+                return Err(anyhow!(
+                    "'{path}' in freshness rule '{s}' must be a relative path within the workspace"
+                ));
+            }
+        }
+
+        let (dir, name) = sources.rsplit_once('/').unwrap_or(("", sources));
+        if dir.contains('*') || name.matches('*').count() > 1 {
+            return Err(anyhow!(
+                "'{sources}' in freshness rule '{s}' may only have a single '*', in its file name"
+            ));
+        }
+
+        let wildcards = generated.matches('*').count();
+        if wildcards > 1 || (wildcards == 1 && !name.contains('*')) {
+            return Err(anyhow!(
+                "'{generated}' in freshness rule '{s}' may only have a single '*', and only when '{sources}' has one"
+            ));
+        }
+
+        Ok(Self {
+            sources: sources.to_string(),
+            generated: generated.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for FreshnessRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.sources, self.generated)
+    }
+}
+
+impl<'de> Deserialize<'de> for FreshnessRule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,96 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer, de};
+use std::collections::BTreeMap;
+
+/// Hints for well-known environment problems, as pairs of a pattern and the hint reported when the pattern matches.
+const BUILTIN_HINTS: &[(&str, &str)] = &[
+    (
+        "linker `cc` not found",
+        "no C toolchain is installed, install one such as the `build-essential` package on Debian or Ubuntu",
+    ),
+    (
+        "Could not find directory of OpenSSL installation|could not find system library 'openssl'",
+        "the OpenSSL development files are missing, install them along with pkg-config, such as the `libssl-dev` and `pkg-config` packages on Debian or Ubuntu",
+    ),
+    (
+        "could not find `pkg-config`|The pkg-config command could not be found",
+        "pkg-config is missing, install it with your system's package manager",
+    ),
+    (
+        "toolchain '[^']+' is not installed",
+        "the required Rust toolchain is missing, install it with `rustup toolchain install`",
+    ),
+    (
+        "no such command: `[^`]+`",
+        "a cargo subcommand is missing, add it to the [tools] table and run `cargo ci install`",
+    ),
+    (
+        "No space left on device",
+        "the disk is full, running `cargo clean` frees up the space used by the target directory",
+    ),
+    (
+        "spurious network error|failed to download from|Couldn't resolve host name",
+        "a network request failed, check your connection or proxy settings and try again",
+    ),
+];
+
+/// A pattern matched against the output of failed commands, along with the hint reported when it matches.
+#[derive(Debug)]
+struct Hint {
+    pattern: Regex,
+    message: String,
+}
+
+/// The hints used to explain the likely causes of failed commands.
+///
+/// Hints from the `[hints]` table are tried first, followed by the built-in ones. A configured hint whose
+/// pattern is identical to a built-in one replaces it, and an empty message disables it.
+#[derive(Debug)]
+pub struct Hints(Vec<Hint>);
+
+impl Hints {
+    /// Gets the hints whose pattern matches the given output.
+    pub fn matching(&self, output: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|hint| !hint.message.is_empty() && hint.pattern.is_match(output))
+            .map(|hint| hint.message.as_str())
+            .collect()
+    }
+
+    fn new(configured: BTreeMap<String, String>) -> Result<Self, regex::Error> {
+        let builtins = BUILTIN_HINTS
+            .iter()
+            .filter(|(pattern, _)| !configured.contains_key(*pattern))
+            .map(|(pattern, message)| (pattern.to_string(), message.to_string()))
+            .collect::<Vec<_>>();
+
+        configured
+            .into_iter()
+            .chain(builtins)
+            .map(|(pattern, message)| {
+                Ok(Hint {
+                    pattern: Regex::new(&pattern)?,
+                    message,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Default for Hints {
+    fn default() -> Self {
+        Self::new(BTreeMap::new()).expect("built-in hint patterns are valid")
+    }
+}
+
+impl<'de> Deserialize<'de> for Hints {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let configured = BTreeMap::<String, String>::deserialize(deserializer)?;
+        Self::new(configured).map_err(|e| de::Error::custom(format!("invalid pattern in the [hints] table: {e}")))
+    }
+}
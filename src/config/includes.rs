@@ -0,0 +1,269 @@
+use crate::config::config::parse_config;
+use crate::fingerprint::{FNV_OFFSET, fnv1a};
+use crate::host::Host;
+use crate::run_lock::RunLock;
+use anyhow::{Context, Result, anyhow};
+use core::time::Duration;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+/// The sections of the configuration which included files can contribute to.
+const SECTIONS: [&str; 3] = ["tools", "tool_bundles", "jobs"];
+
+/// How long fetching an included file may take, in seconds.
+const FETCH_TIMEOUT_SECS: &str = "30";
+
+/// How long a cached copy of an included file is used as is, before checking whether it changed.
+const REVALIDATE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// A file listed in the `include` entry of the configuration, along with the entries it contributed.
+#[derive(Debug)]
+pub struct Included {
+    /// The URL or path the file was included from, as written in the configuration.
+    pub source: String,
+
+    pub text: String,
+
+    /// The entries the file contributed, by section.
+    pub entries: Map<String, Value>,
+}
+
+/// Merges the tools, tool bundles, and jobs of the files listed in the `include` entry of a configuration document into
+/// the document, removing the entry.
+///
+/// The document's own entries take precedence over included ones, and the entries of later files take precedence over
+/// those of earlier ones. Files are given as `https://` URLs, which are cached so that they're only downloaded again
+/// once they change, or as paths relative to the configuration file. When offline, URLs are only read from the cache.
+pub fn merge_includes(host: &impl Host, config_path: &Path, document: &mut Map<String, Value>, offline: bool) -> Result<Vec<Included>> {
+    let Some(sources) = document.remove("include") else {
+        return Ok(Vec::new());
+    };
+
+    let sources: Vec<String> = serde_json::from_value(sources).context("'include' must be an array of URLs or paths")?;
+    let mut included = Vec::new();
+    for source in sources.into_iter().rev() {
+        // included jobs run commands, so they mustn't come over a connection anyone along the way can tamper with
+        let text = if source.starts_with("http://") {
+            return Err(anyhow!("included configuration '{source}' must be fetched over https"));
+        } else if is_url(&source) {
+            fetch(host, &source, offline)?
+        } else {
+            let path = config_path.parent().unwrap_or_else(|| Path::new("")).join(&source);
+            host.read_to_string(&path)
+                .with_context(|| format!("unable to read included configuration {}", path.display()))?
+        };
+
+        // the format follows the extension of the file, ignoring any query string of a URL
+        let name = source.split(['?', '#']).next().unwrap_or_default();
+        let Value::Object(contents) =
+            parse_config(Path::new(name), &text).with_context(|| format!("unable to parse included configuration '{source}'"))?
+        else {
+            return Err(anyhow!("included configuration '{source}' is not a table"));
+        };
+
+        let mut entries = Map::new();
+        for (section, value) in contents {
+            if !SECTIONS.contains(&section.as_str()) {
+                return Err(anyhow!(
+                    "included configuration '{source}' defines '{section}', but included files can only define tools, tool_bundles, and jobs"
+                ));
+            }
+
+            let Value::Object(table) = value else {
+                return Err(anyhow!("'{section}' in included configuration '{source}' is not a table"));
+            };
+
+            let target = document.entry(section.clone()).or_insert_with(|| Value::Object(Map::new()));
+            let Some(target) = target.as_object_mut() else {
+                return Err(anyhow!("'{section}' in the configuration is not a table"));
+            };
+
+            let mut added = Map::new();
+            for (name, value) in table {
+                if !target.contains_key(&name) {
+                    _ = target.insert(name.clone(), value.clone());
+                    _ = added.insert(name, value);
+                }
+            }
+
+            _ = entries.insert(section, Value::Object(added));
+        }
+
+        included.push(Included { source, text, entries });
+    }
+
+    included.reverse();
+    Ok(included)
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("https://")
+}
+
+/// Gets the contents of a URL with `curl`, revalidating the cached copy through its entity tag once it's an hour old.
+///
+/// When the URL can't be fetched, the cached copy is used instead, with a warning.
+fn fetch(host: &impl Host, url: &str, offline: bool) -> Result<String> {
+    let cache = CachedUrl::new(url)?;
+    let cached = fs::read_to_string(&cache.body).ok();
+    if offline {
+        return cached.ok_or_else(|| anyhow!("'{url}' isn't cached yet, so it can't be included when offline"));
+    }
+
+    let now = SystemTime::from(host.local_time());
+    if let Some(text) = &cached
+        && cache
+            .checked()
+            .is_some_and(|checked| now.duration_since(checked).unwrap_or_default() < REVALIDATE_AFTER)
+    {
+        return Ok(text.clone());
+    }
+
+    fs::create_dir_all(&cache.dir).with_context(|| format!("unable to create cache directory {}", cache.dir.display()))?;
+
+    // concurrent runs in other workspaces share the cache
+    let _lock = RunLock::acquire(&cache.lock, false, || {})?;
+    match download(host, url, &cache, cached.is_some()) {
+        Ok(Some(text)) => Ok(text),
+        Ok(None) => cached.ok_or_else(|| anyhow!("'{url}' wasn't modified, but it isn't cached")),
+        Err(e) => match cached {
+            Some(text) => {
+                host.eprintln(format!("WARNING: {e:#}, using the cached copy"));
+                Ok(text)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Downloads a URL into the cache, returning `None` when the cached copy is still current.
+///
+/// The download goes to a file of its own, which only replaces the cached copy once complete.
+fn download(host: &impl Host, url: &str, cache: &CachedUrl, revalidate: bool) -> Result<Option<String>> {
+    let mut cmd = Command::new("curl");
+    _ = cmd
+        .args([
+            "--silent",
+            "--show-error",
+            "--location",
+            "--proto",
+            "=https",
+            "--proto-redir",
+            "=https",
+            "--max-time",
+            FETCH_TIMEOUT_SECS,
+            "--dump-header",
+            "-",
+        ])
+        .arg("--output")
+        .arg(&cache.download)
+        .args(["--write-out", "\n%{http_code}"]);
+
+    if revalidate && let Ok(etag) = fs::read_to_string(&cache.etag) {
+        _ = cmd.arg("--header").arg(format!("If-None-Match: {}", etag.trim()));
+    }
+
+    _ = cmd.arg(url).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let output = host
+        .output(&mut cmd)
+        .with_context(|| format!("unable to run curl to fetch '{url}'"))?;
+    if !output.status.success() {
+        _ = fs::remove_file(&cache.download);
+        return Err(anyhow!(
+            "unable to fetch '{url}': {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (headers, status) = stdout.trim_end().rsplit_once('\n').unwrap_or_else(|| ("", stdout.trim()));
+    match status {
+        "304" => {
+            _ = fs::remove_file(&cache.download);
+            cache.mark_checked(host);
+            Ok(None)
+        }
+        "200" => {
+            match etag(headers) {
+                Some(etag) => fs::write(&cache.etag, etag).with_context(|| format!("unable to cache '{url}'"))?,
+                None => _ = fs::remove_file(&cache.etag),
+            }
+
+            fs::rename(&cache.download, &cache.body).with_context(|| format!("unable to cache '{url}'"))?;
+            cache.mark_checked(host);
+
+            fs::read_to_string(&cache.body)
+                .map(Some)
+                .with_context(|| format!("unable to read the cached copy of '{url}'"))
+        }
+        _ => {
+            _ = fs::remove_file(&cache.download);
+            Err(anyhow!("unable to fetch '{url}': HTTP status {status}"))
+        }
+    }
+}
+
+/// Finds the entity tag among the headers of the last response, since redirects come with headers of their own.
+fn etag(headers: &str) -> Option<&str> {
+    headers
+        .rsplit("\r\n\r\n")
+        .map(str::trim)
+        .find(|response| !response.is_empty())?
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("etag"))
+        .map(|(_, value)| value.trim())
+}
+
+/// Where a URL is cached, in the cargo home directory so that every workspace shares it.
+struct CachedUrl {
+    dir: PathBuf,
+    body: PathBuf,
+    etag: PathBuf,
+
+    /// The file the URL is downloaded to, which is this process's own.
+    download: PathBuf,
+
+    /// The lock held while the cached copy is replaced.
+    lock: PathBuf,
+
+    /// The file whose modification time tells when the cached copy was last known to be current.
+    checked: PathBuf,
+}
+
+impl CachedUrl {
+    fn new(url: &str) -> Result<Self> {
+        let dir = home::cargo_home()
+            .context("unable to determine the cargo home directory")?
+            .join("cargo-ci")
+            .join("includes");
+        let name = format!("{:016x}", fnv1a(FNV_OFFSET, url.as_bytes()));
+        Ok(Self {
+            body: dir.join(format!("{name}.body")),
+            etag: dir.join(format!("{name}.etag")),
+            download: dir.join(format!("{name}.{}.download", std::process::id())),
+            lock: dir.join(format!("{name}.lock")),
+            checked: dir.join(format!("{name}.checked")),
+            dir,
+        })
+    }
+
+    /// When the cached copy was last known to be current.
+    fn checked(&self) -> Option<SystemTime> {
+        fs::metadata(&self.checked).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    /// Notes that the cached copy was just found to be current, which only spares checking again for a while, so
+    /// failing to note it is harmless.
+    fn mark_checked(&self, host: &impl Host) {
+        _ = fs::write(&self.checked, "").and_then(|()| {
+            fs::File::options()
+                .write(true)
+                .open(&self.checked)?
+                .set_modified(SystemTime::from(host.local_time()))
+        });
+    }
+}
@@ -1,10 +1,12 @@
-use crate::config::Step;
 use crate::config::job_id::JobId;
+use crate::config::{Consumes, Os, RateLimit, StageId, Step, ToolId};
 use crate::expressions::{Conditional, ContinueOnError};
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+#[expect(clippy::struct_excessive_bools, reason = "Configuration switches are naturally bools")]
 #[serde(deny_unknown_fields)]
 pub struct Job {
     name: Option<String>,
@@ -13,6 +15,9 @@ pub struct Job {
     #[serde(default)]
     needs: HashSet<JobId>,
 
+    #[serde(default)]
+    stage: Option<StageId>,
+
     #[serde(default, rename = "if")]
     conditional: Conditional,
 
@@ -21,6 +26,44 @@ pub struct Job {
 
     #[serde(default)]
     variables: HashMap<String, String>,
+
+    #[serde(default)]
+    network_rate_limit: Option<RateLimit>,
+
+    #[serde(default)]
+    prebuild_dependencies: bool,
+
+    #[serde(default)]
+    ensure_clean: bool,
+
+    #[serde(default)]
+    os: Vec<Os>,
+
+    #[serde(default)]
+    path: Vec<PathBuf>,
+
+    working_directory: Option<String>,
+
+    #[serde(default)]
+    background: bool,
+
+    #[serde(default)]
+    pure: bool,
+
+    #[serde(default)]
+    include_excluded: bool,
+
+    #[serde(default)]
+    tools: Vec<ToolId>,
+
+    #[serde(default)]
+    consumes: Option<Consumes>,
+
+    #[serde(default)]
+    foreach_features: Option<String>,
+
+    #[serde(default)]
+    skip_features: Vec<String>,
 }
 
 impl Job {
@@ -34,6 +77,11 @@ impl Job {
         &self.needs
     }
 
+    #[must_use]
+    pub const fn stage(&self) -> Option<&StageId> {
+        self.stage.as_ref()
+    }
+
     #[must_use]
     pub const fn steps(&self) -> &Vec<Step> {
         &self.steps
@@ -49,6 +97,111 @@ impl Job {
         &self.continue_on_error
     }
 
+    #[must_use]
+    pub const fn network_rate_limit(&self) -> Option<RateLimit> {
+        self.network_rate_limit
+    }
+
+    /// Whether to build the shared dependencies once before the job's first per-package step.
+    #[must_use]
+    pub const fn prebuild_dependencies(&self) -> bool {
+        self.prebuild_dependencies
+    }
+
+    /// Whether the job fails when it leaves tracked files modified.
+    #[must_use]
+    pub const fn ensure_clean(&self) -> bool {
+        self.ensure_clean
+    }
+
+    /// Whether the job's outcome only depends on the committed sources and the toolchain, so that it passing once on a
+    /// git tree means it passes again on the same tree.
+    #[must_use]
+    pub const fn pure(&self) -> bool {
+        self.pure
+    }
+
+    /// Whether the job's per-package steps also run for the crates excluded from the workspace and for nested
+    /// workspaces.
+    #[must_use]
+    pub const fn include_excluded(&self) -> bool {
+        self.include_excluded
+    }
+
+    /// Whether the job's steps run with reduced CPU and I/O priority, showing nothing in the terminal but failures.
+    #[must_use]
+    pub const fn background(&self) -> bool {
+        self.background
+    }
+
+    /// The operating systems the job runs on, where an empty list means any operating system.
+    #[must_use]
+    pub fn os(&self) -> &[Os] {
+        &self.os
+    }
+
+    /// Directories added to the front of `PATH` for the job's steps, relative to the workspace root.
+    #[must_use]
+    pub fn path(&self) -> &[PathBuf] {
+        &self.path
+    }
+
+    /// The directory the job's steps run in, relative to the workspace root, which may lead out of the workspace, such as
+    /// to a sibling directory of a monorepo checkout.
+    #[must_use]
+    pub fn working_directory(&self) -> Option<&str> {
+        self.working_directory.as_deref()
+    }
+
+    /// The tools, or tool bundles, which must be installed before the job's steps run.
+    #[must_use]
+    pub fn tools(&self) -> &[ToolId] {
+        &self.tools
+    }
+
+    /// The artifacts of another job which must be around before the job starts.
+    #[must_use]
+    pub const fn consumes(&self) -> Option<&Consumes> {
+        self.consumes.as_ref()
+    }
+
+    /// The package for whose every feature the job runs once.
+    #[must_use]
+    pub fn foreach_features(&self) -> Option<&str> {
+        self.foreach_features.as_deref()
+    }
+
+    /// The features `foreach_features` leaves out.
+    #[must_use]
+    pub fn skip_features(&self) -> &[String] {
+        &self.skip_features
+    }
+
+    /// The instance of a job with `foreach_features` which runs for one feature, exposed to its steps as the `feature`
+    /// variable and substituted for `${feature}` in their command lines.
+    #[must_use]
+    pub fn feature_instance(&self, feature: &str) -> Self {
+        let mut job = self.clone();
+        job.name = self.name.as_ref().map(|name| format!("{name} ({feature})"));
+        job.foreach_features = None;
+        job.skip_features.clear();
+        _ = job.variables.insert("feature".to_string(), feature.to_string());
+        for step in &mut job.steps {
+            step.substitute("feature", feature);
+        }
+
+        job
+    }
+
+    /// Turns a job with `foreach_features` into one with no steps of its own, which needs all of its instances, along
+    /// with the jobs it needed itself.
+    pub fn gather_instances(&mut self, instances: HashSet<JobId>) {
+        self.steps.clear();
+        self.needs.extend(instances);
+        self.consumes = None;
+        self.foreach_features = None;
+    }
+
     pub fn variables(&self) -> impl Iterator<Item = (&str, &str)> {
         self.variables.iter().map(|(k, v)| (k.as_str(), v.as_str()))
     }
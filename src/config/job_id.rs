@@ -1,7 +1,7 @@
 use core::fmt::Display;
 use serde::Deserialize;
 
-#[derive(Debug, Default, Hash, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq, PartialOrd, Ord)]
 pub struct JobId(String);
 
 impl JobId {
@@ -25,6 +25,12 @@ impl Display for JobId {
     }
 }
 
+impl From<&str> for JobId {
+    fn from(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
 impl AsRef<str> for JobId {
     fn as_ref(&self) -> &str {
         &self.0
@@ -1,8 +1,10 @@
-use crate::config::{Job, JobId};
+use crate::cargo_messages::uses_json_messages;
+use crate::config::{Job, JobId, Step, StepKind};
 use serde::Deserialize;
 use serde::de::{self, Deserializer};
 use std::collections::HashMap;
 use std::collections::{HashSet, VecDeque};
+use std::path::Path;
 
 #[derive(Debug, Default)]
 pub struct Jobs(HashMap<JobId, Job>);
@@ -16,6 +18,14 @@ impl Jobs {
         self.0.get(id)
     }
 
+    pub fn get_job_mut(&mut self, id: &JobId) -> Option<&mut Job> {
+        self.0.get_mut(id)
+    }
+
+    pub fn insert(&mut self, id: JobId, job: Job) {
+        _ = self.0.insert(id, job);
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (&JobId, &Job)> {
         self.0.iter()
     }
@@ -106,14 +116,97 @@ impl<'de> Deserialize<'de> for Jobs {
                 }
             }
 
+            check_consumes(job_id, job).map_err(de::Error::custom)?;
+            check_foreach_features(job_id, job).map_err(de::Error::custom)?;
+            check_working_directory(job_id, job).map_err(de::Error::custom)?;
+
             // check for duplicate step ids
             let mut seen = HashSet::new();
-            for step in job.steps() {
+            for (index, step) in job.steps().iter().enumerate() {
+                if !step.has_single_command() {
+                    return Err(de::Error::custom(format!(
+                        "step #{} in job '{job_id}' must set exactly one of 'command' and 'cargo_alias'",
+                        index + 1
+                    )));
+                }
+
+                if step.cargo_alias().is_some_and(|alias| shlex::split(alias).is_none()) {
+                    return Err(de::Error::custom(format!(
+                        "step '{step}' in job '{job_id}' has a 'cargo_alias' with an unterminated quote"
+                    )));
+                }
+
                 if let Some(id) = step.id()
                     && !seen.insert(id)
                 {
                     return Err(de::Error::custom(format!("duplicate step id '{id}' found in job '{job_id}'")));
                 }
+
+                if let Some(e) = step.output_filters().error() {
+                    return Err(de::Error::custom(format!("step '{step}' in job '{job_id}' has an {e}")));
+                }
+
+                match (step.kind(), step.generated()) {
+                    (StepKind::CodegenCheck, None) => {
+                        return Err(de::Error::custom(format!(
+                            "step '{step}' in job '{job_id}' is a 'codegen-check' step, which requires 'generated'"
+                        )));
+                    }
+
+                    (StepKind::Command, Some(_)) => {
+                        return Err(de::Error::custom(format!(
+                            "step '{step}' in job '{job_id}' sets 'generated', which requires 'kind = \"codegen-check\"'"
+                        )));
+                    }
+
+                    _ => {}
+                }
+
+                if step.sets_working_directory_mode() && step.working_directory().is_none() {
+                    return Err(de::Error::custom(format!(
+                        "step '{step}' in job '{job_id}' says what to do about its working directory, which requires 'working_directory'"
+                    )));
+                }
+
+                if step.has_conflicting_working_directory_modes() {
+                    return Err(de::Error::custom(format!(
+                        "step '{step}' in job '{job_id}' sets 'create_working_directory', which contradicts its 'working_directory_mode'"
+                    )));
+                }
+
+                if step.kind() == StepKind::Audit && step.per_package() {
+                    return Err(de::Error::custom(format!(
+                        "step '{step}' in job '{job_id}' is an 'audit' step, which runs once for the workspace and can't set 'per_package'"
+                    )));
+                }
+
+                if step.executor().is_some() && step.kind() != StepKind::Command {
+                    return Err(de::Error::custom(format!(
+                        "step '{step}' in job '{job_id}' sets 'executor', which only steps of the 'command' kind support"
+                    )));
+                }
+
+                if step.sarif() && step.cargo_subcommand().as_deref() != Some("fmt") && !uses_json_messages(&step.command()) {
+                    return Err(de::Error::custom(format!(
+                        "step '{step}' in job '{job_id}' sets 'sarif', which requires a 'cargo fmt' command or a cargo command with '--message-format=json'"
+                    )));
+                }
+
+                if step.baseline().is_some() && step.kind() != StepKind::SemverCheck {
+                    return Err(de::Error::custom(format!(
+                        "step '{step}' in job '{job_id}' sets 'baseline', which requires 'kind = \"semver-check\"'"
+                    )));
+                }
+
+                check_batch_packages(job_id, step).map_err(de::Error::custom)?;
+                check_parallel_packages(job_id, step).map_err(de::Error::custom)?;
+                check_regenerate(job_id, step).map_err(de::Error::custom)?;
+                check_fallbacks(job_id, step).map_err(de::Error::custom)?;
+                check_pty(job_id, step).map_err(de::Error::custom)?;
+                check_reuse_result(job_id, step).map_err(de::Error::custom)?;
+                check_update_check(job_id, step).map_err(de::Error::custom)?;
+
+                check_shards(job_id, step).map_err(de::Error::custom)?;
             }
         }
 
@@ -131,6 +224,218 @@ impl<'de> Deserialize<'de> for Jobs {
     }
 }
 
+/// Ensures the working directory of a job is relative to the workspace root, and that none of its steps run for every
+/// package, since they all run in that directory instead.
+fn check_working_directory(job_id: &JobId, job: &Job) -> Result<(), String> {
+    let Some(dir) = job.working_directory() else {
+        return Ok(());
+    };
+
+    if Path::new(dir).is_absolute() {
+        return Err(format!(
+            "job '{job_id}' has working directory '{dir}', which must be relative to the workspace root"
+        ));
+    }
+
+    if let Some(step) = job.steps().iter().find(|step| step.per_package()) {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' runs for every package, which a job with a 'working_directory' can't do, since its steps run once in that directory"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ensures the artifacts a job consumes come from a job it needs, and stay within the workspace.
+fn check_consumes(job_id: &JobId, job: &Job) -> Result<(), String> {
+    let Some(consumes) = job.consumes() else {
+        return Ok(());
+    };
+
+    let from = consumes.from();
+    if !job.needs().contains(from) {
+        return Err(format!(
+            "job '{job_id}' consumes artifacts from job '{from}', which requires listing '{from}' in its 'needs'"
+        ));
+    }
+
+    if consumes.artifacts().is_empty() {
+        return Err(format!("job '{job_id}' must consume at least one artifact from job '{from}'"));
+    }
+
+    if let Some(path) = consumes.escaping_artifact() {
+        return Err(format!(
+            "job '{job_id}' consumes artifact '{}', which must be a relative path within the workspace",
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ensures features are only skipped by jobs which run for every feature of a package.
+fn check_foreach_features(job_id: &JobId, job: &Job) -> Result<(), String> {
+    if !job.skip_features().is_empty() && job.foreach_features().is_none() {
+        return Err(format!("job '{job_id}' sets 'skip_features', which requires 'foreach_features'"));
+    }
+
+    Ok(())
+}
+
+/// Ensures a step running under a pseudo-terminal is a command step run by the local executor, whose environment can be
+/// carried over to the rebuilt command.
+fn check_pty(job_id: &JobId, step: &Step) -> Result<(), String> {
+    if !step.pty() {
+        return Ok(());
+    }
+
+    if step.kind() != StepKind::Command || step.executor().is_some() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'pty', which only steps of the 'command' kind run by the local executor support"
+        ));
+    }
+
+    // the command is rebuilt to run under a pseudo-terminal, which loses the isolation of its environment
+    if !step.inherits_env() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'pty', which can't be combined with 'inherit_env = false'"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ensures a step whose result other jobs reuse only runs a command without effects beyond the workspace.
+fn check_reuse_result(job_id: &JobId, step: &Step) -> Result<(), String> {
+    if step.reuse_result() && (step.kind() != StepKind::Command || step.destructive() || step.sarif()) {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'reuse_result', which only steps of the 'command' kind which aren't 'destructive' and don't set 'sarif' support"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ensures an `update-check` step runs once for the workspace, and that only such steps set `locked`.
+fn check_update_check(job_id: &JobId, step: &Step) -> Result<(), String> {
+    if step.locked() && step.kind() != StepKind::UpdateCheck {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'locked', which requires 'kind = \"update-check\"'"
+        ));
+    }
+
+    if step.kind() == StepKind::UpdateCheck && step.per_package() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' is an 'update-check' step, which runs once for the workspace and can't set 'per_package'"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ensures a step which batches packages is a `per_package` cargo step running from the packages' directories.
+fn check_batch_packages(job_id: &JobId, step: &Step) -> Result<(), String> {
+    let Some(batch_packages) = step.batch_packages() else {
+        return Ok(());
+    };
+
+    if !step.per_package() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'batch_packages', which requires 'per_package = true'"
+        ));
+    }
+
+    if step.cargo_subcommand().is_none() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'batch_packages', which requires a cargo command"
+        ));
+    }
+
+    if step.working_directory().is_some() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'batch_packages', whose batches run from the workspace root, so it can't set 'working_directory'"
+        ));
+    }
+
+    if batch_packages == 0 {
+        return Err(format!("step '{step}' in job '{job_id}' must batch at least one package"));
+    }
+
+    Ok(())
+}
+
+/// Ensures a step split into shards is a `per_package` step with at least one shard.
+fn check_shards(job_id: &JobId, step: &Step) -> Result<(), String> {
+    let Some(shards) = step.shards() else {
+        return Ok(());
+    };
+
+    if !step.per_package() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'shards', which requires 'per_package = true'"
+        ));
+    }
+
+    if shards == 0 {
+        return Err(format!("step '{step}' in job '{job_id}' must have at least one shard"));
+    }
+
+    Ok(())
+}
+
+/// Ensures a step which runs for several packages at once is a `per_package` step which doesn't need a terminal.
+fn check_parallel_packages(job_id: &JobId, step: &Step) -> Result<(), String> {
+    let Some(parallel_packages) = step.parallel_packages() else {
+        return Ok(());
+    };
+
+    if !step.per_package() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'parallel_packages', which requires 'per_package = true'"
+        ));
+    }
+
+    if step.pty() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'parallel_packages', whose commands can't share a terminal, so it can't set 'pty'"
+        ));
+    }
+
+    if parallel_packages == 0 {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' must run for at least one package at a time"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ensures a step which regenerates files says which generated files must be fresh.
+fn check_regenerate(job_id: &JobId, step: &Step) -> Result<(), String> {
+    if step.regenerate().is_some() && step.requires_fresh().is_empty() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'regenerate', which requires 'requires_fresh'"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ensures a step with a list of commands has at least one, and doesn't hand them to an executor, which only gets one.
+fn check_fallbacks(job_id: &JobId, step: &Step) -> Result<(), String> {
+    if step.has_empty_command() {
+        return Err(format!("step '{step}' in job '{job_id}' must have at least one command"));
+    }
+
+    if !step.fallbacks().is_empty() && step.executor().is_some() {
+        return Err(format!(
+            "step '{step}' in job '{job_id}' sets 'executor', which requires a single command"
+        ));
+    }
+
+    Ok(())
+}
+
 fn detect_cycle<'a>(
     job_id: &'a JobId,
     jobs_map: &'a HashMap<JobId, Job>,
@@ -1,21 +1,62 @@
+mod audit_policy;
+mod byte_size;
+mod consumes;
+mod effective_config;
+mod fixable;
+mod freshness_rule;
+mod hints;
+mod includes;
 mod job;
 mod job_id;
 mod jobs;
+mod named_run;
+mod notifications;
+mod os;
+mod output_encoding;
+mod output_filters;
+mod overlay;
+mod rate_limit;
+mod retry_strategy;
+mod stage_id;
 mod step;
+mod step_command;
 mod step_id;
+mod step_kind;
+mod time_span;
 mod tool;
 mod tool_id;
 mod tools;
+mod working_directory_mode;
 
 #[expect(clippy::module_inception, reason = "I like it this way")]
 mod config;
 
-pub use config::Config;
+pub use audit_policy::AuditPolicy;
+pub use byte_size::ByteSize;
+pub use config::{Config, parse_config};
+pub use consumes::Consumes;
+pub use effective_config::EffectiveConfig;
+pub use fixable::Fixable;
+pub use freshness_rule::FreshnessRule;
+pub use hints::Hints;
 pub use job::Job;
 pub use job_id::JobId;
 pub use jobs::Jobs;
-pub use step::Step;
+pub use named_run::NamedRun;
+pub use notifications::Notifications;
+pub use os::Os;
+pub use output_encoding::OutputEncoding;
+pub use output_filters::OutputFilters;
+pub use overlay::Overlay;
+pub use rate_limit::RateLimit;
+pub use retry_strategy::RetryStrategy;
+pub use stage_id::StageId;
+pub use step::{LOCAL_EXECUTOR, Step, shell_words};
+pub use step_command::StepCommand;
 pub use step_id::StepId;
+pub use step_kind::StepKind;
+pub use time_span::TimeSpan;
 pub use tool::Tool;
 pub use tool_id::ToolId;
 pub use tools::Tools;
+pub use working_directory_mode::WorkingDirectoryMode;
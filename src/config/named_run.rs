@@ -0,0 +1,61 @@
+use crate::config::JobId;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// A named combination of options for the `run` subcommand, selected with `--run <NAME>`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NamedRun {
+    #[serde(default)]
+    jobs: Vec<JobId>,
+
+    #[serde(default)]
+    packages: Vec<String>,
+
+    #[serde(default)]
+    variables: BTreeMap<String, String>,
+
+    #[serde(default)]
+    allow_destructive: bool,
+
+    #[serde(default)]
+    check_feature_unification: bool,
+
+    #[serde(default)]
+    explain_conditions: bool,
+}
+
+impl NamedRun {
+    /// The jobs to run, instead of the default jobs.
+    #[must_use]
+    pub fn jobs(&self) -> &[JobId] {
+        &self.jobs
+    }
+
+    /// The package specs to run jobs on, instead of every package.
+    #[must_use]
+    pub fn packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    /// Variables defined as with `--variable`, so their names may be scoped to a job or step.
+    #[must_use]
+    pub const fn variables(&self) -> &BTreeMap<String, String> {
+        &self.variables
+    }
+
+    #[must_use]
+    pub const fn allow_destructive(&self) -> bool {
+        self.allow_destructive
+    }
+
+    #[must_use]
+    pub const fn check_feature_unification(&self) -> bool {
+        self.check_feature_unification
+    }
+
+    #[must_use]
+    pub const fn explain_conditions(&self) -> bool {
+        self.explain_conditions
+    }
+}
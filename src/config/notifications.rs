@@ -0,0 +1,71 @@
+use crate::config::TimeSpan;
+use core::time::Duration;
+use serde::Deserialize;
+
+/// How runs let users who switched away from their terminal know how they're going, as set in the `[notifications]`
+/// table. Nothing is shown unless the output is a terminal.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Notifications {
+    #[serde(default)]
+    title: bool,
+
+    #[serde(default)]
+    bell: NotifyOn,
+
+    #[serde(default)]
+    desktop: NotifyOn,
+
+    min_duration: Option<TimeSpan>,
+}
+
+/// Which runs a notification is given for once they complete.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyOn {
+    #[default]
+    Never,
+
+    /// Runs in which a job failed.
+    Failure,
+
+    Always,
+}
+
+impl NotifyOn {
+    /// Whether a run which succeeded or failed gets the notification.
+    #[must_use]
+    pub const fn applies(self, succeeded: bool) -> bool {
+        match self {
+            Self::Never => false,
+            Self::Failure => !succeeded,
+            Self::Always => true,
+        }
+    }
+}
+
+impl Notifications {
+    /// Whether the terminal's title shows the progress of the run while its jobs run.
+    #[must_use]
+    pub const fn title(&self) -> bool {
+        self.title
+    }
+
+    /// Which runs ring the terminal's bell once they complete.
+    #[must_use]
+    pub const fn bell(&self) -> NotifyOn {
+        self.bell
+    }
+
+    /// Which runs send a desktop notification through the terminal once they complete.
+    #[must_use]
+    pub const fn desktop(&self) -> NotifyOn {
+        self.desktop
+    }
+
+    /// Whether a run which took this long is long enough for the bell and desktop notifications.
+    #[must_use]
+    pub fn long_enough(&self, elapsed: Duration) -> bool {
+        self.min_duration.is_none_or(|min| elapsed >= min.duration())
+    }
+}
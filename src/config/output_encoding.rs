@@ -0,0 +1,66 @@
+use anyhow::anyhow;
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+use serde::{Deserialize, Deserializer, de};
+use std::borrow::Cow;
+
+/// How to decode the output of a step's command, written as an encoding label such as `"cp1252"` or `"shift_jis"`.
+///
+/// The special value `"auto"` guesses the encoding of any output which isn't valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputEncoding {
+    /// UTF-8, with invalid sequences replaced.
+    #[default]
+    Utf8,
+
+    /// UTF-8 when valid, otherwise whatever encoding the output looks like.
+    Auto,
+
+    /// A specific encoding.
+    Fixed(&'static Encoding),
+}
+
+impl OutputEncoding {
+    /// Decodes captured output, replacing anything which can't be decoded.
+    #[must_use]
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(bytes),
+            Self::Auto => {
+                if let Ok(text) = core::str::from_utf8(bytes) {
+                    return Cow::Borrowed(text);
+                }
+
+                let mut detector = EncodingDetector::new();
+                _ = detector.feed(bytes, true);
+                detector.guess(None, true).decode_without_bom_handling(bytes).0
+            }
+            Self::Fixed(encoding) => encoding.decode_without_bom_handling(bytes).0,
+        }
+    }
+}
+
+impl core::str::FromStr for OutputEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
+        }
+
+        match Encoding::for_label(s.as_bytes()) {
+            Some(encoding) if encoding == encoding_rs::UTF_8 => Ok(Self::Utf8),
+            Some(encoding) => Ok(Self::Fixed(encoding)),
+            None => Err(anyhow!("unknown output encoding '{s}'")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OutputEncoding {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
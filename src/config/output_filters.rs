@@ -0,0 +1,55 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+/// Patterns matching the lines of a step's output which aren't worth showing in the terminal, written as a list of
+/// regular expressions such as `["^warning: unused", "progress"]`.
+///
+/// Lines which start with `error` are always shown, whatever the patterns say.
+#[derive(Debug, Clone, Default)]
+pub struct OutputFilters {
+    patterns: Vec<Regex>,
+
+    /// Why a pattern couldn't be compiled, kept for the validation of the job holding the step, since errors raised
+    /// while deserializing a step get lost among the forms a step can take.
+    error: Option<String>,
+}
+
+impl OutputFilters {
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Why one of the patterns is invalid, if any is.
+    #[must_use]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Whether a line of output should be kept from the terminal.
+    #[must_use]
+    pub fn hides(&self, line: &str) -> bool {
+        !line.trim_start().starts_with("error") && self.patterns.iter().any(|pattern| pattern.is_match(line))
+    }
+}
+
+impl<'de> Deserialize<'de> for OutputFilters {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut filters = Self::default();
+        for pattern in Vec::<String>::deserialize(deserializer)? {
+            match Regex::new(&pattern) {
+                Ok(regex) => filters.patterns.push(regex),
+                Err(e) => {
+                    _ = filters
+                        .error
+                        .get_or_insert_with(|| format!("invalid output filter '{pattern}': {e}"));
+                }
+            }
+        }
+
+        Ok(filters)
+    }
+}
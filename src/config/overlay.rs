@@ -0,0 +1,45 @@
+use crate::config::{JobId, TimeSpan, ToolId};
+use cargo_metadata::semver::Version;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Changes applied over the base configuration when an overlay is active.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Overlay {
+    #[serde(default)]
+    variables: HashMap<String, String>,
+
+    default_jobs: Option<HashSet<JobId>>,
+
+    #[serde(default)]
+    tools: HashMap<ToolId, Version>,
+
+    max_run_duration: Option<TimeSpan>,
+}
+
+impl Overlay {
+    /// Variables added to or replacing those of the base configuration.
+    #[must_use]
+    pub const fn variables(&self) -> &HashMap<String, String> {
+        &self.variables
+    }
+
+    /// Jobs replacing the base configuration's default jobs.
+    #[must_use]
+    pub const fn default_jobs(&self) -> Option<&HashSet<JobId>> {
+        self.default_jobs.as_ref()
+    }
+
+    /// Versions replacing those of the base configuration's tools.
+    #[must_use]
+    pub const fn tools(&self) -> &HashMap<ToolId, Version> {
+        &self.tools
+    }
+
+    /// A time budget replacing that of the base configuration.
+    #[must_use]
+    pub const fn max_run_duration(&self) -> Option<TimeSpan> {
+        self.max_run_duration
+    }
+}
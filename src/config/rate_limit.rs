@@ -0,0 +1,77 @@
+use anyhow::{Context, anyhow};
+use core::time::Duration;
+use serde::{Deserialize, Deserializer, de};
+
+/// A maximum number of operations allowed over a period of time, written as `"<count>/<period>"`.
+///
+/// The period is one of `s`, `sec`, `m`, `min`, `h`, or `hour`, optionally preceded by a multiplier, as in `"10/5s"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    count: u32,
+    period: Duration,
+}
+
+impl RateLimit {
+    #[must_use]
+    pub const fn count(&self) -> u32 {
+        self.count
+    }
+
+    #[must_use]
+    pub const fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+impl core::str::FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (count, period) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("invalid rate limit '{s}', expected '<count>/<period>' such as '10/min'"))?;
+
+        let count: u32 = count.trim().parse().with_context(|| format!("invalid count in rate limit '{s}'"))?;
+        if count == 0 {
+            return Err(anyhow!("rate limit '{s}' must allow at least one operation"));
+        }
+
+        let period = period.trim();
+        let unit_start = period.find(|c: char| !c.is_ascii_digit()).unwrap_or(period.len());
+        let (multiplier, unit) = period.split_at(unit_start);
+        let multiplier: u64 = if multiplier.is_empty() {
+            1
+        } else {
+            multiplier.parse().with_context(|| format!("invalid period in rate limit '{s}'"))?
+        };
+
+        let unit_secs = match unit {
+            "s" | "sec" => 1,
+            "m" | "min" => 60,
+            "h" | "hour" => 3600,
+            _ => {
+                return Err(anyhow!(
+                    "invalid period unit '{unit}' in rate limit '{s}', expected one of s, min, or hour"
+                ));
+            }
+        };
+
+        if multiplier == 0 {
+            return Err(anyhow!("rate limit '{s}' must have a non-zero period"));
+        }
+
+        Ok(Self {
+            count,
+            period: Duration::from_secs(multiplier * unit_secs),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for RateLimit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+/// What to do before retrying a step which failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetryStrategy {
+    /// Retries the step as is.
+    Plain,
+
+    /// Removes the incremental compilation data from the target directory before retrying the step.
+    CleanIncremental,
+
+    /// Removes the build artifacts of the step's packages with `cargo clean` before retrying the step.
+    CleanPackage,
+}
+
+impl RetryStrategy {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::CleanIncremental => "clean-incremental",
+            Self::CleanPackage => "clean-package",
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use core::fmt::Display;
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub struct StageId(String);
+
+impl StageId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for StageId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(Self(String::deserialize(deserializer)?))
+    }
+}
+
+impl Display for StageId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for StageId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
@@ -1,13 +1,33 @@
-use crate::config::StepId;
+use crate::config::{
+    ByteSize, Fixable, FreshnessRule, Os, OutputEncoding, OutputFilters, RetryStrategy, StepCommand, StepId, StepKind, TimeSpan,
+    WorkingDirectoryMode,
+};
 use crate::expressions::{Conditional, ContinueOnError};
 use core::fmt;
 use serde::Deserialize;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 
+const NETWORK_HEAVY_SUBCOMMANDS: &[&str] = &["update", "install", "publish"];
+
+/// The command run by `semver-check` steps which don't set one.
+const SEMVER_CHECKS_COMMAND: &str = "cargo semver-checks check-release";
+
+/// The command run by `audit` steps which don't set one, whose report must be written as JSON.
+const AUDIT_COMMAND: &str = "cargo audit --json";
+
+/// The command run by `update-check` steps which don't set one, which reports what it would change in `Cargo.lock`.
+const UPDATE_CHECK_COMMAND: &str = "cargo update --dry-run";
+
+/// The executor which runs steps on this machine through the platform's shell, used by steps which don't name another.
+pub const LOCAL_EXECUTOR: &str = "local";
+
 static EMPTY_VARIABLES: LazyLock<HashMap<String, String>> = LazyLock::new(HashMap::new);
+static NO_FILTERS: LazyLock<OutputFilters> = LazyLock::new(OutputFilters::default);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
 #[serde(deny_unknown_fields)]
 #[expect(clippy::large_enum_variant, reason = "Size doesn't matter, this is for the UX")]
@@ -15,7 +35,8 @@ pub enum Step {
     Simple(String),
 
     Extended {
-        command: String,
+        command: Option<StepCommand>,
+        cargo_alias: Option<String>,
         name: Option<String>,
         id: Option<StepId>,
 
@@ -28,17 +49,154 @@ pub enum Step {
         #[serde(default)]
         per_package: bool,
 
+        shards: Option<usize>,
+
+        batch_packages: Option<usize>,
+
+        parallel_packages: Option<usize>,
+
+        expected_duration: Option<TimeSpan>,
+
+        #[serde(default)]
+        kind: StepKind,
+
+        generated: Option<PathBuf>,
+
+        baseline: Option<String>,
+
+        fixable: Option<Fixable>,
+
+        #[serde(default)]
+        requires_fresh: Vec<FreshnessRule>,
+
+        regenerate: Option<String>,
+
+        lock: Option<String>,
+
+        memory_hint: Option<ByteSize>,
+
+        #[serde(default)]
+        destructive: bool,
+
+        #[serde(default)]
+        reuse_result: bool,
+
+        confirm: Option<String>,
+
+        #[serde(default)]
+        output_encoding: OutputEncoding,
+
+        #[serde(default)]
+        output_filters: OutputFilters,
+
+        #[serde(default)]
+        os: Vec<Os>,
+
+        #[serde(default)]
+        path: Vec<PathBuf>,
+
+        #[serde(default)]
+        retry_strategies: Vec<RetryStrategy>,
+
         #[serde(default)]
         variables: HashMap<String, String>,
+
+        inherit_env: Option<bool>,
+
+        working_directory: Option<String>,
+
+        working_directory_mode: Option<WorkingDirectoryMode>,
+
+        #[serde(default)]
+        create_working_directory: bool,
+
+        executor: Option<String>,
+
+        #[serde(default)]
+        sarif: bool,
+
+        #[serde(default)]
+        pty: bool,
+
+        #[serde(default)]
+        ulimit_core: bool,
+
+        network: Option<bool>,
+
+        #[serde(default)]
+        locked: bool,
     },
 }
 
 impl Step {
+    /// The command line this step runs, with cargo alias steps expanded to a `cargo` invocation.
     #[must_use]
-    pub fn command(&self) -> &str {
+    pub fn command(&self) -> Cow<'_, str> {
         match self {
-            Self::Simple(cmd) => cmd,
-            Self::Extended { command: run, .. } => run,
+            Self::Simple(cmd) => Cow::Borrowed(cmd),
+            Self::Extended { command: Some(run), .. } => Cow::Borrowed(run.primary()),
+            Self::Extended {
+                cargo_alias: None,
+                kind: StepKind::SemverCheck,
+                baseline,
+                ..
+            } => baseline.as_ref().map_or(Cow::Borrowed(SEMVER_CHECKS_COMMAND), |baseline| {
+                Cow::Owned(format!("{SEMVER_CHECKS_COMMAND} --baseline-rev {baseline}"))
+            }),
+            Self::Extended {
+                cargo_alias: None,
+                kind: StepKind::Audit,
+                ..
+            } => Cow::Borrowed(AUDIT_COMMAND),
+            Self::Extended {
+                cargo_alias: None,
+                kind: StepKind::UpdateCheck,
+                ..
+            } => Cow::Borrowed(UPDATE_CHECK_COMMAND),
+            Self::Extended { cargo_alias, .. } => Cow::Owned(format!("cargo {}", cargo_alias.as_deref().unwrap_or_default())),
+        }
+    }
+
+    /// The command lines tried in order when the step's command fails, such as `cargo test` when `cargo nextest run`
+    /// isn't installed.
+    #[must_use]
+    pub fn fallbacks(&self) -> &[String] {
+        match self {
+            Self::Extended {
+                command: Some(command), ..
+            } => command.fallbacks(),
+            _ => &[],
+        }
+    }
+
+    /// Whether the step's command is an empty list of command lines.
+    #[must_use]
+    pub const fn has_empty_command(&self) -> bool {
+        matches!(self, Self::Extended { command: Some(command), .. } if command.is_empty())
+    }
+
+    /// The cargo alias, along with its arguments, run by this step.
+    #[must_use]
+    pub fn cargo_alias(&self) -> Option<&str> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { cargo_alias, .. } => cargo_alias.as_deref(),
+        }
+    }
+
+    /// Whether the step specifies exactly one of `command` and `cargo_alias`, which `semver-check`, `audit`, and
+    /// `update-check` steps may leave out.
+    #[must_use]
+    pub const fn has_single_command(&self) -> bool {
+        match self {
+            Self::Simple(_)
+            | Self::Extended {
+                command: None,
+                cargo_alias: None,
+                kind: StepKind::SemverCheck | StepKind::Audit | StepKind::UpdateCheck,
+                ..
+            } => true,
+            Self::Extended { command, cargo_alias, .. } => command.is_some() != cargo_alias.is_some(),
         }
     }
 
@@ -46,7 +204,21 @@ impl Step {
     pub fn name(&self) -> &str {
         match self {
             Self::Simple(cmd) => cmd,
-            Self::Extended { command: run, name, .. } => name.as_deref().unwrap_or(run),
+            Self::Extended {
+                command,
+                cargo_alias,
+                name,
+                kind,
+                ..
+            } => name
+                .as_deref()
+                .or_else(|| command.as_ref().map(StepCommand::primary))
+                .or(cargo_alias.as_deref())
+                .unwrap_or(match kind {
+                    StepKind::Audit => AUDIT_COMMAND,
+                    StepKind::UpdateCheck => UPDATE_CHECK_COMMAND,
+                    _ => SEMVER_CHECKS_COMMAND,
+                }),
         }
     }
 
@@ -78,7 +250,355 @@ impl Step {
     pub const fn per_package(&self) -> bool {
         match self {
             Self::Simple(_) => false,
-            Self::Extended { per_package, .. } => *per_package,
+            Self::Extended { per_package, kind, .. } => *per_package || matches!(kind, StepKind::SemverCheck),
+        }
+    }
+
+    /// Whether the step runs a cargo command known to make lots of requests to package registries.
+    #[must_use]
+    pub fn is_network_heavy(&self) -> bool {
+        self.cargo_subcommand()
+            .is_some_and(|subcommand| NETWORK_HEAVY_SUBCOMMANDS.contains(&subcommand.as_str()))
+    }
+
+    /// The cargo subcommand this step runs, if its command is a `cargo` invocation.
+    #[must_use]
+    pub fn cargo_subcommand(&self) -> Option<String> {
+        let mut words = shell_words(&self.command()).into_iter();
+        let is_cargo = words.next().is_some_and(|program| {
+            program == "cargo" || program == "cargo.exe" || program.ends_with("/cargo") || program.ends_with("\\cargo.exe")
+        });
+
+        if !is_cargo {
+            return None;
+        }
+
+        // skip toolchain overrides and global options to find the cargo subcommand
+        words.find(|word| !word.starts_with('+') && !word.starts_with('-'))
+    }
+
+    /// The arguments given to cargo by a `cargo_alias` step, starting with the alias itself.
+    #[must_use]
+    pub fn cargo_alias_args(&self) -> Option<Vec<String>> {
+        self.cargo_alias().map(shell_words)
+    }
+
+    /// The number of shards this step's packages are split into, if any.
+    #[must_use]
+    pub const fn shards(&self) -> Option<usize> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { shards, .. } => *shards,
+        }
+    }
+
+    #[must_use]
+    pub const fn kind(&self) -> StepKind {
+        match self {
+            Self::Simple(_) => StepKind::Command,
+            Self::Extended { kind, .. } => *kind,
+        }
+    }
+
+    /// The directory holding the committed output of a `codegen-check` step's generator.
+    #[must_use]
+    pub fn generated(&self) -> Option<&Path> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { generated, .. } => generated.as_deref(),
+        }
+    }
+
+    /// The name of the executor running this step, from the `[executors]` table, or `None` to run it locally.
+    #[must_use]
+    pub fn executor(&self) -> Option<&str> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { executor, .. } => executor.as_deref().filter(|name| *name != LOCAL_EXECUTOR),
+        }
+    }
+
+    /// Whether the step's diagnostics are collected into the run's SARIF file.
+    #[must_use]
+    pub const fn sarif(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended { sarif, .. } => *sarif,
+        }
+    }
+
+    /// Whether an `update-check` step fails when `Cargo.lock` is out of sync with the manifests, as cargo's `--locked`
+    /// option would.
+    #[must_use]
+    pub const fn locked(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended { locked, .. } => *locked,
+        }
+    }
+
+    /// How many packages a `per_package` cargo step runs for with a single command, passing each with `-p`, rather
+    /// than running once for every package.
+    #[must_use]
+    pub const fn batch_packages(&self) -> Option<usize> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { batch_packages, .. } => *batch_packages,
+        }
+    }
+
+    /// How many packages a `per_package` step runs for at once, where `None` means one at a time.
+    #[must_use]
+    pub const fn parallel_packages(&self) -> Option<usize> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { parallel_packages, .. } => *parallel_packages,
+        }
+    }
+
+    /// How long the step usually takes, so steps which take much longer are flagged.
+    #[must_use]
+    pub const fn expected_duration(&self) -> Option<TimeSpan> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { expected_duration, .. } => *expected_duration,
+        }
+    }
+
+    /// The git revision a `semver-check` step compares the packages against, instead of their latest published version.
+    #[must_use]
+    pub fn baseline(&self) -> Option<&str> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { baseline, .. } => baseline.as_deref(),
+        }
+    }
+
+    /// How to fix the problems this step reports, used by `cargo ci fix`.
+    #[must_use]
+    pub const fn fixable(&self) -> Option<&Fixable> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { fixable, .. } => fixable.as_ref(),
+        }
+    }
+
+    /// Mappings from source files to the files generated from them, which must be up to date before the step runs.
+    #[must_use]
+    pub fn requires_fresh(&self) -> &[FreshnessRule] {
+        match self {
+            Self::Simple(_) => &[],
+            Self::Extended { requires_fresh, .. } => requires_fresh,
+        }
+    }
+
+    /// The command which brings stale generated files up to date, run from the workspace root.
+    #[must_use]
+    pub fn regenerate(&self) -> Option<&str> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { regenerate, .. } => regenerate.as_deref(),
+        }
+    }
+
+    /// The key of the lock held while this step runs, so steps sharing a key never run concurrently.
+    #[must_use]
+    pub fn lock(&self) -> Option<&str> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { lock, .. } => lock.as_deref(),
+        }
+    }
+
+    /// How much memory the step is expected to use at most, which the parallel scheduler reserves while it runs.
+    #[must_use]
+    pub const fn memory_hint(&self) -> Option<ByteSize> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { memory_hint, .. } => *memory_hint,
+        }
+    }
+
+    /// Whether the step has effects beyond the workspace, such as deploying or resetting a database.
+    #[must_use]
+    pub const fn destructive(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended { destructive, .. } => *destructive,
+        }
+    }
+
+    /// Whether jobs running the very same command as this step reuse how it turned out in another job of the run.
+    #[must_use]
+    pub const fn reuse_result(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended { reuse_result, .. } => *reuse_result,
+        }
+    }
+
+    /// The question to confirm before the step runs, such as "About to publish to crates.io, continue?".
+    #[must_use]
+    pub fn confirm(&self) -> Option<&str> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { confirm, .. } => confirm.as_deref(),
+        }
+    }
+
+    /// How to decode the output of this step's command.
+    #[must_use]
+    pub const fn output_encoding(&self) -> OutputEncoding {
+        match self {
+            Self::Simple(_) => OutputEncoding::Utf8,
+            Self::Extended { output_encoding, .. } => *output_encoding,
+        }
+    }
+
+    /// Patterns matching the lines of the step's output which are left out of the terminal, though not the log.
+    #[must_use]
+    pub fn output_filters(&self) -> &OutputFilters {
+        match self {
+            Self::Simple(_) => &NO_FILTERS,
+            Self::Extended { output_filters, .. } => output_filters,
+        }
+    }
+
+    /// The operating systems the step runs on, where an empty list means any operating system.
+    #[must_use]
+    pub fn os(&self) -> &[Os] {
+        match self {
+            Self::Simple(_) => &[],
+            Self::Extended { os, .. } => os,
+        }
+    }
+
+    /// Directories added to the front of `PATH` for the step, relative to the workspace root.
+    #[must_use]
+    pub fn path(&self) -> &[PathBuf] {
+        match self {
+            Self::Simple(_) => &[],
+            Self::Extended { path, .. } => path,
+        }
+    }
+
+    /// How to retry the step when it fails, one retry per entry, in order.
+    #[must_use]
+    pub fn retry_strategies(&self) -> &[RetryStrategy] {
+        match self {
+            Self::Simple(_) => &[],
+            Self::Extended { retry_strategies, .. } => retry_strategies,
+        }
+    }
+
+    /// Whether the step's command runs under a pseudo-terminal, for tools which only show colors or progress when
+    /// writing to a terminal.
+    #[must_use]
+    pub const fn pty(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended { pty, .. } => *pty,
+        }
+    }
+
+    /// Whether the processes the step's command starts write a core dump when they crash, when running with
+    /// `--debug-failures`.
+    #[must_use]
+    pub const fn ulimit_core(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended { ulimit_core, .. } => *ulimit_core,
+        }
+    }
+
+    /// Whether the step's command may reach the network. Steps which may not run in a network namespace of their own,
+    /// where the system allows it.
+    #[must_use]
+    pub fn network(&self) -> bool {
+        match self {
+            Self::Simple(_) => true,
+            Self::Extended { network, .. } => network.unwrap_or(true),
+        }
+    }
+
+    /// Whether the step's command inherits the environment `cargo-ci` runs in, rather than getting only the variables
+    /// declared for it along with `PATH`.
+    #[must_use]
+    pub fn inherits_env(&self) -> bool {
+        match self {
+            Self::Simple(_) => true,
+            Self::Extended { inherit_env, .. } => inherit_env.unwrap_or(true),
+        }
+    }
+
+    /// The directory the step runs in, relative to the package's directory or the workspace root, which may refer to
+    /// variables as `${NAME}`.
+    #[must_use]
+    pub fn working_directory(&self) -> Option<&str> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { working_directory, .. } => working_directory.as_deref(),
+        }
+    }
+
+    /// What to do about the step's working directory before the step runs, where `create_working_directory = true`
+    /// stands for `create-if-missing`.
+    #[must_use]
+    pub fn working_directory_mode(&self) -> WorkingDirectoryMode {
+        match self {
+            Self::Simple(_) => WorkingDirectoryMode::MustExist,
+            Self::Extended {
+                working_directory_mode,
+                create_working_directory,
+                ..
+            } => working_directory_mode.unwrap_or(if *create_working_directory {
+                WorkingDirectoryMode::CreateIfMissing
+            } else {
+                WorkingDirectoryMode::MustExist
+            }),
+        }
+    }
+
+    /// Whether the step sets how to treat its working directory, through either of the fields which do.
+    #[must_use]
+    pub const fn sets_working_directory_mode(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended {
+                working_directory_mode,
+                create_working_directory,
+                ..
+            } => working_directory_mode.is_some() || *create_working_directory,
+        }
+    }
+
+    /// Whether `create_working_directory = true` contradicts the step's `working_directory_mode`.
+    #[must_use]
+    pub fn has_conflicting_working_directory_modes(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended {
+                working_directory_mode,
+                create_working_directory,
+                ..
+            } => *create_working_directory && working_directory_mode.is_some_and(|mode| mode != WorkingDirectoryMode::CreateIfMissing),
+        }
+    }
+
+    /// Replaces the references to a variable, written as `${NAME}`, with its value in the command line of the step.
+    pub fn substitute(&mut self, name: &str, value: &str) {
+        let reference = format!("${{{name}}}");
+        match self {
+            Self::Simple(command)
+            | Self::Extended {
+                command: None,
+                cargo_alias: Some(command),
+                ..
+            } => *command = command.replace(&reference, value),
+            Self::Extended {
+                command: Some(command), ..
+            } => command.replace(&reference, value),
+            Self::Extended { .. } => {}
         }
     }
 
@@ -108,3 +628,11 @@ impl From<&str> for Step {
         Self::Simple(s.to_string())
     }
 }
+
+/// Splits a command line into words using the quoting rules of POSIX shells, so quoted arguments can contain spaces.
+///
+/// A line with unbalanced quotes is split on whitespace instead.
+#[must_use]
+pub fn shell_words(line: &str) -> Vec<String> {
+    shlex::split(line).unwrap_or_else(|| line.split_whitespace().map(ToString::to_string).collect())
+}
@@ -0,0 +1,48 @@
+use serde::Deserialize;
+
+/// The command of a step: a single command line, or several which are tried in order until one succeeds, such as
+/// `["cargo nextest run", "cargo test"]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum StepCommand {
+    Single(String),
+    Fallbacks(Vec<String>),
+}
+
+impl StepCommand {
+    /// The command line tried first, which is empty when the list of command lines is.
+    #[must_use]
+    pub fn primary(&self) -> &str {
+        match self {
+            Self::Single(command) => command,
+            Self::Fallbacks(commands) => commands.first().map_or("", String::as_str),
+        }
+    }
+
+    /// The command lines tried in order when the primary one fails.
+    #[must_use]
+    pub fn fallbacks(&self) -> &[String] {
+        match self {
+            Self::Single(_) => &[],
+            Self::Fallbacks(commands) => commands.get(1..).unwrap_or_default(),
+        }
+    }
+
+    /// Whether no command line is given at all.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        matches!(self, Self::Fallbacks(commands) if commands.is_empty())
+    }
+
+    /// Replaces every occurrence of a text in all the command lines.
+    pub fn replace(&mut self, from: &str, to: &str) {
+        match self {
+            Self::Single(command) => *command = command.replace(from, to),
+            Self::Fallbacks(commands) => {
+                for command in commands {
+                    *command = command.replace(from, to);
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use core::fmt::Display;
 use serde::Deserialize;
 
-#[derive(Debug, Default, Hash, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq, PartialOrd, Ord)]
 pub struct StepId(String);
 
 impl StepId {
@@ -0,0 +1,24 @@
+use serde::Deserialize;
+
+/// What a step does with its command.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StepKind {
+    /// Runs the command.
+    #[default]
+    Command,
+
+    /// Runs a code generator into a scratch directory, then compares its output against the committed generated files.
+    CodegenCheck,
+
+    /// Checks every package for semver violations with cargo-semver-checks, installing it when it's missing.
+    SemverCheck,
+
+    /// Audits the workspace's dependencies with cargo-audit and against the allowed licenses, installing cargo-audit when
+    /// it's missing.
+    Audit,
+
+    /// Reports which dependencies `cargo update` would change in `Cargo.lock`, optionally failing when the lockfile is out
+    /// of sync with the manifests.
+    UpdateCheck,
+}
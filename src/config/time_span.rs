@@ -0,0 +1,84 @@
+use anyhow::{Context, anyhow};
+use core::fmt;
+use core::time::Duration;
+use serde::{Deserialize, Deserializer, de};
+
+/// A length of time, written as one or more `"<number><unit>"` parts, such as `"45m"`, `"90s"`, or `"1h30m"`.
+///
+/// The unit is one of `s`, `m`, or `h`, for seconds, minutes, and hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeSpan(Duration);
+
+const UNITS: [(char, u64); 3] = [('s', 1), ('m', 60), ('h', 60 * 60)];
+
+impl TimeSpan {
+    #[must_use]
+    pub const fn duration(self) -> Duration {
+        self.0
+    }
+}
+
+impl core::str::FromStr for TimeSpan {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s.trim();
+        if rest.is_empty() {
+            return Err(anyhow!("invalid duration '{s}', expected a number followed by a unit such as 'm'"));
+        }
+
+        let mut secs = 0_u64;
+        while !rest.is_empty() {
+            let unit_start = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let (number, after) = rest.split_at(unit_start);
+            let number: u64 = number
+                .parse()
+                .with_context(|| format!("invalid duration '{s}', expected a number followed by a unit such as 'm'"))?;
+
+            let mut chars = after.chars();
+            let unit = chars.next().map(|c| c.to_ascii_lowercase());
+            let (_, multiplier) = UNITS
+                .iter()
+                .find(|(name, _)| Some(*name) == unit)
+                .ok_or_else(|| anyhow!("invalid unit in duration '{s}', expected one of s, m, or h"))?;
+
+            secs = number
+                .checked_mul(*multiplier)
+                .and_then(|part| secs.checked_add(part))
+                .ok_or_else(|| anyhow!("duration '{s}' is too long"))?;
+            rest = chars.as_str().trim_start();
+        }
+
+        Ok(Self(Duration::from_secs(secs)))
+    }
+}
+
+impl fmt::Display for TimeSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+        if hours > 0 {
+            write!(f, "{hours}h")?;
+        }
+
+        if minutes > 0 {
+            write!(f, "{minutes}m")?;
+        }
+
+        if seconds > 0 || secs == 0 {
+            write!(f, "{seconds}s")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeSpan {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
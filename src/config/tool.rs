@@ -4,6 +4,7 @@ use serde::Deserialize;
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 #[serde(untagged)]
 #[serde(deny_unknown_fields)]
+#[expect(clippy::large_enum_variant, reason = "Size doesn't matter, this is for the UX")]
 pub enum Tool {
     Simple(Version),
 
@@ -17,6 +18,17 @@ pub enum Tool {
         rev: Option<String>,
         path: Option<String>,
         root: Option<String>,
+
+        #[serde(default)]
+        features: Vec<String>,
+
+        #[serde(default)]
+        no_default_features: bool,
+
+        #[serde(default)]
+        locked: bool,
+
+        profile: Option<String>,
     },
 }
 
@@ -29,6 +41,13 @@ impl Tool {
         }
     }
 
+    pub fn set_version(&mut self, new_version: Version) {
+        match self {
+            Self::Simple(ver) => *ver = new_version,
+            Self::Extended { version, .. } => *version = new_version,
+        }
+    }
+
     #[must_use]
     pub const fn index(&self) -> Option<&String> {
         match self {
@@ -92,4 +111,36 @@ impl Tool {
             Self::Extended { root, .. } => root.as_ref(),
         }
     }
+
+    #[must_use]
+    pub fn features(&self) -> &[String] {
+        match self {
+            Self::Simple(_) => &[],
+            Self::Extended { features, .. } => features,
+        }
+    }
+
+    #[must_use]
+    pub const fn no_default_features(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended { no_default_features, .. } => *no_default_features,
+        }
+    }
+
+    #[must_use]
+    pub const fn locked(&self) -> bool {
+        match self {
+            Self::Simple(_) => false,
+            Self::Extended { locked, .. } => *locked,
+        }
+    }
+
+    #[must_use]
+    pub const fn profile(&self) -> Option<&String> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Extended { profile, .. } => profile.as_ref(),
+        }
+    }
 }
@@ -5,6 +5,12 @@ use serde::Deserialize;
 #[serde(transparent)]
 pub struct ToolId(String);
 
+impl From<&str> for ToolId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
 impl Display for ToolId {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.0)
@@ -10,4 +10,13 @@ impl Tools {
     pub fn iter(&self) -> impl Iterator<Item = (&ToolId, &Tool)> {
         self.0.iter()
     }
+
+    #[must_use]
+    pub fn get(&self, tool_id: &ToolId) -> Option<&Tool> {
+        self.0.get(tool_id)
+    }
+
+    pub fn get_mut(&mut self, tool_id: &ToolId) -> Option<&mut Tool> {
+        self.0.get_mut(tool_id)
+    }
 }
@@ -0,0 +1,16 @@
+use serde::Deserialize;
+
+/// What to do about a step's `working_directory` before the step runs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkingDirectoryMode {
+    /// The directory must already exist, relative to where the step would otherwise run.
+    #[default]
+    MustExist,
+
+    /// The directory is created when it doesn't exist, relative to where the step would otherwise run.
+    CreateIfMissing,
+
+    /// The directory is created empty within the run's temporary directory.
+    Temp,
+}
@@ -0,0 +1,133 @@
+use crate::host::Host;
+use core::time::Duration;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::SystemTime;
+
+/// The variables given to steps with `--debug-failures`, unless they're already set for them, so Rust programs show a
+/// backtrace when they panic and log what they're doing.
+pub const VARIABLES: [(&str, &str); 2] = [("RUST_BACKTRACE", "full"), ("RUST_LOG", "debug")];
+
+/// How much earlier than a step started the files it writes may appear to have been modified.
+const TIMESTAMP_SLACK: Duration = Duration::from_secs(1);
+
+/// Rebuilds a command so the processes it starts write a core dump when they crash, by lifting the limit on the size of
+/// core dumps through the shell before running it.
+///
+/// On Windows, where crash dumps are configured system-wide through Windows Error Reporting, the command is unchanged.
+/// Since the command is rebuilt, this must be called before anything else is set on it.
+#[cfg(unix)]
+pub fn enable_core_dumps(cmd: &Command) -> Command {
+    let mut enabled = Command::new("sh");
+    _ = enabled
+        .arg("-c")
+        .arg(r#"ulimit -c unlimited 2>/dev/null; exec "$0" "$@""#)
+        .arg(cmd.get_program())
+        .args(cmd.get_args());
+    enabled
+}
+
+/// Rebuilds a command so the processes it starts write a core dump when they crash, by lifting the limit on the size of
+/// core dumps through the shell before running it.
+///
+/// On Windows, where crash dumps are configured system-wide through Windows Error Reporting, the command is unchanged.
+/// Since the command is rebuilt, this must be called before anything else is set on it.
+#[cfg(windows)]
+pub fn enable_core_dumps(cmd: &Command) -> Command {
+    let mut enabled = Command::new(cmd.get_program());
+    _ = enabled.args(cmd.get_args());
+    enabled
+}
+
+/// Whether a Rust program printed a backtrace in the output of a step.
+pub fn printed_backtrace(output: &Output) -> bool {
+    let needle = b"stack backtrace:";
+    [&output.stdout, &output.stderr]
+        .iter()
+        .any(|bytes| bytes.windows(needle.len()).any(|window| window == needle))
+}
+
+/// Describes where the core dumps of the processes a step started while it ran, since `since`, can be found.
+///
+/// Core dumps named after a relative pattern are written to the working directory of the process which crashed, so
+/// they're looked for in the given directories, such as the step's working directory and the directories of its
+/// packages, where `cargo test` runs tests. Returns `None` when no core dump was found there, or when the platform
+/// doesn't write core dumps.
+pub fn core_dump_location(host: &impl Host, dirs: &[&Path], since: SystemTime) -> Option<String> {
+    let pattern = core_pattern(host)?;
+    if let Some(handler) = pattern.strip_prefix('|') {
+        let handler = handler.split_whitespace().next().unwrap_or_default();
+        return Some(if handler.contains("systemd-coredump") {
+            "core dumps are kept by systemd-coredump, run `coredumpctl list` to find them".to_string()
+        } else if handler.contains("apport") {
+            "core dumps are kept by apport, in /var/crash".to_string()
+        } else {
+            format!("core dumps are handed to {handler}")
+        });
+    }
+
+    // the name of a core dump starts and ends with what comes before the first and after the last specifier of the
+    // pattern, such as '%e' for the name of the program
+    let pattern = Path::new(&pattern);
+    let name = pattern.file_name()?.to_string_lossy();
+    let prefix = name.split('%').next().unwrap_or_default().to_string();
+    let suffix = name
+        .rsplit_once('%')
+        .map_or("", |(_, last)| last.get(1..).unwrap_or_default())
+        .to_string();
+    if prefix.is_empty() && suffix.is_empty() {
+        return None;
+    }
+
+    let pattern_dir = pattern.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let dirs: Vec<PathBuf> = match pattern_dir {
+        Some(dir) if dir.is_absolute() => vec![dir.to_path_buf()],
+        Some(dir) => dirs.iter().map(|base| base.join(dir)).collect(),
+        None => dirs.iter().map(|base| base.to_path_buf()).collect(),
+    };
+
+    // file systems record modification times with a coarser clock, which may lag behind
+    let since = since.checked_sub(TIMESTAMP_SLACK).unwrap_or(since);
+    let mut dumps: Vec<PathBuf> = dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.starts_with(&prefix) && name.ends_with(&suffix)
+        })
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified >= since && !entry.path().is_dir())
+        })
+        .map(|entry| entry.path())
+        .collect();
+
+    dumps.sort_unstable();
+    dumps.dedup();
+    if dumps.is_empty() {
+        return None;
+    }
+
+    let dumps: Vec<_> = dumps.iter().map(|dump| dump.display().to_string()).collect();
+    Some(format!("core dump(s) written to {}", dumps.join(", ")))
+}
+
+/// The pattern the kernel names core dumps after, or the program they're handed to when it starts with `|`.
+fn core_pattern(host: &impl Host) -> Option<String> {
+    if cfg!(target_os = "linux") {
+        host.read_to_string(Path::new("/proc/sys/kernel/core_pattern"))
+            .ok()
+            .map(|pattern| pattern.trim().to_string())
+    } else if cfg!(target_os = "macos") {
+        Some("/cores/core.%P".to_string())
+    } else if cfg!(unix) {
+        Some("%N.core".to_string())
+    } else {
+        None
+    }
+}
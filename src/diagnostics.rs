@@ -0,0 +1,190 @@
+use crate::exit_codes;
+use clap::ValueEnum;
+use core::error::Error;
+use core::fmt;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// How the error which ends the program is reported.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// As a message for people to read.
+    #[default]
+    Human,
+
+    /// As a line of JSON, with the message, its causes, the exit code, and the diagnostics of the error.
+    Json,
+}
+
+/// Where an error comes from: a place in the configuration, a step of a job, or the environment the run depends on.
+///
+/// Diagnostics are attached to errors rather than replacing them, so messages and causes read the same as ever, while
+/// `--error-format json` reports them in a form tools can act on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Diagnostic {
+    /// The configuration is invalid.
+    Config {
+        file: PathBuf,
+
+        /// The line and column of the offending text, starting at 1, when the format reports them.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        column: Option<usize>,
+
+        /// The path of the offending key, such as `jobs.test.steps[0].kind`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+    },
+
+    /// A step failed, or couldn't run.
+    Execution {
+        job: String,
+        step: String,
+
+        /// The package the step ran for, when it ran for one package at a time.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        package: Option<String>,
+    },
+
+    /// Something the run depends on besides the configuration, such as Cargo or a tool, is missing or misbehaved.
+    Environment,
+}
+
+impl Diagnostic {
+    /// Attaches the diagnostic to an error, leaving its message and causes as they are.
+    #[must_use]
+    pub fn attach(self, error: anyhow::Error) -> anyhow::Error {
+        anyhow::Error::new(Attached { diagnostic: self, error })
+    }
+
+    /// Attaches the diagnostics of an error to a new error derived from it, such as one repeating its message.
+    #[must_use]
+    pub fn carry(from: &anyhow::Error, to: anyhow::Error) -> anyhow::Error {
+        Self::all(from)
+            .into_iter()
+            .rev()
+            .fold(to, |to, diagnostic| diagnostic.clone().attach(to))
+    }
+
+    /// The diagnostics attached to an error and its causes, the outermost first.
+    #[must_use]
+    pub fn all(error: &anyhow::Error) -> Vec<&Self> {
+        let mut found = Vec::new();
+        collect(error, &mut found);
+        found
+    }
+}
+
+/// An error about the configuration file `file`, as located by the parser and by the path of the key being read, with
+/// the offending line quoted.
+pub fn config_error(
+    file: &Path,
+    text: &str,
+    key: Option<&serde_path_to_error::Path>,
+    message: &str,
+    position: Option<(usize, usize)>,
+) -> anyhow::Error {
+    // the path of a value at the root of the document is just '.'
+    let key = key.filter(|key| key.iter().next().is_some()).map(ToString::to_string);
+
+    let mut location = file.display().to_string();
+    if let Some((line, column)) = position {
+        location = format!("{location}:{line}:{column}");
+    }
+
+    let mut full = key.as_ref().map_or_else(
+        || format!("{location}: {message}"),
+        |key| format!("{location}: in '{key}': {message}"),
+    );
+
+    if let Some((line, column)) = position
+        && let Some(source) = text.lines().nth(line.saturating_sub(1))
+    {
+        let gutter = " ".repeat(line.to_string().len());
+        let caret = " ".repeat(column.saturating_sub(1));
+        full = format!("{full}\n{gutter} |\n{line} | {source}\n{gutter} | {caret}^");
+    }
+
+    Diagnostic::Config {
+        file: file.to_path_buf(),
+        line: position.map(|(line, _)| line),
+        column: position.map(|(_, column)| column),
+        key,
+    }
+    .attach(anyhow::Error::msg(full))
+}
+
+/// The line and column, starting at 1, of a byte offset into some text.
+#[must_use]
+pub fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = text.get(..offset).unwrap_or(text);
+    let line = before.rsplit('\n').next().unwrap_or_default();
+    (before.matches('\n').count() + 1, line.chars().count() + 1)
+}
+
+/// An error as reported with `--error-format json`.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    message: String,
+    causes: Vec<String>,
+    exit_code: i32,
+    diagnostics: Vec<&'a Diagnostic>,
+}
+
+/// Renders an error which ends the program as a single line of JSON, with its message, its causes, the exit code it
+/// ends the program with, and its diagnostics.
+#[must_use]
+pub fn to_json(error: &anyhow::Error) -> String {
+    let report = Report {
+        message: error.to_string(),
+        causes: error.chain().skip(1).map(ToString::to_string).collect(),
+        exit_code: exit_codes::exit_code(error),
+        diagnostics: Diagnostic::all(error),
+    };
+
+    serde_json::to_string(&report).unwrap_or(report.message)
+}
+
+/// The error a diagnostic was attached to, when the cause is one.
+#[must_use]
+pub fn attached_error<'a>(cause: &'a (dyn Error + 'static)) -> Option<&'a anyhow::Error> {
+    cause.downcast_ref::<Attached>().map(|attached| &attached.error)
+}
+
+fn collect<'a>(error: &'a anyhow::Error, found: &mut Vec<&'a Diagnostic>) {
+    // wrappers hide themselves from the chain, which continues with the causes of the errors they wrap, so those are
+    // looked into separately
+    for cause in error.chain() {
+        if let Some(attached) = cause.downcast_ref::<Attached>() {
+            found.push(&attached.diagnostic);
+            collect(&attached.error, found);
+            return;
+        }
+
+        if let Some(tagged) = exit_codes::tagged_error(cause) {
+            collect(tagged, found);
+            return;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Attached {
+    diagnostic: Diagnostic,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Attached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl Error for Attached {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
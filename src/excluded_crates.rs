@@ -0,0 +1,178 @@
+use crate::git;
+use crate::host::Host;
+use crate::workspace_root::read_manifest;
+use anyhow::Context;
+use cargo_metadata::Metadata;
+use cargo_metadata::camino::Utf8Path;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A crate under the workspace root which isn't a member of the workspace, so that cargo, and the jobs, leave it out.
+#[derive(Debug, Clone)]
+pub struct ExcludedCrate {
+    /// The name of the crate's package, or the name of its directory for a virtual workspace.
+    pub name: String,
+
+    /// The directory of the crate, relative to the workspace root.
+    pub dir: PathBuf,
+
+    pub exclusion: Exclusion,
+}
+
+/// Directories which hold copies of other people's crates or test data rather than crates of the workspace, and which
+/// are never searched.
+const SKIPPED_DIRS: &[&str] = &["target", "vendor", "node_modules", "fixtures"];
+
+/// Why a crate isn't a member of the workspace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exclusion {
+    /// The crate is under a path listed by the `exclude` entry of the `[workspace]` table.
+    Excluded,
+
+    /// The crate is the root of a workspace of its own, whose members come along with it.
+    NestedWorkspace,
+
+    /// The crate is neither a member nor excluded, which cargo refuses to build.
+    Stray,
+}
+
+impl Exclusion {
+    /// Whether jobs with `include_excluded = true` cover crates excluded this way.
+    #[must_use]
+    pub const fn can_be_included(self) -> bool {
+        !matches!(self, Self::Stray)
+    }
+
+    #[must_use]
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Excluded => "excluded from the workspace",
+            Self::NestedWorkspace => "a nested workspace",
+            Self::Stray => "neither a member nor excluded",
+        }
+    }
+}
+
+/// Finds the crates under the workspace root which aren't members of the workspace, sorted by directory, along with
+/// warnings about the manifests which couldn't be read and were skipped.
+///
+/// Hidden directories, the target directory, the directories the git repository ignores, and directories such as
+/// `vendor` and `fixtures` are left out, and so are the members of nested workspaces, which are represented by the
+/// nested workspace itself.
+pub fn find(host: &impl Host, metadata: &Metadata) -> anyhow::Result<(Vec<ExcludedCrate>, Vec<String>)> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let manifest = read_manifest(&workspace_root.join("Cargo.toml"))?;
+    let excluded: Vec<PathBuf> = manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("exclude"))
+        .and_then(toml::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(toml::Value::as_str)
+        .map(|path| PathBuf::from(path.trim_start_matches("./")))
+        .collect();
+
+    let member_dirs: Vec<&Path> = metadata
+        .workspace_packages()
+        .iter()
+        .filter_map(|pkg| pkg.manifest_path.parent())
+        .map(Utf8Path::as_std_path)
+        .collect();
+
+    // workspaces outside of a git repository have nothing to ignore
+    let ignored: Vec<PathBuf> = git::ignored_dirs(host, workspace_root)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dir| workspace_root.join(dir))
+        .collect();
+
+    let walk = Walk {
+        workspace_root,
+        target_dir: metadata.target_directory.as_std_path(),
+        member_dirs: &member_dirs,
+        excluded: &excluded,
+        ignored: &ignored,
+    };
+
+    let mut crates = Vec::new();
+    let mut warnings = Vec::new();
+    walk.visit(workspace_root, &mut crates, &mut warnings)?;
+    crates.sort_by(|a, b| a.dir.cmp(&b.dir));
+    Ok((crates, warnings))
+}
+
+/// What the search for crates goes by.
+struct Walk<'a> {
+    workspace_root: &'a Path,
+    target_dir: &'a Path,
+    member_dirs: &'a [&'a Path],
+    excluded: &'a [PathBuf],
+    ignored: &'a [PathBuf],
+}
+
+impl Walk<'_> {
+    fn visit(&self, dir: &Path, crates: &mut Vec<ExcludedCrate>, warnings: &mut Vec<String>) -> anyhow::Result<()> {
+        let manifest_path = dir.join("Cargo.toml");
+        if dir != self.workspace_root && !self.member_dirs.contains(&dir) && manifest_path.is_file() {
+            match read_manifest(&manifest_path) {
+                Ok(manifest) => {
+                    let found = self.excluded_crate(dir, &manifest);
+                    let exclusion = found.exclusion;
+                    crates.push(found);
+
+                    // the members of a nested workspace come along with it
+                    if exclusion == Exclusion::NestedWorkspace {
+                        return Ok(());
+                    }
+                }
+
+                Err(e) => warnings.push(format!("skipped a crate which might be outside of the workspace: {e:#}")),
+            }
+        }
+
+        let entries = fs::read_dir(dir).with_context(|| format!("unable to list the files in {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("unable to list the files in {}", dir.display()))?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.')
+                || SKIPPED_DIRS.contains(&name.as_str())
+                || path == self.target_dir
+                || self.ignored.contains(&path)
+                || !entry.file_type()?.is_dir()
+            {
+                continue;
+            }
+
+            self.visit(&path, crates, warnings)?;
+        }
+
+        Ok(())
+    }
+
+    fn excluded_crate(&self, dir: &Path, manifest: &toml::Table) -> ExcludedCrate {
+        let relative = dir.strip_prefix(self.workspace_root).unwrap_or(dir).to_path_buf();
+        let exclusion = if manifest.contains_key("workspace") {
+            Exclusion::NestedWorkspace
+        } else if self.excluded.iter().any(|path| relative.starts_with(path)) {
+            Exclusion::Excluded
+        } else {
+            Exclusion::Stray
+        };
+
+        let name = manifest
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(toml::Value::as_str)
+            .map_or_else(
+                || dir.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                ToString::to_string,
+            );
+
+        ExcludedCrate {
+            name,
+            dir: relative,
+            exclusion,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output};
+
+/// The version of the protocol spoken with executors, which they can check to reject requests they don't understand.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What an executor is asked to run, written as a single JSON document to its standard input.
+#[derive(Debug, Serialize)]
+pub struct Request<'a> {
+    pub protocol: u32,
+    pub step: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<&'a str>,
+
+    /// The step's command line, meant for the platform's shell.
+    pub command: &'a str,
+
+    pub directory: &'a Path,
+
+    /// The variables set for the step, on top of whatever environment the executor provides.
+    pub env: BTreeMap<String, String>,
+}
+
+impl<'a> Request<'a> {
+    /// Describes a step whose local command was already prepared, taking its directory and variables from it.
+    #[must_use]
+    pub fn new(step: &'a str, package: Option<&'a str>, command: &'a str, cmd: &'a Command) -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            step,
+            package,
+            command,
+            directory: cmd.get_current_dir().unwrap_or_else(|| Path::new(".")),
+            env: cmd
+                .get_envs()
+                .filter_map(|(k, v)| Some((k.to_string_lossy().into_owned(), v?.to_string_lossy().into_owned())))
+                .collect(),
+        }
+    }
+}
+
+/// A request as an executor reads it, with only what it takes to run the step.
+#[derive(Debug, Deserialize)]
+pub struct ReceivedRequest {
+    pub protocol: u32,
+    pub command: String,
+    pub directory: PathBuf,
+
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+/// How the step turned out, written as a single JSON document to the executor's standard output.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Response {
+    exit_code: i32,
+
+    #[serde(default)]
+    stdout: String,
+
+    #[serde(default)]
+    stderr: String,
+}
+
+impl From<Output> for Response {
+    /// Describes how a step which ran turned out, where a step killed by a signal has exit code -1.
+    fn from(output: Output) -> Self {
+        Self {
+            exit_code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+}
+
+/// Turns what an executor wrote into the output of the step it ran.
+///
+/// What the executor itself writes to its standard error is only used to explain why it failed to respond.
+pub fn step_output(executor: &Output) -> anyhow::Result<Output> {
+    let response: Response = serde_json::from_slice(&executor.stdout).with_context(|| {
+        let stderr = String::from_utf8_lossy(&executor.stderr);
+        let stderr = stderr.trim();
+        if stderr.is_empty() {
+            format!("the executor didn't respond as expected ({})", executor.status)
+        } else {
+            format!("the executor didn't respond as expected ({}): {stderr}", executor.status)
+        }
+    })?;
+
+    if !executor.status.success() {
+        return Err(anyhow!("the executor responded, but then failed ({})", executor.status));
+    }
+
+    Ok(Output {
+        status: exit_status(response.exit_code),
+        stdout: response.stdout.into_bytes(),
+        stderr: response.stderr.into_bytes(),
+    })
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    // the wait status of a process which exited normally holds its exit code in the second byte
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+
+    ExitStatus::from_raw(u32::from_ne_bytes(code.to_ne_bytes()))
+}
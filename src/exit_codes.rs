@@ -0,0 +1,97 @@
+use crate::diagnostics;
+use core::error::Error;
+use core::fmt;
+
+/// The exit code of failures which don't fall in any of the categories of [`Failure`].
+pub const GENERAL_FAILURE: i32 = 1;
+
+/// What kind of failure ended the program, which decides its exit code so wrapper scripts can tell failures apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The configuration couldn't be loaded or is invalid.
+    Config,
+
+    /// A job or step given on the command line doesn't exist.
+    UnknownJob,
+
+    /// A step failed, failing its job.
+    StepFailed,
+
+    /// The user declined to go ahead.
+    Cancelled,
+
+    /// `cargo-ci` itself misbehaved.
+    Internal,
+}
+
+impl Failure {
+    #[must_use]
+    pub const fn exit_code(self) -> i32 {
+        match self {
+            Self::Config => 2,
+            Self::UnknownJob => 3,
+            Self::StepFailed => 4,
+            Self::Cancelled => 130,
+            Self::Internal => 70,
+        }
+    }
+
+    /// Marks an error as a failure of this kind, leaving its message and causes as they are.
+    #[must_use]
+    pub fn tag(self, error: anyhow::Error) -> anyhow::Error {
+        anyhow::Error::new(Tagged { failure: self, error })
+    }
+
+    /// The kind of failure an error was marked as, where the mark closest to the root cause wins, since it's the most
+    /// specific. A step failing because it wasn't confirmed is a cancellation, for example.
+    #[must_use]
+    pub fn of(error: &anyhow::Error) -> Option<Self> {
+        // a tagged error hides itself from the chain, which continues with its causes, so it's looked into separately,
+        // and so is an error with a diagnostic attached
+        error.chain().find_map(|cause| {
+            cause.downcast_ref::<Tagged>().map_or_else(
+                || diagnostics::attached_error(cause).and_then(Self::of),
+                |tagged| Some(Self::of(&tagged.error).unwrap_or(tagged.failure)),
+            )
+        })
+    }
+
+    /// Marks a new error as the same kind of failure as the error it was derived from, if that one was marked.
+    #[must_use]
+    pub fn carry(from: &anyhow::Error, to: anyhow::Error) -> anyhow::Error {
+        match Self::of(from) {
+            Some(failure) => failure.tag(to),
+            None => to,
+        }
+    }
+}
+
+/// The error a failure was marked on, when the cause is one.
+#[must_use]
+pub fn tagged_error<'a>(cause: &'a (dyn Error + 'static)) -> Option<&'a anyhow::Error> {
+    cause.downcast_ref::<Tagged>().map(|tagged| &tagged.error)
+}
+
+/// The exit code for an error which ends the program.
+#[must_use]
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    Failure::of(error).map_or(GENERAL_FAILURE, Failure::exit_code)
+}
+
+#[derive(Debug)]
+struct Tagged {
+    failure: Failure,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Tagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl Error for Tagged {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
@@ -1,7 +1,7 @@
 use crate::expressions::expression::Expression;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
 pub enum Conditional {
     Bool(bool),
@@ -15,6 +15,14 @@ impl Conditional {
             Self::Expression(expr) => expr.evaluate(variables),
         }
     }
+
+    /// Returns the underlying expression, or `None` when the condition is a constant.
+    pub const fn expression(&self) -> Option<&Expression> {
+        match self {
+            Self::Bool(_) => None,
+            Self::Expression(expr) => Some(expr),
+        }
+    }
 }
 
 impl Default for Conditional {
@@ -1,7 +1,7 @@
 use crate::expressions::expression::Expression;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
 pub enum ContinueOnError {
     Bool(bool),
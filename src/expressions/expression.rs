@@ -1,10 +1,23 @@
+use crate::cargo_tools::CargoTools;
+use crate::expressions::variable_types::{self, VariableType};
 use anyhow::{Context, anyhow};
-use evalexpr::{ContextWithMutableVariables, HashMapContext, Node, Value, build_operator_tree};
+use evalexpr::{ContextWithMutableFunctions, ContextWithMutableVariables, Function, HashMapContext, Node, Value, build_operator_tree};
+use semver::Version;
 use serde::{Deserialize, Deserializer, de};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// The tools installed with `cargo install`, read the first time an expression asks about them. Reading fails when
+/// nothing was ever installed, in which case no tool counts as installed.
+static INSTALLED_TOOLS: LazyLock<Option<CargoTools>> = LazyLock::new(|| CargoTools::read().ok());
 
 #[derive(Debug, Clone)]
 pub struct Expression {
+    text: String,
     tree: Node,
+
+    /// The variables which the expression uses as something other than a string, along with what they're used as.
+    types: HashMap<String, VariableType>,
 }
 
 impl<'de> Deserialize<'de> for Expression {
@@ -19,17 +32,48 @@ impl<'de> Deserialize<'de> for Expression {
 
 impl Expression {
     pub fn new(expr: impl AsRef<str>) -> anyhow::Result<Self> {
-        let tree = build_operator_tree(expr.as_ref()).with_context(|| format!("Failed to parse expression: {}", expr.as_ref()))?;
-        Ok(Self { tree })
+        let mut tree = build_operator_tree(expr.as_ref()).with_context(|| format!("Failed to parse expression: {}", expr.as_ref()))?;
+        let types = variable_types::infer(&mut tree);
+
+        Ok(Self {
+            text: expr.as_ref().to_string(),
+            tree,
+            types,
+        })
+    }
+
+    /// The expression as written in configuration.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The names of the variables the expression reads, without duplicates.
+    pub fn variable_identifiers(&self) -> Vec<&str> {
+        let mut identifiers: Vec<&str> = Vec::new();
+        for identifier in self.tree.iter_read_variable_identifiers() {
+            if !identifiers.contains(&identifier) {
+                identifiers.push(identifier);
+            }
+        }
+
+        identifiers
     }
 
     pub fn evaluate(&self, variables: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<str>)>) -> anyhow::Result<bool> {
         let mut context = HashMapContext::new();
+        add_tool_functions(&mut context)?;
+        variable_types::add_conversion_functions(&mut context).context("unable to define conversion functions")?;
 
         for (k, v) in variables {
+            let (k, v) = (k.as_ref(), v.as_ref());
+            let value = match self.types.get(k) {
+                Some(variable_type) => variable_types::typed_value(k, v, *variable_type)?,
+                None => Value::String(v.to_string()),
+            };
+
             context
-                .set_value(k.as_ref().to_string(), Value::String(v.as_ref().to_string()))
-                .with_context(|| format!("unable to set variable: {}", k.as_ref()))?;
+                .set_value(k.to_string(), value)
+                .with_context(|| format!("unable to set variable: {k}"))?;
         }
 
         let result = self.tree.eval_with_context(&context).context("Failed to evaluate expression")?;
@@ -39,3 +83,34 @@ impl Expression {
         }
     }
 }
+
+/// Adds `tool_installed("name")`, which tells whether a tool was installed with `cargo install`, and
+/// `tool_version("name")`, which gives the version of such a tool, or an empty string when it isn't installed.
+fn add_tool_functions(context: &mut HashMapContext) -> anyhow::Result<()> {
+    context
+        .set_function(
+            "tool_installed".to_string(),
+            Function::new(|arg| {
+                let tool = arg.as_string()?;
+                Ok(Value::Boolean(
+                    INSTALLED_TOOLS.as_ref().is_some_and(|tools| tools.is_installed(&tool)),
+                ))
+            }),
+        )
+        .context("unable to define function: tool_installed")?;
+
+    context
+        .set_function(
+            "tool_version".to_string(),
+            Function::new(|arg| {
+                Ok(Value::String(
+                    installed_version(&arg.as_string()?).map_or_else(String::new, Version::to_string),
+                ))
+            }),
+        )
+        .context("unable to define function: tool_version")
+}
+
+fn installed_version(tool: &str) -> Option<&'static Version> {
+    INSTALLED_TOOLS.as_ref()?.get_install(tool).map(|(key, _)| key.version())
+}
@@ -1,6 +1,9 @@
 mod conditional;
 mod continue_on_error;
 mod expression;
+mod variable_source;
+mod variable_types;
 
 pub use conditional::Conditional;
 pub use continue_on_error::ContinueOnError;
+pub use variable_source::VariableSource;
@@ -0,0 +1,27 @@
+use core::fmt::{self, Display};
+
+/// Where a variable visible to an expression was defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableSource {
+    Environment,
+    Workspace,
+    Job,
+    Package,
+    Step,
+    CommandLine,
+    StepOutcome,
+}
+
+impl Display for VariableSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Environment => "environment",
+            Self::Workspace => "workspace variables",
+            Self::Job => "job variables",
+            Self::Package => "package metadata",
+            Self::Step => "step variables",
+            Self::CommandLine => "command line",
+            Self::StepOutcome => "outcome of an earlier step",
+        })
+    }
+}
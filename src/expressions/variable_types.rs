@@ -0,0 +1,282 @@
+use anyhow::anyhow;
+use evalexpr::{ContextWithMutableFunctions, EvalexprError, EvalexprResult, Function, HashMapContext, Node, Operator, Value};
+use std::collections::HashMap;
+
+/// The number of digits each numeric part of a version is padded to, so versions compare like strings do.
+const VERSION_PART_WIDTH: usize = 10;
+
+/// The type a variable is given in an expression, which variables are all strings until then.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableType {
+    String,
+
+    /// An integer, or a floating-point number when it has a fractional part.
+    Number,
+
+    Boolean,
+
+    /// A version such as `1.80.0`, compared by its parts rather than as text.
+    Version,
+}
+
+/// Works out the type of the variables an expression compares with a literal, or combines with logical operators.
+///
+/// Variables compared with a number are numbers, and those compared with `true` or `false`, or used with `&&`, `||`, or
+/// `!`, are booleans. Variables ordered with `<`, `<=`, `>`, or `>=` against a string which looks like a version, such
+/// as `"1.80"`, are versions, and such strings in the expression are rewritten so they compare as versions too, as are
+/// those compared with calls to `version(...)`. Other variables, including those used in conflicting ways, remain
+/// strings.
+pub fn infer(tree: &mut Node) -> HashMap<String, VariableType> {
+    let mut types = HashMap::new();
+    let mut conflicting = Vec::new();
+    visit(tree, &mut |name, inferred| match types.insert(name.to_string(), inferred) {
+        Some(previous) if previous != inferred => conflicting.push(name.to_string()),
+        _ => {}
+    });
+
+    for name in conflicting {
+        _ = types.insert(name, VariableType::String);
+    }
+
+    rewrite_versions(tree, &types);
+    types.retain(|_, inferred| *inferred != VariableType::String);
+    types
+}
+
+/// Converts the value of a variable to the type the expression uses it as.
+pub fn typed_value(name: &str, value: &str, variable_type: VariableType) -> anyhow::Result<Value> {
+    match variable_type {
+        VariableType::String => Ok(Value::String(value.to_string())),
+        VariableType::Number => {
+            number(value).ok_or_else(|| anyhow!("variable '{name}' is '{value}', which isn't a number, but the expression uses it as one"))
+        }
+        VariableType::Boolean => boolean(value).map(Value::Boolean).ok_or_else(|| {
+            anyhow!("variable '{name}' is '{value}', which isn't 'true' or 'false', but the expression uses it as a boolean")
+        }),
+        VariableType::Version => version_key(value)
+            .map(Value::String)
+            .ok_or_else(|| anyhow!("variable '{name}' is '{value}', which isn't a version, but the expression uses it as one")),
+    }
+}
+
+/// Adds `int(x)`, `float(x)`, `bool(x)`, and `version(x)`, which convert a value to the given type explicitly, for
+/// the uses of variables the expression doesn't type by itself.
+pub fn add_conversion_functions(context: &mut HashMapContext) -> EvalexprResult<()> {
+    context.set_function(
+        "int".to_string(),
+        Function::new(|arg| match arg {
+            Value::Int(_) => Ok(arg.clone()),
+            _ => text(arg)?
+                .parse()
+                .map(Value::Int)
+                .map_err(|e| EvalexprError::CustomMessage(format!("'{arg}' isn't an integer: {e}"))),
+        }),
+    )?;
+
+    context.set_function(
+        "float".to_string(),
+        Function::new(|arg| match arg {
+            Value::Int(_) | Value::Float(_) => Ok(Value::Float(arg.as_number()?)),
+            _ => text(arg)?
+                .parse()
+                .map(Value::Float)
+                .map_err(|e| EvalexprError::CustomMessage(format!("'{arg}' isn't a number: {e}"))),
+        }),
+    )?;
+
+    context.set_function(
+        "bool".to_string(),
+        Function::new(|arg| match arg {
+            Value::Boolean(_) => Ok(arg.clone()),
+            _ => boolean(&text(arg)?)
+                .map(Value::Boolean)
+                .ok_or_else(|| EvalexprError::CustomMessage(format!("'{arg}' isn't 'true' or 'false'"))),
+        }),
+    )?;
+
+    context.set_function(
+        "version".to_string(),
+        Function::new(|arg| {
+            version_key(&text(arg)?)
+                .map(Value::String)
+                .ok_or_else(|| EvalexprError::CustomMessage(format!("'{arg}' isn't a version")))
+        }),
+    )
+}
+
+/// The text of a string or a number.
+fn text(value: &Value) -> EvalexprResult<String> {
+    match value {
+        Value::String(s) => Ok(s.trim().to_string()),
+        Value::Int(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        _ => Err(EvalexprError::expected_string(value.clone())),
+    }
+}
+
+fn number(value: &str) -> Option<Value> {
+    let value = value.trim();
+    value.parse().map(Value::Int).or_else(|_| value.parse().map(Value::Float)).ok()
+}
+
+fn boolean(value: &str) -> Option<bool> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Turns a version such as `1.80`, `v2.0.1`, or `1.0.0-beta.2` into a string which compares with the strings of other
+/// versions the way the versions compare: `1.80` is the same as `1.80.0` and comes after `1.9`, and pre-releases come
+/// before their release.
+fn version_key(version: &str) -> Option<String> {
+    let version = version.trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let version = version.split_once('+').map_or(version, |(version, _)| version);
+    let (core, pre) = version.split_once('-').map_or((version, None), |(core, pre)| (core, Some(pre)));
+
+    let parts: Vec<&str> = core.split('.').collect();
+    if parts.len() < 2
+        || parts
+            .iter()
+            .any(|part| part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+
+    let mut key: Vec<String> = parts.iter().map(|part| pad(part)).collect();
+    key.resize(key.len().max(3), pad("0"));
+    let mut key = key.join(".");
+
+    match pre {
+        // '-' sorts before '~', so pre-releases come before their release
+        Some(pre) => {
+            key.push('-');
+            key.push_str(
+                &pre.split('.')
+                    .map(|part| {
+                        if part.bytes().all(|b| b.is_ascii_digit()) {
+                            pad(part)
+                        } else {
+                            part.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("."),
+            );
+        }
+        None => key.push('~'),
+    }
+
+    Some(key)
+}
+
+fn pad(part: &str) -> String {
+    format!("{part:0>VERSION_PART_WIDTH$}")
+}
+
+/// Calls `record` with every variable whose type the expression implies, along with that type.
+fn visit(node: &Node, record: &mut impl FnMut(&str, VariableType)) {
+    match node.operator() {
+        Operator::Eq | Operator::Neq | Operator::Gt | Operator::Lt | Operator::Geq | Operator::Leq => {
+            if let [left, right] = node.children() {
+                let ordering = !matches!(node.operator(), Operator::Eq | Operator::Neq);
+                for (variable, literal) in [(left, right), (right, left)] {
+                    if let (Some(name), Some(value)) = (variable_name(variable), literal_value(literal)) {
+                        record(name, literal_type(&value, ordering));
+                    }
+                }
+            }
+        }
+        Operator::And | Operator::Or | Operator::Not => {
+            for name in node.children().iter().filter_map(variable_name) {
+                record(name, VariableType::Boolean);
+            }
+        }
+        _ => {}
+    }
+
+    for child in node.children() {
+        visit(child, record);
+    }
+}
+
+/// Rewrites the version strings compared with variables typed as versions, or with calls to `version(...)`, so they
+/// compare as versions.
+fn rewrite_versions(node: &mut Node, types: &HashMap<String, VariableType>) {
+    let is_version =
+        |node: &Node| is_version_call(node) || variable_name(node).is_some_and(|name| types.get(name) == Some(&VariableType::Version));
+    let literal_index = match node.children() {
+        [left, _] if is_version(left) => Some(1),
+        [_, right] if is_version(right) => Some(0),
+        _ => None,
+    };
+
+    if matches!(
+        node.operator(),
+        Operator::Eq | Operator::Neq | Operator::Gt | Operator::Lt | Operator::Geq | Operator::Leq
+    ) && let Some(literal) = literal_index
+        .and_then(|index| node.children_mut().get_mut(index))
+        .and_then(literal_mut)
+        && let Operator::Const { value: Value::String(s) } = literal.operator()
+        && let Some(key) = version_key(s)
+    {
+        *literal.operator_mut() = Operator::Const { value: Value::String(key) };
+    }
+
+    for child in node.children_mut() {
+        rewrite_versions(child, types);
+    }
+}
+
+fn literal_type(value: &Value, ordering: bool) -> VariableType {
+    match value {
+        Value::Int(_) | Value::Float(_) => VariableType::Number,
+        Value::Boolean(_) => VariableType::Boolean,
+        Value::String(s) if ordering && version_key(s).is_some() => VariableType::Version,
+        _ => VariableType::String,
+    }
+}
+
+/// The name of the variable a node reads, looking through parentheses.
+fn variable_name(node: &Node) -> Option<&str> {
+    match node.operator() {
+        Operator::VariableIdentifierRead { identifier } => Some(identifier),
+        Operator::RootNode => match node.children() {
+            [child] => variable_name(child),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether a node calls `version(...)`, looking through parentheses.
+fn is_version_call(node: &Node) -> bool {
+    match node.operator() {
+        Operator::FunctionIdentifier { identifier } => identifier == "version",
+        Operator::RootNode => matches!(node.children(), [child] if is_version_call(child)),
+        _ => false,
+    }
+}
+
+/// The literal value of a node, looking through parentheses and negations.
+fn literal_value(node: &Node) -> Option<Value> {
+    match (node.operator(), node.children()) {
+        (Operator::Const { value }, _) => Some(value.clone()),
+        (Operator::RootNode | Operator::Neg, [child]) => literal_value(child),
+        _ => None,
+    }
+}
+
+/// The node holding a literal, looking through parentheses.
+fn literal_mut(node: &mut Node) -> Option<&mut Node> {
+    match node.operator() {
+        Operator::Const { .. } => Some(node),
+        Operator::RootNode if node.children().len() == 1 => node.children_mut().first_mut().and_then(literal_mut),
+        _ => None,
+    }
+}
@@ -0,0 +1,111 @@
+use crate::host::Host;
+use anyhow::{Context, anyhow};
+use cargo_metadata::Package;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A dependency whose resolved features differ between building a package on its own and building the whole workspace.
+#[derive(Debug, Clone)]
+pub struct FeatureDivergence {
+    pub package: String,
+    pub dependency: String,
+    pub per_package: BTreeSet<String>,
+    pub workspace: BTreeSet<String>,
+}
+
+impl FeatureDivergence {
+    /// Describes the divergence in a single line suitable for the run summary.
+    pub fn describe(&self) -> String {
+        let only_workspace: Vec<_> = self.workspace.difference(&self.per_package).map(String::as_str).collect();
+        let only_package: Vec<_> = self.per_package.difference(&self.workspace).map(String::as_str).collect();
+
+        let mut differences = Vec::new();
+        if !only_workspace.is_empty() {
+            differences.push(format!("only gets [{}] in workspace builds", only_workspace.join(", ")));
+        }
+
+        if !only_package.is_empty() {
+            differences.push(format!("only gets [{}] in per-package builds", only_package.join(", ")));
+        }
+
+        format!(
+            "package '{}': dependency '{}' {}",
+            self.package,
+            self.dependency,
+            differences.join(" and ")
+        )
+    }
+}
+
+/// Compares the features resolved for each package's dependencies when building the package on its own against
+/// those resolved when building the whole workspace.
+///
+/// Cargo unifies features across everything being built, so a package which only compiles thanks to a feature enabled
+/// by one of its workspace siblings will build fine with `--workspace` but fail when built by itself, and vice versa.
+pub fn analyze<H: Host>(host: &H, workspace_root: &Path, packages: &[&Package]) -> anyhow::Result<Vec<FeatureDivergence>> {
+    let workspace = resolved_features(host, workspace_root, None)?;
+
+    let mut divergences = Vec::new();
+    for pkg in packages {
+        for (dependency, per_package) in resolved_features(host, workspace_root, Some(&pkg.name))? {
+            let workspace_features = workspace.get(&dependency).cloned().unwrap_or_default();
+            if workspace_features != per_package {
+                divergences.push(FeatureDivergence {
+                    package: pkg.name.to_string(),
+                    dependency,
+                    per_package,
+                    workspace: workspace_features,
+                });
+            }
+        }
+    }
+
+    Ok(divergences)
+}
+
+/// Uses `cargo tree` to get the features enabled on every crate in the build graph of a package, or of the whole workspace.
+fn resolved_features<H: Host>(
+    host: &H,
+    workspace_root: &Path,
+    package: Option<&str>,
+) -> anyhow::Result<BTreeMap<String, BTreeSet<String>>> {
+    let mut cmd = Command::new("cargo");
+    _ = cmd.current_dir(workspace_root).arg("tree");
+
+    if let Some(package) = package {
+        _ = cmd.arg("--package").arg(package);
+    } else {
+        _ = cmd.arg("--workspace");
+    }
+
+    _ = cmd
+        .args(["--edges", "normal,build", "--format", "{p}|{f}", "--prefix", "none", "--no-dedupe"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let output = host.output(&mut cmd).context("unable to run 'cargo tree'")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'cargo tree' failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut result: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((crate_spec, features)) = line.rsplit_once('|') else {
+            continue;
+        };
+
+        // a crate can show up several times, with different features when used as both a build and a normal dependency
+        result
+            .entry(crate_spec.trim().to_string())
+            .or_default()
+            .extend(features.split(',').filter(|f| !f.is_empty()).map(ToString::to_string));
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,228 @@
+use crate::config::Config;
+use crate::git;
+use crate::host::Host;
+use crate::long_paths;
+use anyhow::Context;
+use cargo_metadata::{Metadata, Package, PackageId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The files at the root of the workspace which affect how pure jobs turn out: the manifest and lock file, along with the
+/// configuration of cargo, the toolchain, and the usual checking tools.
+const WORKSPACE_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    ".cargo/config.toml",
+    ".cargo/config",
+    "rust-toolchain.toml",
+    "rust-toolchain",
+    "clippy.toml",
+    ".clippy.toml",
+    "rustfmt.toml",
+    ".rustfmt.toml",
+    "deny.toml",
+];
+
+/// What a run's outcome depends on beyond the workspace's sources: the toolchain, the variables set for it, and the
+/// versions of the tools it uses.
+///
+/// Entries are keyed by what they describe, such as `rustc`, `variable FOO`, or `tool cargo-nextest`. The values of
+/// variables are only kept as hashes, since they may hold secrets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Fingerprint(BTreeMap<String, String>);
+
+impl Fingerprint {
+    /// Takes the fingerprint of a run about to happen in the given workspace, whose toolchain may be pinned there.
+    pub fn compute<'a>(host: &impl Host, workspace_root: &Path, cfg: &Config, variables: impl Iterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut entries = BTreeMap::new();
+        for program in ["rustc", "cargo"] {
+            let mut cmd = Command::new(program);
+            _ = cmd
+                .arg("--version")
+                .current_dir(workspace_root)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null());
+
+            let version = host.output(&mut cmd).ok().filter(|output| output.status.success()).map_or_else(
+                || "unknown".to_string(),
+                |output| String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            );
+            _ = entries.insert(program.to_string(), version);
+        }
+
+        for (name, value) in variables {
+            _ = entries.insert(format!("variable {name}"), format!("{:016x}", fnv1a(FNV_OFFSET, value.as_bytes())));
+        }
+
+        for (tool_id, tool) in cfg.tools().iter() {
+            _ = entries.insert(format!("tool {tool_id}"), tool.version().to_string());
+        }
+
+        Self(entries)
+    }
+
+    /// Explains what changed since an earlier fingerprint, one entry per change.
+    #[must_use]
+    pub fn changes_since(&self, earlier: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        for (key, value) in &self.0 {
+            match earlier.0.get(key) {
+                None => changes.push(format!("{key} was added")),
+                Some(previous) if previous != value && key.starts_with("variable ") => changes.push(format!("{key} changed")),
+                Some(previous) if previous != value => changes.push(format!("{key} changed from '{previous}' to '{value}'")),
+                Some(_) => {}
+            }
+        }
+
+        for key in earlier.0.keys().filter(|key| !self.0.contains_key(*key)) {
+            changes.push(format!("{key} was removed"));
+        }
+
+        changes
+    }
+
+    /// Digests what the outcome of a run's jobs depends on: this fingerprint, the text of the configuration and the
+    /// overlays applied to it, the given variables, which this fingerprint doesn't cover, and the files of the given
+    /// packages along with those of the workspace packages they depend on.
+    ///
+    /// The workspace's manifest and lock file are included, while hidden files and the target directory are left out.
+    pub fn digest_inputs(
+        &self,
+        cfg: &Config,
+        metadata: &Metadata,
+        packages: &[&Package],
+        variables: &BTreeMap<String, String>,
+    ) -> anyhow::Result<String> {
+        let mut hash = FNV_OFFSET;
+        for (key, value) in self.0.iter().chain(variables) {
+            hash = fnv1a(fnv1a(hash, key.as_bytes()), value.as_bytes());
+        }
+
+        hash = fnv1a(hash, cfg.text().as_bytes());
+        for overlay in cfg.applied_overlays() {
+            hash = fnv1a(hash, overlay.as_bytes());
+        }
+
+        let workspace_root = metadata.workspace_root.as_std_path();
+        let target_dir = metadata.target_directory.as_std_path();
+        let mut files: BTreeSet<_> = ["Cargo.toml", "Cargo.lock"]
+            .into_iter()
+            .map(|name| workspace_root.join(name))
+            .filter(|path| path.is_file())
+            .collect();
+
+        for id in with_workspace_dependencies(metadata, packages) {
+            if let Some(dir) = metadata[id].manifest_path.parent() {
+                collect_files(dir.as_std_path(), target_dir, &mut files)?;
+            }
+        }
+
+        for path in &files {
+            let contents = fs::read(long_paths::extended(path)?).with_context(|| format!("unable to read {}", path.display()))?;
+            let name = path.strip_prefix(workspace_root).unwrap_or(path);
+            hash = fnv1a(fnv1a(hash, name.to_string_lossy().as_bytes()), &contents);
+        }
+
+        Ok(format!("{hash:016x}"))
+    }
+
+    /// Digests what the outcome of a pure job depends on: this fingerprint, the text of the configuration, and the
+    /// committed trees of the given packages, those of the workspace packages they depend on, and the workspace's
+    /// manifest, lock file, and tool configuration files.
+    ///
+    /// Returns `None` when any of these paths has uncommitted changes, since the committed trees don't tell what a job
+    /// would see then.
+    pub fn digest_trees(
+        &self,
+        host: &impl Host,
+        cfg: &Config,
+        metadata: &Metadata,
+        packages: &[&Package],
+    ) -> anyhow::Result<Option<String>> {
+        let workspace_root = metadata.workspace_root.as_std_path();
+        let mut paths: BTreeSet<String> = WORKSPACE_FILES.iter().map(ToString::to_string).collect();
+        for id in with_workspace_dependencies(metadata, packages) {
+            if let Some(dir) = metadata[id].manifest_path.parent() {
+                let dir = dir.as_std_path().strip_prefix(workspace_root).unwrap_or_else(|_| dir.as_std_path());
+                _ = paths.insert(if dir.as_os_str().is_empty() {
+                    ".".to_string()
+                } else {
+                    dir.to_string_lossy().replace('\\', "/")
+                });
+            }
+        }
+
+        let paths: Vec<_> = paths.into_iter().collect();
+        if git::has_changes(host, workspace_root, &paths)? {
+            return Ok(None);
+        }
+
+        let mut hash = FNV_OFFSET;
+        for (key, value) in &self.0 {
+            hash = fnv1a(fnv1a(hash, key.as_bytes()), value.as_bytes());
+        }
+
+        hash = fnv1a(hash, cfg.text().as_bytes());
+        hash = fnv1a(hash, &git::committed_trees(host, workspace_root, &paths)?);
+        Ok(Some(format!("{hash:016x}")))
+    }
+}
+
+/// The given packages along with the workspace packages they depend on, directly or not, since changes to those
+/// affect them too.
+fn with_workspace_dependencies<'a>(metadata: &'a Metadata, packages: &[&'a Package]) -> HashSet<&'a PackageId> {
+    let mut pending: Vec<_> = packages.iter().map(|pkg| &pkg.id).collect();
+    let mut covered = HashSet::new();
+    while let Some(id) = pending.pop() {
+        if !covered.insert(id) {
+            continue;
+        }
+
+        let node = metadata
+            .resolve
+            .as_ref()
+            .and_then(|resolve| resolve.nodes.iter().find(|node| node.id == *id));
+        pending.extend(
+            node.into_iter()
+                .flat_map(|node| &node.dependencies)
+                .filter(|dep| metadata.workspace_members.contains(dep)),
+        );
+    }
+
+    covered
+}
+
+/// Lists the files within a directory and its subdirectories, leaving out hidden entries and the target directory.
+fn collect_files(dir: &Path, target_dir: &Path, files: &mut BTreeSet<PathBuf>) -> anyhow::Result<()> {
+    let entries = fs::read_dir(long_paths::extended(dir)?).with_context(|| format!("unable to list the files in {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("unable to list the files in {}", dir.display()))?;
+        let path = dir.join(entry.file_name());
+        if entry.file_name().to_string_lossy().starts_with('.') || path == target_dir {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            collect_files(&path, target_dir, files)?;
+        } else if path.is_file() {
+            _ = files.insert(path);
+        }
+    }
+
+    Ok(())
+}
+
+pub const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Hashes with FNV-1a, whose results, unlike those of the standard library's hasher, stay the same across releases.
+///
+/// Hashing starts from [`FNV_OFFSET`], or from an earlier hash to extend it with more bytes.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,100 @@
+use crate::config::FreshnessRule;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A generated file which is missing, or older than a source file it's generated from.
+#[derive(Debug)]
+pub struct StaleFile {
+    source: PathBuf,
+    generated: PathBuf,
+    missing: bool,
+}
+
+impl StaleFile {
+    /// Describes the stale file in a single line, with paths relative to the workspace root.
+    pub fn describe(&self) -> String {
+        if self.missing {
+            format!(
+                "'{}' is missing, but is generated from '{}'",
+                self.generated.display(),
+                self.source.display()
+            )
+        } else {
+            format!("'{}' is older than '{}'", self.generated.display(), self.source.display())
+        }
+    }
+}
+
+/// Finds the generated files which are missing or older than their sources, according to the rules of a step.
+///
+/// Source files which don't exist are ignored, so a rule whose pattern matches nothing is never stale.
+pub fn stale_files(workspace_root: &Path, rules: &[FreshnessRule]) -> io::Result<Vec<StaleFile>> {
+    let mut stale = Vec::new();
+    for rule in rules {
+        for (source, generated) in expand(workspace_root, rule)? {
+            let modified = fs::metadata(workspace_root.join(&source))?.modified()?;
+            let missing = match fs::metadata(workspace_root.join(&generated)) {
+                Ok(metadata) => {
+                    if metadata.modified()? >= modified {
+                        continue;
+                    }
+
+                    false
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+                Err(e) => return Err(e),
+            };
+
+            stale.push(StaleFile {
+                source,
+                generated,
+                missing,
+            });
+        }
+    }
+
+    Ok(stale)
+}
+
+/// Lists the source files a rule matches along with the files generated from them, sorted by source.
+fn expand(workspace_root: &Path, rule: &FreshnessRule) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let (dir, name) = rule.sources().rsplit_once('/').unwrap_or_else(|| ("", rule.sources()));
+    let Some((prefix, suffix)) = name.split_once('*') else {
+        let source = PathBuf::from(rule.sources());
+        return Ok(if workspace_root.join(&source).is_file() {
+            vec![(source, PathBuf::from(rule.generated()))]
+        } else {
+            Vec::new()
+        });
+    };
+
+    let entries = match fs::read_dir(workspace_root.join(dir)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut pairs = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+
+        let Some(matched) = file_name.strip_prefix(prefix).and_then(|rest| rest.strip_suffix(suffix)) else {
+            continue;
+        };
+
+        if entry.path().is_file() {
+            pairs.push((
+                Path::new(dir).join(file_name),
+                PathBuf::from(rule.generated().replace('*', matched)),
+            ));
+        }
+    }
+
+    pairs.sort();
+    Ok(pairs)
+}
@@ -0,0 +1,205 @@
+use crate::host::Host;
+use anyhow::{Context, anyhow};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Gets the tracked files with uncommitted changes in the repository containing `dir`, mapped to their two-letter
+/// `git status --porcelain` status code.
+///
+/// Paths are relative to the root of the repository. Renamed and copied files are listed under their new path.
+pub fn modified_files(host: &impl Host, dir: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let output = git(host, dir, &["status", "--porcelain", "-z", "--untracked-files=no"])?;
+    let output = String::from_utf8_lossy(&output);
+
+    // with -z, paths are neither quoted nor escaped, and renames and copies are followed by their original path
+    let mut files = BTreeMap::new();
+    let mut entries = output.split('\0').filter(|entry| !entry.is_empty());
+    while let Some(entry) = entries.next() {
+        let (Some(status), Some(path)) = (entry.get(..2), entry.get(3..)) else {
+            continue;
+        };
+
+        if status.contains(['R', 'C']) {
+            _ = entries.next();
+        }
+
+        _ = files.insert(path.to_string(), status.to_string());
+    }
+
+    Ok(files)
+}
+
+/// Gets the directories under `dir` which the repository ignores, such as through `.gitignore`, relative to `dir`.
+pub fn ignored_dirs(host: &impl Host, dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let output = git(
+        host,
+        dir,
+        &[
+            "ls-files",
+            "--others",
+            "--ignored",
+            "--exclude-standard",
+            "--directory",
+            "-z",
+            "--",
+            ".",
+        ],
+    )?;
+
+    // directories are listed with a trailing slash, files without one
+    Ok(String::from_utf8_lossy(&output)
+        .split('\0')
+        .filter_map(|entry| entry.strip_suffix('/'))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Whether any of the given paths, relative to `dir`, have uncommitted changes, including untracked files which aren't
+/// ignored.
+pub fn has_changes(host: &impl Host, dir: &Path, paths: &[String]) -> anyhow::Result<bool> {
+    let mut args = vec!["status", "--porcelain", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    Ok(!git(host, dir, &args)?.is_empty())
+}
+
+/// Lists the objects committed at `HEAD` for the given paths, relative to `dir`, which changes whenever the committed
+/// content of any of them does. Paths which aren't committed are left out.
+pub fn committed_trees(host: &impl Host, dir: &Path, paths: &[String]) -> anyhow::Result<Vec<u8>> {
+    let mut args = vec!["ls-tree", "HEAD", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    git(host, dir, &args)
+}
+
+/// Gets the root directory of the repository containing `dir`.
+pub fn toplevel(host: &impl Host, dir: &Path) -> anyhow::Result<PathBuf> {
+    let output = git(host, dir, &["rev-parse", "--show-toplevel"])?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&output).trim()))
+}
+
+/// Gets the object hash of the current content of each of the given files, by their paths relative to the root of the
+/// repository. Files which don't exist are left out.
+pub fn content_hashes(host: &impl Host, dir: &Path, paths: &[&str]) -> anyhow::Result<BTreeMap<String, String>> {
+    let root = toplevel(host, dir)?;
+    let existing: Vec<&str> = paths.iter().copied().filter(|path| host.is_file(&root.join(path))).collect();
+    if existing.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+
+    let mut args = vec!["hash-object", "--"];
+    args.extend(&existing);
+    let output = git(host, &root, &args)?;
+
+    // git prints one hash per file, in the order they were given
+    Ok(existing
+        .into_iter()
+        .map(ToString::to_string)
+        .zip(String::from_utf8_lossy(&output).lines().map(ToString::to_string))
+        .collect())
+}
+
+/// Gets the content of a file as staged in the index, given its path relative to the root of the repository.
+pub fn staged_content(host: &impl Host, dir: &Path, path: &str) -> anyhow::Result<Vec<u8>> {
+    git(host, dir, &["show", &format!(":{path}")])
+}
+
+/// Resolves a revision, such as a branch name or `HEAD~3`, to the full hash of the commit it names.
+pub fn resolve_commit(host: &impl Host, dir: &Path, rev: &str) -> anyhow::Result<String> {
+    let output = git(host, dir, &["rev-parse", "--verify", "--quiet", &format!("{rev}^{{commit}}")])
+        .map_err(|_e| anyhow!("'{rev}' doesn't name a commit"))?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Gets the files tracked at a commit within `dir`, relative to `dir`.
+pub fn files_at(host: &impl Host, dir: &Path, commit: &str) -> anyhow::Result<Vec<String>> {
+    let output = git(host, dir, &["ls-tree", "-r", "-z", "--name-only", commit])?;
+    Ok(output
+        .split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect())
+}
+
+/// Gets the content of a file at a commit, given its path relative to `dir`.
+pub fn content_at(host: &impl Host, dir: &Path, commit: &str, path: &str) -> anyhow::Result<Vec<u8>> {
+    git(host, dir, &["show", &format!("{commit}:./{path}")])
+}
+
+/// Checks a commit out, with a detached `HEAD`, in a new worktree of the repository containing `dir`, at `dest`.
+pub fn add_worktree(host: &impl Host, dir: &Path, dest: &Path, commit: &str) -> anyhow::Result<()> {
+    _ = git(
+        host,
+        dir,
+        &["worktree", "add", "--detach", "--quiet", &dest.to_string_lossy(), commit],
+    )?;
+    Ok(())
+}
+
+/// Removes the worktree at `dest` of the repository containing `dir`, even when files in it changed, and has the
+/// repository forget about worktrees which are gone.
+pub fn remove_worktree(host: &impl Host, dir: &Path, dest: &Path) -> anyhow::Result<()> {
+    let removed = git(host, dir, &["worktree", "remove", "--force", &dest.to_string_lossy()]);
+    _ = git(host, dir, &["worktree", "prune"])?;
+    removed.map(|_| ())
+}
+
+/// Clones the latest commit of a repository into `dest`, which must not exist yet.
+pub fn shallow_clone(host: &impl Host, url: &str, dest: &Path) -> anyhow::Result<()> {
+    let parent = dest.parent().unwrap_or(dest);
+    _ = git(host, parent, &["clone", "--depth", "1", "--quiet", url, &dest.to_string_lossy()])?;
+    Ok(())
+}
+
+/// Gets the variables describing the state of the repository containing `dir`: `git_sha`, `git_short_sha`,
+/// `git_branch`, `git_dirty`, and `git_tag` when `HEAD` is tagged.
+///
+/// The branch is empty when `HEAD` is detached. Fails when `dir` isn't in a repository with commits.
+pub fn variables(host: &impl Host, dir: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let text = |args: &[&str]| git(host, dir, args).map(|output| String::from_utf8_lossy(&output).trim().to_string());
+
+    let mut variables = vec![
+        ("git_sha".to_string(), text(&["rev-parse", "HEAD"])?),
+        ("git_short_sha".to_string(), text(&["rev-parse", "--short", "HEAD"])?),
+        (
+            "git_branch".to_string(),
+            text(&["symbolic-ref", "--quiet", "--short", "HEAD"]).unwrap_or_default(),
+        ),
+        ("git_dirty".to_string(), (!modified_files(host, dir)?.is_empty()).to_string()),
+    ];
+
+    if let Ok(tag) = text(&["describe", "--tags", "--exact-match", "HEAD"]) {
+        variables.push(("git_tag".to_string(), tag));
+    }
+
+    Ok(variables)
+}
+
+/// Gets the URL of a remote of the repository containing `dir`.
+pub fn remote_url(host: &impl Host, dir: &Path, remote: &str) -> anyhow::Result<String> {
+    let output = git(host, dir, &["remote", "get-url", remote])?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Gets the name of the branch checked out in the repository containing `dir`, failing when `HEAD` is detached.
+pub fn current_branch(host: &impl Host, dir: &Path) -> anyhow::Result<String> {
+    let output = git(host, dir, &["symbolic-ref", "--quiet", "--short", "HEAD"]).context("no branch is checked out")?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Runs git with the given arguments, returning its output.
+fn git(host: &impl Host, dir: &Path, args: &[&str]) -> anyhow::Result<Vec<u8>> {
+    let mut cmd = Command::new("git");
+    _ = cmd.current_dir(dir).args(args).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let subcommand = args.first().copied().unwrap_or_default();
+    let output = host.output(&mut cmd).with_context(|| format!("unable to run 'git {subcommand}'"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'git {subcommand}' failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
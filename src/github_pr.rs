@@ -0,0 +1,256 @@
+use crate::git;
+use crate::host::Host;
+use anyhow::{Context, anyhow};
+use serde_json::{Value, json};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The environment variables the token used to post to GitHub is read from, in order of preference.
+pub const TOKEN_VARIABLES: [&str; 2] = ["GITHUB_TOKEN", "GH_TOKEN"];
+
+/// Marks the comment holding the summary, so later runs update it rather than add another one.
+const SUMMARY_MARKER: &str = "<!-- cargo-ci run summary -->";
+
+/// The most characters GitHub accepts in a comment, leaving room for the marker and the truncation note.
+const MAX_COMMENT_CHARS: usize = 65_000;
+
+/// How long a request to GitHub may take, in seconds.
+const REQUEST_TIMEOUT_SECS: &str = "30";
+
+/// How many items GitHub lists per page at most.
+const PAGE_SIZE: usize = 100;
+
+/// Posts a Markdown summary as a comment on the open pull request of the commit or branch checked out in `dir`, updating
+/// the comment posted by an earlier run instead when there is one. Returns the URL of the comment.
+///
+/// The repository is taken from `GITHUB_REPOSITORY` when it's set, and from the URL of the `origin` remote otherwise.
+/// Requests go to `GITHUB_API_URL` when it's set, for GitHub Enterprise, and to `https://api.github.com` otherwise.
+/// Scratch files, including one only readable by the current user which holds the token so it never shows up on a
+/// command line, are written to `scratch_dir` under names of this process's own, and removed once done.
+pub fn post_summary(host: &impl Host, dir: &Path, scratch_dir: &Path, markdown: &str) -> anyhow::Result<String> {
+    let var = |name: &str| {
+        host.vars()
+            .find(|(key, value)| key == name && !value.is_empty())
+            .map(|(_, value)| value)
+    };
+
+    let token = TOKEN_VARIABLES
+        .iter()
+        .find_map(|name| var(name))
+        .ok_or_else(|| anyhow!("no GitHub token found, set {}", TOKEN_VARIABLES.join(" or ")))?;
+    let repo = match var("GITHUB_REPOSITORY") {
+        Some(repo) => repo,
+        None => repository(&git::remote_url(host, dir, "origin")?)?,
+    };
+    let api_url = var("GITHUB_API_URL").unwrap_or_else(|| "https://api.github.com".to_string());
+
+    // CI systems check out pull requests on a detached HEAD, telling the pull request or its branch through variables
+    let head = Head {
+        pull_ref: var("GITHUB_REF"),
+        commit: git::resolve_commit(host, dir, "HEAD").ok(),
+        branch: var("GITHUB_HEAD_REF").or_else(|| git::current_branch(host, dir).ok()),
+    };
+
+    let id = std::process::id();
+    let headers = scratch_dir.join(format!("github-headers-{id}"));
+    write_private(&headers, &format!("Authorization: Bearer {token}\n"))
+        .with_context(|| format!("unable to write {}", headers.display()))?;
+    let client = Client {
+        host,
+        headers: &headers,
+        body: &scratch_dir.join(format!("github-body-{id}.json")),
+    };
+
+    let result = post(&client, &format!("{api_url}/repos/{repo}"), &repo, &head, markdown);
+    _ = fs::remove_file(client.headers);
+    _ = fs::remove_file(client.body);
+    result
+}
+
+/// What tells which pull request a run is for.
+struct Head {
+    /// The ref checked out by CI, which is `refs/pull/<number>/merge` for pull requests.
+    pull_ref: Option<String>,
+
+    commit: Option<String>,
+    branch: Option<String>,
+}
+
+fn post(client: &Client<'_, impl Host>, repo_url: &str, repo: &str, head: &Head, markdown: &str) -> anyhow::Result<String> {
+    let number = pull_request(client, repo_url, head)?.ok_or_else(|| {
+        head.branch.as_ref().map_or_else(
+            || anyhow!("no open pull request found for the checked out commit of {repo}"),
+            |branch| anyhow!("no open pull request found for branch '{branch}' of {repo}"),
+        )
+    })?;
+
+    let existing = client
+        .request_all(&format!("{repo_url}/issues/{number}/comments"))?
+        .into_iter()
+        .find_map(|comment| {
+            let body = comment["body"].as_str()?;
+            body.starts_with(SUMMARY_MARKER).then(|| comment["id"].as_u64()).flatten()
+        });
+
+    let body = json!({ "body": comment_body(markdown) });
+    let comment = match existing {
+        Some(id) => client.request("PATCH", &format!("{repo_url}/issues/comments/{id}"), Some(&body))?,
+        None => client.request("POST", &format!("{repo_url}/issues/{number}/comments"), Some(&body))?,
+    };
+
+    Ok(comment["html_url"].as_str().unwrap_or_default().to_string())
+}
+
+/// Finds the number of the open pull request a run is for: the one CI checked out, or else the one whose head is the
+/// checked out commit, or else one whose head is a branch of the same name, which may be in a fork.
+fn pull_request(client: &Client<'_, impl Host>, repo_url: &str, head: &Head) -> anyhow::Result<Option<u64>> {
+    if let Some(number) = head
+        .pull_ref
+        .as_deref()
+        .and_then(|pull_ref| pull_ref.strip_prefix("refs/pull/"))
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|number| number.parse().ok())
+    {
+        return Ok(Some(number));
+    }
+
+    let is_open = |pull: &Value| pull["state"].as_str() == Some("open");
+    if let Some(commit) = &head.commit {
+        // a commit which was never pushed is unknown to GitHub, which isn't an error here
+        let pulls = client
+            .request("GET", &format!("{repo_url}/commits/{commit}/pulls"), None)
+            .unwrap_or_default();
+        if let Some(number) = pulls
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|pull| is_open(pull))
+            .and_then(|pull| pull["number"].as_u64())
+        {
+            return Ok(Some(number));
+        }
+    }
+
+    let Some(branch) = &head.branch else {
+        return Ok(None);
+    };
+
+    Ok(client
+        .request_all(&format!("{repo_url}/pulls?state=open"))?
+        .into_iter()
+        .find(|pull| pull["head"]["ref"].as_str() == Some(branch))
+        .and_then(|pull| pull["number"].as_u64()))
+}
+
+/// Writes a file only the current user can read.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    _ = options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        _ = options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// The body of the comment, marked as the summary and cut short when it's too long for GitHub.
+fn comment_body(markdown: &str) -> String {
+    let mut body = format!("{SUMMARY_MARKER}\n{markdown}");
+    if let Some((cut, _)) = body.char_indices().nth(MAX_COMMENT_CHARS) {
+        body.truncate(cut);
+        body.push_str("\n\n*The summary was cut short, see the log for the rest.*\n");
+    }
+
+    body
+}
+
+/// Gets `owner/repo` from the URL of a GitHub remote, such as `https://github.com/owner/repo.git` or
+/// `git@github.com:owner/repo.git`.
+fn repository(remote_url: &str) -> anyhow::Result<String> {
+    let path = remote_url
+        .split_once("github.com")
+        .map(|(_, path)| path.trim_start_matches([':', '/']).trim_end_matches('/'))
+        .ok_or_else(|| anyhow!("the 'origin' remote, {remote_url}, isn't on GitHub, set GITHUB_REPOSITORY to the repository to post to"))?;
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    match path.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => Ok(path.to_string()),
+        _ => Err(anyhow!("unable to tell the repository from the 'origin' remote, {remote_url}")),
+    }
+}
+
+/// Sends requests to the GitHub REST API through curl.
+struct Client<'a, H> {
+    host: &'a H,
+
+    /// The file holding the authorization header.
+    headers: &'a Path,
+
+    /// The file the body of a request is written to.
+    body: &'a Path,
+}
+
+impl<H: Host> Client<'_, H> {
+    /// Gets every item of a list, page by page.
+    fn request_all(&self, url: &str) -> anyhow::Result<Vec<Value>> {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let mut items = Vec::new();
+        for page in 1.. {
+            let response = self.request("GET", &format!("{url}{separator}per_page={PAGE_SIZE}&page={page}"), None)?;
+            let Value::Array(page_items) = response else {
+                break;
+            };
+
+            let last = page_items.len() < PAGE_SIZE;
+            items.extend(page_items);
+            if last {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+
+    fn request(&self, method: &str, url: &str, body: Option<&Value>) -> anyhow::Result<Value> {
+        let mut cmd = Command::new("curl");
+        _ = cmd
+            .args(["--silent", "--show-error", "--max-time", REQUEST_TIMEOUT_SECS, "--request", method])
+            .args(["--header", "Accept: application/vnd.github+json"])
+            .args(["--header", "X-GitHub-Api-Version: 2022-11-28"])
+            .arg("--header")
+            .arg(format!("@{}", self.headers.display()))
+            .args(["--write-out", "\n%{http_code}"]);
+
+        if let Some(body) = body {
+            fs::write(self.body, body.to_string()).with_context(|| format!("unable to write {}", self.body.display()))?;
+            _ = cmd
+                .args(["--header", "Content-Type: application/json"])
+                .arg("--data-binary")
+                .arg(format!("@{}", self.body.display()));
+        }
+
+        _ = cmd.arg(url).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let output = self.host.output(&mut cmd).context("unable to run curl to reach GitHub")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "unable to reach GitHub: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (response, status) = stdout.trim_end().rsplit_once('\n').unwrap_or_else(|| ("", stdout.trim()));
+        let response: Value = serde_json::from_str(response).unwrap_or(Value::Null);
+        if !status.starts_with('2') {
+            let message = response["message"].as_str().unwrap_or("no details given");
+            return Err(anyhow!("GitHub refused {method} {url}: HTTP status {status}, {message}"));
+        }
+
+        Ok(response)
+    }
+}
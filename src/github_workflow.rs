@@ -0,0 +1,455 @@
+use anyhow::Context;
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+
+/// A GitHub Actions workflow, read only as far as running its jobs locally, or translating them, goes.
+#[derive(Debug, Deserialize)]
+pub struct Workflow {
+    #[serde(default)]
+    pub name: Option<String>,
+
+    #[serde(default)]
+    env: Mapping,
+
+    #[serde(default)]
+    jobs: Mapping,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RawJob {
+    #[serde(default)]
+    name: Option<String>,
+
+    #[serde(default)]
+    runs_on: Value,
+
+    #[serde(default, rename = "if")]
+    condition: Option<Value>,
+
+    #[serde(default)]
+    needs: Value,
+
+    #[serde(default)]
+    env: Mapping,
+
+    #[serde(default)]
+    strategy: Value,
+
+    #[serde(default)]
+    uses: Option<String>,
+
+    #[serde(default)]
+    steps: Vec<RawStep>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RawStep {
+    #[serde(default)]
+    name: Option<String>,
+
+    #[serde(default)]
+    run: Option<String>,
+
+    #[serde(default)]
+    uses: Option<String>,
+
+    #[serde(default)]
+    with: Mapping,
+
+    #[serde(default)]
+    env: Mapping,
+
+    #[serde(default)]
+    working_directory: Option<String>,
+
+    #[serde(default)]
+    shell: Option<String>,
+
+    #[serde(default, rename = "if")]
+    condition: Option<Value>,
+}
+
+/// A job of a workflow, along with what running it locally comes down to.
+#[derive(Debug)]
+pub struct WorkflowJob {
+    pub id: String,
+    pub name: String,
+    pub needs: Vec<String>,
+    pub mapping: JobMapping,
+}
+
+/// Whether, and how, a job of a workflow runs locally.
+#[derive(Debug)]
+pub enum JobMapping {
+    Runnable(Vec<LocalStep>),
+
+    /// The job doesn't apply here, such as when it runs on another OS or only for some events.
+    Skipped(String),
+
+    /// The job needs something with no local equivalent, such as an arbitrary action.
+    Unmappable(String),
+}
+
+/// A step of a workflow job, as it runs locally.
+#[derive(Debug)]
+pub struct LocalStep {
+    pub name: String,
+    pub condition: StepCondition,
+    pub action: LocalAction,
+}
+
+/// When a step runs, given how the earlier steps of its job turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepCondition {
+    /// When every earlier step succeeded, which is the default.
+    Success,
+    Failure,
+    Always,
+
+    /// Conditions which can't be evaluated locally, whose steps are skipped.
+    Unknown,
+}
+
+/// A job of a workflow as written, whichever machine it runs on, for translating it to a `cargo-ci` configuration.
+#[derive(Debug)]
+pub struct PortableJob {
+    pub id: String,
+    pub name: String,
+    pub needs: Vec<String>,
+
+    /// The labels of the runners the job runs on, from `runs-on` or the `os` entry of its matrix.
+    pub runners: Vec<String>,
+
+    pub condition: Option<String>,
+
+    /// The reusable workflow the job calls instead of running steps.
+    pub reusable_workflow: Option<String>,
+
+    /// The names of the environment variables the workflow and the job set.
+    pub env: Vec<String>,
+
+    pub steps: Vec<PortableStep>,
+}
+
+/// A step of a workflow job as written.
+#[derive(Debug)]
+pub struct PortableStep {
+    /// The name the step is given, if any.
+    pub name: Option<String>,
+
+    /// What the step is called in messages, which is its name or what it runs.
+    pub label: String,
+
+    pub condition: StepCondition,
+    pub shell: Option<String>,
+    pub working_directory: Option<String>,
+
+    /// The script the step runs, `None` when it uses an action which only matters in the cloud, or why it can't run
+    /// locally.
+    pub script: Result<Option<String>, String>,
+}
+
+#[derive(Debug)]
+pub enum LocalAction {
+    /// Runs a script through a shell, given as the program and arguments which precede the script.
+    Run {
+        shell: Vec<String>,
+        script: String,
+        working_directory: Option<String>,
+        env: BTreeMap<String, String>,
+    },
+
+    /// Does nothing locally, for the given reason.
+    Skip(&'static str),
+}
+
+/// Actions which are only needed in the cloud, along with why they're skipped locally.
+const CLOUD_ONLY_ACTIONS: [(&str, &str); 12] = [
+    ("actions/checkout", "the local checkout is used"),
+    ("actions/cache", "caching only matters in the cloud"),
+    ("swatinem/rust-cache", "caching only matters in the cloud"),
+    ("mozilla-actions/sccache-action", "caching only matters in the cloud"),
+    ("actions/upload-artifact", "it only uploads to the cloud"),
+    ("actions/download-artifact", "it only downloads from the cloud"),
+    ("codecov/codecov-action", "it only uploads to the cloud"),
+    ("coverallsapp/github-action", "it only uploads to the cloud"),
+    ("dtolnay/rust-toolchain", "the local toolchain is used"),
+    ("actions-rs/toolchain", "the local toolchain is used"),
+    ("actions-rust-lang/setup-rust-toolchain", "the local toolchain is used"),
+    ("taiki-e/install-action", "tools are expected to be installed locally"),
+];
+
+impl Workflow {
+    /// Parses the text of a workflow file.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        serde_yaml::from_str(text).context("invalid workflow")
+    }
+
+    /// The jobs of the workflow, in the order they're written in, each mapped to what running it locally takes.
+    pub fn jobs(&self) -> anyhow::Result<Vec<WorkflowJob>> {
+        let workflow_env = literal_env(&self.env);
+        self.jobs
+            .iter()
+            .map(|(id, job)| {
+                let id = id.as_str().context("invalid job id")?.to_string();
+                let job: RawJob = serde_yaml::from_value(job.clone()).with_context(|| format!("invalid job '{id}'"))?;
+
+                let mut env = workflow_env.clone();
+                env.extend(literal_env(&job.env));
+
+                Ok(WorkflowJob {
+                    name: job.name.clone().unwrap_or_else(|| id.clone()),
+                    needs: strings(&job.needs),
+                    mapping: map_job(&job, &env),
+                    id,
+                })
+            })
+            .collect()
+    }
+
+    /// The jobs of the workflow in the order they're written in, whether or not they can run on this machine.
+    pub fn portable_jobs(&self) -> anyhow::Result<Vec<PortableJob>> {
+        self.jobs
+            .iter()
+            .map(|(id, job)| {
+                let id = id.as_str().context("invalid job id")?.to_string();
+                let job: RawJob = serde_yaml::from_value(job.clone()).with_context(|| format!("invalid job '{id}'"))?;
+
+                let mut runners = strings(&job.runs_on);
+                if runners.iter().any(|label| label.contains("${{")) {
+                    runners = strings(&job.strategy["matrix"]["os"]);
+                }
+
+                let steps = job
+                    .steps
+                    .iter()
+                    .enumerate()
+                    .map(|(index, step)| PortableStep {
+                        name: step.name.clone(),
+                        label: step_name(step, index),
+                        condition: step_condition(step.condition.as_ref()),
+                        shell: step.shell.clone(),
+                        working_directory: step.working_directory.clone(),
+                        script: portable_script(step),
+                    })
+                    .collect();
+
+                Ok(PortableJob {
+                    name: job.name.clone().unwrap_or_else(|| id.clone()),
+                    needs: strings(&job.needs),
+                    runners,
+                    condition: job.condition.as_ref().map(scalar_text),
+                    reusable_workflow: job.uses.clone(),
+                    env: self
+                        .env
+                        .keys()
+                        .chain(job.env.keys())
+                        .filter_map(Value::as_str)
+                        .map(ToString::to_string)
+                        .collect(),
+                    steps,
+                    id,
+                })
+            })
+            .collect()
+    }
+}
+
+fn map_job(job: &RawJob, env: &BTreeMap<String, String>) -> JobMapping {
+    if let Some(workflow) = &job.uses {
+        return JobMapping::Unmappable(format!("calls the reusable workflow '{workflow}'"));
+    }
+
+    if let Some(condition) = &job.condition {
+        return JobMapping::Skipped(format!("its condition, `{}`, can't be evaluated locally", scalar_text(condition)));
+    }
+
+    if let Some(reason) = unsupported_runner(job) {
+        return JobMapping::Skipped(reason);
+    }
+
+    let mut steps = Vec::new();
+    for (index, step) in job.steps.iter().enumerate() {
+        let name = step_name(step, index);
+        match map_step(step, env) {
+            Ok(action) => steps.push(LocalStep {
+                condition: step_condition(step.condition.as_ref()),
+                name,
+                action,
+            }),
+            Err(reason) => return JobMapping::Unmappable(format!("step '{name}' {reason}")),
+        }
+    }
+
+    JobMapping::Runnable(steps)
+}
+
+/// The name of a step, or what it runs when it has none.
+fn step_name(step: &RawStep, index: usize) -> String {
+    step.name.clone().unwrap_or_else(|| {
+        step.uses
+            .clone()
+            .or_else(|| step.run.as_deref().and_then(|run| run.lines().next()).map(ToString::to_string))
+            .unwrap_or_else(|| format!("step {}", index + 1))
+    })
+}
+
+/// The script a step runs, whichever shell it runs through.
+fn portable_script(step: &RawStep) -> Result<Option<String>, String> {
+    let script = match (&step.run, &step.uses) {
+        (Some(run), _) => run.clone(),
+        (None, Some(uses)) => match map_action(uses, &step.with)? {
+            Ok(script) => script,
+            Err(_) => return Ok(None),
+        },
+        (None, None) => return Err("neither runs a command nor uses an action".to_string()),
+    };
+
+    if script.contains("${{") {
+        return Err("uses expressions, which can't be evaluated locally".to_string());
+    }
+
+    Ok(Some(script))
+}
+
+/// Explains why the job's runner doesn't match this machine, if it doesn't.
+///
+/// Runners given through the `os` entry of the job's matrix match when any entry of the matrix matches.
+fn unsupported_runner(job: &RawJob) -> Option<String> {
+    let mut labels = strings(&job.runs_on);
+    if labels.iter().any(|label| label.contains("${{")) {
+        labels = strings(&job.strategy["matrix"]["os"]);
+        if labels.is_empty() {
+            return Some("its runner is chosen by an expression which can't be evaluated locally".to_string());
+        }
+
+        return (!labels.iter().any(|label| runner_matches(label)))
+            .then(|| format!("none of its runners, {}, match this OS", labels.join(", ")));
+    }
+
+    labels
+        .iter()
+        .find(|label| !runner_matches(label))
+        .map(|label| format!("it runs on '{label}'"))
+}
+
+fn runner_matches(label: &str) -> bool {
+    let label = label.to_ascii_lowercase();
+    if label.contains("windows") {
+        cfg!(windows)
+    } else if label.contains("macos") {
+        cfg!(target_os = "macos")
+    } else {
+        // self-hosted runners whose labels don't name an OS are assumed to match
+        cfg!(target_os = "linux") || !(label.contains("ubuntu") || label.contains("linux"))
+    }
+}
+
+fn map_step(step: &RawStep, job_env: &BTreeMap<String, String>) -> Result<LocalAction, String> {
+    let (script, shell) = match (&step.run, &step.uses) {
+        (Some(run), _) => (run.clone(), step.shell.as_deref()),
+        (None, Some(uses)) => match map_action(uses, &step.with)? {
+            Ok(script) => (script, None),
+            Err(reason) => return Ok(LocalAction::Skip(reason)),
+        },
+        (None, None) => return Err("neither runs a command nor uses an action".to_string()),
+    };
+
+    if script.contains("${{") {
+        return Err("uses expressions, which can't be evaluated locally".to_string());
+    }
+
+    let mut env = job_env.clone();
+    env.extend(literal_env(&step.env));
+
+    Ok(LocalAction::Run {
+        shell: shell_program(shell)?,
+        script,
+        working_directory: step.working_directory.clone(),
+        env,
+    })
+}
+
+/// Maps an action to the script it amounts to, or to the reason it's skipped locally.
+fn map_action(uses: &str, with: &Mapping) -> Result<Result<String, &'static str>, String> {
+    let action = uses.split_once('@').map_or(uses, |(action, _)| action).to_ascii_lowercase();
+    if let Some((_, reason)) = CLOUD_ONLY_ACTIONS.iter().find(|(name, _)| *name == action) {
+        return Ok(Err(reason));
+    }
+
+    let input = |name: &str| with.get(name).map(scalar_text).unwrap_or_default();
+    match action.as_str() {
+        "actions-rs/cargo" if input("use-cross") != "true" => {
+            Ok(Ok(format!("cargo {} {}", input("command"), input("args")).trim().to_string()))
+        }
+        "actions-rs/clippy-check" => Ok(Ok(format!("cargo clippy {}", input("args")).trim().to_string())),
+        _ => Err(format!("uses the action '{uses}', which has no local equivalent")),
+    }
+}
+
+/// The program and arguments preceding the script for a step's shell, as GitHub runs them.
+fn shell_program(shell: Option<&str>) -> Result<Vec<String>, String> {
+    let program: &[&str] = match shell {
+        None if cfg!(windows) => &["pwsh", "-Command"],
+        None => &["bash", "-e", "-c"],
+        Some("bash") => &["bash", "--noprofile", "--norc", "-eo", "pipefail", "-c"],
+        Some("sh") => &["sh", "-e", "-c"],
+        Some("pwsh") => &["pwsh", "-Command"],
+        Some("powershell") => &["powershell", "-Command"],
+        Some("python") => &["python", "-c"],
+        Some("cmd") => &["cmd", "/D", "/E:ON", "/V:OFF", "/S", "/C"],
+        Some(shell) => return Err(format!("uses the shell '{shell}', which isn't supported locally")),
+    };
+
+    Ok(program.iter().map(ToString::to_string).collect())
+}
+
+fn step_condition(condition: Option<&Value>) -> StepCondition {
+    let Some(condition) = condition else {
+        return StepCondition::Success;
+    };
+
+    let text = scalar_text(condition);
+    let text = text.trim();
+    let text = text
+        .strip_prefix("${{")
+        .and_then(|text| text.strip_suffix("}}"))
+        .unwrap_or(text)
+        .trim();
+
+    match text {
+        "success()" => StepCondition::Success,
+        "failure()" => StepCondition::Failure,
+        "always()" | "!cancelled()" => StepCondition::Always,
+        _ => StepCondition::Unknown,
+    }
+}
+
+/// The variables of an `env` table whose values are known without evaluating expressions.
+fn literal_env(env: &Mapping) -> BTreeMap<String, String> {
+    env.iter()
+        .filter_map(|(key, value)| Some((key.as_str()?.to_string(), scalar_text(value))))
+        .filter(|(_, value)| !value.contains("${{"))
+        .collect()
+}
+
+/// The strings of a value which is either a single string or a list of them.
+fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Sequence(values) => values.iter().filter_map(Value::as_str).map(ToString::to_string).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
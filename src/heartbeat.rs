@@ -0,0 +1,267 @@
+use crate::host::Host;
+use crate::summary::format_duration;
+use core::fmt::Write;
+use core::time::Duration;
+use std::io::{self, Read};
+use std::process::{Child, ExitStatus, Output};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Instant;
+
+/// How often to check on a step while waiting for it.
+const TICK: Duration = Duration::from_secs(1);
+
+/// How long to keep collecting output once a stalled step is killed, since processes it started may keep its pipes open.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How to watch over steps which go quiet.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Heartbeat {
+    /// How long a step may go without output before a heartbeat is reported, and then between heartbeats.
+    pub interval: Option<Duration>,
+
+    /// How long a step may go without output before it's reported as possibly stalled.
+    pub stall_after: Option<Duration>,
+
+    /// Whether to kill steps once they're reported as possibly stalled.
+    pub kill_stalled: bool,
+
+    /// When to kill steps which are still running, once the run went over its time budget and the grace period ended.
+    pub deadline: Option<Instant>,
+
+    /// Whether to keep checking if the step's job was cancelled, killing the step when it is.
+    pub cancellable: bool,
+
+    /// How long a step may run before it's reported as running much longer than expected.
+    pub overdue_after: Option<Duration>,
+}
+
+impl Heartbeat {
+    const fn is_enabled(&self) -> bool {
+        self.interval.is_some() || self.stall_after.is_some() || self.deadline.is_some() || self.cancellable || self.overdue_after.is_some()
+    }
+}
+
+/// How a step ended.
+#[derive(Debug)]
+pub enum Waited {
+    Exited(Output),
+
+    /// The step was killed after going without output for the given time.
+    Killed(Output, Duration),
+
+    /// The step was killed since the run went over its time budget.
+    OutOfTime(Output),
+
+    /// The step was killed since its job was cancelled.
+    Cancelled,
+}
+
+/// Waits for a step to finish, collecting its output, while reporting heartbeats for as long as it produces no output
+/// and flagging it once it looks stalled.
+///
+/// For cancellable steps, `cancelled` is asked every now and then whether the step's job was cancelled.
+pub fn wait<H: Host>(
+    host: &H,
+    mut child: Child,
+    name: &str,
+    heartbeat: Heartbeat,
+    mut report: impl FnMut(String),
+    cancelled: impl Fn() -> bool,
+) -> io::Result<Waited> {
+    if !heartbeat.is_enabled() {
+        let pid = child.id();
+        let output = child.wait_with_output();
+        host.release_group(pid);
+        return output.map(Waited::Exited);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward(stdout, Stream::Stdout, tx.clone());
+    }
+
+    if let Some(stderr) = child.stderr.take() {
+        forward(stderr, Stream::Stderr, tx);
+    } else {
+        drop(tx);
+    }
+
+    let start = host.now();
+    let mut last_output = start;
+    let mut last_report = start;
+    let mut last_cpu = None;
+    let mut stalled = false;
+    let mut overdue = false;
+    let mut killed = None;
+    let mut stop: Option<fn(Output) -> Waited> = None;
+    let mut output = Output {
+        status: ExitStatus::default(),
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    };
+
+    loop {
+        let timeout = if killed.is_some() { DRAIN_TIMEOUT } else { TICK };
+        match rx.recv_timeout(timeout) {
+            Ok((Stream::Stdout, chunk)) => output.stdout.extend(chunk),
+            Ok((Stream::Stderr, chunk)) => output.stderr.extend(chunk),
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) if killed.is_some() => break,
+            Err(RecvTimeoutError::Timeout) => {
+                let now = host.now();
+                let silence = now - last_output;
+
+                if let Some(overdue_after) = heartbeat.overdue_after
+                    && now - start >= overdue_after
+                    && !overdue
+                {
+                    overdue = true;
+                    report(format!(
+                        "step '{name}' has been running for {}, much longer than expected",
+                        format_duration(now - start)
+                    ));
+                }
+
+                if let Some(deadline) = heartbeat.deadline
+                    && now >= deadline
+                {
+                    report(format!("killing step '{name}', since the run went over its time budget"));
+                    host.kill_group(&mut child)?;
+                    killed = Some(silence);
+                    stop = Some(Waited::OutOfTime);
+                } else if heartbeat.cancellable && cancelled() {
+                    report(format!("killing step '{name}', since its job was cancelled"));
+                    host.kill_group(&mut child)?;
+                    killed = Some(silence);
+                    stop = Some(|_| Waited::Cancelled);
+                } else if let Some(stall_after) = heartbeat.stall_after
+                    && silence >= stall_after
+                    && !stalled
+                {
+                    stalled = true;
+                    last_report = now;
+                    report(format!(
+                        "step '{name}' is possibly stalled, with no output for {}",
+                        format_duration(silence)
+                    ));
+                    if heartbeat.kill_stalled {
+                        report(format!("killing step '{name}'"));
+                        host.kill_group(&mut child)?;
+                        killed = Some(silence);
+                    }
+                } else if let Some(interval) = heartbeat.interval
+                    && now - last_output.max(last_report) >= interval
+                {
+                    last_report = now;
+                    report(beat(name, now - start, silence, cpu_time(child.id()), &mut last_cpu));
+                }
+
+                continue;
+            }
+        }
+
+        last_output = host.now();
+        stalled = false;
+    }
+
+    let status = child.wait();
+    host.release_group(child.id());
+    output.status = status?;
+    Ok(match (killed, stop) {
+        (Some(_), Some(stop)) => stop(output),
+        (Some(silence), None) => Waited::Killed(output, silence),
+        (None, _) => Waited::Exited(output),
+    })
+}
+
+/// Describes a step which is still running.
+fn beat(name: &str, elapsed: Duration, silence: Duration, cpu: Option<Duration>, last_cpu: &mut Option<Duration>) -> String {
+    let mut message = format!(
+        "step '{name}' still running after {}, with no output for {}",
+        format_duration(elapsed),
+        format_duration(silence)
+    );
+
+    if let Some(cpu) = cpu {
+        _ = write!(message, ", {} of CPU time used", format_duration(cpu));
+        if let Some(last) = last_cpu.replace(cpu) {
+            _ = write!(message, " ({} since the last heartbeat)", format_duration(cpu.saturating_sub(last)));
+        }
+    }
+
+    message
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Reads a pipe on a separate thread, sending along whatever comes out of it.
+fn forward(mut pipe: impl Read + Send + 'static, stream: Stream, tx: mpsc::Sender<(Stream, Vec<u8>)>) {
+    _ = thread::spawn(move || {
+        let mut buffer = [0; 8192];
+        loop {
+            match pipe.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if tx.send((stream, buffer[..n].to_vec())).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// The CPU time used so far by a process along with all of its descendants.
+#[cfg(target_os = "linux")]
+fn cpu_time(root: u32) -> Option<Duration> {
+    // the kernel reports CPU time in clock ticks, of which there are 100 per second on every Linux platform
+    const TICKS_PER_SEC: u64 = 100;
+
+    let mut processes = Vec::new();
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+
+        // the process name comes in parentheses and may hold anything, so fields are counted from its end
+        let Some((_, fields)) = stat.rsplit_once(')') else {
+            continue;
+        };
+
+        let fields: Vec<_> = fields.split_whitespace().collect();
+        let field = |index: usize| fields.get(index)?.parse::<u64>().ok();
+        if let (Some(ppid), Some(utime), Some(stime), Some(cutime), Some(cstime)) = (field(1), field(11), field(12), field(13), field(14)) {
+            processes.push((pid, ppid, utime + stime + cutime + cstime));
+        }
+    }
+
+    let mut tree = vec![root];
+    let mut ticks = 0;
+    let mut index = 0;
+    while let Some(&pid) = tree.get(index) {
+        index += 1;
+        for &(child, ppid, used) in &processes {
+            if child == pid {
+                ticks += used;
+            } else if u64::from(pid) == ppid {
+                tree.push(child);
+            }
+        }
+    }
+
+    Some(Duration::from_millis(ticks * 1000 / TICKS_PER_SEC))
+}
+
+#[cfg(not(target_os = "linux"))]
+const fn cpu_time(_root: u32) -> Option<Duration> {
+    None
+}
@@ -0,0 +1,256 @@
+use crate::fingerprint::Fingerprint;
+use crate::long_paths;
+use anyhow::Context;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// How many runs the history keeps.
+const MAX_RUNS: usize = 200;
+
+/// How many earlier runs of a step it takes to tell how long it usually takes.
+const MIN_DURATION_SAMPLES: usize = 3;
+
+/// How many of a step's most recent runs are considered by default.
+pub const DEFAULT_WINDOW: usize = 20;
+
+/// How many times a step's outcome must flip between success and failure within the runs considered for it to be flaky.
+pub const FLAKY_FLIPS: usize = 3;
+
+/// How a step turned out in one run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepRecord {
+    pub job: String,
+    pub step: String,
+    pub failed: bool,
+
+    /// How long the step took, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+
+    /// How long the step took for each package, in milliseconds, for sharded steps.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub package_durations_ms: BTreeMap<String, u64>,
+}
+
+/// How one run turned out.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub timestamp: String,
+    pub steps: Vec<StepRecord>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
+
+    /// The digest of the run's inputs, as computed by [`Fingerprint::digest_inputs`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<String>,
+
+    /// The jobs which passed, including those skipped because they passed before with the same inputs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passed: Vec<String>,
+
+    /// How many bytes the target directory took up once the run was over.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_size: Option<u64>,
+
+    /// Whether the run never finished, having been killed or having crashed, as noticed by the next run.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub crashed: bool,
+}
+
+/// The outcomes of the steps of past runs, kept in the target directory as one JSON document per run.
+#[derive(Debug, Default)]
+pub struct History {
+    runs: Vec<RunRecord>,
+}
+
+/// How a step fared over its recent runs.
+#[derive(Debug)]
+pub struct StepStats {
+    pub job: String,
+    pub step: String,
+    pub runs: usize,
+    pub failures: usize,
+
+    /// How many times the outcome changed from one run to the next.
+    pub flips: usize,
+
+    /// Whether the latest run failed, along with how many runs in a row ended the same way.
+    pub streak: (bool, usize),
+}
+
+impl StepStats {
+    #[must_use]
+    pub const fn is_flaky(&self) -> bool {
+        self.flips >= FLAKY_FLIPS
+    }
+
+    #[must_use]
+    #[expect(clippy::cast_precision_loss, reason = "Run counts are small")]
+    pub fn failure_rate(&self) -> f64 {
+        self.failures as f64 / self.runs.max(1) as f64
+    }
+}
+
+impl History {
+    /// Where the history of a workspace is kept.
+    #[must_use]
+    pub fn path(target_dir: &Path) -> PathBuf {
+        target_dir.join("ci-history.jsonl")
+    }
+
+    /// Reads the history, which is empty when the file doesn't exist yet.
+    ///
+    /// Lines which can't be parsed, such as one cut short by an interrupted write, are ignored.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = match fs::read_to_string(long_paths::extended(path)?) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("unable to read run history from {}", path.display())),
+        };
+
+        Ok(Self {
+            runs: text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+        })
+    }
+
+    /// Adds a run to the history file, dropping the oldest runs once there are too many, and returns the updated history.
+    pub fn append(path: &Path, run: RunRecord) -> anyhow::Result<Self> {
+        let mut history = Self::load(path)?;
+        history.runs.push(run);
+
+        let excess = history.runs.len().saturating_sub(MAX_RUNS);
+        _ = history.runs.drain(..excess);
+
+        let mut text = Vec::new();
+        for run in &history.runs {
+            serde_json::to_writer(&mut text, run)?;
+            text.write_all(b"\n")?;
+        }
+
+        fs::write(long_paths::extended(path)?, text).with_context(|| format!("unable to write run history to {}", path.display()))?;
+        Ok(history)
+    }
+
+    /// The fingerprint of the latest run which recorded one.
+    #[must_use]
+    pub fn last_fingerprint(&self) -> Option<&Fingerprint> {
+        self.runs.iter().rev().find_map(|run| run.fingerprint.as_ref())
+    }
+
+    /// The size of the target directory after the latest run which recorded one.
+    #[must_use]
+    pub fn last_target_size(&self) -> Option<u64> {
+        self.runs.iter().rev().find_map(|run| run.target_size)
+    }
+
+    /// The jobs which passed the last time they ran, in a run whose inputs had the given digest.
+    #[must_use]
+    pub fn passed_jobs(&self, inputs: &str) -> HashSet<&str> {
+        let mut seen = HashSet::new();
+        let mut passed = HashSet::new();
+        for run in self.runs.iter().rev() {
+            let jobs = run
+                .passed
+                .iter()
+                .map(String::as_str)
+                .chain(run.steps.iter().map(|step| step.job.as_str()));
+            for job in jobs {
+                if seen.insert(job) && run.passed.iter().any(|x| x == job) && run.inputs.as_deref() == Some(inputs) {
+                    _ = passed.insert(job);
+                }
+            }
+        }
+
+        passed
+    }
+
+    /// How the steps of the latest run turned out.
+    #[must_use]
+    pub fn last_run_steps(&self) -> &[StepRecord] {
+        self.runs.last().map_or(&[], |run| &run.steps)
+    }
+
+    /// The median of how long a step took over the last `window` runs it took part in, when there are enough of them
+    /// for it to mean anything.
+    #[must_use]
+    pub fn typical_duration(&self, job: &str, step: &str, window: usize) -> Option<Duration> {
+        let mut durations: Vec<u64> = self
+            .runs
+            .iter()
+            .flat_map(|run| &run.steps)
+            .filter(|record| record.job == job && record.step == step)
+            .filter_map(|record| record.duration_ms)
+            .collect();
+
+        let excess = durations.len().saturating_sub(window);
+        _ = durations.drain(..excess);
+        if durations.len() < MIN_DURATION_SAMPLES {
+            return None;
+        }
+
+        durations.sort_unstable();
+        durations.get(durations.len() / 2).copied().map(Duration::from_millis)
+    }
+
+    /// The median of how long a step took for each package over the last `window` runs that processed the package, by
+    /// package name, for the packages such runs recorded durations for.
+    #[must_use]
+    pub fn package_durations(&self, job: &str, step: &str, window: usize) -> HashMap<String, Duration> {
+        let mut samples: HashMap<&str, Vec<u64>> = HashMap::new();
+        for record in self
+            .runs
+            .iter()
+            .flat_map(|run| &run.steps)
+            .filter(|record| record.job == job && record.step == step)
+        {
+            for (package, duration_ms) in &record.package_durations_ms {
+                samples.entry(package).or_default().push(*duration_ms);
+            }
+        }
+
+        samples
+            .into_iter()
+            .filter_map(|(package, mut durations)| {
+                let excess = durations.len().saturating_sub(window);
+                _ = durations.drain(..excess);
+                durations.sort_unstable();
+                let median = durations.get(durations.len() / 2)?;
+                Some((package.to_string(), Duration::from_millis(*median)))
+            })
+            .collect()
+    }
+
+    /// Summarizes how every step fared over the last `window` runs it took part in, in the order steps first appear.
+    #[must_use]
+    pub fn step_stats(&self, window: usize) -> Vec<StepStats> {
+        let mut outcomes: Vec<((&str, &str), Vec<bool>)> = Vec::new();
+        for record in self.runs.iter().flat_map(|run| &run.steps) {
+            let key = (record.job.as_str(), record.step.as_str());
+            match outcomes.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, failed)) => failed.push(record.failed),
+                None => outcomes.push((key, vec![record.failed])),
+            }
+        }
+
+        outcomes
+            .into_iter()
+            .map(|((job, step), failed)| {
+                let recent = &failed[failed.len().saturating_sub(window)..];
+                let last = recent.last().copied().unwrap_or_default();
+                StepStats {
+                    job: job.to_string(),
+                    step: step.to_string(),
+                    runs: recent.len(),
+                    failures: recent.iter().filter(|failed| **failed).count(),
+                    flips: recent.iter().zip(recent.iter().skip(1)).filter(|(a, b)| a != b).count(),
+                    streak: (last, recent.iter().rev().take_while(|failed| **failed == last).count()),
+                }
+            })
+            .collect()
+    }
+}
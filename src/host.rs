@@ -1,16 +1,73 @@
-use std::process::{Child, Command};
+use crate::child_process;
+use chrono::{DateTime, Local};
+use core::time::Duration;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output};
+use std::time::Instant;
 
 /// Abstract the host environment to enable testing
+///
+/// Covers spawning processes, environment variables, whether stdout is a terminal, the clock, reading configuration
+/// files, stdin, and the lines printed to stdout and stderr. Other file system access, such as to logs, history, and
+/// caches under the target directory, along with the terminal handling of the status board and the outputter, goes to
+/// the real system directly.
 pub trait Host: Send + Sync {
     /// Spawn the given command
-    fn spawn(&self, cmd: &mut Command) -> std::io::Result<Child>;
+    fn spawn(&self, cmd: &mut Command) -> io::Result<Child>;
+
+    /// Spawn the given command, in a process group of its own or under a pseudo-terminal as the options ask
+    ///
+    /// Where a pseudo-terminal isn't available, the command is spawned as it is.
+    fn spawn_with(&self, cmd: &mut Command, options: SpawnOptions) -> io::Result<Child>;
+
+    /// Kill a child along with the processes it started, for children spawned in a process group of their own
+    fn kill_group(&self, child: &mut Child) -> io::Result<()>;
+
+    /// Stop forwarding Ctrl-C to a child spawned in a process group of its own, given its pid, once it exited
+    fn release_group(&self, pid: u32);
+
+    /// Run the given command to completion, capturing its output
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        self.spawn(cmd).and_then(Child::wait_with_output)
+    }
 
     /// Gets all environment variables as key-value pairs
     fn vars(&self) -> impl Iterator<Item = (String, String)>;
 
+    /// Gets the current working directory
+    fn current_dir(&self) -> io::Result<PathBuf>;
+
+    /// Check whether stdout is attached to a terminal
+    fn is_terminal(&self) -> bool;
+
+    /// Check whether stdin is attached to a terminal, so someone can answer questions
+    fn stdin_is_terminal(&self) -> bool;
+
+    /// Gets a monotonic timestamp, used to measure durations
+    fn now(&self) -> Instant;
+
+    /// Gets the current local date and time
+    fn local_time(&self) -> DateTime<Local>;
+
+    /// Block the current thread for the given duration
+    fn sleep(&self, duration: Duration);
+
+    /// Check whether the given path exists and is a file
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Read the entire content of a file
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Read a line from stdin, returning an empty string at the end of the input.
+    fn read_line(&self) -> io::Result<String>;
+
     /// Write formatted output to stdout.
     fn println_fmt(&self, args: core::fmt::Arguments<'_>);
 
+    /// Write formatted output to stdout without ending the line, such as an escape sequence.
+    fn print_fmt(&self, args: core::fmt::Arguments<'_>);
+
     /// Write formatted output to stderr.
     fn eprintln_fmt(&self, args: core::fmt::Arguments<'_>);
 
@@ -31,6 +88,17 @@ pub trait Host: Send + Sync {
     fn should_fail_silently(&self) -> bool;
 }
 
+/// How [`Host::spawn_with`] starts a child.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpawnOptions {
+    /// Start the child in a process group of its own, so [`Host::kill_group`] also kills the processes it starts.
+    /// Ctrl-C is forwarded to the group until [`Host::release_group`] is called.
+    pub process_group: bool,
+
+    /// Attach the child's output to a pseudo-terminal, merging its standard error into its standard output.
+    pub pty: bool,
+}
+
 /// Default host that runs real OS commands.
 #[derive(Debug, Clone, Default)]
 pub struct RealHost {
@@ -46,19 +114,87 @@ impl RealHost {
 }
 
 impl Host for RealHost {
-    fn spawn(&self, cmd: &mut Command) -> std::io::Result<Child> {
+    fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
         cmd.spawn()
     }
 
+    fn spawn_with(&self, cmd: &mut Command, options: SpawnOptions) -> io::Result<Child> {
+        let mut wrapped = options.pty.then(|| child_process::under_pty(cmd)).flatten();
+        let cmd = wrapped.as_mut().unwrap_or(cmd);
+        if options.process_group {
+            child_process::set_process_group(cmd);
+        }
+
+        let child = cmd.spawn()?;
+        if options.process_group {
+            child_process::track_group(&child);
+        }
+
+        Ok(child)
+    }
+
+    fn kill_group(&self, child: &mut Child) -> io::Result<()> {
+        child_process::kill_group(child)
+    }
+
+    fn release_group(&self, pid: u32) {
+        child_process::release_group(pid);
+    }
+
     fn vars(&self) -> impl Iterator<Item = (String, String)> {
         std::env::vars_os().map(|(k, v)| (k.to_string_lossy().into_owned(), v.to_string_lossy().into_owned()))
     }
 
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        std::env::current_dir()
+    }
+
+    fn is_terminal(&self) -> bool {
+        io::stdout().is_terminal()
+    }
+
+    fn stdin_is_terminal(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn local_time(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn read_line(&self) -> io::Result<String> {
+        let mut line = String::new();
+        _ = io::stdin().read_line(&mut line)?;
+        Ok(line)
+    }
+
     #[expect(clippy::print_stdout, reason = "Real host outputs to stdout")]
     fn println_fmt(&self, args: core::fmt::Arguments<'_>) {
         println!("{args}");
     }
 
+    fn print_fmt(&self, args: core::fmt::Arguments<'_>) {
+        // written in one go under the lock of stdout, so it doesn't land in the middle of lines other threads print
+        let mut stdout = io::stdout().lock();
+        _ = stdout.write_fmt(args);
+        _ = stdout.flush();
+    }
+
     #[expect(clippy::print_stderr, reason = "Real host outputs to stderr")]
     fn eprintln_fmt(&self, args: core::fmt::Arguments<'_>) {
         eprintln!("{args}");
@@ -0,0 +1,106 @@
+use crate::git;
+use crate::host::Host;
+use crate::long_paths;
+use crate::run_lock::RunLock;
+use anyhow::Context;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The start of the names of the worktrees within the scratch directory.
+const PREFIX: &str = "isolated-";
+
+/// A git worktree with a commit of a workspace's repository checked out, where runs with `--isolated` happen, so the
+/// uncommitted changes and untracked files of the checkout can't affect them.
+///
+/// Every run gets a worktree of its own, named after the commit and the process, and holds a lock on it until it calls
+/// [`Self::remove`]. Worktrees whose lock nobody holds, left behind by runs which were killed, are removed when the next
+/// one is created.
+#[derive(Debug)]
+pub struct IsolatedWorktree {
+    repo: PathBuf,
+    root: PathBuf,
+    workspace_root: PathBuf,
+    commit: String,
+    lock: RunLock,
+}
+
+impl IsolatedWorktree {
+    /// Checks the given revision of the repository containing `workspace_root` out in a worktree within `scratch_dir`.
+    pub fn create(host: &impl Host, workspace_root: &Path, scratch_dir: &Path, rev: &str) -> anyhow::Result<Self> {
+        let commit = git::resolve_commit(host, workspace_root, rev)?;
+        let repo = git::toplevel(host, workspace_root)?;
+        let root = scratch_dir.join(format!("{PREFIX}{}-{}", short(&commit), std::process::id()));
+
+        // the lock is taken before the worktree exists, so other runs never take it for one left behind
+        let lock = RunLock::acquire(&lock_path(&root), true, || {})?;
+        remove_abandoned(host, &repo, scratch_dir, &root);
+
+        fs::create_dir_all(long_paths::extended(scratch_dir)?).with_context(|| format!("unable to create {}", scratch_dir.display()))?;
+        git::add_worktree(host, &repo, &root, &commit).with_context(|| format!("unable to check '{rev}' out in a worktree"))?;
+
+        // the workspace may be in a subdirectory of the repository
+        let workspace_root = root.join(workspace_root.strip_prefix(&repo).unwrap_or_else(|_| Path::new("")));
+        Ok(Self {
+            repo,
+            root,
+            workspace_root,
+            commit,
+            lock,
+        })
+    }
+
+    /// Where the workspace is in the worktree.
+    #[must_use]
+    pub fn workspace_root(&self) -> &Path {
+        &self.workspace_root
+    }
+
+    /// The abbreviated hash of the commit checked out in the worktree.
+    #[must_use]
+    pub fn short_commit(&self) -> &str {
+        short(&self.commit)
+    }
+
+    /// Removes the worktree, even when the run changed files in it, and has the repository forget about it.
+    pub fn remove(self, host: &impl Host) {
+        remove(host, &self.repo, &self.root);
+        drop(self.lock);
+        _ = fs::remove_file(lock_path(&self.root));
+    }
+}
+
+fn short(commit: &str) -> &str {
+    commit.get(..12).unwrap_or(commit)
+}
+
+fn lock_path(root: &Path) -> PathBuf {
+    root.with_extension("lock")
+}
+
+/// Removes the worktrees in the scratch directory which no run holds the lock of anymore, other than `own`.
+fn remove_abandoned(host: &impl Host, repo: &Path, scratch_dir: &Path, own: &Path) {
+    let Ok(entries) = fs::read_dir(scratch_dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let root = entry.path();
+        let is_worktree = entry.file_name().to_string_lossy().starts_with(PREFIX) && root.is_dir();
+        if !is_worktree || root == own {
+            continue;
+        }
+
+        if let Ok(lock) = RunLock::acquire(&lock_path(&root), true, || {}) {
+            remove(host, repo, &root);
+            drop(lock);
+            _ = fs::remove_file(lock_path(&root));
+        }
+    }
+}
+
+fn remove(host: &impl Host, repo: &Path, root: &Path) {
+    _ = git::remove_worktree(host, repo, root);
+    if let Ok(root) = long_paths::extended(root) {
+        _ = fs::remove_dir_all(root);
+    }
+}
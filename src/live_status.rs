@@ -0,0 +1,215 @@
+use crate::log::LogTail;
+use crate::summary::{JobResult, JobStatus, StepStatus};
+use core::net::{Ipv4Addr, SocketAddr};
+use core::time::Duration;
+use serde::Serialize;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::thread;
+
+/// How often the endpoint checks for connections, and whether the run is over.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the endpoint waits for a request once a client connects.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How much of a request the endpoint reads at most, which is plenty for the request line and headers of a `GET`.
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// What a run is up to, as served by the status endpoint: its jobs and their steps, in the order they were selected.
+#[derive(Debug, Default, Serialize)]
+pub struct LiveStatus {
+    run_id: String,
+    jobs: Vec<LiveJob>,
+}
+
+#[derive(Debug, Serialize)]
+struct LiveJob {
+    id: String,
+    name: String,
+    running: bool,
+
+    /// How the job turned out, once it's done.
+    status: Option<JobStatus>,
+
+    steps: Vec<LiveStep>,
+}
+
+#[derive(Debug, Serialize)]
+struct LiveStep {
+    name: String,
+    running: bool,
+
+    /// How the step turned out, once it's done.
+    status: Option<StepStatus>,
+}
+
+impl LiveStatus {
+    /// The status of a run about to start, given its jobs as their id, name, and the names of their steps.
+    pub fn new<'a>(run_id: &str, jobs: impl IntoIterator<Item = (&'a str, &'a str, Vec<String>)>) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            jobs: jobs
+                .into_iter()
+                .map(|(id, name, steps)| LiveJob {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    running: false,
+                    status: None,
+                    steps: steps
+                        .into_iter()
+                        .map(|name| LiveStep {
+                            name,
+                            running: false,
+                            status: None,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn start_job(&mut self, job_id: &str) {
+        if let Some(job) = self.job(job_id) {
+            job.running = true;
+        }
+    }
+
+    pub fn start_step(&mut self, job_id: &str, step: &str) {
+        if let Some(step) = self.job(job_id).and_then(|job| job.steps.iter_mut().find(|x| x.name == step)) {
+            step.running = true;
+        }
+    }
+
+    pub fn finish_step(&mut self, job_id: &str, step: &str, status: StepStatus) {
+        if let Some(step) = self.job(job_id).and_then(|job| job.steps.iter_mut().find(|x| x.name == step)) {
+            step.running = false;
+            step.status = Some(status);
+        }
+    }
+
+    /// Notes how a job turned out, along with all of its steps.
+    pub fn finish_job(&mut self, result: &JobResult) {
+        let Some(job) = self.job(&result.id) else {
+            return;
+        };
+
+        job.running = false;
+        job.status = Some(result.status);
+        job.steps = result
+            .steps
+            .iter()
+            .map(|step| LiveStep {
+                name: step.name.clone(),
+                running: false,
+                status: Some(step.status),
+            })
+            .collect();
+    }
+
+    /// How many jobs are done and what's running, for the terminal's title, such as `cargo ci [2/5] test: build`, where
+    /// the running step is only named when a single job is running.
+    #[must_use]
+    pub fn title(&self) -> String {
+        let done = self.jobs.iter().filter(|job| job.status.is_some()).count();
+        let running: Vec<&LiveJob> = self.jobs.iter().filter(|job| job.running).collect();
+        let activity = match running.as_slice() {
+            [job] => job
+                .steps
+                .iter()
+                .find(|step| step.running)
+                .map_or_else(|| job.name.clone(), |step| format!("{}: {}", job.name, step.name)),
+            jobs => jobs.iter().map(|job| job.name.as_str()).collect::<Vec<_>>().join(", "),
+        };
+
+        format!("cargo ci [{done}/{}] {activity}", self.jobs.len()).trim_end().to_string()
+    }
+
+    fn job(&mut self, job_id: &str) -> Option<&mut LiveJob> {
+        self.jobs.iter_mut().find(|job| job.id == job_id)
+    }
+}
+
+/// Serves the status of a run as JSON over HTTP on a local port, along with the latest lines of its log, until the
+/// status is dropped. Port 0 picks any free port.
+///
+/// Returns the address the endpoint listens on.
+pub fn serve(port: u16, status: &Arc<Mutex<LiveStatus>>, log: LogTail) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    listener.set_nonblocking(true)?;
+    let address = listener.local_addr()?;
+
+    let status = Arc::downgrade(status);
+    _ = thread::spawn(move || {
+        while status.strong_count() > 0 {
+            match listener.accept() {
+                // a client which is slow to send its request mustn't hold up the others
+                Ok((stream, _)) => {
+                    let (status, log) = (Weak::clone(&status), log.clone());
+                    _ = thread::spawn(move || respond(stream, address.port(), &status, &log));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(_) => return,
+            }
+        }
+    });
+
+    Ok(address)
+}
+
+fn respond(mut stream: TcpStream, port: u16, status: &Weak<Mutex<LiveStatus>>, log: &LogTail) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let request = read_request(&mut stream)?;
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    // web pages can reach the endpoint through a host name resolving to 127.0.0.1, but they can't hide that name
+    let (code, body) = match (path, status.upgrade()) {
+        _ if !is_local_host(&request, port) => ("403 Forbidden", r#"{"error":"only requests for localhost are served"}"#.to_string()),
+        ("/" | "/status", Some(status)) => {
+            let mut body = serde_json::to_value(&*status.lock().unwrap_or_else(PoisonError::into_inner)).unwrap_or_default();
+            body["log"] = log.lines().into();
+            ("200 OK", body.to_string())
+        }
+        ("/" | "/status", None) => ("503 Service Unavailable", r#"{"error":"the run is over"}"#.to_string()),
+        _ => ("404 Not Found", r#"{"error":"unknown path, use /status"}"#.to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {code}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Reads a request up to the end of its headers, the body of a `GET` being empty.
+fn read_request(stream: &mut TcpStream) -> io::Result<String> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while request.len() < MAX_REQUEST_LEN && !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let len = stream.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+
+        request.extend_from_slice(buffer.get(..len).unwrap_or_default());
+    }
+
+    Ok(String::from_utf8_lossy(&request).into_owned())
+}
+
+/// Whether a request was made for the endpoint by its local address, as `localhost:PORT` or `127.0.0.1:PORT`.
+fn is_local_host(request: &str, port: u16) -> bool {
+    request
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("host"))
+        .is_some_and(|(_, host)| {
+            let host = host.trim();
+            host == format!("localhost:{port}") || host == format!("127.0.0.1:{port}")
+        })
+}
@@ -0,0 +1,79 @@
+use core::fmt;
+
+/// How `cargo update` would change a package in `Cargo.lock`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Updated,
+    Downgraded,
+}
+
+/// A change `cargo update` would make to `Cargo.lock`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockfileChange {
+    pub kind: ChangeKind,
+    pub package: String,
+
+    /// The version locked now, for packages which are removed, updated, or downgraded.
+    pub from: Option<String>,
+
+    /// The version which would be locked, for packages which are added, updated, or downgraded.
+    pub to: Option<String>,
+}
+
+impl fmt::Display for LockfileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let from = self.from.as_deref().unwrap_or_default();
+        let to = self.to.as_deref().unwrap_or_default();
+        match self.kind {
+            ChangeKind::Added => write!(f, "{} {to} (new)", self.package),
+            ChangeKind::Removed => write!(f, "{} {from} (removed)", self.package),
+            ChangeKind::Updated => write!(f, "{} {from} -> {to}", self.package),
+            ChangeKind::Downgraded => write!(f, "{} {from} -> {to} (downgrade)", self.package),
+        }
+    }
+}
+
+/// Picks the changes to `Cargo.lock` out of the output of `cargo update`, such as with `--dry-run`.
+///
+/// Cargo reports each change on a line of its own, such as `Updating anyhow v1.0.99 -> v1.0.100` or
+/// `Adding foo v0.2.0`, possibly followed by a note about newer incompatible versions. Lines about fetching registry
+/// indexes, such as `Updating crates.io index`, are left out, since they don't name a version.
+#[must_use]
+pub fn parse(output: &str) -> Vec<LockfileChange> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (kind, package, rest) = match words.as_slice() {
+                ["Adding", package, rest @ ..] => (ChangeKind::Added, package, rest),
+                ["Removing", package, rest @ ..] => (ChangeKind::Removed, package, rest),
+                ["Updating", package, rest @ ..] => (ChangeKind::Updated, package, rest),
+                ["Downgrading", package, rest @ ..] => (ChangeKind::Downgraded, package, rest),
+                _ => return None,
+            };
+
+            let (from, to) = match (kind, rest) {
+                (ChangeKind::Added, [to, ..]) => (None, Some(version(to)?)),
+                (ChangeKind::Removed, [from, ..]) => (Some(version(from)?), None),
+                (ChangeKind::Updated | ChangeKind::Downgraded, [from, "->", to, ..]) => (Some(version(from)?), Some(version(to)?)),
+                _ => return None,
+            };
+
+            Some(LockfileChange {
+                kind,
+                package: (*package).to_string(),
+                from,
+                to,
+            })
+        })
+        .collect()
+}
+
+/// The version in a word such as `v1.0.100`.
+fn version(word: &str) -> Option<String> {
+    word.strip_prefix('v')
+        .filter(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+        .map(ToString::to_string)
+}
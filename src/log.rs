@@ -1,43 +1,125 @@
-use core::cell::RefCell;
+use core::time::Duration;
+use std::collections::VecDeque;
 use std::ffi::OsStr;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::thread;
 use std::time::SystemTime;
 
-use chrono::Local;
+use crate::long_paths;
+use chrono::{DateTime, Local, NaiveDateTime};
+
+/// Written when a log is created, so a log which doesn't end with [`RUN_ENDED`] tells of a run which never finished.
+const RUN_STARTED: &str = "cargo-ci started";
+
+/// Written when a log is closed, once the run is over, whether it succeeded or not.
+const RUN_ENDED: &str = "cargo-ci ended";
+
+/// Appended to the log of a run which never finished once a later run noticed, so it's only reported once.
+const RUN_CRASHED: &str = "cargo-ci never finished this run";
+
+/// How long log lines may sit in memory before they're written out and synced to disk, so a run which gets killed
+/// loses at most this much of its log.
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many of the latest log lines are kept in memory for [`LogTail`].
+const RECENT_LINES: usize = 100;
 
 pub struct Log {
-    file: RefCell<BufWriter<File>>,
+    file: Arc<Mutex<LogFile>>,
+    path: PathBuf,
+}
+
+struct LogFile {
+    writer: BufWriter<File>,
+
+    /// Whether lines were written since the file was last synced.
+    dirty: bool,
+
+    /// The latest lines written, oldest first.
+    recent: VecDeque<String>,
+}
+
+impl LogFile {
+    fn sync(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.dirty = false;
+        self.writer.get_ref().sync_data()
+    }
 }
 
 impl Log {
-    pub fn new(target_dir: &Path, log_prefix: &str, log_file: Option<&Path>, log_retention_count: usize) -> io::Result<Self> {
+    pub fn new(
+        target_dir: &Path,
+        log_prefix: &str,
+        started: DateTime<Local>,
+        log_file: Option<&Path>,
+        log_retention_count: usize,
+    ) -> io::Result<Self> {
         let log_path = if let Some(path) = log_file {
             path.to_path_buf()
         } else {
-            let log_dir = target_dir.join("logs").join("cargo-ci");
-            fs::create_dir_all(&log_dir)?;
+            let log_dir = log_dir(target_dir);
+            fs::create_dir_all(long_paths::extended(&log_dir)?)?;
 
             prune_old_logs(&log_dir, log_prefix, log_retention_count);
 
-            let now = Local::now();
-            let timestamp = now.format("%Y-%m-%dT%H-%M-%S").to_string();
+            let timestamp = started.format("%Y-%m-%dT%H-%M-%S").to_string();
             log_dir.join(format!("{log_prefix}-{timestamp}.log"))
         };
 
-        let file = OpenOptions::new().create(true).append(true).open(log_path)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(long_paths::extended(&log_path)?)?;
 
-        Ok(Self {
-            file: RefCell::new(BufWriter::new(file)),
-        })
+        let log = Self {
+            file: Arc::new(Mutex::new(LogFile {
+                writer: BufWriter::new(file),
+                dirty: false,
+                recent: VecDeque::new(),
+            })),
+            path: log_path,
+        };
+
+        log.log("INFO", format!("{RUN_STARTED} (process {})", std::process::id()))?;
+        log.file.lock().unwrap_or_else(PoisonError::into_inner).sync()?;
+        sync_periodically(Arc::downgrade(&log.file));
+        Ok(log)
+    }
+
+    /// The file the log is written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A handle on the latest lines of the log, which other threads may read while the run goes on.
+    pub fn tail(&self) -> LogTail {
+        LogTail(Arc::downgrade(&self.file))
     }
 
     fn log(&self, level: &str, message: impl AsRef<str>) -> io::Result<()> {
-        let mut file = self.file.borrow_mut();
         let now = Local::now();
         let timestamp = now.format("%Y-%m-%d %H:%M:%S");
-        writeln!(file, "[{timestamp}] [{level}] {}", message.as_ref())
+        let line = format!("[{timestamp}] [{level}] {}", message.as_ref());
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        writeln!(file.writer, "{line}")?;
+        file.dirty = true;
+
+        if file.recent.len() == RECENT_LINES {
+            _ = file.recent.pop_front();
+        }
+        file.recent.push_back(line);
+
+        // problems are written out right away, since they're what matters most when a run gets killed
+        if level != "INFO" {
+            file.sync()?;
+        }
+
+        drop(file);
+        Ok(())
     }
 
     #[expect(clippy::print_stderr, reason = "The point...")]
@@ -62,11 +144,113 @@ impl Log {
     }
 }
 
+/// The latest lines of a log, which are gone once the log is dropped.
+#[derive(Debug, Clone)]
+pub struct LogTail(Weak<Mutex<LogFile>>);
+
+impl LogTail {
+    #[must_use]
+    pub fn lines(&self) -> Vec<String> {
+        self.0
+            .upgrade()
+            .map(|file| file.lock().unwrap_or_else(PoisonError::into_inner).recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Log {
+    fn drop(&mut self) {
+        self.info(RUN_ENDED);
+        _ = self.file.lock().unwrap_or_else(PoisonError::into_inner).sync();
+    }
+}
+
+/// Syncs the lines written to a log every [`SYNC_INTERVAL`], until the log is dropped.
+fn sync_periodically(file: Weak<Mutex<LogFile>>) {
+    _ = thread::spawn(move || {
+        loop {
+            thread::sleep(SYNC_INTERVAL);
+            let Some(file) = file.upgrade() else {
+                return;
+            };
+
+            let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+            if file.dirty {
+                _ = file.sync();
+            }
+        }
+    });
+}
+
+/// The directory logs are written to by default.
+pub fn log_dir(target_dir: &Path) -> PathBuf {
+    target_dir.join("logs").join("cargo-ci")
+}
+
+/// The id of the run which wrote a log, as printed when runs start, made of the time the run started, which the name
+/// of the log tells, and the process which ran it, which the log's first line tells.
+pub fn run_id(path: &Path, text: &str) -> Option<String> {
+    let name = path.file_stem()?.to_str()?;
+    let (_, timestamp) = name.split_once('-')?;
+    let started = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H-%M-%S").ok()?;
+
+    let first = text.lines().next()?;
+    let (_, process) = first.split_once(&format!("{RUN_STARTED} (process "))?;
+    let process = process.strip_suffix(')')?;
+
+    Some(format!("{}-{process}", started.format("%Y%m%dT%H%M%S")))
+}
+
+/// Finds the latest log of the given kind in the default log directory when its run never finished, as happens when the
+/// process is killed or crashes.
+///
+/// Only logs which start with the marker written by [`Log::new`] are considered, so logs from older versions of
+/// `cargo-ci` are never mistaken for unfinished runs.
+pub fn unfinished(target_dir: &Path, log_prefix: &str) -> Option<PathBuf> {
+    let (_, latest) = logs(&log_dir(target_dir), log_prefix).into_iter().next()?;
+    let text = fs::read_to_string(long_paths::extended(&latest).ok()?).ok()?;
+
+    let started = text.lines().next().is_some_and(|line| line.contains(RUN_STARTED));
+    let ended = text
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| line.contains(RUN_ENDED) || line.contains(RUN_CRASHED));
+
+    (started && !ended).then_some(latest)
+}
+
+/// Marks the log of a run which never finished as such, so it's no longer reported by [`unfinished`].
+pub fn note_unfinished(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).open(long_paths::extended(path)?)?;
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+    writeln!(file, "[{timestamp}] [WARN] {RUN_CRASHED}")
+}
+
+/// The last lines of a log file.
+pub fn tail(path: &Path, count: usize) -> io::Result<Vec<String>> {
+    let text = fs::read_to_string(long_paths::extended(path)?)?;
+    let lines: Vec<_> = text.lines().map(ToString::to_string).collect();
+    Ok(lines[lines.len().saturating_sub(count)..].to_vec())
+}
+
 /// Keeps only the N most recent log files in the given directory.
 fn prune_old_logs(log_dir: &Path, log_prefix: &str, log_retention_count: usize) {
+    let logs = logs(log_dir, log_prefix);
+
+    // Delete old log files beyond retention count
+    if logs.len() > log_retention_count {
+        for (_, path) in &logs[log_retention_count..] {
+            _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The log files with the given prefix in a directory, newest first.
+pub fn logs(log_dir: &Path, log_prefix: &str) -> Vec<(SystemTime, PathBuf)> {
     let Ok(entries) = fs::read_dir(log_dir) else {
         // Directory probably doesn't exist yet, which is fine.
-        return;
+        return Vec::new();
     };
 
     let mut logs: Vec<(SystemTime, PathBuf)> = entries
@@ -93,12 +277,6 @@ fn prune_old_logs(log_dir: &Path, log_prefix: &str, log_retention_count: usize)
         .collect();
 
     // Sort by time (newest first)
-    logs.sort_unstable_by(|a, b| b.0.cmp(&a.0));
-
-    // Delete old log files beyond retention count
-    if logs.len() > log_retention_count {
-        for (_, path) in &logs[log_retention_count..] {
-            _ = fs::remove_file(path);
-        }
-    }
+    logs.sort_unstable_by_key(|b| core::cmp::Reverse(b.0));
+    logs
 }
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+/// A line of the log of a run, along with the job, step, and package it was written for, when it was written for one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// The line's number in the log, starting at 1.
+    pub line: usize,
+
+    /// When the line was written, as written in the log.
+    pub time: String,
+
+    /// `INFO`, `WARN`, or `ERROR`.
+    pub level: String,
+
+    /// The job, by the name shown while it runs.
+    pub job: Option<String>,
+    pub step: Option<String>,
+    pub package: Option<String>,
+
+    /// What the line says, without the job it's attributed to.
+    pub message: String,
+}
+
+/// Where a job was at when a line was written.
+#[derive(Debug, Clone, Default)]
+struct Context {
+    step: String,
+    package: Option<String>,
+}
+
+/// Splits the log of a run into entries, telling what job, step, and package each line was written for.
+///
+/// Jobs run concurrently, so their lines interleave. Lines written for a job start with its name, such as
+/// `test: step 'clippy' for package 'foo'`, and the latest such step line of a job gives the step and package of the
+/// lines written for the job after it. Packages a step runs for in parallel have lines of their own, starting with
+/// `test [foo]: `. Lines starting with the name of one of the given jobs before the job's first step line, such as
+/// those telling of tools being installed for it, are written for the job without being written for any step. Lines
+/// which aren't written for any job, such as those of the summary, have no context.
+#[must_use]
+pub fn entries(text: &str, jobs: &[&str]) -> Vec<LogEntry> {
+    let mut contexts: HashMap<String, Context> = HashMap::new();
+    let mut entries = Vec::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let Some((time, level, message)) = split_line(raw) else {
+            continue;
+        };
+
+        let mut entry = LogEntry {
+            line: index + 1,
+            time: time.to_string(),
+            level: level.to_string(),
+            job: None,
+            step: None,
+            package: None,
+            message: message.to_string(),
+        };
+
+        if let Some((job, rest)) = message.split_once(": ") {
+            if let Some(context) = step_started(rest) {
+                _ = contexts.insert(job.to_string(), context);
+            }
+
+            if let Some(context) = contexts.get(job) {
+                entry.job = Some(job_name(job).to_string());
+                entry.step = Some(context.step.clone());
+                entry.package.clone_from(&context.package);
+                entry.message = rest.to_string();
+            } else if jobs.contains(&job_name(job)) {
+                entry.job = Some(job_name(job).to_string());
+                entry.message = rest.to_string();
+            }
+        }
+
+        entries.push(entry);
+    }
+
+    entries
+}
+
+/// The name of the job a line was written for, given what the line starts with, which is `NAME [PKG]` for the lines
+/// written for one of the packages a step runs for in parallel.
+fn job_name(attributed: &str) -> &str {
+    attributed
+        .strip_suffix(']')
+        .and_then(|rest| rest.rsplit_once(" ["))
+        .map_or(attributed, |(job, _)| job)
+}
+
+/// Splits a line such as `[2025-01-31 12:00:00] [INFO] message` into its time, level, and message.
+fn split_line(line: &str) -> Option<(&str, &str, &str)> {
+    let (time, rest) = line.strip_prefix('[')?.split_once("] [")?;
+    let (level, message) = rest.split_once("] ")?;
+    Some((time, level, message))
+}
+
+/// The step and package a job moved on to, when the rest of a line written for it tells of a step starting, as
+/// `step 'NAME'`, `step 'NAME' for package 'PKG'`, or `step 'NAME' for packages PKG, PKG`.
+fn step_started(rest: &str) -> Option<Context> {
+    let rest = rest.strip_prefix("step '")?;
+
+    if let Some((step, packages)) = rest.rsplit_once("' for packages ") {
+        return Some(Context {
+            step: step.to_string(),
+            package: Some(packages.to_string()),
+        });
+    }
+
+    let rest = rest.strip_suffix('\'')?;
+    Some(match rest.rsplit_once("' for package '") {
+        Some((step, package)) => Context {
+            step: step.to_string(),
+            package: Some(package.to_string()),
+        },
+        None => Context {
+            step: rest.to_string(),
+            package: None,
+        },
+    })
+}
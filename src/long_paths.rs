@@ -0,0 +1,62 @@
+use std::borrow::Cow;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// The longest path most Windows APIs accept without the extended-length prefix.
+const MAX_PATH: usize = 260;
+
+/// The longest directory Windows can start a process in, which leaves room for an 8.3 file name.
+const MAX_WORKING_DIR: usize = MAX_PATH - 12;
+
+/// Prepares a path for file system operations, switching to the extended-length `\\?\` form on Windows when
+/// the path is too long for the usual APIs.
+pub fn extended(path: &Path) -> io::Result<Cow<'_, Path>> {
+    if !cfg!(windows) || utf16_len(path) < MAX_PATH {
+        return Ok(Cow::Borrowed(path));
+    }
+
+    let Some(text) = path.to_str() else {
+        return Err(too_long(path, "it isn't valid Unicode"));
+    };
+
+    if text.starts_with(r"\\?\") {
+        return Ok(Cow::Borrowed(path));
+    }
+
+    // extended-length paths are passed to the file system as-is, so they must be absolute and normalized
+    if !path.is_absolute() || path.components().any(|c| matches!(c, Component::CurDir | Component::ParentDir)) {
+        return Err(too_long(path, "it isn't an absolute, normalized path"));
+    }
+
+    let text = text.replace('/', "\\");
+    let extended = text
+        .strip_prefix(r"\\")
+        .map_or_else(|| format!(r"\\?\{text}"), |unc| format!(r"\\?\UNC\{unc}"));
+
+    Ok(Cow::Owned(PathBuf::from(extended)))
+}
+
+/// Ensures a process can be started in the given directory, which has a hard length limit on Windows.
+pub fn check_working_dir(dir: &Path) -> io::Result<()> {
+    if cfg!(windows) && utf16_len(dir) > MAX_WORKING_DIR {
+        return Err(io::Error::other(format!(
+            "the working directory {} is {} characters long, which exceeds the Windows limit of {MAX_WORKING_DIR} characters, consider moving the workspace to a shorter path",
+            dir.display(),
+            utf16_len(dir)
+        )));
+    }
+
+    Ok(())
+}
+
+fn too_long(path: &Path, reason: &str) -> io::Error {
+    io::Error::other(format!(
+        "the path {} is {} characters long, which exceeds the Windows limit of {MAX_PATH} characters, and can't be made into an extended-length path since {reason}",
+        path.display(),
+        utf16_len(path)
+    ))
+}
+
+fn utf16_len(path: &Path) -> usize {
+    path.to_string_lossy().encode_utf16().count()
+}
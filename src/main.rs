@@ -95,10 +95,30 @@
 //!
 //! - `run`. Executes CI jobs (default).
 //!
+//! - `fix`. Runs the fix commands of fixable steps, such as `cargo fmt` for a formatting check.
+//!
+//! - `plan`. Writes the fully resolved execution plan of a set of jobs.
+//!
+//! - `exec-plan`. Executes a plan, or a slice of it.
+//!
 //! - `list-jobs`. Lists all defined CI jobs.
 //!
 //! - `install`. Installs or updates required tools for the CI jobs.
 //!
+//! - `validate`. Checks the configuration for likely mistakes.
+//!
+//! - `config show`. Prints the effective configuration, noting where each entry came from.
+//!
+//! - `flaky`. Lists the steps whose outcome changes most often from one run to the next.
+//!
+//! - `logs`. Searches the logs of past runs.
+//!
+//! - `init`. Creates a configuration file from a shared template.
+//!
+//! - `migrate`. Converts a legacy configuration into a `ci.toml` file.
+//!
+//! - `cancel`. Cancels one job of a run in progress, letting the rest of the run continue.
+//!
 //! If no subcommand is specified, `run` is assumed. For example, `cargo ci lint` is equivalent to `cargo ci run lint`.
 //!
 //! ## Global Options
@@ -110,6 +130,15 @@
 //! - `-c, --config <PATH>`: Path to the `cargo-ci` configuration file. Defaults to any of `ci.toml`,
 //!   `ci.yml`, `ci.yaml`, or `ci.json` in the workspace root.
 //!
+//! - `--overlay <NAME>`: Apply the named overlay from the `[overlays]` table. Can be repeated, in which case overlays
+//!   are applied in the order given. Defaults to `ci` when the `CI` environment variable is set and such an overlay exists.
+//!
+//! - `--offline`: Only read the configuration files listed by `include` from the cache, without fetching them. Fails
+//!   when a URL isn't cached yet.
+//!
+//! - `--error-format <FORMAT>`: How to report the error which ends `cargo-ci`: `human` (default) or `json`. See
+//!   [Errors](#errors).
+//!
 //! ## The `run` Subcommand
 //!
 //! This is the main workhorse subcommand: it lets you execute jobs. This is the default subcommand, so you don't
@@ -120,31 +149,301 @@
 //! - `[JOBS]...`. A space-separated list of job IDs to run. If omitted, runs the `default_jobs` defined in the configuration file.
 //!   If there are no default jobs defined, all available jobs are run.
 //!
-//! - `-n, --dry-run`. Show the execution plan without running any commands.
+//! - `--run <NAME>`. Apply the options of a named run from the `[runs]` section. Jobs and packages given on the command
+//!   line replace those of the named run, and variables given on the command line override its variables.
+//!
+//! - `--workspace-dir <DIR>`. Run the jobs in the workspace in the given directory instead of the current one. Can be
+//!   given multiple times, such as `--workspace-dir ./service-a --workspace-dir ./service-b`, to run the same jobs in
+//!   every workspace of a checkout holding several repositories. Each workspace uses its own configuration and runs
+//!   in turn, and a single summary covers them all, with job names prefixed by the directory they came from. The
+//!   configuration of every workspace is loaded before any jobs run, so `--config` can't be used along with this option.
+//!
+//! - `-n, --dry-run`. Show the execution plan without running any commands. Each command that would run is shown as a
+//!   line ready to paste into a shell, quoted and with the directory it runs in and the variables set for it, and steps
+//!   running a cargo alias also show what cargo expands the alias to.
+//!
+//! - `--at <REV>`. Along with `--dry-run`, show what would have run at the given git revision, such as a commit hash,
+//!   tag, or `HEAD~3`, without checking it out. The configuration and the workspace's manifests are read from the
+//!   revision with `git show` and recreated in a scratch directory under `target/ci-tmp/`, so cargo resolves the
+//!   packages of that revision. The `plan` subcommand accepts this option too, without needing `--dry-run`.
+//!
+//! - `--isolated[=<REV>]`. Run the jobs in a temporary git worktree of `HEAD`, or of the given revision, so that
+//!   uncommitted changes and untracked files can't affect them, as when validating what's about to be pushed. The
+//!   worktree is created under `target/ci-tmp/`, one per run, and removed once the run is over. Worktrees left behind
+//!   by runs which were killed are removed by the next isolated run. The configuration is read from the
+//!   worktree too, including a file given with `--config` when it's within the workspace. Builds go to
+//!   `target/ci-isolated/`, given to steps as `CARGO_TARGET_DIR` unless they set it themselves, which keeps them apart
+//!   from those of the checkout while letting one isolated run reuse what the previous one built. The run's logs,
+//!   history, and cached results are kept there as well. Submodules aren't checked out in the worktree. This option
+//!   can't be combined with `--at` or `--workspace-dir`.
 //!
 //! - `-p, --package <SPEC>`. Run jobs only on specified packages. This flag can be used multiple times.
 //!
+//! - `--shard <INDEX/COUNT>`. Only process one shard of the packages handled by steps that set `shards`, such as `--shard 2/4`.
+//!   The count must match the step's `shards` value. This makes it possible to split huge workspaces across terminal
+//!   sessions or machines.
+//!
+//! - `--balance-shards <FILE>`. Used with `--shard`, assigns packages to shards by how long the step took for each of
+//!   them according to a file saved with `--save-shard-durations`, rather than by their names. The longest packages are
+//!   assigned first, each to the shard with the least work so far, so shards finish at similar times. Packages missing
+//!   from the file are expected to take as long as the average package. Give every shard the same file, so they all
+//!   split packages the same way, even on machines with different run histories. Dry runs show the packages each shard
+//!   gets, along with how long it's expected to take.
+//!
+//! - `--save-shard-durations <FILE>`. Save how long the sharded steps of the selected jobs took for each package, over
+//!   the recent runs kept in the run history, to a JSON file for `--balance-shards`, before running the jobs. For
+//!   example, `cargo ci run --dry-run --save-shard-durations shards.json` on the machine keeping the history, then
+//!   `cargo ci run --shard 2/4 --balance-shards shards.json` for each shard.
+//!
+//! - `--scope <package|workspace>`. Run every step which runs a command once for each package, in the package's
+//!   directory, or once for the whole workspace, from its root, regardless of its `per_package` setting. This makes it
+//!   quick to tell whether a failure has to do with the directory a step runs in or with the features cargo unifies
+//!   across the workspace. Steps of other kinds, such as `audit` or `semver-check`, keep their own scope, and so do the
+//!   steps of jobs with a `working_directory`.
+//!
+//! - `--since-last-green`. Skip the jobs which passed the last time they ran, according to the run history, as long as
+//!   their inputs haven't changed since: the configuration file and the overlays applied to it, the environment
+//!   fingerprint described under the `flaky` subcommand, the variables let through from the environment and computed
+//!   by `variables_from` commands, and the files of the selected packages and of the workspace packages they depend on.
+//!   Jobs are only skipped when the jobs they need are skipped too. This makes rerunning everything cheap while
+//!   iterating locally. Only runs with this option digest their inputs, so only they count as having passed before.
+//!
+//! - `--no-cache`. Run pure jobs even when they passed before on the same git trees, and don't cache their results. See
+//!   the `pure` property of jobs. This also runs every step of every job, rather than reusing the results of steps
+//!   setting `reuse_result`.
+//!
+//! - `--resume`. Pick up where the last run left off when it failed, was interrupted with Ctrl-C, or crashed, skipping
+//!   the steps which succeeded in it, along with the packages per-package steps succeeded for. Progress is saved to
+//!   `ci-progress.json` in the target directory as soon as each step or package succeeds, and is forgotten once a run
+//!   succeeds or a run starts without `--resume`. When the configuration changed since, every step runs.
+//!
 //! - `-v, --variable <KEY=VAL>`. Define a variable for expression evaluation. This can be used multiple times and will override variables from other sources.
+//!   `--var` is an alias of this option.
+//!   Prefixing the name with a job or step ID and a dot scopes the variable, so `-v test.RUST_LOG=debug` only applies to the `test` job and
+//!   `-v clippy-check.LEVEL=pedantic` only applies to the step with that ID.
+//!
+//! - `--check-feature-unification`. Before running jobs that contain per-package steps, compare the features Cargo
+//!   resolves for each selected package's dependencies when building that package on its own against those resolved
+//!   for a whole-workspace build, and report any differences as warnings in the summary. Feature unification can let
+//!   a package build fine with `--workspace` and yet fail when built by itself, or vice versa.
+//!
+//! - `--allow-destructive`. Run steps marked as `destructive` without asking for confirmation.
+//!
+//! - `--install-tools`. Install the tools listed in the `tools` property of the jobs being run which aren't installed at
+//!   the version the `[tools]` table asks for. Without it, such jobs fail, saying how to install what's missing.
+//!
+//! - `-y, --yes`. Answer yes to the questions of steps which set `confirm`.
+//!
+//! - `--shuffle[=SEED]`. Process packages in a random order, which helps uncover tests that only pass because another
+//!   package ran first, as well as other stateful flakes. The seed is printed at the start of the run; when no seed is
+//!   given, one is picked at random.
+//!
+//! - `--shuffle-seed <SEED>`. Process packages in the order produced by the given seed, reproducing the order of an
+//!   earlier `--shuffle` run.
+//!
+//! - `--explain-conditions`. For every `if` condition evaluated, print the expression, the value of each variable it
+//!   references along with where that variable was defined, and the result. Useful to figure out why a job or step
+//!   unexpectedly ran or was skipped.
+//!
+//! - `--verbose`. Print the command line of every step as it runs. Given twice, also list every variable the step
+//!   receives, the value it ends up with, where that value came from (environment, workspace, job, step, package
+//!   metadata, or command line), and which other definitions it overrides.
+//!
+//! - `--heartbeat <SECS>`. Once a step goes this many seconds without output, print a heartbeat line, and keep printing
+//!   one every as many seconds until it produces more. Heartbeats show how long the step has been running and, on Linux,
+//!   how much CPU time it and the processes it started have used, which tells a slow step from a stuck one. They also
+//!   keep CI services from timing out runs that produce no output. Defaults to 60, and 0 disables heartbeats.
+//!
+//! - `--stall-timeout <SECS>`. Report a step as possibly stalled once it goes this many seconds without output.
+//!
+//! - `--kill-stalled`. Kill steps reported as possibly stalled, failing them. Requires `--stall-timeout`. Processes the
+//!   step started are killed along with it, except when `cargo-ci` runs in a terminal, as described under
+//!   [the `cancel` subcommand](#the-cancel-subcommand).
+//!
+//! - `--debug-failures`. Help investigate crashes. Steps get `RUST_BACKTRACE=full` and `RUST_LOG=debug`, unless those
+//!   variables are already set for them, and the processes started by steps with `ulimit_core = true` write core dumps
+//!   when they crash. When a step fails, the failure report points to the backtraces in its output, which is kept in the
+//!   log, and to the core dumps written while it ran. Core dumps are looked for where the kernel writes them, such as
+//!   the step's working directory and the directories of its packages, where `cargo test` runs tests. When the kernel
+//!   hands them to a program such as `systemd-coredump` instead, the report says where that keeps them.
+//!
+//! - `--no-wait`. Fail right away when another run holds the workspace lock, instead of waiting for it to finish.
+//!
+//! - `--no-lock`. Don't take the workspace lock, letting this run proceed concurrently with other runs.
 //!
 //! - `-l, --log-file <FILE>`. Redirect detailed log output to a specific file. By default, logs are stored in `target/logs/cargo-ci/`.
 //!
 //! - `--log-file-retention-count <COUNT>`. Number of log files to retain (default: 16).
 //!
+//! - `--badges-dir <DIR>`. Directory where job status badges are written. Defaults to `target/ci-badges/`.
+//!
+//! - `--sarif-file <FILE>`. Where to write the diagnostics of steps with `sarif = true`, as a single SARIF file for the
+//!   whole run. Defaults to `target/ci.sarif`. The file is only written when one of the selected jobs has such a step.
+//!
+//! - `--otlp-endpoint <URL>`. Once the run completes, export OpenTelemetry spans for it to an OTLP/HTTP collector, so
+//!   the performance of local runs can be analyzed in an existing tracing backend. The run span contains a span for
+//!   every job, which contains a span for every step, which in turn contains a span for every package the step ran for.
+//!   Spans carry their durations, statuses, and the command line and directory of the commands they ran. Both `http://`
+//!   and `https://` endpoints are supported. Spans are sent as JSON with `curl`, to `/v1/traces` unless the URL has a
+//!   path of its own, so `http://localhost:4318` sends them to `http://localhost:4318/v1/traces`. Failing to export the
+//!   spans is reported as a warning. Dry runs don't export spans.
+//!
+//! - `--status-port <PORT>`. While the run goes on, serve its live status as JSON over HTTP on this port of
+//!   `127.0.0.1`, so IDE extensions can show it without parsing terminal output. `GET /status` returns the run id, every
+//!   job with its steps, whether each one is running and how it turned out once it's done, along with the latest lines
+//!   of the log. Port 0 picks a free port, and the address is printed when the run starts. Requests must be for
+//!   `localhost:<PORT>` or `127.0.0.1:<PORT>`, as their `Host` header says, so web pages can't read the status through
+//!   a host name of their own resolving to `127.0.0.1`. Dry runs don't serve their status.
+//!
+//! - `--summary-file <FILE>`. Write a compact Markdown summary of the run to a file, with the status and duration of
+//!   each job, excerpts of any failures, and the location of the log. Handy for pasting into a PR description or for
+//!   sending by a script wrapping unattended runs.
+//!
+//! - `--summary-packages`. Show how every step of every job turned out for every package in the summary, as a grid
+//!   with one row per step and one column per package. The summary file gets the same grid as a table for each job.
+//!
+//! - `--post-summary github-pr`. Post the Markdown summary of the run as a comment on the open GitHub pull request of
+//!   the checked out commit or branch, so a full run on your own machine documents its results on the PR. The pull
+//!   request is the one `GITHUB_REF` names, as on GitHub Actions, or else the one whose head is the checked out commit,
+//!   or else one whose head is a branch of the same name as the checked out one or `GITHUB_HEAD_REF`, including branches
+//!   of forks. The comment is updated by later runs rather than repeated. The token is read from `GITHUB_TOKEN` or `GH_TOKEN`, the repository
+//!   from `GITHUB_REPOSITORY` or the `origin` remote, and `GITHUB_API_URL` points at GitHub Enterprise. A failure to
+//!   post is reported but doesn't fail the run, and dry runs don't post.
+//!
 //! - `--color <WHEN>`. Control when to use colored output. Valid values are `auto` (default), `always`, or `never`.
 //!
+//! - `--lang <LANG>`. Language for the step headings, captured output banners, job and step statuses, and summary shown
+//!   in the terminal. Valid values are `en` (default), `de`, or `ja`. Other messages, such as warnings, errors, and
+//!   progress notes, are in English, as are log files so they can be shared.
+//!
+//! After every run other than a dry run, `cargo-ci` writes a `<job-id>.svg` status badge and a `<job-id>.json` status
+//! file for each job that ran, so local docs or dashboards can embed the current status of your jobs. Files for jobs
+//! that didn't run are left untouched.
+//!
+//! Once all jobs have completed, a summary reports the status and duration of each stage and job. For every job, it
+//! counts the steps which ran and the steps which were skipped, such as by their conditions, along with the packages
+//! skipped by steps which did run for other packages. A step skipped for every package counts as a skipped step, while a
+//! package skipped by several steps counts once per step. A last line adds these up over the whole run, along with the
+//! steps which weren't run because their job stopped early.
+//!
+//! Concurrent runs in the same workspace would trample on each other's builds in the target directory, so each run
+//! other than a dry run holds an advisory lock on `target/cargo-ci.lock`. A run started while another is in progress
+//! waits for it to finish, unless `--no-wait` or `--no-lock` is given.
+//!
+//! ## The `fix` Subcommand
+//!
+//! Check steps like `cargo fmt --check` or `cargo clippy -- -D warnings` can declare a `fixable` command which fixes the
+//! problems they report. This subcommand runs the fix commands of the selected jobs' fixable steps, shows a diff of the
+//! files that changed, and asks for confirmation before keeping the changes. When the changes are declined, or a fix
+//! command fails, the files are restored to their previous content, including any uncommitted edits they had.
+//! Conditions are not evaluated, so every fixable step of the selected jobs runs.
+//!
+//! **Usage**: `cargo ci fix [OPTIONS] [JOBS]...`
+//!
+//! - `[JOBS]...`. A space-separated list of job IDs whose fixable steps to run. If omitted, all jobs are considered.
+//!
+//! - `-y, --yes`. Keep the changes without asking for confirmation.
+//!
+//! - `-l, --log-file <FILE>`. Redirect log output to a specific file.
+//!
+//! - `--log-file-retention-count <COUNT>`. Number of log files to retain (default: 16).
+//!
+//! - `--color <WHEN>`. Control when to use colored output. Valid values are `auto` (default), `always`, or `never`.
+//!
+//! - `--lang <LANG>`. Language for the step headings, captured output banners, job and step statuses, and summary shown
+//!   in the terminal. Valid values are `en` (default), `de`, or `ja`. Other messages, such as warnings, errors, and
+//!   progress notes, are in English, as are log files so they can be shared.
+//!
+//! ## The `test` Subcommand
+//!
+//! Runs the workspace's tests without needing a configuration file. The tests run as a job of their own, with
+//! cargo-nextest when it's installed and `cargo test` otherwise, so they get the same package selection, variables,
+//! logging, and reporting as any other job. The summary counts the tests which passed, failed, or were ignored, and
+//! lists the ones which failed, and so does the summary file.
+//!
+//! **Usage**: `cargo ci test [OPTIONS] [-- <ARGS>...]`
+//!
+//! - `--runner <RUNNER>`. Which test runner to use. Valid values are `auto` (default), `nextest`, or `cargo`.
+//!
+//! - `--per-package`. Run the tests of every selected package separately, from the package's directory.
+//!
+//! - `[-- <ARGS>...]`. Arguments passed along to the test runner, such as test name filters.
+//!
+//! The `run` subcommand's options are also accepted, except for job names.
+//!
+//! ## The `plan` Subcommand
+//!
+//! Resolves the jobs to run into an execution plan, written as JSON, without running anything. Conditions are
+//! evaluated and per-package steps are expanded, so the plan lists every command to run along with the directory it
+//! runs in, relative to the workspace root. Plans let a run be distributed across machines without a central
+//! coordinator, each machine executing a slice of the plan with `exec-plan`. Steps of the `codegen-check`,
+//! `semver-check`, and `audit` kinds can't be part of a plan.
+//!
+//! **Usage**: `cargo ci plan [OPTIONS] [JOBS]...`
+//!
+//! - `-o, --output <FILE>`. Write the plan to a file instead of standard output.
+//! - `--for <PLATFORM>`. Evaluate conditions as if running on another platform, given as a comma-separated list of
+//!   `os=<OS>` and `arch=<ARCH>` entries, such as `--for os=windows,arch=aarch64`. Whatever is left out stays the same
+//!   as this machine's. The `os` restrictions of jobs and steps apply to the given OS, the `runner.os` and `runner.arch`
+//!   variables describe the given platform, and the environment variables of this machine are left out, since they
+//!   wouldn't be those of the other one. Define the variables which matter there with `--var`, such as
+//!   `--var CI=true`. This lets maintainers review how the configuration behaves on platforms they don't have.
+//!
+//! The `run` subcommand's options for selecting jobs, packages, shards, and variables are also accepted.
+//!
+//! ## The `exec-plan` Subcommand
+//!
+//! Executes the jobs of a plan written by the `plan` subcommand, in the order they appear in the plan. Steps get the
+//! variables the plan recorded for them, those declared in the configuration or on the command line, along with the
+//! variables of this machine's environment, platform, and git repository, as they would in a run.
+//!
+//! **Usage**: `cargo ci exec-plan [OPTIONS] <PLAN>`
+//!
+//! - `--slice <INDEX/COUNT>`. Only execute one slice of the plan's jobs, such as `2/3`. Jobs are dealt to slices
+//!   round-robin. A slice doesn't wait for jobs in other slices, so jobs which need each other should run in the same
+//!   slice or in successive invocations.
+//!
+//! - `-l, --log-file <FILE>`. Redirect log output to a specific file.
+//!
+//! - `--log-file-retention-count <COUNT>`. Number of log files to retain (default: 16).
+//!
+//! - `--color <WHEN>`. Control when to use colored output. Valid values are `auto` (default), `always`, or `never`.
+//!
+//! - `--lang <LANG>`. Language for the step headings, captured output banners, job and step statuses, and summary shown
+//!   in the terminal. Valid values are `en` (default), `de`, or `ja`. Other messages, such as warnings, errors, and
+//!   progress notes, are in English, as are log files so they can be shared.
+//!
 //! ## The `list-jobs` Subcommand
 //!
-//! Lists all jobs defined in configuration.
+//! Lists the jobs defined in configuration, sorted by ID. Only the configuration is read, without running
+//! `cargo metadata`, so listing is quick even on large workspaces.
 //!
 //! **Usage**: `cargo ci list-jobs [OPTIONS]`
 //!
 //! - `-s, --show-steps`. Show the steps for each job.
 //!
+//! - `--filter <SUBSTRING>`. Only list jobs whose ID or name contains the given text, ignoring case.
+//!
+//! - `--needs-of <JOB>`. Show the tree of jobs the given job needs, directly or indirectly.
+//!
+//! - `--reverse-needs <JOB>`. Show the tree of jobs which need the given job, directly or indirectly.
+//!
+//! - `--depth <LEVELS>`. Limit how many levels of the tree shown by `--needs-of` or `--reverse-needs` are displayed.
+//!
+//! - `--excluded-crates`. Also list the crates under the workspace root which aren't members of the workspace, telling
+//!   whether they're excluded from the workspace, nested workspaces, or neither, and which jobs cover them. See the
+//!   `include_excluded` property of jobs. This takes running `cargo metadata`. Hidden directories, the target
+//!   directory, directories ignored by git, and directories named `target`, `vendor`, `node_modules`, or `fixtures` aren't
+//!   searched, and manifests which can't be read are skipped with a warning.
+//!
 //! ## The `install` Subcommand
 //!
 //! Installs or updates the tools defined in configuration.
 //!
-//! **Usage**: `cargo ci install [OPTIONS]`
+//! **Usage**: `cargo ci install [OPTIONS] [TOOLS]...`
+//!
+//! - `[TOOLS]...`. Only install these tools or tool bundles, rather than every tool in the `[tools]` table. Bundles are
+//!   expanded into the tools they include, recursively.
 //!
 //! - `-l, --log-file <FILE>`. Redirect installation log output to a specific file.
 //!
@@ -152,6 +451,286 @@
 //!
 //! - `--color <WHEN>`. Control when to use colored output. Valid values are `auto` (default), `always`, or `never`.
 //!
+//! - `--lang <LANG>`. Language for the step headings, captured output banners, job and step statuses, and summary shown
+//!   in the terminal. Valid values are `en` (default), `de`, or `ja`. Other messages, such as warnings, errors, and
+//!   progress notes, are in English, as are log files so they can be shared.
+//!
+//! ## The `validate` Subcommand
+//!
+//! Loads the configuration, reporting any errors, and then checks it for likely mistakes that don't prevent it
+//! from loading. Currently, it warns about `cargo_alias` steps which run an alias that isn't defined in the
+//! workspace's cargo configuration, about `command` steps running `cargo <NAME>` where `<NAME>` is neither a cargo
+//! command, a listed tool, an installed `cargo-<NAME>` program, nor a defined alias, and about `path` entries which
+//! don't exist. Only the configuration is read, without running `cargo metadata`, unless `--packages` is given.
+//!
+//! **Usage**: `cargo ci validate [OPTIONS]`
+//!
+//! - `--strict`. Fail when validation produces any warnings.
+//!
+//! - `--packages`. Also check the workspace's packages against the configuration, which takes running
+//!   `cargo metadata`. This warns about untrusted packages from outside the workspace root and trusted packages which
+//!   don't exist, about packages overriding the command of a step no job has, and about crates under the workspace root
+//!   which no job covers: crates which are neither members of the workspace nor excluded from it, and nested workspaces
+//!   when no job has `include_excluded = true`.
+//!
+//! ## The `config show` Subcommand
+//!
+//! Prints the configuration as `cargo-ci` sees it once the selected overlays and the platform-specific
+//! `passthrough_env_variables_*` lists are applied. Each entry is annotated with where it came from: the configuration
+//! file, or the overlay which set it, including whether that overlay was applied because the `CI` environment variable is
+//! set. The variables packages define in their `Cargo.toml`, and the environment variables imported for expressions, are
+//! listed after the configuration. Useful to figure out why a variable is set, or why a job runs by default.
+//!
+//! **Usage**: `cargo ci config show [OPTIONS]`
+//!
+//! - `--format <FORMAT>`. Either `toml` (default), which annotates entries with comments and can be loaded back as a
+//!   configuration file, or `json`, which lists the sources of entries in a separate `provenance` object keyed by the
+//!   dotted path of each entry.
+//!
+//! ## The `flaky` Subcommand
+//!
+//! Every run records how its steps turned out in `ci-history.jsonl` in the target directory, which keeps the last 200
+//! runs. Skipped steps and dry runs aren't recorded. A step whose outcome flipped between success and failure at least 3
+//! times over its last 20 runs is considered flaky, and runs in which such a step takes part warn about it in their
+//! summary.
+//!
+//! Each recorded run also keeps a fingerprint of its environment: the versions of `rustc` and `cargo`, hashes of the
+//! values of its variables, and the versions pinned in the `[tools]` table. When the fingerprint differs from the
+//! previous run's, the summary warns about what changed, since outcomes recorded earlier may not hold anymore.
+//!
+//! This subcommand lists the steps whose outcome changed over their recent runs, those changing most often first, along
+//! with their failure rate and whether they have been passing or failing lately.
+//!
+//! **Usage**: `cargo ci flaky [OPTIONS]`
+//!
+//! - `--runs <RUNS>`. How many of each step's most recent runs to consider. Defaults to 20.
+//!
+//! - `--top <COUNT>`. How many steps to list. Defaults to 10.
+//!
+//! ## The `logs` Subcommand
+//!
+//! This subcommand searches the logs the `run` subcommand keeps in `target/logs/cargo-ci/`, newest first, and shows the
+//! matching lines grouped by run, each with the job, step, and package it was written for, such as:
+//!
+//! ```text
+//! Run 20250131T120000-4242 (target/logs/cargo-ci/run-2025-01-31T12-00-00.log):
+//!   line 14 [ERROR] test, step 'build', package 'foo': error[E0308]: mismatched types
+//! ```
+//!
+//! Lines which weren't written for a job, such as those of the summary, are shown without context. Logs written with
+//! `--log-file` aren't searched.
+//!
+//! **Usage**: `cargo ci logs [OPTIONS]`
+//!
+//! - `--run <RUN_ID>`. Only search the log of this run, by its id as printed when it starts, or the start of it, such
+//!   as `20250131` for the runs of a day.
+//!
+//! - `--job <JOB>`. Only show the lines written for this job, by its id or by the name shown while it runs. Lines
+//!   written for the job before its first step, such as those about installing its tools, are shown as well.
+//!
+//! - `--grep <REGEX>`. Only show the lines matching this regular expression, such as `--grep 'error\[E'` for the
+//!   compiler's errors.
+//!
+//! ## The `init` Subcommand
+//!
+//! Organizations can keep a shared template of their CI configuration, with its jobs and tools, and instantiate it in each
+//! repository. A template is a directory, or a git repository, holding a configuration file such as `ci.toml`, in which
+//! `{{ name }}` placeholders stand for project-specific values. Those values are declared in the template's
+//! `template.toml` file:
+//!
+//! ```toml
+//! [parameters.project]
+//! prompt = "Project name"
+//!
+//! [parameters.msrv]
+//! prompt = "Minimum supported Rust version"
+//! default = "1.85"
+//! ```
+//!
+//! Values not given on the command line are prompted for when running in a terminal, and taken from their defaults
+//! otherwise. The resulting configuration is checked before it is written, and a diff-style preview of the file is shown.
+//!
+//! Projects which already run on another CI provider can instead start from a configuration mirroring their existing
+//! pipelines with `--detect`. It looks for `.github/workflows`, `.gitlab-ci.yml`, and a `Jenkinsfile` in the workspace
+//! root or the closest directory above it which has any, and proposes a job for each job of the workflows, each job of
+//! the GitLab pipeline, and each stage of a declarative Jenkins pipeline. The translation is best-effort:
+//!
+//! - Scripts become steps, one per line unless they continue lines, use control structures, or have lines changing the
+//!   directory or environment of the lines after them, such as `cd`, `export`, `source`, or variable assignments, since
+//!   every step runs in a shell of its own. Steps keep their names and working directories, and steps of Jenkins `dir`
+//!   blocks run in that directory.
+//! - `needs` carry over, GitLab stages become `stages`, and Jenkins stages need the stage before them. Runner labels
+//!   naming an OS, including those of a job's `matrix.os`, become `os`, as do Windows shells. GitLab's `allow_failure`
+//!   becomes `continue_on_error`.
+//! - Actions which only matter in the cloud are left out, as for the `simulate` subcommand.
+//! - Everything else, such as conditions, environment variables, other actions, reusable workflows, GitLab `rules`, or
+//!   Jenkins `junit` steps, is left out and annotated with a `# NOTE:` comment. Jobs left with no steps are only
+//!   mentioned in a note. Jobs whose id is taken by a job of another pipeline are prefixed with where they come from.
+//!
+//! **Usage**: `cargo ci init --from <SOURCE> [OPTIONS]` or `cargo ci init --detect [OPTIONS]`
+//!
+//! - `--from <SOURCE>`. The template to instantiate, either a local directory or the URL of a git repository, which is
+//!   cloned with `git`.
+//!
+//! - `--detect`. Propose a configuration mirroring the pipelines of the CI providers the repository already uses,
+//!   instead of instantiating a template.
+//!
+//! - `--set <KEY=VALUE>`. Sets a template parameter instead of being prompted for it. Can be given multiple times.
+//!
+//! - `-o, --output <PATH>`. Where to write the configuration. Defaults to the template's configuration file name, such as
+//!   `ci.toml`, in the workspace root, and to `ci.toml` in the workspace root with `--detect`.
+//!
+//! - `-n, --dry-run`. Show the preview without writing anything.
+//!
+//! - `-f, --force`. Overwrite the output file if it already exists.
+//!
+//! ## The `migrate` Subcommand
+//!
+//! Older versions of `cargo-ci` read their configuration from the `[workspace.metadata.ci]` table of the workspace's
+//! `Cargo.toml`, and adjusted jobs and steps through a list of `modifiers`. This subcommand converts such a configuration
+//! into an equivalent `ci.toml` using the current schema, printing a diff-style preview of the file before writing it.
+//!
+//! **Usage**: `cargo ci migrate [OPTIONS]`
+//!
+//! - `-o, --output <PATH>`. Where to write the migrated configuration. Defaults to `ci.toml` in the workspace root.
+//!
+//! - `-n, --dry-run`. Show the preview without writing anything.
+//!
+//! - `-f, --force`. Overwrite the output file if it already exists.
+//!
+//! Modifiers are translated as follows, and any other modifier is reported as a warning:
+//!
+//! - `when:<expr>` becomes an `if` condition. Multiple conditions are combined with `&&`.
+//! - `var:<KEY>=<VALUE>` becomes an entry in the job or step's `variables` table.
+//! - `continue-on-error` becomes `continue_on_error = true`.
+//! - `per-package` becomes `per_package = true`. On a job, it applies to every step of the job.
+//!
+//! ## The `simulate` Subcommand
+//!
+//! Runs the parts of the repository's hosted CI workflows which can run locally, to catch failures before pushing.
+//! The workflows are read from the `.github/workflows` directory in the workspace root or the closest directory above
+//! it, and their steps run from the directory holding `.github`.
+//!
+//! **Usage**: `cargo ci simulate [OPTIONS]`
+//!
+//! - `--provider <PROVIDER>`. The CI provider whose workflows to simulate. Only `github` (default), for GitHub Actions,
+//!   is supported.
+//!
+//! - `-w, --workflow <FILE>`. Only simulate this workflow file.
+//!
+//! - `-j, --job <ID>`. Only simulate the workflow jobs with this id. Can be given several times. Jobs needed by the
+//!   selected jobs aren't simulated, and are assumed to pass.
+//!
+//! - `-n, --dry-run`. Report how each job maps to this machine without running anything.
+//!
+//! - `-l, --log-file <FILE>`, `--log-file-retention-count <COUNT>`, `--color <WHEN>`, and `--lang <LANG>`, as for
+//!   the `exec-plan` subcommand.
+//!
+//! Jobs run after the jobs they need, and each job is reported as one of:
+//!
+//! - **Simulated**, when its `run` steps ran locally, along with whether they passed. Steps run through `bash`, or the
+//!   shell they ask for, with the workflow's and the job's environment variables, except those whose values use
+//!   expressions. Steps conditioned on `success()`, `failure()`, or `always()` run accordingly, and steps with
+//!   other conditions are skipped. Actions which only matter in the cloud, such as `actions/checkout`, caches,
+//!   toolchain setup, and artifact or coverage uploads, are skipped, while `actions-rs/cargo` and
+//!   `actions-rs/clippy-check` run the `cargo` command they stand for.
+//!
+//! - **Skipped**, when the job runs on another OS, when all entries of its `matrix.os` do, when it has a condition,
+//!   or when a job it needs didn't pass.
+//!
+//! - **Unmappable**, when the job calls a reusable workflow, uses any other action, uses expressions in a script, or
+//!   asks for an unsupported shell.
+//!
+//! The subcommand exits with the step failure code when any simulated job failed.
+//!
+//! ## The `clean` Subcommand
+//!
+//! Frees up space in the target directory. Over time, the target directory collects the build artifacts of
+//! packages which were renamed, removed from the workspace, or are no longer among its dependencies, and `cargo` never
+//! removes them on its own.
+//!
+//! **Usage**: `cargo ci clean --target-gc [OPTIONS]`
+//!
+//! - `--target-gc`. Remove the artifacts of packages which are neither in the workspace nor among its dependencies
+//!   anymore, from every build profile and target. Only files and directories named like `cargo` names artifacts are
+//!   considered, so anything else in the target directory is left alone.
+//!
+//! - `-n, --dry-run`. List what would be removed, and how much space it takes up, without removing anything.
+//!
+//! - `--no-wait`. Fail right away if a run is in progress in this workspace, instead of waiting for it to finish.
+//!
+//! Unless it's a dry run, this takes the same lock on `target/cargo-ci.lock` as runs do before removing anything, so
+//! it never removes artifacts a run in progress is using.
+//!
+//! ## The `cancel` Subcommand
+//!
+//! Cancels one job of a run in progress in another terminal, such as a slow job whose outcome no longer matters,
+//! letting the rest of the run continue.
+//!
+//! **Usage**: `cargo ci cancel <RUN_ID> <JOB>`
+//!
+//! A run which has more than one job prints its id when it starts, along with the command to cancel one of its jobs.
+//! Cancelling a job kills the step it's running, and its remaining steps don't run. Jobs which need the cancelled job
+//! don't run either. The job shows up as cancelled in the summary, and once the rest of the run is done, the run exits
+//! with `130` unless it failed for another reason.
+//!
+//! Each step runs in a process group of its own, so killing the step, whether it's cancelled, stalled, or over the time
+//! budget, also kills the processes it started, such as the subprocesses of a test harness. On Windows, the step's
+//! whole process tree is killed. Elsewhere, Ctrl-C, or an interrupt signal sent to `cargo-ci`, is forwarded to the
+//! process groups of the running steps before `cargo-ci` ends.
+//!
+//! ## Exit Codes
+//!
+//! `cargo-ci` exits with 0 when everything went well. Otherwise, the exit code tells what kind of failure ended it, so
+//! wrapper scripts can react differently to, say, a broken configuration and a failing test:
+//!
+//! - `2`. The configuration couldn't be loaded or is invalid. Mistakes on the command line, such as unknown options,
+//!   also exit with 2.
+//!
+//! - `3`. A job or run given on the command line isn't defined, or a `--variable` is scoped to a job or step which
+//!   doesn't exist.
+//!
+//! - `4`. A step failed, failing its job. Jobs with `continue_on_error` don't count.
+//!
+//! - `130`. A step asking for confirmation wasn't confirmed, or a job was cancelled with `cargo ci cancel`. This
+//!   matches the exit code of a run interrupted with Ctrl-C, by convention.
+//!
+//! - `70`. `cargo-ci` itself ran into a bug.
+//!
+//! - `1`. Any other failure, such as being unable to run `cargo metadata` or to create the temporary directory.
+//!
+//! ## Errors
+//!
+//! Errors in the configuration file point at where the mistake is, with the file, line, and column, the path of the
+//! offending key, and the offending line:
+//!
+//! ```text
+//! ERROR: ci.toml:2:1: in 'jobs.test.stepz': unknown field `stepz`, expected one of `name`, `steps`, ...
+//!   |
+//! 2 | stepz = ["cargo test"]
+//!   | ^
+//! ```
+//!
+//! YAML and JSON files are located the same way. Checks of a whole table, such as whether a job's `needs` exist, point
+//! at the table.
+//!
+//! With `--error-format json`, the error which ends `cargo-ci` is written to stderr as a single line of JSON instead,
+//! even for runs, which otherwise leave reporting failed steps to their summary. It has the error's `message`, its
+//! `causes`, the `exit_code`, and a list of `diagnostics` telling where the error comes from, the outermost first. Each
+//! diagnostic has a `kind`:
+//!
+//! - `config`. The configuration is invalid, in `file`, along with the `line`, `column`, and `key` when they're known.
+//!
+//! - `execution`. A step failed or couldn't run, as `step` of `job`, for `package` when it ran for one package at a
+//!   time.
+//!
+//! - `environment`. Something `cargo-ci` depends on is missing or misbehaved, such as `cargo metadata` or a tool
+//!   installed for a step.
+//!
+//! ```json
+//! {"message":"unable to run step 'test': exit status: 101","causes":[],"exit_code":4,"diagnostics":[{"kind":"execution","job":"test","step":"test"}]}
+//! ```
+//!
 //! # Configuration File
 //!
 //! Jobs and steps are defined in the `cargo-ci` configuration file, normally called `ci.toml` and located at the root of
@@ -160,6 +739,23 @@
 //!
 //! ## Top-Level Values
 //!
+//! - `include`. (Optional) An array of configuration files whose tools, tool bundles, and jobs are merged into this
+//!   configuration, so that an organization can evolve its standard jobs and tools in one place for many repositories.
+//!   Files are given as `https://` URLs, or as paths relative to this configuration file, and their format
+//!   follows their extension. Entries defined by this configuration take precedence over included ones, and entries of
+//!   later files take precedence over those of earlier ones. Included files can only define `[tools]`, `[tool_bundles]`,
+//!   and `[jobs]`, and can't include other files.
+//!
+//!   URLs are fetched with `curl` and cached in the `cargo-ci/includes` directory of the cargo home directory. Since
+//!   included jobs run commands, plain `http://` URLs are refused, and so are redirects to them. A cached copy is used
+//!   as is for an hour, and then revalidated through its entity tag, so it's only downloaded again when it changed.
+//!   When a URL can't be fetched, its cached copy is used, with a warning. `cargo ci config show` tells which entries came from
+//!   which file.
+//!
+//!   ```toml
+//!   include = ["https://raw.githubusercontent.com/org/ci-defaults/main/base.toml"]
+//!   ```
+//!
 //! - `default_jobs`. (Optional) An array of job IDs to run when `cargo ci run` is invoked without specific jobs. When this
 //!   value is not defined, then the default behavior is to run all defined jobs.
 //!
@@ -181,6 +777,54 @@
 //!   available at runtime to the various tools invoked by `cargo-ci`. This helps ensure that only intended environment variables
 //!   influence the CI process.
 //!
+//! - `trusted_packages`. (Optional) An array of package names whose `[package.metadata.ci]` variables and
+//!   `variables_from` commands are used even though the packages come from outside the workspace root. See
+//!   [Variables and Expressions](#variables-and-expressions).
+//!
+//!   ```toml
+//!   trusted_packages = ["shared-build-tools"]
+//!   ```
+//!
+//! - `stages`. (Optional) An ordered array of stage names. Jobs assigned to a stage via their `stage` property run
+//!   stage by stage, in the order declared here. Jobs within the same stage run in parallel, subject to their `needs`.
+//!   Jobs without a stage run one at a time, after all declared stages.
+//!
+//!   While jobs run in parallel on a terminal, a status region at the bottom of the output shows one line per running
+//!   job, with its current step, the package being processed, and how long it has been at it. Steps with
+//!   `parallel_packages` add one line per package in flight. Other output, such as
+//!   failures, is printed above the region. When the output isn't a terminal, the jobs still running are listed every
+//!   30 seconds instead.
+//!
+//!   ```toml
+//!   stages = ["build", "verify"]
+//!   ```
+//!
+//! - `target_dir_budget`. (Optional) How much disk space the target directory may take up, such as `"20GB"` or
+//!   `"512 MiB"`. Units are `B`, `KB`, `MB`, `GB`, and `TB`, or `KiB`, `MiB`, `GiB`, and `TiB` for powers of 1024, as
+//!   are the shorthands `K`, `M`, `G`, and `T`. Every run other than a dry run records the size of the target directory
+//!   in the run history, and when it's over budget, the summary warns about it along with how much it grew since the
+//!   previous run.
+//!
+//!   ```toml
+//!   target_dir_budget = "20GB"
+//!   ```
+//!
+//! - `max_run_duration`. (Optional) How long a run may take, such as `"45m"`, `"90s"`, or `"1h30m"`. Once it's used up,
+//!   no more steps start, and steps still running get 30 seconds to finish before they're killed. The jobs cut short
+//!   fail, those which never started aren't run, and the summary states that the run was truncated by its time budget.
+//!   Dry runs ignore it. It's mostly useful in an overlay, to keep quick runs such as those of a pre-commit hook quick.
+//!
+//!   ```toml
+//!   max_run_duration = "45m"
+//!   ```
+//!
+//! - `git_variables`. (Optional) If `false`, runs don't probe git for the git variables described under "Variables and
+//!   Expressions", for environments without git. Defaults to `true`.
+//!
+//!   ```toml
+//!   git_variables = false
+//!   ```
+//!
 //! ## The `[tools]` Table
 //!
 //! This table defines the `cargo` tools required by your jobs. These can be installed or updated using `cargo ci install`.
@@ -193,11 +837,28 @@
 //!
 //! # Extended configuration, mapping to `cargo install` options
 //! cargo-nextest = { version = "0.9.62", git = "https://github.com/nextest-rs/nextest.git", rev = "..." }
+//! cargo-udeps = { version = "0.1.50", features = ["vendored-openssl"], locked = true }
+//! ```
+//!
+//! The extended form supports `version`, `index`, `registry`, `git`, `branch`, `tag`, `rev`, `path`, `root`, `features`,
+//! `no_default_features`, `locked`, and `profile` fields. These map directly to the corresponding
+//! [`cargo install`](https://doc.rust-lang.org/cargo/commands/cargo-install.html) command-line options and provide
+//! you fine-grained control over how each tools is installed.
+//!
+//! ## The `[tool_bundles]` Table
+//!
+//! This table groups tools under a name, so a stack of tools shared across jobs or repositories can be referred to
+//! at once. Each entry lists tools from the `[tools]` table or other bundles, which are expanded recursively.
+//!
+//! ```toml
+//! [tool_bundles]
+//! lints = ["cargo-clippy-sarif", "sarif-fmt"]
+//! testing = ["cargo-nextest", "cargo-llvm-cov"]
+//! all = ["lints", "testing"]
 //! ```
 //!
-//! The extended form supports `version`, `index`, `registry`, `git`, `branch`, `tag`, `rev`, and `path` fields. These
-//! map directly to the corresponding [`cargo install`](https://doc.rust-lang.org/cargo/commands/cargo-install.html)
-//! command-line options and provide you fine-grained control over how each tools is installed.
+//! Bundles can't share a name with a tool, nor include themselves, directly or through other bundles. Jobs refer to
+//! bundles in their `tools` property, and `cargo ci install <BUNDLE>` installs the tools a bundle includes.
 //!
 //! ## The `[jobs.<job-id>]` Tables
 //!
@@ -220,12 +881,74 @@
 //!
 //! - `name`. (Optional) A human-friendly display name for the job, used in logs and output. Defaults to the job ID.
 //! - `needs`. (Optional) An array of job IDs that must complete successfully before this job starts.
+//! - `stage`. (Optional) The stage this job belongs to, which must be declared in the top-level `stages` array. A job can't
+//!   need a job from a later stage.
 //! - `if`. (Optional) An expression that must evaluate to `true` for the job to run.
 //! - `continue_on_error`. (Optional) A boolean or an expression. If `true`, a failure in this job will not stop the entire CI run. Defaults to `false`.
 //! - `steps`. (Required) An array of steps to execute.
 //! - `variables`. (Optional) A table of variables specific to this job that can be used in expressions.
-//!
-//! ### Steps
+//! - `network_rate_limit`. (Optional) Limits how often this job's network-heavy steps (`cargo update`, `cargo install`, and
+//!   `cargo publish`) can start, written as `"<count>/<period>"` such as `"10/min"` or `"1/5s"`. Short bursts up to the count
+//!   are allowed, after which steps are delayed. Network-heavy steps of rate-limited jobs also never run concurrently,
+//!   which helps avoid registry rate-limit errors when many packages run such steps.
+//! - `prebuild_dependencies`. (Optional) If `true`, runs a single `cargo build` of the dependencies of the selected packages
+//!   from the workspace root before the job's first `per_package` step, so the dependencies they share are compiled once
+//!   and reused from the target directory rather than rebuilt for every package. Only dependencies from outside the
+//!   workspace are built, the workspace's own packages are left to the job's steps. Defaults to `false`.
+//! - `ensure_clean`. (Optional) If `true`, the job fails when it leaves tracked files modified, listing the files that
+//!   changed. This catches formatting or code generation drift without a hand-written `git status` check. Files which
+//!   were already modified when the job started are only reported if their status or content changes, and changes made
+//!   by jobs running concurrently in the same stage are indistinguishable from the job's own. Defaults to `false`.
+//! - `os`. (Optional) The operating systems the job runs on, any of `"linux"`, `"macos"`, and `"windows"`. On other
+//!   systems, the job is skipped and reported as not run. Defaults to running everywhere.
+//! - `path`. (Optional) Directories to add to the front of `PATH` for the job's steps, relative to the workspace root,
+//!   such as `["./scripts", "./node_modules/.bin"]`. Lets steps run wrapper scripts and tools by name. See the `path`
+//!   property of steps.
+//! - `working_directory`. (Optional) The directory the job's steps run in, relative to the workspace root, such as
+//!   `"../infra"` for a job working on a sibling directory of a monorepo checkout. It may lead out of the workspace, and
+//!   `${NAME}` is replaced with the value of the variable `NAME`, as for steps. The job's steps run once in this
+//!   directory, so none of them may be `per_package`, and `--scope package` leaves them be. A step's own
+//!   `working_directory` is relative to the job's. The run fails when the directory doesn't exist, or when variables
+//!   make it an absolute path. Dry runs show the directory each step would run in.
+//!
+//! - `background`. (Optional) If `true`, the job's steps run with the lowest CPU and I/O priority, using `nice` and
+//!   `ionice` on Unix and the below-normal priority class on Windows, and the terminal shows nothing of the job unless it
+//!   fails. The log still records everything. Lets a heavy job run while you keep working on the machine.
+//!
+//! - `include_excluded`. (Optional) If `true`, the job's per-package steps also run for the crates the workspace
+//!   excludes and for nested workspaces under the workspace root, along with all the members of those. They run in the
+//!   crate's directory, and `batch_packages` leaves them out of its batches. Crates which are neither members of the
+//!   workspace nor excluded from it are never covered, since cargo refuses to build them, and neither are any of them
+//!   when packages are selected with `--package`. Defaults to `false`.
+//!
+//! - `pure`. (Optional) If `true`, the job's outcome only depends on the committed sources and the toolchain, as is the
+//!   case for checks like `cargo fmt`, `cargo clippy`, or `cargo deny`. Once the job passes, it's skipped in later runs
+//!   on the same git trees: the trees committed at `HEAD` for the selected packages, the workspace packages they depend
+//!   on, and the workspace's manifest and lock file, along with the files at the workspace root configuring cargo, the
+//!   toolchain, clippy, rustfmt, and cargo-deny, the configuration file, and the environment fingerprint described under
+//!   the `flaky` subcommand. Results are only cached when none of these paths has uncommitted changes,
+//!   including untracked files, and they're kept in `ci-results.json` in the target directory. Pass `--no-cache` to run
+//!   the job anyway. Defaults to `false`.
+//!
+//! - `tools`. (Optional) Tools from the `[tools]` table, or tool bundles, which must be installed before the job's steps
+//!   run, such as `["lints", "cargo-nextest"]`. When one isn't installed at the version the `[tools]` table asks for,
+//!   the job fails, unless the run was given `--install-tools`, which installs it first.
+//! - `consumes`. (Optional) Artifacts produced by another job which must be around before this job starts, such as
+//!   `{ from = "build", artifacts = ["target/release/mybin"] }`. The producing job must be listed in `needs`, and
+//!   artifacts are files or directories relative to the workspace root. Whenever the producing job succeeds, copies of
+//!   the artifacts are kept in the `ci-artifacts` directory of the target directory. Before this job starts, the
+//!   producing job must have succeeded, either in the same run or in an earlier one, such as when it's skipped for having
+//!   passed before, and missing artifacts are restored from those copies. Otherwise, this job fails without running any
+//!   of its steps. Dry runs don't check artifacts.
+//!
+//! - `foreach_features`. (Optional) The name of a workspace package, such as `"my-crate"`. The job then runs once for
+//!   every feature of the package other than `default`, as jobs named `<job>-<feature>` which expose the feature to
+//!   expressions through the `feature` variable. `${feature}` in the command lines of their steps is replaced with the
+//!   feature, so steps can run commands like `cargo test -p my-crate --features ${feature}`. The job
+//!   itself runs no steps and needs all of these, along with the jobs it lists in `needs`, so other jobs can depend on it
+//!   as a whole. Since finding the features takes `cargo metadata`, `cargo ci list-jobs` and `cargo ci validate` run it
+//!   for configurations with such jobs, which they otherwise spare.
+//! - `skip_features`. (Optional) Features `foreach_features` leaves out, such as `["nightly"]`.
 //!
 //! A step can be a simple command string or a table for more configuration.
 //!
@@ -243,14 +966,202 @@
 //!
 //! Here are the properties you can set in the extended form:
 //!
-//! - `command`: (Required) The shell command to execute for this step.
+//! - `command`: The shell command to execute for this step. Each step needs either a `command` or a `cargo_alias`.
+//!   It can also be a list of commands, such as `["cargo nextest run", "cargo test"]`, which are tried in order until
+//!   one succeeds, with a message saying which command the step fell back to. Handy for optional faster tools. Packages
+//!   which replace the step's command through `[package.metadata.ci.overrides]` don't fall back, and steps with an
+//!   `executor` can only have a single command.
+//! - `cargo_alias`: A cargo alias to run, along with its arguments, such as `"xtask lint"`. The alias is resolved by cargo
+//!   from the `.cargo/config.toml` files visible from the step's working directory, without going through a shell.
+//!   Arguments are split like a shell would, so quotes keep an argument with spaces together.
 //! - `name`: (Optional) A display name for the step, used for logs and output. Defaults to the command string.
 //! - `id`: (Optional) A stable identifier, used when steps depend on one another.
 //! - `if`: (Optional) An expression to conditionally run this step.
 //! - `continue_on_error`. (Optional) A boolean or an expression. If `true`, a failure in this step will not stop the entire job. Defaults to `false`.
 //! - `per_package`: (Optional) If `true`, run this step for each selected package in the workspace. The working directory will be the package's root. Otherwise,
-//!   the step runs once in the workspace root. Defaults to `false`.
+//!   the step runs once in the workspace root. Defaults to `false`. When the step runs `cargo test`, `cargo bench`, or
+//!   `cargo doc`, packages without any targets for that subcommand, such as packages without benchmarks for
+//!   `cargo bench`, are skipped.
+//! - `shards`. (Optional) Splits the packages processed by a `per_package` step into this many shards. Packages are
+//!   assigned to shards deterministically, based on their names. Use the `--shard` option of the `run` subcommand to
+//!   process a single shard; all shards are processed otherwise. The run history records how long sharded steps take
+//!   for each package, which `--save-shard-durations` saves for the `--balance-shards` option.
+//! - `batch_packages`. (Optional) For `per_package` steps running a cargo command, runs the command once for every batch
+//!   of up to this many packages, from the workspace root with a `-p` option for each package, rather than once in
+//!   every package's directory. This saves cargo's startup and dependency checks in large workspaces. The `-p` options
+//!   go right after the cargo subcommand. Packages whose `continue_on_error` differs end up in separate batches, and
+//!   packages which override the step's command still run on their own. A failure fails every package of the batch.
+//!   Can't be combined with `working_directory`.
+//! - `parallel_packages`. (Optional) For `per_package` steps, runs the step for up to this many packages at once rather
+//!   than one package at a time. Each package being processed gets a line of its own in the status region, and its
+//!   messages start with the job's name followed by the package, as in `test [foo]: ...`. Once the step fails for a
+//!   package without `continue_on_error`, no more packages are started, but those already running finish. Can't be
+//!   combined with `pty`.
+//! - `expected_duration`. (Optional) How long the step usually takes as a whole, such as `"2m"` or `"1h30m"`. While
+//!   the step runs, a command of it running for more than twice this long is reported, which helps catch network
+//!   stalls or builds accidentally made in debug mode. Once the run is over, the summary warns about steps which took
+//!   more than twice their expected duration, and, for steps with at least three earlier runs in the run history,
+//!   about steps which took at least 10 seconds and more than twice as long as the median of their recent runs.
+//! - `kind`. (Optional) What the step does with its command. Defaults to `"command"`, which just runs it. See below for
+//!   the other kinds.
+//! - `generated`. (Optional) For `codegen-check` steps, the directory holding the committed generated files, relative to
+//!   the step's working directory.
+//! - `baseline`. (Optional) For `semver-check` steps, the git revision to compare the packages against, such as
+//!   `"origin/main"`, instead of their latest published version.
+//! - `locked`. (Optional) For `update-check` steps, if `true`, the step fails when `Cargo.lock` is out of sync with the
+//!   manifests, as builds with cargo's `--locked` option would. Defaults to `false`.
+//! - `fixable`. (Optional) A table whose `command` fixes the problems this step reports, used by `cargo ci fix`. For
+//!   example, `fixable = { command = "cargo clippy --fix --allow-dirty" }` for a clippy step.
+//! - `requires_fresh`. (Optional) Generated files which must be up to date before the step runs, as mappings from source
+//!   files to the files generated from them, such as `["proto/*.proto:generated/*.rs"]`. Paths are relative to the
+//!   workspace root. A single `*` in the file name of the sources matches any part of the name, and a `*` in the
+//!   generated path stands for the same part, so `proto/user.proto` maps to `generated/user.rs`. Without a `*` in the
+//!   generated path, every source maps to the same file. When a generated file is missing or older than its source, the
+//!   step fails without running, instead of failing confusingly on stale code. Dry runs don't check generated files,
+//!   and steps which require fresh files can't be part of a plan.
+//! - `regenerate`. (Optional) A command run from the workspace root when `requires_fresh` finds stale generated files,
+//!   such as `"cargo xtask codegen"`. The step then only fails if the files are still stale afterwards.
+//! - `lock`. (Optional) A lock key, such as `"docker"`. Steps sharing a lock key never run at the same time, even when
+//!   they belong to jobs running concurrently. Useful for steps that bind fixed ports or modify global state like `~/.cargo`.
+//! - `memory_hint`. (Optional) How much memory the step may use at most, such as `"2G"` or `"4 GiB"`, using the same
+//!   units as `target_dir_budget`. Steps with a hint reserve that much of the memory available when the run starts
+//!   while they run, and steps of concurrent jobs wait for others to finish rather than start when their hints add up
+//!   to more than what's available, so link-heavy builds running in parallel don't get killed for running out of
+//!   memory. A step whose hint exceeds the available memory runs on its own. When the available memory can't be
+//!   detected, hints are ignored.
+//! - `destructive`. (Optional) If `true`, the step has effects beyond the workspace, such as deploying or resetting a
+//!   database. Destructive steps only run when `--allow-destructive` is given or, when both stdin and stdout are a
+//!   terminal, once confirmed. They are skipped otherwise. Defaults to `false`.
+//! - `reuse_result`. (Optional) If `true`, the step doesn't run when another job of the run already ran the very same
+//!   command, for the same packages, in the same directory, and with the same variables, such as a build step shared by
+//!   several jobs. How it turned out then is reused instead, showing `reused result from job '<job>'`, and jobs running
+//!   it at the same time wait for the first one to finish. Only set this for steps without side effects, since it
+//!   skips them. Steps of a `kind` other than running a command, `destructive` steps, and those producing SARIF can't
+//!   set it. `--no-cache` runs them anyway. Defaults to `false`.
+//! - `confirm`. (Optional) A question to ask before the step runs, such as `"About to publish to crates.io, continue?"`.
+//!   When both stdin and stdout are a terminal, the job fails unless the answer is yes. Elsewhere the job fails right away, explaining
+//!   that `--yes` confirms the step, and so does `plan`. Protects shared configurations from accidental runs of steps
+//!   which can't be undone.
+//! - `output_encoding`. (Optional) The encoding of the command's output, such as `"cp1252"` or `"shift_jis"`, or `"auto"`
+//!   to guess the encoding of output which isn't valid UTF-8. Defaults to UTF-8. When a step fails with output that isn't
+//!   valid UTF-8, the raw bytes are also saved to the run's temporary directory.
+//! - `output_filters`. (Optional) Regular expressions matching lines of the step's output which aren't worth showing, such
+//!   as `["^warning: unused", "progress"]`. Matching lines are left out of the output shown in the terminal when the
+//!   step fails, along with a note of how many were left out, but they're still written to the log. Lines starting with
+//!   `error` are always shown, so chatty tools can be tamed without hiding their errors.
+//! - `os`. (Optional) The operating systems the step runs on, any of `"linux"`, `"macos"`, and `"windows"`. On other
+//!   systems, the step is skipped with a message saying so, and its outcome is `skipped`. Defaults to running everywhere.
+//! - `path`. (Optional) Directories to add to the front of `PATH` for this step, relative to the workspace root. They
+//!   come ahead of the directories listed by the step's job. Each directory must exist by the time the step starts, so
+//!   it may be created by an earlier step, and `cargo ci validate` warns about directories which don't exist yet.
+//! - `retry_strategies`. (Optional) How to retry the step when it fails, such as
+//!   `["plain", "clean-incremental", "clean-package"]`. Each entry is one more attempt, made after a progressively more
+//!   aggressive cleanup, until the step succeeds. `plain` retries the step as is, `clean-incremental` first removes the
+//!   incremental compilation data from the target directory, and `clean-package` first runs `cargo clean` for the step's
+//!   packages. This resolves spurious failures caused by stale incremental compilation data. Failures which are retried
+//!   are reported as ignored. Steps with retry strategies can't be part of a plan. Defaults to no retries.
 //! - `variables`. (Optional) A table of variables specific to this step that can be used in expressions.
+//! - `inherit_env`. (Optional) If `false`, the command doesn't inherit the environment `cargo-ci` runs in. It only gets
+//!   `PATH`, `CI_TEMP`, and the variables declared for the step in the configuration or with `--variable`, as
+//!   environment variables. Insulates steps from developer-specific settings such as `RUSTFLAGS` or `CARGO_TARGET_DIR`.
+//!   Defaults to `true`.
+//! - `working_directory`. (Optional) The directory the step runs in, relative to the package's directory for
+//!   `per_package` steps and to the workspace root otherwise, such as `"target/generated/${PROFILE}"`. Variables are
+//!   interpolated as `${NAME}`, and the step fails when one isn't defined.
+//! - `working_directory_mode`. (Optional) What to do about `working_directory` before the step runs. With `"must-exist"`
+//!   (default) the step fails when the directory doesn't exist, with `"create-if-missing"` the directory is created
+//!   when needed, and with `"temp"` the directory is created empty within the run's temporary directory, named by
+//!   `working_directory`, which must then be a relative path without `..`. Every job, step, and package gets a
+//!   temporary directory of its own, as `<CI_TEMP>/work/<job>/<step>/<package>/<working_directory>`, where `<step>` is
+//!   the position of the step in its job, starting at 0. Steps running in a temporary directory can't be part of a plan.
+//! - `create_working_directory`. (Optional) If `true`, the same as `working_directory_mode = "create-if-missing"`.
+//!   Handy for steps pointed at generated output directories, which an earlier run may not have created.
+//! - `executor`. (Optional) The name of the executor which runs the step, from the `[executors]` table. Defaults to
+//!   `"local"`, the built-in executor which runs the command through the platform's shell. Only steps of the `command`
+//!   kind can use another executor, and such steps can't be part of a plan.
+//!
+//! - `sarif`. (Optional) If `true`, the step's diagnostics are collected into the run's SARIF file, which code-scanning
+//!   services and editors understand. The step must either be a `cargo fmt --check` command, whose output lists the
+//!   files it would reformat, or a cargo command reporting JSON messages, such as
+//!   `cargo clippy --message-format=json`, whose diagnostics are converted. Each tool, whether `clippy`, `rustc`, or
+//!   `rustfmt`, gets its own run within the file, and diagnostics reported more than once appear once.
+//! - `pty`. (Optional) If `true`, the command runs under a pseudo-terminal, for tools which only show colors or progress
+//!   when writing to a terminal. Its standard error is then merged into its standard output. This goes through the
+//!   `script` utility, so it's ignored on Windows. Can't be combined with `inherit_env = false` or an `executor`.
+//!   Defaults to `false`.
+//! - `ulimit_core`. (Optional) If `true`, the processes the step starts write core dumps when they crash, when running
+//!   with `--debug-failures`, by lifting the limit on their size through the shell. Ignored on Windows, where crash dumps
+//!   are configured system-wide. Defaults to `false`.
+//! - `network`. (Optional) If `false`, the step runs without network access, so tests meant to be hermetic fail loudly
+//!   when they reach for the network. The step's command runs with `unshare` in network and user namespaces of its own,
+//!   where only the loopback interface is up, so tests can still talk to servers they start, and where it appears to
+//!   run as root. This takes Linux with unprivileged user namespaces enabled, and the `ip` command, which brings up
+//!   the loopback interface. Elsewhere, the step runs with network
+//!   access, and the run's summary warns about it. Steps which set `network = false` can't be part of a plan. Defaults
+//!   to `true`.
+//!
+//! #### Cargo JSON Messages
+//!
+//! When a step runs cargo with `--message-format=json`, or any of its `json-*` variants, and fails, the thousands of
+//! lines of JSON it produces are not shown. Instead, the compiler messages are summarized: the number of errors for
+//! each package along with their error codes, and the first error of each package with its location. Any output which
+//! isn't a JSON message, such as the output of tests, is shown as-is, and the first error is included in the run's
+//! summary. The full messages are saved to the run's temporary directory.
+//!
+//! #### Codegen Check Steps
+//!
+//! Projects that commit generated sources, such as Protocol Buffers bindings, can verify those sources are current
+//! with a `codegen-check` step. Its command runs the generator, writing into the scratch directory named by the
+//! `CODEGEN_OUT` environment variable. The generated files are then compared against the committed ones in the
+//! `generated` directory, and the step fails with a diff of every file that changed, appeared, or disappeared.
+//!
+//! ```toml
+//! [jobs.codegen]
+//! steps = [
+//!   { kind = "codegen-check", command = "protoc --rust_out=$CODEGEN_OUT proto/*.proto", generated = "src/generated" }
+//! ]
+//! ```
+//!
+//! #### Semver Check Steps
+//!
+//! A `semver-check` step checks every package with a library for changes to its public API which break semver, using
+//! cargo-semver-checks. The tool is installed when it's missing, at the version listed in the `[tools]` table if it's
+//! there, or at its latest version otherwise. Packages are compared against their latest published version, skipping
+//! those with `publish = false`, or against the git revision given as `baseline`. The step always runs once per package
+//! and doesn't need a command, though one can be given to pass options to cargo-semver-checks. When the step fails,
+//! the violated lints are listed along with the items which broke them, and the error in the summary names them too.
+//!
+//! ```toml
+//! [jobs.semver]
+//! steps = [{ kind = "semver-check", baseline = "origin/main" }]
+//! ```
+//!
+//! #### Audit Steps
+//!
+//! An `audit` step checks the workspace's dependencies against the policy of the `[audit]` table. Security advisories
+//! come from cargo-audit, which is installed when it's missing like cargo-semver-checks is for `semver-check` steps,
+//! while licenses are read from the dependencies' manifests. The step runs once for the whole workspace and doesn't
+//! need a command; one given to pass options to cargo-audit must keep its `--json` report. Every finding is listed,
+//! whether it fails the step or the policy tolerates it, and the error in the summary names the failing ones.
+//!
+//! ```toml
+//! [jobs.audit]
+//! steps = [{ kind = "audit" }]
+//! ```
+//!
+//! #### Update Check Steps
+//!
+//! An `update-check` step lists the dependencies `cargo update` would change in `Cargo.lock`: those it would update,
+//! downgrade, add, or remove. It runs `cargo update --dry-run` once for the whole workspace unless given another
+//! command, such as `cargo +nightly update -Z minimal-versions --dry-run` to see what minimal versions would resolve
+//! to, which must still only report the changes rather than make them. Available updates don't fail the step. With
+//! `locked = true`, the step fails when `Cargo.lock` is out of sync with the manifests, such as when a dependency was
+//! added to a manifest without updating the lockfile.
+//!
+//! ```toml
+//! [jobs.deps]
+//! steps = [{ kind = "update-check", locked = true }]
+//! ```
 //!
 //! ## The `[variables]` Table
 //!
@@ -261,6 +1172,155 @@
 //! FOO = "Bar"
 //! ```
 //!
+//! ## The `[hints]` Table
+//!
+//! When a step fails, its captured output is matched against a library of regular expressions describing well-known
+//! problems, and the hints for any matching patterns are appended to the failure report and the run summary. A few
+//! common environment errors, such as a missing C linker or OpenSSL installation, are recognized out of the box. This
+//! table adds your own patterns, mapping each regular expression to a hint:
+//!
+//! ```toml
+//! [hints]
+//! "linker `cc` not found" = "run `apt install build-essential`"
+//! "error: could not compile `my-sys`" = "my-sys needs the vendor SDK, see docs/setup.md"
+//! ```
+//!
+//! Configured hints are tried before the built-in ones. Using the same pattern as a built-in hint replaces it, and
+//! an empty hint disables it.
+//!
+//! ## The `[audit]` Table
+//!
+//! The policy `audit` steps apply to the workspace's dependencies. Vulnerabilities always fail the step, unless their
+//! advisory is ignored.
+//!
+//! ```toml
+//! [audit]
+//! ignore = ["RUSTSEC-2020-0071"]
+//! allowed_licenses = ["MIT", "Apache-2.0", "Unicode-3.0"]
+//! deny_warnings = true
+//! ```
+//!
+//! - `ignore`. (Optional) Advisories which were looked into and deemed harmless, so they don't fail the step.
+//! - `allowed_licenses`. (Optional) The SPDX identifiers of the licenses dependencies may use. A dependency passes when
+//!   its license expression, such as `MIT OR Apache-2.0`, can be satisfied with allowed licenses alone, and fails when it
+//!   declares no license. Licenses aren't checked when the list is empty, which is the default.
+//! - `deny_warnings`. (Optional) If `true`, dependencies which are unmaintained, unsound, or yanked fail the step too.
+//!   They are only listed otherwise. Defaults to `false`.
+//!
+//! ## The `[executors]` Table
+//!
+//! Executors run steps somewhere other than on this machine, such as in a VM, a Kubernetes pod, or as a Bazel action,
+//! without any changes to `cargo-ci`. This table maps the name of each executor to the command line which starts it,
+//! run through the platform's shell from the workspace root. Steps pick an executor with their `executor` field.
+//!
+//! ```toml
+//! [executors]
+//! vm = "python3 tools/run-in-vm.py"
+//! ```
+//!
+//! For every execution of a step, the executor is started and receives a JSON request on its standard input, which is
+//! then closed:
+//!
+//! ```json
+//! {
+//!   "protocol": 1,
+//!   "step": "cargo test",
+//!   "package": "my-crate",
+//!   "command": "cargo test",
+//!   "directory": "/work/my-workspace/crates/my-crate",
+//!   "env": { "CI_TEMP": "/work/my-workspace/target/ci-tmp/20250101T120000-42" }
+//! }
+//! ```
+//!
+//! `package` is left out for steps which don't run per package, and `env` holds the variables set for the step. Once
+//! the step has run, the executor writes a JSON response to its standard output and exits successfully:
+//!
+//! ```json
+//! { "exit_code": 0, "stdout": "...", "stderr": "..." }
+//! ```
+//!
+//! The step's output is then handled as if the step had run locally. When the executor fails or its response can't
+//! be read, the step fails, and whatever the executor wrote to its standard error is included in the error.
+//!
+//! `cargo ci execute` is the built-in local executor speaking this protocol. It runs the requested command through the
+//! platform's shell, from the requested directory and with the requested variables, just as steps without an executor
+//! run, and writes the response. Executors which only need to get somewhere else first can hand the request over to it,
+//! such as one running `ssh build-box cargo ci execute`, and it's a handy way to try out a step through the protocol:
+//!
+//! ```toml
+//! [executors]
+//! same-machine = "cargo ci execute"
+//! ```
+//!
+//! ## The `[notifications]` Table
+//!
+//! How runs let you know how they're going when you've switched to another terminal tab or window. Nothing is shown
+//! when the output isn't a terminal, nor for dry runs.
+//!
+//! ```toml
+//! [notifications]
+//! title = true
+//! bell = "failure"
+//! desktop = "always"
+//! min_duration = "5m"
+//! ```
+//!
+//! - `title`. (Optional) If `true`, the terminal's title shows how many jobs are done and what's running, such as
+//!   `cargo ci [2/5] test: cargo test`, while the jobs run. The title the terminal had before comes back once they're
+//!   done, in terminals supporting the title stack of xterm. Defaults to `false`.
+//! - `bell`. (Optional) Rings the terminal's bell once the run is over: `never`, on `failure`, or `always`. Defaults to
+//!   `never`.
+//! - `desktop`. (Optional) Sends a desktop notification saying how the run turned out once it's over: `never`, on
+//!   `failure`, or `always`. This uses the OSC 9 escape sequence, which terminals such as iTerm2, Windows Terminal, and
+//!   kitty turn into a notification, and others ignore. Defaults to `never`.
+//! - `min_duration`. (Optional) Only ring the bell and send desktop notifications for runs which took at least this
+//!   long, such as `"5m"`. By default, every run gets them.
+//!
+//! ## The `[overlays.<name>]` Tables
+//!
+//! Overlays let the same configuration file behave slightly differently in different environments, such as on a
+//! developer's machine versus in a CI system. Each overlay can patch a few values of the base configuration:
+//!
+//! ```toml
+//! [overlays.ci]
+//! default_jobs = ["build", "test", "lint"]
+//! tools = { cargo-nextest = "0.9.100" }
+//! variables = { RELEASE = "true" }
+//! ```
+//!
+//! - `variables`. Variables added to the `[variables]` table, replacing any with the same name.
+//!
+//! - `default_jobs`. Replaces the list of jobs run when none are specified on the command line.
+//!
+//! - `tools`. New versions for tools defined in the `[tools]` table.
+//!
+//! - `max_run_duration`. Replaces the time budget of a run.
+//!
+//! Overlays are selected with the `--overlay` option. When no overlay is selected and the `CI` environment variable is
+//! set to anything but `false` or `0`, as most CI services do, the `ci` overlay is applied if it is defined.
+//!
+//! ## The `[runs.<name>]` Tables
+//!
+//! Complex invocations, such as a nightly run of every job with some variables set, can be given a name rather than
+//! living in shell history or scripts. They are selected with `cargo ci run --run <NAME>`:
+//!
+//! ```toml
+//! [runs.nightly-full]
+//! jobs = ["build", "test", "bench"]
+//! packages = ["core", "cli"]
+//! variables = { RELEASE = "true", "test.THOROUGH" = "true" }
+//! allow_destructive = true
+//! ```
+//!
+//! - `jobs`. The jobs to run, as if given on the command line.
+//!
+//! - `packages`. The packages to run jobs on, as with `--package`.
+//!
+//! - `variables`. Variables defined as with `--variable`, so their names can be scoped to a job or step id.
+//!
+//! - `allow_destructive`, `check_feature_unification`, and `explain_conditions`. Turn on the command-line flags of the
+//!   same name.
+//!
 //! ## File Formats
 //!
 //! `cargo-ci` supports configuration files in TOML, YAML, and JSON formats. The file extension
@@ -281,6 +1341,17 @@
 //!   `passthrough_env_variables` setting in `ci.toml`. Only the variables listed there will be available for use in expressions.
 //!   If no such setting is defined, then all environment variables are imported.
 //!
+//! - **Platform Variables**. `runner.os` is the operating system `cargo-ci` runs on, such as `linux`, `macos`, or
+//!   `windows`, and `runner.arch` its architecture, such as `x86_64` or `aarch64`. They are part of the environment,
+//!   so every other source takes precedence over them.
+//!
+//! - **Git Variables**. `git_sha` and `git_short_sha` are the full and abbreviated hashes of the commit at `HEAD`,
+//!   `git_branch` the name of the current branch, which is empty when `HEAD` is detached, `git_dirty` is `true` when
+//!   tracked files have uncommitted changes and `false` otherwise, and `git_tag` the tag at `HEAD`, defined only when
+//!   `HEAD` is tagged. They're left out when the workspace isn't in a git repository or `git_variables = false`. Like the
+//!   platform variables, they are part of the environment, so every other source takes precedence over them, and a
+//!   step's `working_directory` can refer to them, such as `"dist/${git_short_sha}"`.
+//!
 //! - **Workspace Variables**. You can define global variables in the `[variables]` table in the configuration file.
 //!
 //! - **Job and Step Variables**. You can define variables specific to a job or step using the `variables` property.
@@ -294,8 +1365,77 @@
 //!     SPECIAL_FLAG = "true"
 //!     ```
 //!
+//!   A crate can also compute variables when a run starts, with a `variables_from` command run through the shell in the
+//!   crate's directory. Each `KEY=VALUE` line it outputs defines a variable, taking precedence over those of the
+//!   `[package.metadata.ci.variables]` table. Blank lines and lines starting with `#` are ignored.
+//!
+//!     ```toml
+//!     # In a crate's Cargo.toml
+//!     [package.metadata.ci]
+//!     variables_from = "scripts/ci-vars.sh"
+//!     ```
+//!
+//!   A crate can also change the command of a step with an `id` when the step runs for it, through the
+//!   `[package.metadata.ci.overrides]` table. An entry's `command` replaces the step's command, and its `args` are
+//!   appended to the command, so a crate can swap in another test runner or pass a few extra flags of its own. How the
+//!   step's output is handled still follows the step's own command, so a replacement should produce the same kind of
+//!   output. `cargo ci validate --packages` warns about overrides naming a step id which no step has.
+//!
+//!     ```toml
+//!     # In a crate's Cargo.toml
+//!     [package.metadata.ci.overrides.test]
+//!     command = "cargo nextest run"
+//!
+//!     [package.metadata.ci.overrides.clippy]
+//!     args = "-- -A clippy::pedantic"
+//!     ```
+//!
+//!   Only crates within the workspace root are trusted with package metadata. Members pulled in from elsewhere, such as
+//!   a directory outside the workspace root, have their variables and overrides ignored and their `variables_from`
+//!   command isn't run, with a warning, unless they are listed in `trusted_packages`.
+//!
 //! - **Command-Line Variables**. You can define variables directly via the command-line using the `-v, --variable <KEY=VAL>` option.
-//!   These variables take precedence over all other variable sources.
+//!   These variables take precedence over all other variable sources. A variable named `<SCOPE>.<KEY>` is scoped: it only
+//!   applies to the job or step whose ID is `<SCOPE>`, and takes precedence over unscoped command-line variables. Variables
+//!   scoped to a step take precedence over those scoped to its job.
+//!
+//! - **Step Outcomes**. Once a step with an `id` has finished, the conditions of the later steps of the same job can
+//!   read how it turned out from the `steps.<id>.outcome` variable: `success`, `failure` (including failures ignored
+//!   through `continue_on_error`), or `skipped` when its condition skipped every package or it was a destructive step
+//!   which wasn't allowed to run. Dry runs and plans assume every step that would run succeeds. Step IDs used this way
+//!   must be valid expression identifiers, so stick to letters, digits, and underscores.
+//!
+//! Besides being visible to expressions, the variables a step gets are set as environment variables for its command,
+//! the one of highest precedence winning, so commands can refer to them as `$NAME`, or `%NAME%` on Windows. That
+//! includes the variables of the environment, platform, and git repository, unless the step sets `inherit_env = false`.
+//!
+//! Expressions can also ask about the tools installed with `cargo install`, which lets steps use a faster tool when
+//! it's around and fall back to something else otherwise:
+//!
+//! - `tool_installed("<name>")` is true when the named tool is installed.
+//!
+//! - `tool_version("<name>")` is the version of the named tool, or an empty string when it isn't installed.
+//!
+//! Tools installed some other way, such as through a system package manager, aren't known to these functions.
+//!
+//! Variables are strings, but an expression gives them another type where its use of them makes it clear:
+//!
+//! - Variables compared with a number, such as `threads > 4` or `ratio >= 0.5`, are numbers.
+//!
+//! - Variables compared with `true` or `false`, or used with `&&`, `||`, or `!`, such as `!git_dirty`, are booleans.
+//!   Their values must be `true` or `false`, in any case.
+//!
+//! - Variables ordered with `<`, `<=`, `>`, or `>=` against a string which looks like a version, such as
+//!   `rustc_version >= "1.80"`, are versions. Versions are compared by their numeric parts, so `1.80` is the same as
+//!   `1.80.0` and comes after `1.9`, a leading `v` and build metadata are ignored, and pre-releases such as
+//!   `1.0.0-beta.2` come before their release.
+//!
+//! Variables used in any other way, or in ways which disagree on their type, remain strings, so `CI == "true"` compares
+//! strings as it always did. When a variable's value doesn't fit its type, such as `threads = "auto"`, the expression
+//! fails to evaluate with an error naming the variable. Types can also be given explicitly with `int(x)`,
+//! `float(x)`, `bool(x)`, and `version(x)`, which convert strings and numbers. `version(x)` gives a string which only
+//! compares meaningfully with other versions, so a string literal compared with it is converted as well:
+//! `version(tool_version("cargo-nextest")) >= "0.9"` is the same as `version(tool_version("cargo-nextest")) >= version("0.9")`.
 //!
 //! Given all these sources, it gets complicated to know which variable takes effect when and what is the precedence of selection
 //! in case there are conflicting definitions. Hopefully, the following helps clarify things:
@@ -320,6 +1460,7 @@
 //! - Job variables
 //! - Package metadata variables
 //! - Command-line variables
+//! - Step outcomes
 //!
 //! When evaluating `Step::continue_on_error`, precedence from lowest to highest is:
 //!
@@ -351,6 +1492,26 @@
 //! ]
 //! ```
 //!
+//! This step uploads crash dumps only when the tests failed.
+//!
+//! ```toml
+//! [jobs.test]
+//! steps = [
+//!   { id = "tests", command = "cargo test", continue_on_error = true },
+//!   { command = "scripts/upload-dumps.sh", if = 'steps.tests.outcome == "failure"' }
+//! ]
+//! ```
+//!
+//! These steps run the tests with `cargo-nextest` when it's installed, and with `cargo test` otherwise.
+//!
+//! ```toml
+//! [jobs.test]
+//! steps = [
+//!   { command = "cargo nextest run", if = 'tool_installed("cargo-nextest")' },
+//!   { command = "cargo test", if = '!tool_installed("cargo-nextest")' }
+//! ]
+//! ```
+//!
 //! # Logging
 //!
 //! `cargo-ci` generates detailed logs for each use of the `run` or `install` subcommands. The logs are
@@ -361,6 +1522,30 @@
 //! You can specify a custom log file using the `--log-file <FILE>` option, and you can control how many
 //! log files to retain with the `--log-file-retention-count <COUNT>` option.
 //!
+//! In a terminal, the progress line which is updated in place is cut short with an ellipsis when it doesn't fit the
+//! width of the terminal, counting CJK characters and emoji as two columns. The log always has the full line.
+//!
+//! Logs are synced to disk every second, and right away for warnings and errors, so a run which gets killed or crashes
+//! loses little of its log. Every log starts with a line saying `cargo-ci` started and ends with one saying it ended.
+//! When a run finds that the log of the previous run never got to its end, it warns about it, offers to show the end
+//! of that log when running in a terminal, and records the run as crashed in the run history. This check is skipped
+//! with `--no-lock`, since another run may still be going on, and with `--log-file`.
+//!
+//! The lines written for a job, including the commands it runs and their output when they fail, start with the job's
+//! name, so the lines of jobs running concurrently can be told apart. The `logs` subcommand relies on this to search
+//! past runs.
+//!
+//! # Temporary Files
+//!
+//! Each use of the `run` subcommand gets its own scratch directory under `target/ci-tmp/`, whose path is given to
+//! every step in the `CI_TEMP` environment variable. Scripts can put intermediate files there instead of littering
+//! the repository or `/tmp`. The directory is deleted once all jobs have succeeded. When any job fails, the
+//! directory is kept so its content can help diagnose the failure, and its path is printed after the summary.
+//!
+//! On Windows, paths longer than the traditional 260 character limit are accessed as extended-length `\\?\` paths
+//! when writing logs, badges, and temporary files. Processes can't be started in directories that long though, so a
+//! step whose working directory exceeds the limit fails with an error suggesting to move the workspace to a shorter path.
+//!
 //! # Using `cargo-ci` in Real CI Systems
 //!
 //! `cargo-ci` is designed to be compatible with real CI systems. You can use it in your CI pipelines
@@ -373,59 +1558,451 @@
 //! in your CI environment before invoking it.
 
 mod args;
-//mod cargo_tools;
+mod artifact_store;
+mod audit;
+mod badges;
+mod cancellation;
+mod cargo_aliases;
+mod cargo_messages;
+#[expect(dead_code, reason = "expressions only need to know which tools are installed, not how")]
+mod cargo_tools;
+mod child_process;
+mod ci_detection;
+mod codegen;
 mod color_modes;
 mod commands;
 mod config;
+mod debug_failures;
+mod diagnostics;
+mod diff;
+mod excluded_crates;
+mod executor;
+mod exit_codes;
 mod expressions;
+mod feature_unification;
+mod fingerprint;
+mod freshness;
+mod git;
+mod github_pr;
+mod github_workflow;
+mod heartbeat;
+mod history;
 mod host;
+mod isolated_worktree;
+mod keyed_locks;
+mod live_status;
+mod lockfile_changes;
 mod log;
+mod log_search;
+mod long_paths;
+mod memory_budget;
+mod messages;
+mod network_isolation;
 mod outputter;
 mod pkg_data;
+mod plan;
+mod platform;
+mod required_targets;
+mod result_cache;
+mod revision_snapshot;
+mod run_lock;
+mod run_progress;
+mod sarif;
+mod semver_checks;
+mod sharding;
+mod shared_results;
+mod shuffle;
+mod status_board;
+mod step_outcomes;
+mod summary;
+mod target_dir;
+mod telemetry;
+mod terminal_notifications;
+mod test_results;
+mod token_bucket;
+mod toml_text;
+mod workspace_root;
 
 use crate::args::{Args, CargoSubcommand, Commands};
-//use crate::cargo_tools::CargoTools;
-use crate::config::Config;
-use anyhow::{Context, Result};
+use crate::config::{Config, EffectiveConfig};
+use anyhow::{Context, Result, anyhow};
 use args::Cli;
-use cargo_metadata::MetadataCommand;
+use cargo_metadata::{Metadata, MetadataCommand};
 use clap::Parser;
-use commands::{install_tools, list_jobs, run_jobs};
+use commands::{
+    RunArgs, cancel, clean, config, exec_plan, execute, fix, flaky, init, install_tools, list_jobs, logs, migrate, plan, run_jobs,
+    run_workspaces, simulate, test, validate,
+};
+use diagnostics::{Diagnostic, ErrorFormat};
+use exit_codes::Failure;
 use host::{Host, RealHost};
+use isolated_worktree::IsolatedWorktree;
+use revision_snapshot::RevisionSnapshot;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 
 fn main() {
     let CargoSubcommand::Ci(args) = Cli::parse().command;
     let mut host = RealHost::new();
 
+    // a panic anywhere, including in the threads running jobs, is a bug rather than a failure of the run
+    let report_panic = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        report_panic(info);
+        std::process::exit(Failure::Internal.exit_code());
+    }));
+
     if let Err(e) = inner_main(&args, &mut host) {
-        if !host.should_fail_silently() {
-            host.eprintln(format!("ERROR: {e}"));
+        // commands which report their errors themselves do so for people, so tools still get the JSON
+        match args.error_format {
+            ErrorFormat::Human if host.should_fail_silently() => {}
+            ErrorFormat::Human => host.eprintln(format!("ERROR: {e}")),
+            ErrorFormat::Json => host.eprintln(diagnostics::to_json(&e)),
         }
-        std::process::exit(1);
+        std::process::exit(exit_codes::exit_code(&e));
     }
 }
 
 fn inner_main<H: Host>(args: &Args, host: &mut H) -> Result<()> {
+    // the current directory needn't be a workspace when running jobs in other ones
+    if let Commands::Run(ref run_args) = args.get_command()
+        && !run_args.workspace_dirs().is_empty()
+    {
+        return run_in_workspaces(args, run_args, host);
+    }
+
+    if let Some(result) = run_without_metadata(args, host) {
+        return result;
+    }
+
     let mut cmd = MetadataCommand::new();
     _ = cmd.manifest_path(&args.manifest_path);
 
-    let metadata = cmd.no_deps().exec().context("unable to obtain cargo metadata")?;
-    let cfg = Config::load(metadata.workspace_root.as_std_path(), args.config.as_ref())?;
-    //    let _tools = CargoTools::read()?;
+    let metadata = read_metadata(host, cmd.no_deps())?;
+    let load_config = |host: &H| load_workspace_config(args, host, &metadata);
 
     match args.get_command() {
-        Commands::Run(ref args) => {
-            run_jobs(args, host, &cfg, &metadata)?;
+        Commands::Run(ref run_args) => run(args, run_args, host, &metadata)?,
+
+        Commands::Test(ref test_args) => {
+            // projects without a configuration file can still run their tests
+            let cfg = if args.config.is_none() && Config::find(host, metadata.workspace_root.as_std_path()).is_none() {
+                Config::default()
+            } else {
+                load_config(host)?
+            };
+
+            test(test_args, host, cfg, &metadata)?;
+        }
+
+        Commands::Fix(ref args) => {
+            let cfg = load_config(host)?;
+            fix(args, host, &cfg, &metadata)?;
+        }
+
+        Commands::Plan(ref plan_args) => {
+            if let Some(rev) = plan_args.run_args().at() {
+                let (_snapshot, cfg, metadata) = load_at_revision(args, host, &metadata, rev)?;
+                plan(plan_args, host, &cfg, &metadata)?;
+            } else {
+                plan(plan_args, host, &load_config(host)?, &metadata)?;
+            }
+        }
+
+        Commands::ExecPlan(ref args) => {
+            let cfg = load_config(host)?;
+            exec_plan(args, host, &cfg, &metadata)?;
+        }
+
+        Commands::Simulate(ref args) => {
+            simulate(args, host, &metadata)?;
+        }
+
+        Commands::Cancel(ref args) => {
+            cancel(args, host, &load_config(host)?, &metadata)?;
         }
 
         Commands::ListJobs(ref args) => {
-            list_jobs(args, host, &cfg);
+            list_jobs(args, host, &load_config(host)?, Some(&metadata))?;
         }
 
         Commands::Install(ref args) => {
+            let cfg = load_config(host)?;
             install_tools(args, host, &cfg, &metadata)?;
         }
+
+        Commands::Validate(ref args) => {
+            validate(
+                args,
+                host,
+                &load_config(host)?,
+                metadata.workspace_root.as_std_path(),
+                Some(&metadata),
+            )?;
+        }
+
+        Commands::Config(ref config_args) => {
+            let effective = EffectiveConfig::load(
+                host,
+                metadata.workspace_root.as_std_path(),
+                args.config.as_ref(),
+                &args.overlay,
+                args.offline,
+            )
+            .map_err(|e| Failure::Config.tag(e))?;
+            config(config_args, host, &effective, &metadata)?;
+        }
+
+        Commands::Flaky(ref args) => {
+            flaky(args, host, &metadata)?;
+        }
+
+        Commands::Logs(ref args) => {
+            // the logs of past runs can still be searched when the configuration is broken
+            logs(args, host, load_config(host).ok().as_ref(), &metadata)?;
+        }
+
+        Commands::Init(ref args) => {
+            init(args, host, &metadata)?;
+        }
+
+        Commands::Migrate(ref args) => {
+            migrate(args, host, &metadata)?;
+        }
+
+        Commands::Execute(_) => unreachable!("steps are executed without the metadata"),
+
+        Commands::Clean(ref clean_args) => {
+            // telling stale artifacts apart takes knowing about every dependency, not just the workspace's packages
+            let mut cmd = MetadataCommand::new();
+            _ = cmd.manifest_path(&args.manifest_path);
+            clean(clean_args, host, &read_metadata(host, &cmd)?)?;
+        }
     }
 
     Ok(())
 }
+
+/// Loads the configuration of a workspace as the arguments say, tagging errors as configuration errors.
+fn load_config(args: &Args, host: &impl Host, workspace_root: &Path) -> Result<Config> {
+    Config::load(host, workspace_root, args.config.as_ref(), &args.overlay, args.offline).map_err(|e| Failure::Config.tag(e))
+}
+
+/// Loads the configuration of the workspace described by the metadata, with the jobs running for every feature of a
+/// package expanded.
+fn load_workspace_config(args: &Args, host: &impl Host, metadata: &Metadata) -> Result<Config> {
+    let cfg = load_config(args, host, metadata.workspace_root.as_std_path())?;
+    with_feature_jobs(cfg, metadata)
+}
+
+/// Expands the jobs which run for every feature of a package, which takes knowing about the workspace's packages.
+fn with_feature_jobs(mut cfg: Config, metadata: &Metadata) -> Result<Config> {
+    cfg.expand_feature_jobs(metadata).map_err(|e| Failure::Config.tag(e))?;
+    Ok(cfg)
+}
+
+/// Lists or validates jobs by only reading the configuration, unless they're asked about packages, which spares running
+/// `cargo metadata`, a slow affair on large workspaces. Steps handed over by executors don't even need the configuration.
+///
+/// Returns `None` for the commands which need the metadata, which includes listing or validating jobs which run for
+/// every feature of a package.
+fn run_without_metadata<H: Host>(args: &Args, host: &H) -> Option<Result<()>> {
+    // jobs running for every feature of a package can't be expanded without knowing the package's features
+    let load = || -> Result<Option<(PathBuf, Config)>> {
+        let workspace_root = workspace_root::find(&args.manifest_path)?;
+        let cfg = load_config(args, host, &workspace_root)?;
+        let feature_jobs = cfg.jobs().iter().any(|(_, job)| job.foreach_features().is_some());
+        Ok((!feature_jobs).then_some((workspace_root, cfg)))
+    };
+
+    match args.get_command() {
+        Commands::ListJobs(ref list_args) if !list_args.needs_metadata() => load().transpose().map(|loaded| {
+            let (_, cfg) = loaded?;
+            list_jobs(list_args, host, &cfg, None)
+        }),
+        Commands::Validate(ref validate_args) if !validate_args.needs_metadata() => load().transpose().map(|loaded| {
+            let (workspace_root, cfg) = loaded?;
+            validate(validate_args, host, &cfg, &workspace_root, None)
+        }),
+        Commands::Execute(ref execute_args) => Some(execute(execute_args, host)),
+        _ => None,
+    }
+}
+
+/// Loads the configuration and packages of the workspace as they were at a git revision, for `--at`.
+///
+/// The returned snapshot holds the recreated workspace, which is removed once it's dropped.
+fn load_at_revision<H: Host>(args: &Args, host: &H, metadata: &Metadata, rev: &str) -> Result<(RevisionSnapshot, Config, Metadata)> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let scratch_dir = metadata.target_directory.as_std_path().join("ci-tmp");
+    let config_path = args
+        .config
+        .as_ref()
+        .map(|path| host.current_dir().map(|cwd| cwd.join(path)))
+        .transpose()?;
+    let snapshot = RevisionSnapshot::create(host, workspace_root, &scratch_dir, rev, config_path.as_deref())?;
+
+    let mut cmd = MetadataCommand::new();
+    _ = cmd.manifest_path(snapshot.root().join("Cargo.toml"));
+    let mut snapshot_metadata = read_metadata(host, cmd.no_deps())
+        .map_err(|e| Diagnostic::carry(&e, anyhow!("unable to resolve the packages as of '{rev}': {e:#}")))?;
+
+    // logs and history belong with the workspace itself rather than with its scratch copy
+    snapshot_metadata.target_directory = metadata.target_directory.clone();
+
+    let config_path = config_path.map(|path| snapshot.root().join(path.strip_prefix(workspace_root).unwrap_or(&path)));
+    let cfg = Config::load(host, snapshot.root(), config_path.as_ref(), &args.overlay, args.offline).map_err(|e| {
+        Failure::Config.tag(Diagnostic::carry(
+            &e,
+            anyhow!("unable to load the configuration as of '{rev}': {e:#}"),
+        ))
+    })?;
+    let cfg = with_feature_jobs(cfg, &snapshot_metadata)?;
+
+    host.eprintln(format!(
+        "Using the configuration and packages as of '{rev}' ({})",
+        snapshot.short_commit()
+    ));
+    Ok((snapshot, cfg, snapshot_metadata))
+}
+
+/// Checks a git revision out in a worktree for `--isolated`, and loads the configuration and packages from there.
+///
+/// Builds go to the `ci-isolated` directory of the checkout's target directory, which is kept from one isolated run to
+/// the next, so dependencies needn't be rebuilt every time. The caller removes the returned worktree once the run is
+/// over.
+fn load_isolated<H: Host>(args: &Args, host: &H, metadata: &Metadata, rev: &str) -> Result<(IsolatedWorktree, Config, Metadata)> {
+    let target_dir = metadata.target_directory.as_std_path();
+    let worktree = IsolatedWorktree::create(host, metadata.workspace_root.as_std_path(), &target_dir.join("ci-tmp"), rev)?;
+    match load_from_worktree(args, host, metadata, rev, &worktree) {
+        Ok((cfg, worktree_metadata)) => Ok((worktree, cfg, worktree_metadata)),
+        Err(e) => {
+            worktree.remove(host);
+            Err(e)
+        }
+    }
+}
+
+/// Loads the configuration and packages of the workspace checked out in a worktree for `--isolated`.
+fn load_from_worktree<H: Host>(
+    args: &Args,
+    host: &H,
+    metadata: &Metadata,
+    rev: &str,
+    worktree: &IsolatedWorktree,
+) -> Result<(Config, Metadata)> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let target_dir = metadata.target_directory.as_std_path();
+
+    let mut cmd = MetadataCommand::new();
+    _ = cmd
+        .manifest_path(worktree.workspace_root().join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", target_dir.join("ci-isolated"));
+    let worktree_metadata = read_metadata(host, cmd.no_deps())
+        .map_err(|e| Diagnostic::carry(&e, anyhow!("unable to resolve the packages as of '{rev}': {e:#}")))?;
+
+    // a configuration file within the workspace is read as committed
+    let config_path = args
+        .config
+        .as_ref()
+        .map(|path| host.current_dir().map(|cwd| cwd.join(path)))
+        .transpose()?
+        .map(|path| {
+            path.strip_prefix(workspace_root)
+                .map_or_else(|_| path.clone(), |relative| worktree.workspace_root().join(relative))
+        });
+    let cfg = Config::load(host, worktree.workspace_root(), config_path.as_ref(), &args.overlay, args.offline).map_err(|e| {
+        Failure::Config.tag(Diagnostic::carry(
+            &e,
+            anyhow!("unable to load the configuration as of '{rev}': {e:#}"),
+        ))
+    })?;
+    let cfg = with_feature_jobs(cfg, &worktree_metadata)?;
+
+    let uncommitted = git::modified_files(host, workspace_root).map_or(0, |files| files.len());
+    let left_out = if uncommitted > 0 && rev == "HEAD" {
+        format!(", leaving out {uncommitted} uncommitted change(s)")
+    } else {
+        String::new()
+    };
+    host.eprintln(format!(
+        "Running in an isolated worktree of '{rev}' ({}){left_out}",
+        worktree.short_commit()
+    ));
+    Ok((cfg, worktree_metadata))
+}
+
+/// Runs jobs in the workspace, with the configuration and packages of another revision for `--at` and `--isolated`.
+fn run<H: Host>(args: &Args, run_args: &RunArgs, host: &mut H, metadata: &Metadata) -> Result<()> {
+    if let Some(rev) = run_args.at() {
+        if !run_args.is_dry_run() {
+            return Err(anyhow!(
+                "--at requires --dry-run, since the jobs of another revision can't run in this checkout"
+            ));
+        }
+
+        let (_snapshot, cfg, metadata) = load_at_revision(args, host, metadata, rev)?;
+        run_jobs(run_args, host, &cfg, &metadata)
+    } else if let Some(rev) = run_args.isolated() {
+        let (worktree, cfg, metadata) = load_isolated(args, host, metadata, rev)?;
+        let outcome = run_jobs(run_args, host, &cfg, &metadata);
+        worktree.remove(&*host);
+        outcome
+    } else {
+        let cfg = load_workspace_config(args, host, metadata)?;
+        run_jobs(run_args, host, &cfg, metadata)
+    }
+}
+
+/// Loads the metadata and configuration of every workspace given with `--workspace-dir`, so mistakes surface before
+/// any jobs run, and then runs the jobs in each of them.
+fn run_in_workspaces<H: Host>(args: &Args, run_args: &RunArgs, host: &mut H) -> Result<()> {
+    if args.config.is_some() {
+        return Err(anyhow!(
+            "--config can't be combined with --workspace-dir, since every workspace uses its own configuration"
+        ));
+    }
+
+    let mut workspaces = Vec::new();
+    for dir in run_args.workspace_dirs() {
+        let label = match dir.strip_prefix(".") {
+            Ok(rest) if !rest.as_os_str().is_empty() => rest.display().to_string(),
+            _ => dir.display().to_string(),
+        };
+
+        let mut cmd = MetadataCommand::new();
+        _ = cmd.manifest_path(dir.join("Cargo.toml"));
+        let metadata =
+            read_metadata(host, cmd.no_deps()).map_err(|e| Diagnostic::carry(&e, anyhow!("unable to load workspace '{label}': {e:#}")))?;
+        let cfg = Config::load(host, metadata.workspace_root.as_std_path(), None, &args.overlay, args.offline).map_err(|e| {
+            Failure::Config.tag(Diagnostic::carry(
+                &e,
+                anyhow!("unable to load the configuration of workspace '{label}': {e:#}"),
+            ))
+        })?;
+        let cfg = with_feature_jobs(cfg, &metadata)?;
+        workspaces.push((label, cfg, metadata));
+    }
+
+    run_workspaces(run_args, host, &workspaces)
+}
+
+fn read_metadata(host: &impl Host, cmd: &MetadataCommand) -> Result<Metadata> {
+    let read = || {
+        let mut cargo = cmd.cargo_command();
+        _ = cargo.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let output = host.output(&mut cargo).context("unable to obtain cargo metadata")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "unable to obtain cargo metadata: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let stdout = String::from_utf8(output.stdout).context("cargo metadata produced invalid UTF-8")?;
+        MetadataCommand::parse(stdout).context("unable to parse cargo metadata")
+    };
+
+    read().map_err(|e| Diagnostic::Environment.attach(e))
+}
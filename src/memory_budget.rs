@@ -0,0 +1,95 @@
+use crate::host::Host;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// The memory steps with a `memory_hint` share while running in parallel, so link-heavy steps don't start together
+/// and get killed for running out of memory.
+///
+/// Steps reserve their hint before they start and release it once they're done. A step whose hint is more than the
+/// whole budget still runs, but only once nothing else holds a reservation.
+#[derive(Debug, Default)]
+pub struct MemoryBudget {
+    /// The memory available to steps in bytes, or `None` when it's unknown and steps aren't held back.
+    total: Option<u64>,
+    reserved: Mutex<u64>,
+    released: Condvar,
+}
+
+/// Memory reserved by a running step, which is released when dropped.
+#[derive(Debug)]
+pub struct MemoryReservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    /// Creates a budget for the memory available on this machine when the run starts.
+    pub fn detect(host: &impl Host) -> Self {
+        Self {
+            total: available_memory(host),
+            ..Self::default()
+        }
+    }
+
+    /// Reserves memory for a step if it's available right away.
+    pub fn try_reserve(&self, bytes: u64) -> Option<MemoryReservation<'_>> {
+        let reserved = self.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+        self.fits(*reserved, bytes).then(|| self.grant(reserved, bytes))
+    }
+
+    /// Reserves memory for a step, waiting for other steps to release theirs until enough is available.
+    pub fn reserve(&self, bytes: u64) -> MemoryReservation<'_> {
+        let mut reserved = self.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+        while !self.fits(*reserved, bytes) {
+            reserved = self.released.wait(reserved).unwrap_or_else(PoisonError::into_inner);
+        }
+
+        self.grant(reserved, bytes)
+    }
+
+    fn fits(&self, reserved: u64, bytes: u64) -> bool {
+        self.total
+            .is_none_or(|total| reserved == 0 || reserved.saturating_add(bytes) <= total)
+    }
+
+    fn grant(&self, mut reserved: MutexGuard<'_, u64>, bytes: u64) -> MemoryReservation<'_> {
+        *reserved = reserved.saturating_add(bytes);
+        drop(reserved);
+        MemoryReservation { budget: self, bytes }
+    }
+}
+
+impl Drop for MemoryReservation<'_> {
+    fn drop(&mut self) {
+        let mut reserved = self.budget.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+        *reserved = reserved.saturating_sub(self.bytes);
+        drop(reserved);
+        self.budget.released.notify_all();
+    }
+}
+
+/// Detects how much memory is available, in bytes.
+///
+/// On Linux, this is the memory available without swapping according to `/proc/meminfo`, falling back to the total
+/// memory on kernels which don't report it. On macOS, it's the physical memory. Elsewhere, it's unknown.
+fn available_memory(host: &impl Host) -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        let meminfo = host.read_to_string(Path::new("/proc/meminfo")).ok()?;
+        let field = |name: &str| {
+            meminfo.lines().find_map(|line| {
+                let kib = line.strip_prefix(name)?.strip_prefix(':')?.trim().strip_suffix("kB")?;
+                kib.trim().parse::<u64>().ok().map(|kib| kib * 1024)
+            })
+        };
+
+        field("MemAvailable").or_else(|| field("MemTotal"))
+    } else if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("sysctl");
+        _ = cmd.args(["-n", "hw.memsize"]).stdout(Stdio::piped()).stderr(Stdio::null());
+        let output = host.output(&mut cmd).ok().filter(|output| output.status.success())?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    } else {
+        None
+    }
+}
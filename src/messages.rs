@@ -0,0 +1,287 @@
+use crate::summary::{JobStatus, StepStatus};
+use clap::{Parser, ValueEnum};
+use std::borrow::Cow;
+
+/// The languages the step headings, statuses, and summary shown in the terminal can be in.
+#[derive(Parser, ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Ja,
+}
+
+/// Text that can be shown in the user's language.
+///
+/// Plain strings are shown as-is, while [`Msg`] entries are looked up in the message catalog.
+pub trait Localize {
+    fn localize(&self, lang: Lang) -> Cow<'_, str>;
+}
+
+impl Localize for str {
+    fn localize(&self, _lang: Lang) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl Localize for String {
+    fn localize(&self, _lang: Lang) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<T: Localize + ?Sized> Localize for &T {
+    fn localize(&self, lang: Lang) -> Cow<'_, str> {
+        (**self).localize(lang)
+    }
+}
+
+/// Text shown after some leading indentation.
+#[derive(Debug, Clone)]
+pub struct Indented<'a, T>(pub &'a str, pub T);
+
+impl<T: Localize> Localize for Indented<'_, T> {
+    fn localize(&self, lang: Lang) -> Cow<'_, str> {
+        Cow::Owned(format!("{}{}", self.0, self.1.localize(lang)))
+    }
+}
+
+/// The message catalog.
+#[derive(Debug, Clone)]
+pub enum Msg<'a> {
+    CommandLineUsed,
+    CapturedStdout,
+    CapturedStderr,
+    End,
+    LinesHidden(usize),
+    ErrorCode(i32),
+    ErrorCodeIgnored(i32),
+    Hint(&'a str),
+    Status(JobStatus),
+    StepStatus(StepStatus),
+    RanSteps(usize),
+    Step(&'a str),
+    StepForPackage(&'a str, &'a str),
+    StepForPackages(&'a str, &'a str),
+    PackageSkippedByJob(&'a str),
+    PackageSkippedByStep(&'a str),
+    PackageSkippedNoTargets(&'a str, &'a str),
+    UnableToRunStep,
+    TempFilesKept(&'a str),
+    Summary,
+    SummaryStage(&'a str, JobStatus, &'a str),
+    SummaryJob(&'a str, JobStatus, [usize; 3], &'a str),
+    SummaryJobNotRun(&'a str),
+    SummaryError(&'a str),
+    SummaryTotal([usize; 4], &'a str),
+    SummaryCancelled(usize),
+    SummarySteps([usize; 4]),
+    SummaryTests([usize; 3]),
+    SummaryFailedTest(&'a str),
+    SummaryOutOfTime(&'a str),
+    Warnings,
+}
+
+impl Localize for Msg<'_> {
+    #[expect(clippy::too_many_lines, reason = "It's a catalog, which grows with every message")]
+    fn localize(&self, lang: Lang) -> Cow<'_, str> {
+        let text = match (self, lang) {
+            (Self::CommandLineUsed, Lang::En) => "--- command-line used".into(),
+            (Self::CommandLineUsed, Lang::De) => "--- verwendete Befehlszeile".into(),
+            (Self::CommandLineUsed, Lang::Ja) => "--- 使用したコマンドライン".into(),
+
+            (Self::CapturedStdout, Lang::En) => "--- captured stdout".into(),
+            (Self::CapturedStdout, Lang::De) => "--- erfasste Standardausgabe".into(),
+            (Self::CapturedStdout, Lang::Ja) => "--- 取得した標準出力".into(),
+
+            (Self::CapturedStderr, Lang::En) => "--- captured stderr".into(),
+            (Self::CapturedStderr, Lang::De) => "--- erfasste Standardfehlerausgabe".into(),
+            (Self::CapturedStderr, Lang::Ja) => "--- 取得した標準エラー出力".into(),
+
+            (Self::End, Lang::En) => "--- end".into(),
+            (Self::End, Lang::De) => "--- Ende".into(),
+            (Self::End, Lang::Ja) => "--- 終了".into(),
+
+            (Self::LinesHidden(n), Lang::En) => format!("({n} line(s) hidden by output_filters, see the log for the full output)"),
+            (Self::LinesHidden(n), Lang::De) => {
+                format!("({n} Zeile(n) durch output_filters ausgeblendet, die vollständige Ausgabe steht im Log)")
+            }
+            (Self::LinesHidden(n), Lang::Ja) => {
+                format!("(output_filters により {n} 行を非表示にしました。完全な出力はログを参照してください)")
+            }
+
+            (Self::ErrorCode(code), Lang::En) => format!(" (error code {code})"),
+            (Self::ErrorCode(code), Lang::De) => format!(" (Fehlercode {code})"),
+            (Self::ErrorCode(code), Lang::Ja) => format!(" (エラーコード {code})"),
+
+            (Self::ErrorCodeIgnored(code), Lang::En) => format!(" (error code {code}, ignored)"),
+            (Self::ErrorCodeIgnored(code), Lang::De) => format!(" (Fehlercode {code}, ignoriert)"),
+            (Self::ErrorCodeIgnored(code), Lang::Ja) => format!(" (エラーコード {code}、無視)"),
+
+            (Self::Hint(hint), Lang::En) => format!("hint: {hint}"),
+            (Self::Hint(hint), Lang::De) => format!("Hinweis: {hint}"),
+            (Self::Hint(hint), Lang::Ja) => format!("ヒント: {hint}"),
+
+            (Self::Status(status), lang) => status_text(*status, lang).into(),
+            (Self::StepStatus(status), lang) => step_status_text(*status, lang).into(),
+
+            (Self::RanSteps(n), Lang::En) => format!("ran {n} step(s)"),
+            (Self::RanSteps(n), Lang::De) => format!("{n} Schritt(e) ausgeführt"),
+            (Self::RanSteps(n), Lang::Ja) => format!("{n} 個のステップを実行しました"),
+
+            (Self::Step(step), Lang::En) => format!("step '{step}'"),
+            (Self::Step(step), Lang::De) => format!("Schritt '{step}'"),
+            (Self::Step(step), Lang::Ja) => format!("ステップ '{step}'"),
+
+            (Self::StepForPackage(step, pkg), Lang::En) => format!("step '{step}' for package '{pkg}'"),
+            (Self::StepForPackage(step, pkg), Lang::De) => format!("Schritt '{step}' für Paket '{pkg}'"),
+            (Self::StepForPackage(step, pkg), Lang::Ja) => format!("パッケージ '{pkg}' のステップ '{step}'"),
+
+            (Self::StepForPackages(step, pkgs), Lang::En) => format!("step '{step}' for packages {pkgs}"),
+            (Self::StepForPackages(step, pkgs), Lang::De) => format!("Schritt '{step}' für die Pakete {pkgs}"),
+            (Self::StepForPackages(step, pkgs), Lang::Ja) => format!("パッケージ {pkgs} のステップ '{step}'"),
+
+            (Self::PackageSkippedByJob(pkg), Lang::En) => format!("Package '{pkg}' skipped due to job-level condition"),
+            (Self::PackageSkippedByJob(pkg), Lang::De) => format!("Paket '{pkg}' wegen einer Bedingung des Jobs übersprungen"),
+            (Self::PackageSkippedByJob(pkg), Lang::Ja) => format!("ジョブの条件によりパッケージ '{pkg}' をスキップしました"),
+
+            (Self::PackageSkippedByStep(pkg), Lang::En) => format!("Package '{pkg}' skipped due to step-level condition"),
+            (Self::PackageSkippedByStep(pkg), Lang::De) => format!("Paket '{pkg}' wegen einer Bedingung des Schritts übersprungen"),
+            (Self::PackageSkippedByStep(pkg), Lang::Ja) => format!("ステップの条件によりパッケージ '{pkg}' をスキップしました"),
+
+            (Self::PackageSkippedNoTargets(pkg, kind), Lang::En) => format!("Package '{pkg}' skipped (no {kind} targets)"),
+            (Self::PackageSkippedNoTargets(pkg, kind), Lang::De) => format!("Paket '{pkg}' übersprungen (keine {kind}-Ziele)"),
+            (Self::PackageSkippedNoTargets(pkg, kind), Lang::Ja) => {
+                format!("パッケージ '{pkg}' をスキップしました ({kind} ターゲットがありません)")
+            }
+
+            (Self::UnableToRunStep, Lang::En) => "unable to run step".into(),
+            (Self::UnableToRunStep, Lang::De) => "Schritt konnte nicht ausgeführt werden".into(),
+            (Self::UnableToRunStep, Lang::Ja) => "ステップを実行できませんでした".into(),
+
+            (Self::TempFilesKept(dir), Lang::En) => format!("Temporary files were kept in {dir}"),
+            (Self::TempFilesKept(dir), Lang::De) => format!("Temporäre Dateien wurden in {dir} aufbewahrt"),
+            (Self::TempFilesKept(dir), Lang::Ja) => format!("一時ファイルは {dir} に残されています"),
+
+            (Self::Summary, Lang::En) => "Summary:".into(),
+            (Self::Summary, Lang::De) => "Zusammenfassung:".into(),
+            (Self::Summary, Lang::Ja) => "概要:".into(),
+
+            (Self::SummaryStage(name, status, duration), lang) => {
+                let status = status_text(*status, lang);
+                match lang {
+                    Lang::En => format!("stage '{name}': {status} ({duration})"),
+                    Lang::De => format!("Phase '{name}': {status} ({duration})"),
+                    Lang::Ja => format!("ステージ '{name}': {status} ({duration})"),
+                }
+            }
+
+            (Self::SummaryJob(name, status, [ran, skipped, packages], duration), lang) => {
+                let status = status_text(*status, lang);
+                match lang {
+                    Lang::En => {
+                        format!("{name}: {status}, ran {ran} step(s), skipped {skipped} step(s) and {packages} package(s) ({duration})")
+                    }
+                    Lang::De => format!(
+                        "{name}: {status}, {ran} Schritt(e) ausgeführt, {skipped} Schritt(e) und {packages} Paket(e) übersprungen ({duration})"
+                    ),
+                    Lang::Ja => {
+                        format!("{name}: {status}、{ran} ステップを実行、{skipped} ステップと {packages} パッケージをスキップ ({duration})")
+                    }
+                }
+            }
+
+            (Self::SummaryJobNotRun(name), lang) => format!("{name}: {}", status_text(JobStatus::NotRun, lang)),
+
+            (Self::SummaryError(error), Lang::En) => format!("error: {error}"),
+            (Self::SummaryError(error), Lang::De) => format!("Fehler: {error}"),
+            (Self::SummaryError(error), Lang::Ja) => format!("エラー: {error}"),
+
+            (Self::SummaryTotal([succeeded, failed, ignored, not_run], duration), Lang::En) => {
+                format!("total: {succeeded} succeeded, {failed} failed, {ignored} ignored failure(s), {not_run} not run ({duration})")
+            }
+            (Self::SummaryTotal([succeeded, failed, ignored, not_run], duration), Lang::De) => format!(
+                "gesamt: {succeeded} erfolgreich, {failed} fehlgeschlagen, {ignored} ignorierte(r) Fehler, {not_run} nicht ausgeführt ({duration})"
+            ),
+            (Self::SummaryTotal([succeeded, failed, ignored, not_run], duration), Lang::Ja) => {
+                format!("合計: 成功 {succeeded}、失敗 {failed}、無視された失敗 {ignored}、未実行 {not_run} ({duration})")
+            }
+
+            (Self::SummaryCancelled(count), Lang::En) => format!("cancelled: {count} job(s)"),
+            (Self::SummaryCancelled(count), Lang::De) => format!("abgebrochen: {count} Job(s)"),
+            (Self::SummaryCancelled(count), Lang::Ja) => format!("キャンセル: {count} ジョブ"),
+
+            (Self::SummarySteps([ran, skipped, not_run, packages]), Lang::En) => {
+                format!("steps: {ran} ran, {skipped} skipped, {not_run} not run, {packages} package(s) skipped by steps which ran")
+            }
+            (Self::SummarySteps([ran, skipped, not_run, packages]), Lang::De) => format!(
+                "Schritte: {ran} ausgeführt, {skipped} übersprungen, {not_run} nicht ausgeführt, {packages} Paket(e) von ausgeführten Schritten übersprungen"
+            ),
+            (Self::SummarySteps([ran, skipped, not_run, packages]), Lang::Ja) => {
+                format!(
+                    "ステップ: 実行 {ran}、スキップ {skipped}、未実行 {not_run}、実行されたステップでスキップされたパッケージ {packages}"
+                )
+            }
+
+            (Self::SummaryTests([passed, failed, ignored]), Lang::En) => {
+                format!("tests: {passed} passed, {failed} failed, {ignored} ignored")
+            }
+            (Self::SummaryTests([passed, failed, ignored]), Lang::De) => {
+                format!("Tests: {passed} bestanden, {failed} fehlgeschlagen, {ignored} ignoriert")
+            }
+            (Self::SummaryTests([passed, failed, ignored]), Lang::Ja) => format!("テスト: 成功 {passed}、失敗 {failed}、無視 {ignored}"),
+
+            (Self::SummaryFailedTest(name), Lang::En) => format!("failed test: {name}"),
+            (Self::SummaryFailedTest(name), Lang::De) => format!("fehlgeschlagener Test: {name}"),
+            (Self::SummaryFailedTest(name), Lang::Ja) => format!("失敗したテスト: {name}"),
+
+            (Self::SummaryOutOfTime(budget), Lang::En) => {
+                format!("truncated: the run went over its time budget of {budget}, so the steps after that weren't run")
+            }
+            (Self::SummaryOutOfTime(budget), Lang::De) => {
+                format!("abgebrochen: der Lauf hat sein Zeitbudget von {budget} überschritten, spätere Schritte wurden nicht ausgeführt")
+            }
+            (Self::SummaryOutOfTime(budget), Lang::Ja) => {
+                format!("打ち切り: 実行が時間予算 {budget} を超えたため、以降のステップは実行されませんでした")
+            }
+
+            (Self::Warnings, Lang::En) => "Warnings:".into(),
+            (Self::Warnings, Lang::De) => "Warnungen:".into(),
+            (Self::Warnings, Lang::Ja) => "警告:".into(),
+        };
+
+        Cow::Owned(text)
+    }
+}
+
+const fn step_status_text(status: StepStatus, lang: Lang) -> &'static str {
+    match (status, lang) {
+        (StepStatus::Succeeded, _) => status_text(JobStatus::Succeeded, lang),
+        (StepStatus::Failed, _) => status_text(JobStatus::Failed, lang),
+        (StepStatus::FailedIgnored, _) => status_text(JobStatus::FailedIgnored, lang),
+        (StepStatus::NotRun, _) => status_text(JobStatus::NotRun, lang),
+        (StepStatus::Skipped, Lang::En) => "skipped",
+        (StepStatus::Skipped, Lang::De) => "übersprungen",
+        (StepStatus::Skipped, Lang::Ja) => "スキップ",
+    }
+}
+
+const fn status_text(status: JobStatus, lang: Lang) -> &'static str {
+    match (status, lang) {
+        (JobStatus::Succeeded, Lang::En) => "succeeded",
+        (JobStatus::Succeeded, Lang::De) => "erfolgreich",
+        (JobStatus::Succeeded, Lang::Ja) => "成功",
+        (JobStatus::Failed, Lang::En) => "failed",
+        (JobStatus::Failed, Lang::De) => "fehlgeschlagen",
+        (JobStatus::Failed, Lang::Ja) => "失敗",
+        (JobStatus::FailedIgnored, Lang::En) => "failed, but ignored",
+        (JobStatus::FailedIgnored, Lang::De) => "fehlgeschlagen, aber ignoriert",
+        (JobStatus::FailedIgnored, Lang::Ja) => "失敗 (無視)",
+        (JobStatus::NotRun, Lang::En) => "not run",
+        (JobStatus::NotRun, Lang::De) => "nicht ausgeführt",
+        (JobStatus::NotRun, Lang::Ja) => "未実行",
+        (JobStatus::Cancelled, Lang::En) => "cancelled",
+        (JobStatus::Cancelled, Lang::De) => "abgebrochen",
+        (JobStatus::Cancelled, Lang::Ja) => "キャンセル",
+    }
+}
@@ -0,0 +1,64 @@
+use crate::host::Host;
+use std::io;
+use std::process::{Command, Output, Stdio};
+
+/// The options of `unshare` giving a command network and user namespaces of its own, where it appears as root so it can
+/// bring up its loopback interface.
+const UNSHARE_ARGS: [&str; 4] = ["--user", "--map-root-user", "--net", "--"];
+
+/// Why steps can't be kept off the network on this system, or `None` when they can.
+///
+/// Steps are kept off the network by running them in a network namespace of their own, which takes Linux with
+/// unprivileged user namespaces enabled and the `unshare` command, along with the `ip` command to bring up the loopback
+/// interface there.
+pub fn unsupported_reason(host: &impl Host) -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return Some("network namespaces are only available on Linux".to_string());
+    }
+
+    match probe(host, &["true"]) {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            return Some(format!(
+                "unprivileged user namespaces aren't available ({})",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Err(e) => return Some(format!("unable to run 'unshare': {e}")),
+    }
+
+    match probe(host, &["ip", "link", "set", "lo", "up"]) {
+        Ok(output) if output.status.success() => None,
+        Ok(output) => Some(format!(
+            "the loopback interface can't be brought up with 'ip' ({})",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Err(e) => Some(format!("unable to run 'ip' to bring up the loopback interface: {e}")),
+    }
+}
+
+/// Runs a command in network and user namespaces of its own.
+fn probe(host: &impl Host, command: &[&str]) -> io::Result<Output> {
+    let mut probe = Command::new("unshare");
+    _ = probe
+        .args(UNSHARE_ARGS)
+        .args(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    host.output(&mut probe)
+}
+
+/// Rebuilds a command so it runs without network access, in a network namespace of its own whose only interface is
+/// the loopback interface, so tests can still talk to servers they start themselves.
+///
+/// Since the command is rebuilt, this must be called before anything else is set on it.
+pub fn disable_network(cmd: &Command) -> Command {
+    let mut isolated = Command::new("unshare");
+    _ = isolated
+        .args(UNSHARE_ARGS)
+        .args(["sh", "-c", r#"ip link set lo up && exec "$0" "$@""#])
+        .arg(cmd.get_program())
+        .args(cmd.get_args());
+    isolated
+}
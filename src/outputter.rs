@@ -1,15 +1,35 @@
 use crate::color_modes::ColorModes;
+use crate::config::{Hints, OutputEncoding, OutputFilters};
 use crate::host::Host;
 use crate::log::Log;
-use console::{StyledObject, Term, style};
+use crate::messages::{Lang, Localize, Msg};
+use crate::status_board::StatusBoard;
+use console::{StyledObject, Term, style, truncate_str};
 use core::cell::RefCell;
+use std::borrow::Cow;
 use std::path::Path;
 use std::process::{Command, ExitStatus, Output};
+use std::sync::Arc;
 
 struct InnerOutputter {
     term: Term,
     activity: String,
     cmdline: String,
+    encoding: OutputEncoding,
+    filters: OutputFilters,
+    quiet: bool,
+}
+
+impl InnerOutputter {
+    /// A line of the log attributed to the current activity, if any, so the lines of concurrent activities, which
+    /// interleave in the log, can still be told apart.
+    fn attributed(&self, message: &str) -> String {
+        if self.activity.is_empty() {
+            message.to_string()
+        } else {
+            format!("{}: {message}", self.activity)
+        }
+    }
 }
 
 pub struct Outputter<'a, H> {
@@ -17,10 +37,16 @@ pub struct Outputter<'a, H> {
     log: &'a Log,
     inner: RefCell<InnerOutputter>,
     color: ColorModes,
+    lang: Lang,
+    line_mode: bool,
+    board: Option<Arc<StatusBoard>>,
 }
 
 impl<'a, H: Host> Outputter<'a, H> {
-    pub fn new(host: &'a H, log: &'a Log, color: ColorModes) -> Self {
+    /// Creates an outputter which shows messages in the given language.
+    ///
+    /// Whatever the language, messages are always written to the log in English so logs can be shared.
+    pub fn new(host: &'a H, log: &'a Log, color: ColorModes, lang: Lang) -> Self {
         Self {
             host,
             log,
@@ -28,8 +54,51 @@ impl<'a, H: Host> Outputter<'a, H> {
                 term: Term::stdout(),
                 activity: String::new(),
                 cmdline: String::new(),
+                encoding: OutputEncoding::Utf8,
+                filters: OutputFilters::default(),
+                quiet: false,
             }),
             color,
+            lang,
+            line_mode: false,
+            board: None,
+        }
+    }
+
+    /// The file messages and the output of commands are logged to.
+    pub fn log_path(&self) -> &Path {
+        self.log.path()
+    }
+
+    /// Creates an outputter for an activity that runs concurrently with others.
+    ///
+    /// Concurrent activities can't share the in-place line updates used for a single activity,
+    /// so the returned outputter always emits complete lines.
+    pub fn fork(&self) -> Self {
+        let mut forked = Self::new(self.host, self.log, self.color, self.lang);
+        forked.line_mode = true;
+        forked.set_quiet(self.inner.borrow().quiet);
+        forked
+    }
+
+    /// Creates an outputter for an activity that runs concurrently with others, whose progress is shown on a status
+    /// board shared with them.
+    pub fn fork_onto(&self, board: &Arc<StatusBoard>) -> Self {
+        let mut forked = self.fork();
+        forked.board = Some(Arc::clone(board));
+        forked
+    }
+
+    /// The status board this outputter's activity is shown on, if any.
+    pub const fn board(&self) -> Option<&Arc<StatusBoard>> {
+        self.board.as_ref()
+    }
+
+    /// Runs code writing to the terminal, keeping its output clear of the status board, if any.
+    fn above<R>(&self, print: impl FnOnce() -> R) -> R {
+        match &self.board {
+            Some(board) => board.print_above(self.host.now(), print),
+            None => print(),
         }
     }
 
@@ -37,44 +106,88 @@ impl<'a, H: Host> Outputter<'a, H> {
         let mut inner = self.inner.borrow_mut();
         inner.activity = activity.as_ref().into();
 
-        if inner.term.is_term() {
+        if let Some(board) = &self.board
+            && !inner.quiet
+        {
+            board.update(&inner.activity, "starting", self.host.now());
+        }
+
+        if self.host.is_terminal() && !self.line_mode {
             _ = inner.term.hide_cursor();
         }
     }
 
-    pub fn complete_activity(&self, final_message: impl AsRef<str>) {
+    /// Keeps progress out of the terminal, which then only shows failures, while the log still gets everything.
+    pub fn set_quiet(&self, quiet: bool) {
+        self.inner.borrow_mut().quiet = quiet;
+    }
+
+    pub fn complete_activity(&self, final_message: impl Localize) {
         let mut inner = self.inner.borrow_mut();
-        _ = inner.term.clear_line();
-        _ = inner.term.write_line(&format!("{}: {}", inner.activity, final_message.as_ref()));
+        if let Some(board) = &self.board {
+            board.remove(&inner.activity, self.host.now());
+        }
+
+        if inner.quiet {
+            inner.activity = String::new();
+            return;
+        }
+
+        if !self.line_mode {
+            _ = inner.term.clear_line();
+        }
+        self.above(|| {
+            _ = inner
+                .term
+                .write_line(&format!("{}: {}", inner.activity, final_message.localize(self.lang)));
+        });
         inner.activity = String::new();
     }
 
     pub fn run_command(&self, cmd: &Command) {
         let mut inner = self.inner.borrow_mut();
         inner.cmdline = format!("{}> {cmd:?}", cmd.get_current_dir().unwrap_or_else(|| Path::new("?")).display());
+        inner.encoding = OutputEncoding::Utf8;
+        inner.filters = OutputFilters::default();
 
-        self.log.info(format!("Running command: {}", inner.cmdline));
+        self.log.info(inner.attributed(&format!("Running command: {}", inner.cmdline)));
     }
 
-    pub fn command_error(&self, failure_message: impl AsRef<str>, status: Option<ExitStatus>, output: Option<&Output>, fatal: bool) {
-        let failure_msg = failure_message.as_ref();
+    /// Sets how to decode the output of the command most recently passed to [`Self::run_command`].
+    pub fn set_output_encoding(&self, encoding: OutputEncoding) {
+        self.inner.borrow_mut().encoding = encoding;
+    }
+
+    /// Sets which lines of the output of the command most recently passed to [`Self::run_command`] are left out of the
+    /// terminal, though not the log.
+    pub fn set_output_filters(&self, filters: &OutputFilters) {
+        self.inner.borrow_mut().filters = filters.clone();
+    }
+
+    pub fn command_error(&self, failure_message: impl Localize, status: Option<ExitStatus>, output: Option<&Output>, fatal: bool) {
+        self.above(|| self.write_command_error(failure_message, status, output, fatal));
+    }
+
+    fn write_command_error(&self, failure_message: impl Localize, status: Option<ExitStatus>, output: Option<&Output>, fatal: bool) {
         let inner = self.inner.borrow();
 
-        let tail = status.map_or_else(String::new, |status| {
+        let tail = status.map(|status| {
             let code = status.code().unwrap_or(-1);
-            if fatal {
-                format!(" (error code {code})")
-            } else {
-                format!(" (error code {code}, ignored)")
-            }
+            if fatal { Msg::ErrorCode(code) } else { Msg::ErrorCodeIgnored(code) }
         });
+        let tail_in = |lang| tail.as_ref().map_or_else(String::new, |tail| tail.localize(lang).into_owned());
 
-        let styled_message = if fatal { self.red(failure_msg) } else { self.yellow(failure_msg) };
+        let failure_msg = failure_message.localize(self.lang);
+        let styled_message = if fatal { self.red(&failure_msg) } else { self.yellow(&failure_msg) };
+        let tail = tail_in(self.lang);
 
-        if inner.term.is_term() {
-            _ = inner.term.write_line(&format!(" -> {styled_message}{tail}"));
+        // when progress is kept out of the terminal, nothing says yet which activity failed
+        let activity = if inner.quiet { inner.activity.as_str() } else { "" };
+        if self.host.is_terminal() {
+            _ = inner.term.write_line(&format!("{activity} -> {styled_message}{tail}"));
         } else {
-            let print_message = format!("{styled_message}{tail}");
+            let separator = if activity.is_empty() { "" } else { ": " };
+            let print_message = format!("{activity}{separator}{styled_message}{tail}");
             if fatal {
                 self.host.eprintln(&print_message);
             } else {
@@ -82,7 +195,7 @@ impl<'a, H: Host> Outputter<'a, H> {
             }
         }
 
-        let log_message = format!("{failure_msg}{tail}");
+        let log_message = inner.attributed(&format!("{}{}", failure_message.localize(Lang::En), tail_in(Lang::En)));
         if fatal {
             self.log.error(&log_message);
         } else {
@@ -96,21 +209,25 @@ impl<'a, H: Host> Outputter<'a, H> {
         };
 
         let log_fn: &dyn Fn(&str) = if fatal {
-            &|s: &str| self.log.error(s)
+            &|s: &str| self.log.error(inner.attributed(s))
         } else {
-            &|s: &str| self.log.warn(s)
+            &|s: &str| self.log.warn(inner.attributed(s))
+        };
+
+        let heading = |msg: &Msg| {
+            print_fn(&msg.localize(self.lang));
+            log_fn(&msg.localize(Lang::En));
         };
 
-        print_fn("--- command-line used");
+        print_fn(&Msg::CommandLineUsed.localize(self.lang));
         print_fn(&inner.cmdline);
 
         if let Some(output) = output {
             if !output.stdout.is_empty() {
-                let stdout_str = String::from_utf8_lossy(&output.stdout);
-                print_fn("--- captured stdout");
-                log_fn("--- captured stdout");
+                let stdout_str = inner.encoding.decode(&output.stdout);
+                heading(&Msg::CapturedStdout);
 
-                let styled_stdout = style(stdout_str.trim()).italic().to_string();
+                let styled_stdout = style(filtered(&stdout_str, &inner.filters, self.lang).trim()).italic().to_string();
                 print_fn(&styled_stdout);
 
                 for line in stdout_str.lines() {
@@ -119,11 +236,10 @@ impl<'a, H: Host> Outputter<'a, H> {
             }
 
             if !output.stderr.is_empty() {
-                let stderr_str = String::from_utf8_lossy(&output.stderr);
-                print_fn("--- captured stderr");
-                log_fn("--- captured stderr");
+                let stderr_str = inner.encoding.decode(&output.stderr);
+                heading(&Msg::CapturedStderr);
 
-                let styled_stderr = style(stderr_str.trim()).italic().to_string();
+                let styled_stderr = style(filtered(&stderr_str, &inner.filters, self.lang).trim()).italic().to_string();
                 print_fn(&styled_stderr);
 
                 for line in stderr_str.lines() {
@@ -132,29 +248,88 @@ impl<'a, H: Host> Outputter<'a, H> {
             }
         }
 
-        print_fn("--- end");
-        log_fn("--- end");
+        heading(&Msg::End);
+    }
+
+    /// Reports the hints matching a failed command's output, returning them in a form suitable for appending to an error message.
+    pub fn report_hints(&self, hints: &Hints, output: &Output, fatal: bool) -> String {
+        self.above(|| self.write_hints(hints, output, fatal))
+    }
+
+    fn write_hints(&self, hints: &Hints, output: &Output, fatal: bool) -> String {
+        let encoding = self.inner.borrow().encoding;
+        let text = format!("{}\n{}", encoding.decode(&output.stdout), encoding.decode(&output.stderr));
+
+        let mut suffix = String::new();
+        for hint in hints.matching(&text) {
+            let hint = Msg::Hint(hint);
+            let message = hint.localize(self.lang);
+            if fatal {
+                self.host.eprintln(self.yellow(&message).to_string());
+            } else {
+                self.host.println(self.yellow(&message).to_string());
+            }
+
+            self.log.info(hint.localize(Lang::En));
+            suffix = format!("{suffix} ({message})");
+        }
+
+        suffix
     }
 
-    pub fn message(&self, message: impl AsRef<str>) {
+    pub fn message(&self, message: impl Localize) {
         let inner = self.inner.borrow();
-        let formatted = format!("{}: {}", inner.activity, message.as_ref());
+        let localized = message.localize(self.lang);
+        let formatted = format!("{}: {localized}", inner.activity);
 
-        if inner.term.is_term() {
-            _ = inner.term.clear_line();
-            _ = inner.term.write_str(&formatted);
-        } else {
-            self.host.println(&formatted);
+        if let Some(board) = &self.board
+            && !inner.quiet
+        {
+            board.update(&inner.activity, &localized, self.host.now());
+        }
+
+        // progress shown on a status board drawn in place would only clutter the output above it
+        let on_board = self.board.as_ref().is_some_and(|board| board.is_interactive());
+        if !inner.quiet && !on_board {
+            if self.host.is_terminal() && !self.line_mode {
+                _ = inner.term.clear_line();
+                _ = inner.term.write_str(&fit_to_width(&inner.term, &formatted));
+            } else {
+                self.above(|| self.host.println(&formatted));
+            }
         }
 
-        self.log.info(&formatted);
+        self.log.info(format!("{}: {}", inner.activity, message.localize(Lang::En)));
+    }
+
+    /// Writes a complete line of detail attributed to the current activity.
+    pub fn detail(&self, message: impl Localize) {
+        let inner = self.inner.borrow();
+        let formatted = format!("{}: {}", inner.activity, message.localize(self.lang));
+
+        if !inner.quiet {
+            if self.host.is_terminal() && !self.line_mode {
+                _ = inner.term.clear_line();
+                _ = inner.term.write_line(&formatted);
+            } else {
+                self.above(|| self.host.println(&formatted));
+            }
+        }
+
+        self.log.info(format!("{}: {}", inner.activity, message.localize(Lang::En)));
+    }
+
+    /// Writes a line that isn't tied to any activity, such as the end-of-run summary.
+    pub fn line(&self, message: impl Localize) {
+        self.above(|| self.host.println(message.localize(self.lang)));
+        self.log.info(message.localize(Lang::En));
     }
 
     fn should_use_color(&self) -> bool {
         match self.color {
             ColorModes::Always => true,
             ColorModes::Never => false,
-            ColorModes::Auto => self.inner.borrow().term.is_term(),
+            ColorModes::Auto => self.host.is_terminal(),
         }
     }
 
@@ -171,6 +346,41 @@ impl<'a, H: Host> Outputter<'a, H> {
     }
 }
 
+/// Truncates a line which is updated in place to the width of the terminal, ending it with an ellipsis, since a line
+/// which wraps can't be cleared in one go.
+///
+/// The width is measured in terminal columns, where CJK characters and most emoji take up two.
+fn fit_to_width<'l>(term: &Term, line: &'l str) -> Cow<'l, str> {
+    match term.size_checked() {
+        Some((_, columns)) => truncate_str(line, usize::from(columns).saturating_sub(1), "…"),
+        None => Cow::Borrowed(line),
+    }
+}
+
+/// Leaves out the lines of output matching the filters, noting how many were left out.
+fn filtered<'o>(output: &'o str, filters: &OutputFilters, lang: Lang) -> Cow<'o, str> {
+    if filters.is_empty() {
+        return Cow::Borrowed(output);
+    }
+
+    let mut hidden = 0;
+    let mut kept = String::new();
+    for line in output.lines() {
+        if filters.hides(line) {
+            hidden += 1;
+        } else {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+
+    if hidden > 0 {
+        kept.push_str(&Msg::LinesHidden(hidden).localize(lang));
+    }
+
+    Cow::Owned(kept)
+}
+
 impl Drop for InnerOutputter {
     fn drop(&mut self) {
         if self.term.is_term() {
@@ -1,3 +1,5 @@
 mod package_data;
 
-pub use package_data::variables;
+pub use package_data::{
+    is_trusted, overridden_steps, parse_variables, step_command, untrusted_package_warnings, variables, variables_from,
+};
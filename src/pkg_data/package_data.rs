@@ -1,4 +1,6 @@
 use cargo_metadata::Package;
+use std::collections::HashSet;
+use std::path::Path;
 
 pub fn variables(p: &Package) -> impl Iterator<Item = (&str, &str)> {
     p.metadata
@@ -8,3 +10,71 @@ pub fn variables(p: &Package) -> impl Iterator<Item = (&str, &str)> {
         .into_iter()
         .flat_map(|table| table.iter().filter_map(|(k, v)| v.as_str().map(|s| (k.as_str(), s))))
 }
+
+/// The command whose `KEY=VALUE` output lines define additional variables for the package.
+pub fn variables_from(p: &Package) -> Option<&str> {
+    p.metadata.get("ci")?.get("variables_from")?.as_str()
+}
+
+/// The command a package runs for the step with the given id in place of `command`, the step's own.
+///
+/// The step's entry in the `[package.metadata.ci.overrides]` table may replace the command with its own `command`,
+/// append `args` to it, or both.
+pub fn step_command(p: &Package, step_id: &str, command: &str) -> Option<String> {
+    let entry = p.metadata.get("ci")?.get("overrides")?.get(step_id)?;
+    let command = entry.get("command").and_then(|v| v.as_str()).unwrap_or(command);
+    Some(
+        entry
+            .get("args")
+            .and_then(|v| v.as_str())
+            .map_or_else(|| command.to_string(), |args| format!("{command} {args}")),
+    )
+}
+
+/// The ids of the steps a package overrides the command of.
+pub fn overridden_steps(p: &Package) -> impl Iterator<Item = &str> {
+    p.metadata
+        .get("ci")
+        .and_then(|ci| ci.get("overrides"))
+        .and_then(|v| v.as_object())
+        .into_iter()
+        .flat_map(|table| table.keys().map(String::as_str))
+}
+
+/// Parses the `KEY=VALUE` lines output by a package's `variables_from` command, ignoring blank lines and `#` comments.
+pub fn parse_variables(output: &str) -> anyhow::Result<Vec<(String, String)>> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("invalid line '{line}', expected KEY=VALUE"))?;
+            Ok((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Whether a package's `[package.metadata.ci]` settings may feed expressions and run commands.
+///
+/// Packages within the workspace root are trusted, while those pulled in from elsewhere, such as a registry, a git
+/// repository, or a directory outside the workspace, need to be listed in `trusted_packages`.
+pub fn is_trusted(p: &Package, workspace_root: &Path, trusted_packages: &HashSet<String>) -> bool {
+    (p.source.is_none() && p.manifest_path.as_std_path().starts_with(workspace_root)) || trusted_packages.contains(p.name.as_str())
+}
+
+/// Warns about the packages whose `[package.metadata.ci]` settings are ignored because they aren't trusted.
+pub fn untrusted_package_warnings(packages: &[&Package], workspace_root: &Path, trusted_packages: &HashSet<String>) -> Vec<String> {
+    packages
+        .iter()
+        .filter(|p| variables(p).next().is_some() || variables_from(p).is_some() || overridden_steps(p).next().is_some())
+        .filter(|p| !is_trusted(p, workspace_root, trusted_packages))
+        .map(|p| {
+            format!(
+                "package '{}' comes from outside the workspace, so its [package.metadata.ci] variables and overrides are ignored, add it to trusted_packages to use them",
+                p.name
+            )
+        })
+        .collect()
+}
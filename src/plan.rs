@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The fully resolved steps of a set of jobs, with conditions evaluated and per-package steps expanded.
+///
+/// Plans let the work of a run be split across machines, each executing a slice of the jobs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Plan {
+    pub jobs: Vec<PlannedJob>,
+}
+
+/// A job of a plan, whose steps run in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedJob {
+    pub id: String,
+    pub name: String,
+    pub continue_on_error: bool,
+    pub steps: Vec<PlannedStep>,
+}
+
+/// A single execution of a step, either for one package or for the whole workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[expect(clippy::struct_excessive_bools, reason = "Configuration switches are naturally bools")]
+pub struct PlannedStep {
+    pub name: String,
+    pub package: Option<String>,
+
+    /// Where the step runs, relative to the workspace root.
+    pub directory: PathBuf,
+
+    pub command: String,
+
+    /// Command lines tried in order when the command fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<String>,
+
+    /// For steps which run a cargo alias, the alias and its arguments, which are passed to cargo without going through a shell.
+    pub cargo_alias: Option<String>,
+
+    /// Directories to add to the front of `PATH`, relative to the workspace root.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<PathBuf>,
+
+    /// The variables declared for the step in the configuration or on the command line, which are set for its command
+    /// along with the variables of the environment, platform, and git repository of the machine executing the plan.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
+
+    /// Whether the step doesn't inherit the environment, so its declared variables are the only ones it gets besides
+    /// `PATH`.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub isolated_env: bool,
+
+    /// Whether to create the step's directory when it doesn't exist.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub create_directory: bool,
+
+    /// Whether the step runs with reduced CPU and I/O priority.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub background: bool,
+
+    pub continue_on_error: bool,
+}
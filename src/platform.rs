@@ -0,0 +1,71 @@
+use crate::config::Os;
+use anyhow::{anyhow, bail};
+use core::str::FromStr;
+
+/// The platform jobs are evaluated for: the one this program runs on, or another one given to `cargo ci plan --for`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    /// The operating system, or `None` when it isn't one jobs and steps can be restricted to.
+    os: Option<Os>,
+    arch: String,
+}
+
+impl Platform {
+    /// The platform this program is running on.
+    #[must_use]
+    pub fn current() -> Self {
+        Self {
+            os: Os::current(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+
+    /// Whether a list of operating systems includes this platform's, where an empty list means any operating system.
+    #[must_use]
+    pub fn runs_on(&self, list: &[Os]) -> bool {
+        list.is_empty() || self.os.is_some_and(|os| list.contains(&os))
+    }
+
+    /// The variables describing the platform to expressions, `runner.os` and `runner.arch`.
+    #[must_use]
+    pub fn variables(&self) -> [(String, String); 2] {
+        [
+            (
+                "runner.os".to_string(),
+                self.os.map_or(std::env::consts::OS, Os::as_str).to_string(),
+            ),
+            ("runner.arch".to_string(), self.arch.clone()),
+        ]
+    }
+}
+
+/// Parses a comma-separated list of `os=<OS>` and `arch=<ARCH>` entries, where whatever is left out is the same as
+/// this platform's.
+impl FromStr for Platform {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut platform = Self::current();
+        for entry in s.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid platform entry '{entry}', expected KEY=VALUE"))?;
+
+            match key.trim() {
+                "os" => {
+                    platform.os = Some(match value.trim() {
+                        "linux" => Os::Linux,
+                        "macos" => Os::Macos,
+                        "windows" => Os::Windows,
+                        other => bail!("unknown operating system '{other}', expected linux, macos, or windows"),
+                    });
+                }
+                "arch" if !value.trim().is_empty() => platform.arch = value.trim().to_string(),
+                "arch" => bail!("the architecture can't be empty"),
+                other => bail!("unknown platform key '{other}', expected os or arch"),
+            }
+        }
+
+        Ok(platform)
+    }
+}
@@ -0,0 +1,45 @@
+use cargo_metadata::Package;
+
+/// The targets a package needs for a cargo subcommand to have anything to do with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequiredTargets {
+    Test,
+    Bench,
+    Doc,
+    Lib,
+}
+
+impl RequiredTargets {
+    /// Determines the targets a cargo subcommand needs, if it's one which only operates on some kinds of targets.
+    #[must_use]
+    pub fn for_subcommand(subcommand: &str) -> Option<Self> {
+        match subcommand {
+            "test" | "t" => Some(Self::Test),
+            "bench" => Some(Self::Bench),
+            "doc" | "d" => Some(Self::Doc),
+            "semver-checks" => Some(Self::Lib),
+            _ => None,
+        }
+    }
+
+    /// Whether the package has any of the required targets.
+    #[must_use]
+    pub fn present_in(self, pkg: &Package) -> bool {
+        pkg.targets.iter().any(|target| match self {
+            Self::Test => target.test || target.doctest,
+            Self::Bench => target.is_bench(),
+            Self::Doc => target.doc,
+            Self::Lib => target.is_lib(),
+        })
+    }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Test => "test",
+            Self::Bench => "bench",
+            Self::Doc => "doc",
+            Self::Lib => "lib",
+        }
+    }
+}
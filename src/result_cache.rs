@@ -0,0 +1,54 @@
+use crate::long_paths;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many trees are remembered for each job, so that switching back and forth between branches keeps hitting.
+const MAX_TREES_PER_JOB: usize = 16;
+
+/// Remembers the git trees on which pure jobs passed, keyed by the digest of the trees and the toolchain, so that
+/// running such a job again on the same trees can be skipped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ResultCache(BTreeMap<String, Vec<String>>);
+
+impl ResultCache {
+    /// Where the cached results of a workspace are kept.
+    #[must_use]
+    pub fn path(target_dir: &Path) -> PathBuf {
+        target_dir.join("ci-results.json")
+    }
+
+    /// Reads the cached results, which are empty when the file doesn't exist yet or can't be parsed.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match fs::read_to_string(long_paths::extended(path)?) {
+            Ok(text) => Ok(serde_json::from_str(&text).unwrap_or_default()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("unable to read cached results from {}", path.display())),
+        }
+    }
+
+    /// Whether the job passed on the trees with the given digest.
+    #[must_use]
+    pub fn passed(&self, job_id: &str, trees: &str) -> bool {
+        self.0.get(job_id).is_some_and(|passed| passed.iter().any(|x| x == trees))
+    }
+
+    /// Records that the job passed on the trees with the given digest, forgetting the oldest trees once there are too
+    /// many.
+    pub fn record(&mut self, job_id: &str, trees: &str) {
+        let passed = self.0.entry(job_id.to_string()).or_default();
+        passed.retain(|x| x != trees);
+        passed.push(trees.to_string());
+
+        let excess = passed.len().saturating_sub(MAX_TREES_PER_JOB);
+        _ = passed.drain(..excess);
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(long_paths::extended(path)?, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("unable to write cached results to {}", path.display()))
+    }
+}
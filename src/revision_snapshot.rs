@@ -0,0 +1,92 @@
+use crate::git;
+use crate::host::Host;
+use crate::long_paths;
+use anyhow::Context;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files whose content matters to resolving the jobs and packages of a workspace, besides the manifests.
+const CONFIG_FILES: &[&str] = &["ci.toml", "ci.yml", "ci.yaml", "ci.json", ".cargo/config.toml", ".cargo/config"];
+
+/// A workspace's manifests and configuration as they were at some commit, recreated in a scratch directory so cargo can
+/// resolve the packages of that commit without checking it out.
+///
+/// Source files are recreated empty, since cargo only needs them to exist to discover the targets of packages. The
+/// directory is removed once the snapshot is dropped.
+#[derive(Debug)]
+pub struct RevisionSnapshot {
+    root: PathBuf,
+    commit: String,
+}
+
+impl RevisionSnapshot {
+    /// Recreates the workspace at `workspace_root` as it was at the given revision, within `scratch_dir`.
+    ///
+    /// A configuration file given with `--config` is included when it's within the workspace.
+    pub fn create(
+        host: &impl Host,
+        workspace_root: &Path,
+        scratch_dir: &Path,
+        rev: &str,
+        config_path: Option<&Path>,
+    ) -> anyhow::Result<Self> {
+        let commit = git::resolve_commit(host, workspace_root, rev)?;
+        let root = scratch_dir.join(format!("at-{}", short(&commit)));
+        if root.exists() {
+            fs::remove_dir_all(long_paths::extended(&root)?).with_context(|| format!("unable to remove {}", root.display()))?;
+        }
+
+        let snapshot = Self { root, commit };
+        let config_path = config_path
+            .and_then(|path| path.strip_prefix(workspace_root).ok())
+            .map(|path| path.to_string_lossy().replace('\\', "/"));
+
+        for path in git::files_at(host, workspace_root, &snapshot.commit)? {
+            let is_manifest = path == "Cargo.toml" || path.ends_with("/Cargo.toml");
+            let is_config = CONFIG_FILES.contains(&path.as_str()) || config_path.as_deref() == Some(path.as_str());
+            let is_source = Path::new(&path).extension().is_some_and(|ext| ext == "rs");
+            if !is_manifest && !is_config && !is_source {
+                continue;
+            }
+
+            let content = if is_source {
+                Vec::new()
+            } else {
+                git::content_at(host, workspace_root, &snapshot.commit, &path)?
+            };
+
+            let dest = snapshot.root.join(&path);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(long_paths::extended(parent)?).with_context(|| format!("unable to create {}", parent.display()))?;
+            }
+
+            fs::write(long_paths::extended(&dest)?, content).with_context(|| format!("unable to write {}", dest.display()))?;
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Where the workspace was recreated.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The abbreviated hash of the commit the workspace was recreated from.
+    #[must_use]
+    pub fn short_commit(&self) -> &str {
+        short(&self.commit)
+    }
+}
+
+fn short(commit: &str) -> &str {
+    commit.get(..12).unwrap_or(commit)
+}
+
+impl Drop for RevisionSnapshot {
+    fn drop(&mut self) {
+        if let Ok(root) = long_paths::extended(&self.root) {
+            _ = fs::remove_dir_all(root);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::{Context, anyhow};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+
+/// An advisory lock preventing concurrent runs in the same workspace, released when dropped.
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+impl RunLock {
+    /// Acquires the lock stored at the given path.
+    ///
+    /// When another process holds the lock, `on_wait` is called and this blocks until the lock is released, unless
+    /// `fail_fast` is set in which case an error is returned right away.
+    pub fn acquire(path: &Path, fail_fast: bool, on_wait: impl FnOnce()) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("unable to create directory {}", dir.display()))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("unable to open lock file {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+
+            Err(TryLockError::WouldBlock) => {
+                if fail_fast {
+                    let holder = fs::read_to_string(path).ok().and_then(|pid| pid.trim().parse::<u32>().ok());
+                    let holder = holder.map_or_else(String::new, |pid| format!(" (process {pid})"));
+                    return Err(anyhow!(
+                        "another cargo-ci run{holder} is in progress in this workspace, use --no-lock to run anyway (lock file: {})",
+                        path.display()
+                    ));
+                }
+
+                on_wait();
+                file.lock().with_context(|| format!("unable to lock {}", path.display()))?;
+            }
+
+            Err(TryLockError::Error(e)) => return Err(e).with_context(|| format!("unable to lock {}", path.display())),
+        }
+
+        // record who holds the lock, purely as a courtesy for error messages
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+
+        Ok(Self { _file: file })
+    }
+}
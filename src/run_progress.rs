@@ -0,0 +1,118 @@
+use crate::fingerprint::{FNV_OFFSET, fnv1a};
+use crate::long_paths;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The steps, and the packages of per-package steps, which succeeded during a run, so that `--resume` picks up where
+/// an interrupted or failed run left off.
+///
+/// Progress is saved as soon as anything completes by writing a temporary file and renaming it over the previous one,
+/// so a crash or Ctrl-C at any point leaves either the old or the new progress behind, never a torn file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunProgress {
+    /// A digest of the configuration the progress was made with, since steps may do something else once it changes.
+    config: String,
+
+    /// The progress of each job, by job id.
+    jobs: BTreeMap<String, JobProgress>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JobProgress {
+    /// The indexes of the steps which succeeded.
+    steps: BTreeSet<usize>,
+
+    /// The packages which succeeded for the steps which didn't, by step index.
+    packages: BTreeMap<usize, BTreeSet<String>>,
+}
+
+impl RunProgress {
+    /// Where the progress of the runs of a workspace is kept.
+    #[must_use]
+    pub fn path(target_dir: &Path) -> PathBuf {
+        target_dir.join("ci-progress.json")
+    }
+
+    /// Starts tracking the progress of a run with the given configuration text.
+    #[must_use]
+    pub fn new(config_text: &str) -> Self {
+        Self {
+            config: digest(config_text),
+            jobs: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the progress of the last run, which is `None` when there's none to resume.
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let text = match fs::read_to_string(long_paths::extended(path)?) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("unable to read run progress from {}", path.display())),
+        };
+
+        serde_json::from_str(&text)
+            .map(Some)
+            .with_context(|| format!("unable to parse run progress from {}", path.display()))
+    }
+
+    /// Whether the progress was made with the given configuration text.
+    #[must_use]
+    pub fn matches_config(&self, config_text: &str) -> bool {
+        self.config == digest(config_text)
+    }
+
+    /// Whether a step of a job succeeded.
+    #[must_use]
+    pub fn step_completed(&self, job_id: &str, step: usize) -> bool {
+        self.jobs.get(job_id).is_some_and(|job| job.steps.contains(&step))
+    }
+
+    /// Whether a step of a job succeeded for a package.
+    #[must_use]
+    pub fn package_completed(&self, job_id: &str, step: usize, package: &str) -> bool {
+        self.jobs
+            .get(job_id)
+            .and_then(|job| job.packages.get(&step))
+            .is_some_and(|packages| packages.contains(package))
+    }
+
+    /// Records that a step of a job succeeded, which covers all of its packages.
+    pub fn complete_step(&mut self, job_id: &str, step: usize) {
+        let job = self.jobs.entry(job_id.to_string()).or_default();
+        _ = job.packages.remove(&step);
+        _ = job.steps.insert(step);
+    }
+
+    /// Records that a step of a job succeeded for a package.
+    pub fn complete_package(&mut self, job_id: &str, step: usize, package: &str) {
+        let job = self.jobs.entry(job_id.to_string()).or_default();
+        _ = job.packages.entry(step).or_default().insert(package.to_string());
+    }
+
+    /// Writes the progress, replacing the previous file atomically.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let temp = path.with_extension("json.tmp");
+        fs::write(long_paths::extended(&temp)?, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("unable to write run progress to {}", temp.display()))?;
+        fs::rename(long_paths::extended(&temp)?, long_paths::extended(path)?)
+            .with_context(|| format!("unable to write run progress to {}", path.display()))
+    }
+
+    /// Forgets the progress of the last run, once there's nothing left to resume.
+    pub fn remove(path: &Path) -> anyhow::Result<()> {
+        match fs::remove_file(long_paths::extended(path)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("unable to remove run progress {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn digest(config_text: &str) -> String {
+    format!("{:016x}", fnv1a(FNV_OFFSET, config_text.as_bytes()))
+}
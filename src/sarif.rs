@@ -0,0 +1,211 @@
+use anyhow::Context;
+use cargo_metadata::Message;
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticLevel};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The rule reported for files which `cargo fmt --check` would reformat.
+const FORMATTING_RULE: &str = "rustfmt::formatting";
+
+/// A diagnostic reported by a step, as it goes into a SARIF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// The tool which reported the diagnostic, such as `clippy`, `rustc`, or `rustfmt`.
+    pub tool: &'static str,
+    pub rule: Option<String>,
+    pub level: &'static str,
+    pub message: String,
+    pub location: Location,
+}
+
+/// Where a diagnostic points to, with the file relative to the workspace root whenever it's inside of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub start_line: usize,
+    pub start_column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+}
+
+/// Picks the diagnostics out of the JSON messages of a cargo command such as `cargo clippy --message-format=json`.
+///
+/// Diagnostics which don't point to any code, like rustc's closing recap of how many warnings it emitted, are left out.
+#[must_use]
+pub fn from_cargo_messages(stdout: &[u8], workspace_root: &Path) -> Vec<Finding> {
+    Message::parse_stream(stdout)
+        .map_while(Result::ok)
+        .filter_map(|message| match message {
+            Message::CompilerMessage(message) => finding(&message.message, workspace_root),
+            _ => None,
+        })
+        .collect()
+}
+
+fn finding(diagnostic: &Diagnostic, workspace_root: &Path) -> Option<Finding> {
+    let span = diagnostic.spans.iter().find(|span| span.is_primary)?;
+    let rule = diagnostic.code.as_ref().map(|code| code.code.clone());
+    let level = match diagnostic.level {
+        DiagnosticLevel::Error | DiagnosticLevel::Ice => "error",
+        DiagnosticLevel::Warning => "warning",
+        _ => "note",
+    };
+
+    Some(Finding {
+        tool: if rule.as_deref().is_some_and(|rule| rule.starts_with("clippy::")) {
+            "clippy"
+        } else {
+            "rustc"
+        },
+        rule,
+        level,
+        message: diagnostic.message.clone(),
+        location: Location {
+            file: relative_file(&span.file_name, workspace_root),
+            start_line: span.line_start,
+            start_column: Some(span.column_start),
+            end_line: Some(span.line_end),
+            end_column: Some(span.column_end),
+        },
+    })
+}
+
+/// Picks the files which would be reformatted out of the output of `cargo fmt --check`, which starts every difference
+/// with a `Diff in <file>:<line>:` line, or `Diff in <file> at line <line>:` with older versions of rustfmt.
+#[must_use]
+pub fn from_rustfmt_check(stdout: &str, workspace_root: &Path) -> Vec<Finding> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let place = line.trim().strip_prefix("Diff in ")?.strip_suffix(':')?;
+            let (file, line) = place.rsplit_once(" at line ").or_else(|| place.rsplit_once(':'))?;
+            let start_line = line.parse().ok()?;
+
+            Some(Finding {
+                tool: "rustfmt",
+                rule: Some(FORMATTING_RULE.to_string()),
+                level: "warning",
+                message: "this code isn't formatted like rustfmt formats it, run `cargo fmt` to fix it".to_string(),
+                location: Location {
+                    file: relative_file(file, workspace_root),
+                    start_line,
+                    start_column: None,
+                    end_line: None,
+                    end_column: None,
+                },
+            })
+        })
+        .collect()
+}
+
+fn relative_file(file: &str, workspace_root: &Path) -> String {
+    let path = Path::new(file);
+    path.strip_prefix(workspace_root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Writes findings to a SARIF 2.1.0 file, with one run per tool, leaving out findings reported more than once, as
+/// happens when several targets of a package share the same code.
+pub fn write(path: &Path, workspace_root: &Path, findings: &[Finding]) -> anyhow::Result<()> {
+    let mut by_tool: BTreeMap<&str, Vec<&Finding>> = BTreeMap::new();
+    for finding in findings {
+        let results = by_tool.entry(finding.tool).or_default();
+        if !results.contains(&finding) {
+            results.push(finding);
+        }
+    }
+
+    let root_uri = format!("file:///{}/", workspace_root.to_string_lossy().replace('\\', "/").trim_matches('/'));
+    let runs: Vec<Value> = by_tool
+        .into_iter()
+        .map(|(tool, findings)| {
+            let mut rules: Vec<&str> = findings.iter().filter_map(|finding| finding.rule.as_deref()).collect();
+            rules.sort_unstable();
+            rules.dedup();
+
+            json!({
+                "tool": {
+                    "driver": {
+                        "name": tool,
+                        "informationUri": information_uri(tool),
+                        "rules": rules.iter().map(|rule| rule_descriptor(rule)).collect::<Vec<_>>(),
+                    }
+                },
+                "originalUriBaseIds": { "SRCROOT": { "uri": root_uri } },
+                "results": findings.iter().map(|finding| result(finding)).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": runs,
+    });
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("unable to create {}", dir.display()))?;
+    }
+
+    fs::write(path, serde_json::to_string_pretty(&log)?).with_context(|| format!("unable to write {}", path.display()))
+}
+
+const fn information_uri(tool: &str) -> &'static str {
+    match tool.as_bytes() {
+        b"clippy" => "https://rust-lang.github.io/rust-clippy/",
+        b"rustfmt" => "https://github.com/rust-lang/rustfmt",
+        _ => "https://doc.rust-lang.org/rustc/",
+    }
+}
+
+fn rule_descriptor(rule: &str) -> Value {
+    let mut descriptor = json!({ "id": rule });
+    if let Some(lint) = rule.strip_prefix("clippy::") {
+        descriptor["helpUri"] = json!(format!("https://rust-lang.github.io/rust-clippy/master/index.html#{lint}"));
+    } else if rule
+        .strip_prefix('E')
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+    {
+        descriptor["helpUri"] = json!(format!("https://doc.rust-lang.org/error_codes/{rule}.html"));
+    }
+
+    descriptor
+}
+
+fn result(finding: &Finding) -> Value {
+    let mut result = json!({
+        "level": finding.level,
+        "message": { "text": finding.message },
+    });
+
+    if let Some(rule) = &finding.rule {
+        result["ruleId"] = json!(rule);
+    }
+
+    let location = &finding.location;
+    let mut region = json!({ "startLine": location.start_line });
+    if let Some(column) = location.start_column {
+        region["startColumn"] = json!(column);
+    }
+
+    if let Some(line) = location.end_line {
+        region["endLine"] = json!(line);
+    }
+
+    if let Some(column) = location.end_column {
+        region["endColumn"] = json!(column);
+    }
+
+    result["locations"] = json!([{
+        "physicalLocation": {
+            "artifactLocation": { "uri": location.file, "uriBaseId": "SRCROOT" },
+            "region": region,
+        }
+    }]);
+
+    result
+}
@@ -0,0 +1,53 @@
+/// A lint of cargo-semver-checks which failed, along with the items which broke it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The lint's identifier, such as `function_missing`.
+    pub lint: String,
+
+    /// What the lint checks, such as `pub fn removed or renamed`.
+    pub description: String,
+
+    /// The offending items, such as `function a::g, previously in file src/lib.rs:1`.
+    pub items: Vec<String>,
+}
+
+impl Violation {
+    /// Describes the violation, one line per offending item.
+    #[must_use]
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![format!("{}: {}", self.lint, self.description)];
+        lines.extend(self.items.iter().map(|item| format!("  {item}")));
+        lines
+    }
+}
+
+/// Picks the failed lints out of the output of `cargo semver-checks`.
+///
+/// Each failed lint is announced with a `--- failure <lint>: <description> ---` line, and the items which broke it are
+/// listed one per line after a `Failed in:` line, up to the next blank line.
+#[must_use]
+pub fn parse(output: &str) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = Vec::new();
+    let mut in_items = false;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if let Some(heading) = trimmed.strip_prefix("--- failure ").and_then(|rest| rest.strip_suffix(" ---")) {
+            let (lint, description) = heading.split_once(':').unwrap_or((heading, ""));
+            violations.push(Violation {
+                lint: lint.trim().to_string(),
+                description: description.trim().to_string(),
+                items: Vec::new(),
+            });
+            in_items = false;
+        } else if trimmed == "Failed in:" {
+            in_items = true;
+        } else if trimmed.is_empty() {
+            in_items = false;
+        } else if in_items && let Some(violation) = violations.last_mut() {
+            violation.items.push(trimmed.to_string());
+        }
+    }
+
+    violations
+}
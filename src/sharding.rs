@@ -0,0 +1,172 @@
+use crate::long_paths;
+use anyhow::{Context, anyhow};
+use cargo_metadata::Package;
+use core::fmt::{self, Display};
+use core::str::FromStr;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// One slice of the packages processed by sharded steps, written as `<index>/<count>` with a 1-based index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    index: usize,
+    count: usize,
+}
+
+impl Shard {
+    #[must_use]
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Whether the item at the given 0-based position of a stable ordering belongs to this shard.
+    #[must_use]
+    pub const fn includes(&self, position: usize) -> bool {
+        position % self.count == self.index - 1
+    }
+
+    /// Returns the packages belonging to this shard, preserving their original order.
+    ///
+    /// Packages are assigned to shards round-robin after sorting them by name, so a given package always lands in
+    /// the same shard no matter the order packages were selected in.
+    pub fn select<'a>(&self, packages: &[&'a Package]) -> Vec<&'a Package> {
+        let mut by_name: Vec<&Package> = packages.to_vec();
+        by_name.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let members: Vec<&Package> = by_name
+            .into_iter()
+            .enumerate()
+            .filter(|(position, _)| self.includes(*position))
+            .map(|(_, pkg)| pkg)
+            .collect();
+
+        packages
+            .iter()
+            .copied()
+            .filter(|pkg| members.iter().any(|m| m.id == pkg.id))
+            .collect()
+    }
+
+    /// Splits the packages into shards by how long each is expected to take, returning the packages of every shard in
+    /// their original order, along with how long the shard is expected to take.
+    ///
+    /// Packages are handed out longest first, each to the shard expected to finish soonest, so shards finish at
+    /// similar times. Packages missing from `durations` are expected to take as long as the average of the others, and
+    /// ties go by package name and shard index, so every shard computes the same assignment from the same durations.
+    pub fn balance<'a>(&self, packages: &[&'a Package], durations: &HashMap<String, Duration>) -> Vec<(Vec<&'a Package>, Duration)> {
+        let known: Vec<Duration> = packages
+            .iter()
+            .filter_map(|pkg| durations.get(pkg.name.as_str()).copied())
+            .collect();
+        let fallback = u32::try_from(known.len())
+            .ok()
+            .filter(|count| *count > 0)
+            .map_or(Duration::from_secs(1), |count| known.iter().sum::<Duration>() / count);
+
+        let mut by_duration: Vec<(&Package, Duration)> = packages
+            .iter()
+            .map(|pkg| (*pkg, durations.get(pkg.name.as_str()).copied().unwrap_or(fallback)))
+            .collect();
+        by_duration.sort_by(|(a, a_duration), (b, b_duration)| b_duration.cmp(a_duration).then_with(|| a.name.cmp(&b.name)));
+
+        let mut shards: Vec<(Vec<&Package>, Duration)> = vec![(Vec::new(), Duration::ZERO); self.count];
+        for (pkg, duration) in by_duration {
+            if let Some((members, load)) = shards.iter_mut().min_by_key(|(_, load)| *load) {
+                members.push(pkg);
+                *load += duration;
+            }
+        }
+
+        shards
+            .into_iter()
+            .map(|(members, load)| {
+                let members = packages
+                    .iter()
+                    .copied()
+                    .filter(|pkg| members.iter().any(|m| m.id == pkg.id))
+                    .collect();
+                (members, load)
+            })
+            .collect()
+    }
+
+    /// Returns the packages belonging to this shard when packages are split by how long each is expected to take, as
+    /// done by [`Self::balance`].
+    pub fn select_balanced<'a>(&self, packages: &[&'a Package], durations: &HashMap<String, Duration>) -> Vec<&'a Package> {
+        self.balance(packages, durations)
+            .into_iter()
+            .nth(self.index - 1)
+            .map(|(members, _)| members)
+            .unwrap_or_default()
+    }
+}
+
+/// How long sharded steps took for each package, in milliseconds by job, step, and package name.
+///
+/// Saved with `--save-shard-durations` and given to every shard with `--balance-shards`, so all shards split packages
+/// the same way, whatever run history each of them has.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ShardDurations(BTreeMap<String, BTreeMap<String, BTreeMap<String, u64>>>);
+
+impl ShardDurations {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(long_paths::extended(path)?)
+            .with_context(|| format!("unable to read shard durations from {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("unable to parse shard durations in {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = serde_json::to_string_pretty(self).context("unable to serialize shard durations")?;
+        fs::write(long_paths::extended(path)?, text).with_context(|| format!("unable to write {}", path.display()))
+    }
+
+    /// Records how long a step of a job took for each package.
+    pub fn insert(&mut self, job: &str, step: &str, durations: &HashMap<String, Duration>) {
+        let packages = durations
+            .iter()
+            .map(|(package, duration)| (package.clone(), u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)))
+            .collect();
+        _ = self.0.entry(job.to_string()).or_default().insert(step.to_string(), packages);
+    }
+
+    /// How long a step of a job took for each package, by package name.
+    #[must_use]
+    pub fn get(&self, job: &str, step: &str) -> HashMap<String, Duration> {
+        self.0
+            .get(job)
+            .and_then(|steps| steps.get(step))
+            .into_iter()
+            .flatten()
+            .map(|(package, ms)| (package.clone(), Duration::from_millis(*ms)))
+            .collect()
+    }
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("invalid shard '{s}', expected '<index>/<count>' such as '2/4'"))?;
+
+        let index: usize = index.trim().parse().with_context(|| format!("invalid shard index in '{s}'"))?;
+        let count: usize = count.trim().parse().with_context(|| format!("invalid shard count in '{s}'"))?;
+
+        if count == 0 || index == 0 || index > count {
+            return Err(anyhow!("invalid shard '{s}', the index must be between 1 and the shard count"));
+        }
+
+        Ok(Self { index, count })
+    }
+}
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
@@ -0,0 +1,40 @@
+use crate::keyed_locks::KeyedLocks;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// How step commands turned out in this run, by what they ran, so that jobs running the very same command as an
+/// earlier job reuse its result instead of running it again.
+#[derive(Debug, Default)]
+pub struct SharedResults {
+    results: Mutex<HashMap<String, SharedResult>>,
+
+    /// Held while running a command whose result may be reused, so jobs running it concurrently wait rather than run it
+    /// too.
+    locks: KeyedLocks,
+}
+
+/// How a step command turned out.
+#[derive(Debug, Clone)]
+pub struct SharedResult {
+    /// The job which ran the command.
+    pub job_id: String,
+
+    /// Why the command failed, when it did.
+    pub error: Option<String>,
+}
+
+impl SharedResults {
+    /// Gets the lock to hold while running the command with the given key.
+    pub fn lock(&self, key: &str) -> Arc<Mutex<()>> {
+        self.locks.get(key)
+    }
+
+    /// How the command with the given key turned out, when a job already ran it.
+    pub fn get(&self, key: &str) -> Option<SharedResult> {
+        self.results.lock().unwrap_or_else(PoisonError::into_inner).get(key).cloned()
+    }
+
+    pub fn record(&self, key: String, result: SharedResult) {
+        _ = self.results.lock().unwrap_or_else(PoisonError::into_inner).insert(key, result);
+    }
+}
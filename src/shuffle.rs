@@ -0,0 +1,24 @@
+/// Shuffles items in place with a seeded pseudo-random generator, so any order can be reproduced from its seed.
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    for i in (1..items.len()).rev() {
+        let bound = u64::try_from(i + 1).expect("slice lengths fit in 64 bits");
+        let j = usize::try_from(next(&mut state) % bound).expect("value is below a slice length");
+        items.swap(i, j);
+    }
+}
+
+/// Derives a seed from the current time, for when the user doesn't provide one.
+pub fn seed_from_time(time: chrono::DateTime<chrono::Local>) -> u64 {
+    let mut state = time.timestamp().cast_unsigned() ^ u64::from(time.timestamp_subsec_nanos()) ^ u64::from(std::process::id());
+    next(&mut state)
+}
+
+/// The `SplitMix64` generator, which is tiny and good enough for shuffling.
+const fn next(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,149 @@
+use crate::summary::format_duration;
+use console::{Term, truncate_str};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+/// How often the plain-text status of the running jobs is printed when the output isn't a terminal.
+pub const PLAIN_STATUS_INTERVAL_SECS: u64 = 30;
+
+/// What the jobs running concurrently are doing, one line per job, plus one per package for steps running for several
+/// packages at once.
+///
+/// On a terminal, the lines make up a region at the bottom of the output which is redrawn in place. Everything else
+/// is written above it through [`Self::print_above`], so output of concurrent jobs doesn't tear the region apart.
+/// Otherwise, the lines are only printed now and then, as plain text.
+pub struct StatusBoard {
+    interactive: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    jobs: Vec<JobStatus>,
+
+    /// How many lines of the region are currently on the terminal.
+    drawn: usize,
+}
+
+struct JobStatus {
+    job: String,
+    activity: String,
+    since: Instant,
+}
+
+impl StatusBoard {
+    #[must_use]
+    pub fn new(interactive: bool) -> Self {
+        Self {
+            interactive,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Whether the board is drawn in place, in which case jobs report their progress only through it.
+    #[must_use]
+    pub const fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Notes what a job has moved on to, such as a step for a package.
+    pub fn update(&self, job: &str, activity: &str, now: Instant) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.jobs.iter_mut().find(|status| status.job == job) {
+            Some(status) => {
+                activity.clone_into(&mut status.activity);
+                status.since = now;
+            }
+            None => state.jobs.push(JobStatus {
+                job: job.to_string(),
+                activity: activity.to_string(),
+                since: now,
+            }),
+        }
+
+        self.redraw(&mut state, now);
+        drop(state);
+    }
+
+    /// Takes a job off the board once it has finished.
+    pub fn remove(&self, job: &str, now: Instant) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.jobs.retain(|status| status.job != job);
+        self.redraw(&mut state, now);
+        drop(state);
+    }
+
+    /// Writes output while the region is out of the way, so it ends up above the region rather than within it.
+    pub fn print_above<R>(&self, now: Instant, print: impl FnOnce() -> R) -> R {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if !self.interactive {
+            return print();
+        }
+
+        clear(&mut state);
+        let result = print();
+        self.redraw(&mut state, now);
+        result
+    }
+
+    /// Redraws the region, to keep the elapsed times current.
+    pub fn refresh(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        self.redraw(&mut state, now);
+    }
+
+    /// Describes what every running job is doing, and for how long it has been at it.
+    #[must_use]
+    pub fn lines(&self, now: Instant) -> Vec<String> {
+        render(&self.state.lock().unwrap_or_else(PoisonError::into_inner), now)
+    }
+
+    /// Leaves the terminal without the region, once every job is done.
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.jobs.clear();
+        if self.interactive {
+            clear(&mut state);
+        }
+
+        drop(state);
+    }
+
+    fn redraw(&self, state: &mut State, now: Instant) {
+        if !self.interactive {
+            return;
+        }
+
+        clear(state);
+
+        // lines which wrap would take up more rows than the region knows to clear
+        let term = Term::stdout();
+        let width = usize::from(term.size().1).saturating_sub(1);
+        for line in render(state, now) {
+            _ = term.write_line(&truncate_str(&line, width, "…"));
+            state.drawn += 1;
+        }
+    }
+}
+
+fn clear(state: &mut State) {
+    if state.drawn > 0 {
+        _ = Term::stdout().clear_last_lines(state.drawn);
+        state.drawn = 0;
+    }
+}
+
+fn render(state: &State, now: Instant) -> Vec<String> {
+    state
+        .jobs
+        .iter()
+        .map(|status| {
+            format!(
+                "{}: {} ({})",
+                status.job,
+                status.activity,
+                format_duration(now.saturating_duration_since(status.since))
+            )
+        })
+        .collect()
+}
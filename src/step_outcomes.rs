@@ -0,0 +1,41 @@
+use crate::config::Step;
+
+/// How a step of a job turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Success,
+    Failure,
+    Skipped,
+}
+
+impl StepOutcome {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+/// The outcomes of the steps of a job so far, exposed to the conditions of later steps as `steps.<id>.outcome`.
+///
+/// Only steps with an `id` are tracked.
+#[derive(Debug, Default)]
+pub struct StepOutcomes {
+    outcomes: Vec<(String, StepOutcome)>,
+}
+
+impl StepOutcomes {
+    /// Records how a step turned out.
+    pub fn record(&mut self, step: &Step, outcome: StepOutcome) {
+        if let Some(id) = step.id() {
+            self.outcomes.push((format!("steps.{id}.outcome"), outcome));
+        }
+    }
+
+    /// Returns an iterator over the variables describing the outcomes recorded so far.
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.outcomes.iter().map(|(name, outcome)| (name.as_str(), outcome.as_str()))
+    }
+}
@@ -0,0 +1,455 @@
+use crate::config::TimeSpan;
+use crate::host::Host;
+use crate::messages::{Indented, Lang, Localize, Msg};
+use crate::outputter::Outputter;
+use crate::test_results::{TestOutcome, TestResult};
+use console::{Alignment, measure_text_width, pad_str};
+use core::fmt::Write;
+use core::time::Duration;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// The final status of a job within a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Succeeded,
+    Failed,
+    FailedIgnored,
+    NotRun,
+
+    /// The job was cancelled with `cargo ci cancel` while the run was in progress.
+    Cancelled,
+}
+
+/// How a step turned out, either for one package or as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Succeeded,
+    Failed,
+    FailedIgnored,
+
+    /// The step's conditions, its platform, or it being destructive kept it from running.
+    Skipped,
+
+    /// The job stopped before getting to the step, or never started.
+    NotRun,
+}
+
+impl StepStatus {
+    /// Whether the step actually ran, however it turned out.
+    #[must_use]
+    pub const fn ran(self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::FailedIgnored)
+    }
+}
+
+/// How a step turned out for one of the packages it was meant for.
+#[derive(Debug, Clone)]
+pub struct PackageResult {
+    pub package: String,
+    pub status: StepStatus,
+
+    /// How long the step took for the package, when it ran for that package on its own.
+    pub duration: Option<Duration>,
+}
+
+impl PackageResult {
+    #[must_use]
+    pub fn new(package: impl Into<String>, status: StepStatus) -> Self {
+        Self {
+            package: package.into(),
+            status,
+            duration: None,
+        }
+    }
+}
+
+/// What happened to a single step of a job.
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub name: String,
+    pub status: StepStatus,
+
+    /// How the step turned out for every package it was meant for, including those its conditions skipped. Steps which
+    /// ran once for the whole workspace have no entries.
+    pub packages: Vec<PackageResult>,
+}
+
+impl StepResult {
+    #[must_use]
+    pub fn not_run(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: StepStatus::NotRun,
+            packages: Vec::new(),
+        }
+    }
+
+    /// How the step turned out for a package. Steps which ran once for the whole workspace turned out the same way for
+    /// every package, while packages the step wasn't meant for count as not run.
+    #[must_use]
+    pub fn status_for(&self, package: &str) -> StepStatus {
+        if self.packages.is_empty() {
+            return self.status;
+        }
+
+        self.packages
+            .iter()
+            .find(|pkg| pkg.package == package)
+            .map_or(StepStatus::NotRun, |pkg| pkg.status)
+    }
+}
+
+/// What happened to a single job during a run.
+#[derive(Debug, Clone)]
+pub struct JobResult {
+    pub id: String,
+    pub name: String,
+    pub status: JobStatus,
+    pub duration: Duration,
+    pub steps: Vec<StepResult>,
+    pub error: Option<String>,
+}
+
+impl JobResult {
+    /// Counts the steps which ran, the steps which were skipped, and the steps which weren't run, followed by the
+    /// packages skipped by the steps which did run.
+    ///
+    /// A step skipped for every package counts as a skipped step rather than as skipped packages.
+    #[must_use]
+    pub fn step_counts(&self) -> [usize; 4] {
+        let count = |status| self.steps.iter().filter(|step| step.status == status).count();
+        let skipped_packages = self
+            .steps
+            .iter()
+            .filter(|step| step.status.ran())
+            .flat_map(|step| &step.packages)
+            .filter(|pkg| pkg.status == StepStatus::Skipped)
+            .count();
+
+        [
+            self.steps.iter().filter(|step| step.status.ran()).count(),
+            count(StepStatus::Skipped),
+            count(StepStatus::NotRun),
+            skipped_packages,
+        ]
+    }
+}
+
+/// What happened to a group of jobs that ran together.
+///
+/// Jobs that aren't assigned to a stage are reported in a group with no name.
+#[derive(Debug, Clone, Default)]
+pub struct StageResult {
+    pub name: Option<String>,
+    pub duration: Duration,
+    pub jobs: Vec<JobResult>,
+}
+
+impl StageResult {
+    fn status(&self) -> JobStatus {
+        if self.jobs.iter().any(|j| j.status == JobStatus::Failed) {
+            JobStatus::Failed
+        } else if self.jobs.iter().all(|j| j.status == JobStatus::NotRun) {
+            JobStatus::NotRun
+        } else if self.jobs.iter().any(|j| j.status == JobStatus::FailedIgnored) {
+            JobStatus::FailedIgnored
+        } else {
+            JobStatus::Succeeded
+        }
+    }
+}
+
+/// Everything reported at the end of a run.
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    pub stages: Vec<StageResult>,
+    pub warnings: Vec<String>,
+
+    /// The outcomes of individual tests, picked out of the output of steps which ran tests.
+    pub tests: Vec<TestResult>,
+
+    /// The time budget which cut the run short, if it did.
+    pub time_budget: Option<TimeSpan>,
+}
+
+impl RunSummary {
+    pub fn jobs(&self) -> impl Iterator<Item = &JobResult> {
+        self.stages.iter().flat_map(|s| s.jobs.iter())
+    }
+}
+
+/// Prints the summary of a run, optionally with a grid of how every step turned out for every package.
+pub fn print_summary<H: Host>(outputter: &Outputter<H>, summary: &RunSummary, by_package: bool) {
+    let stages = &summary.stages;
+
+    outputter.line("");
+    outputter.line(Msg::Summary);
+
+    for stage in stages {
+        if let Some(name) = &stage.name {
+            outputter.line(Indented(
+                "  ",
+                Msg::SummaryStage(name, stage.status(), &format_duration(stage.duration)),
+            ));
+        }
+
+        let indent = if stage.name.is_some() { "    " } else { "  " };
+
+        for job in &stage.jobs {
+            if job.status == JobStatus::NotRun {
+                outputter.line(Indented(indent, Msg::SummaryJobNotRun(&job.name)));
+            } else {
+                let [ran, skipped, _, skipped_packages] = job.step_counts();
+                let duration = format_duration(job.duration);
+                outputter.line(Indented(
+                    indent,
+                    Msg::SummaryJob(&job.name, job.status, [ran, skipped, skipped_packages], &duration),
+                ));
+            }
+
+            if by_package && job.steps.iter().any(|step| !step.packages.is_empty()) {
+                outputter.line(PackageGrid(&format!("{indent}    "), job));
+            }
+
+            if let Some(error) = &job.error {
+                outputter.line(Indented(indent, Indented("  ", Msg::SummaryError(error))));
+            }
+        }
+    }
+
+    let jobs = || stages.iter().flat_map(|s| s.jobs.iter());
+    let count = |status| jobs().filter(|j| j.status == status).count();
+    let total: Duration = stages.iter().map(|s| s.duration).sum();
+
+    let counts = [
+        count(JobStatus::Succeeded),
+        count(JobStatus::Failed),
+        count(JobStatus::FailedIgnored),
+        count(JobStatus::NotRun),
+    ];
+    outputter.line(Indented("  ", Msg::SummaryTotal(counts, &format_duration(total))));
+    if count(JobStatus::Cancelled) > 0 {
+        outputter.line(Indented("  ", Msg::SummaryCancelled(count(JobStatus::Cancelled))));
+    }
+
+    let step_counts = jobs().map(JobResult::step_counts).fold([0; 4], |mut totals, counts| {
+        for (total, count) in totals.iter_mut().zip(counts) {
+            *total += count;
+        }
+
+        totals
+    });
+    outputter.line(Indented("  ", Msg::SummarySteps(step_counts)));
+
+    if let Some(budget) = summary.time_budget {
+        outputter.line(Indented("  ", Msg::SummaryOutOfTime(&budget.to_string())));
+    }
+
+    if !summary.tests.is_empty() {
+        outputter.line(Indented("  ", Msg::SummaryTests(test_counts(&summary.tests))));
+        for test in summary.tests.iter().filter(|test| test.outcome == TestOutcome::Failed) {
+            outputter.line(Indented("    ", Msg::SummaryFailedTest(&test_label(test))));
+        }
+    }
+
+    if !summary.warnings.is_empty() {
+        outputter.line("");
+        outputter.line(Msg::Warnings);
+        for warning in &summary.warnings {
+            outputter.line(format!("  {warning}"));
+        }
+    }
+}
+
+/// How every step of a job turned out for every package, one row per step and one column per package.
+///
+/// Steps which ran once for the whole workspace show the same status for every package.
+struct PackageGrid<'a>(&'a str, &'a JobResult);
+
+impl Localize for PackageGrid<'_> {
+    fn localize(&self, lang: Lang) -> Cow<'_, str> {
+        let Self(indent, job) = *self;
+        let packages: BTreeSet<_> = job
+            .steps
+            .iter()
+            .flat_map(|step| &step.packages)
+            .map(|pkg| pkg.package.as_str())
+            .collect();
+
+        let mut rows = vec![
+            core::iter::once("")
+                .chain(packages.iter().copied())
+                .map(Cow::Borrowed)
+                .collect::<Vec<_>>(),
+        ];
+        for step in &job.steps {
+            let mut row = vec![Cow::Borrowed(step.name.as_str())];
+            for package in &packages {
+                row.push(Msg::StepStatus(step.status_for(package)).localize(lang).into_owned().into());
+            }
+
+            rows.push(row);
+        }
+
+        let widths: Vec<_> = (0..=packages.len())
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row.get(column).map_or(0, |cell| measure_text_width(cell)))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let lines: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                let cells: Vec<_> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| pad_str(cell, *width, Alignment::Left, None))
+                    .collect();
+                format!("{indent}{}", cells.join("  ").trim_end())
+            })
+            .collect();
+
+        Cow::Owned(lines.join("\n"))
+    }
+}
+
+/// The most lines of a job's error included in a Markdown summary.
+const MAX_EXCERPT_LINES: usize = 20;
+
+/// Renders a compact Markdown summary of a run, suitable for pasting into a PR description.
+///
+/// The summary is always in English, like the log.
+pub fn render_markdown(summary: &RunSummary, started: &str, log_file: &Path, kept_dir: Option<&Path>, by_package: bool) -> String {
+    let failed = summary.jobs().any(|j| j.status == JobStatus::Failed);
+    let total: Duration = summary.stages.iter().map(|s| s.duration).sum();
+    let status = |status| Msg::Status(status).localize(Lang::En).into_owned();
+
+    let mut md = String::new();
+    _ = writeln!(
+        md,
+        "## cargo-ci run {}\n",
+        if failed {
+            status(JobStatus::Failed)
+        } else {
+            status(JobStatus::Succeeded)
+        }
+    );
+    _ = writeln!(md, "Started {started}, took {}.\n", format_duration(total));
+    if let Some(budget) = summary.time_budget {
+        _ = writeln!(md, "**{}**\n", Msg::SummaryOutOfTime(&budget.to_string()).localize(Lang::En));
+    }
+
+    _ = writeln!(
+        md,
+        "| Job | Stage | Status | Steps Run | Steps Skipped | Packages Skipped | Duration |"
+    );
+    _ = writeln!(md, "| --- | --- | --- | --- | --- | --- | --- |");
+
+    for stage in &summary.stages {
+        for job in &stage.jobs {
+            let [ran, skipped, _, skipped_packages] = job.step_counts();
+            _ = writeln!(
+                md,
+                "| {} | {} | {} | {ran} | {skipped} | {skipped_packages} | {} |",
+                job.name,
+                stage.name.as_deref().unwrap_or("-"),
+                status(job.status),
+                format_duration(job.duration)
+            );
+        }
+    }
+
+    if by_package {
+        for job in summary.jobs().filter(|job| job.steps.iter().any(|step| !step.packages.is_empty())) {
+            _ = writeln!(md, "\n### {}\n", job.name);
+            render_package_table(&mut md, job);
+        }
+    }
+
+    let errors: Vec<_> = summary.jobs().filter_map(|j| j.error.as_ref().map(|e| (j, e))).collect();
+    if !errors.is_empty() {
+        _ = writeln!(md, "\n### Failures");
+        for (job, error) in errors {
+            let lines: Vec<_> = error.lines().collect();
+            let excerpt = &lines[lines.len().saturating_sub(MAX_EXCERPT_LINES)..];
+            _ = writeln!(
+                md,
+                "\n**{}** ({})\n\n```text\n{}\n```",
+                job.name,
+                status(job.status),
+                excerpt.join("\n")
+            );
+        }
+    }
+
+    if !summary.tests.is_empty() {
+        _ = writeln!(md, "\n### Tests\n");
+        _ = writeln!(md, "{}", Msg::SummaryTests(test_counts(&summary.tests)).localize(Lang::En));
+        let failed: Vec<_> = summary.tests.iter().filter(|test| test.outcome == TestOutcome::Failed).collect();
+        if !failed.is_empty() {
+            _ = writeln!(md);
+            for test in failed {
+                _ = writeln!(md, "- failed: `{}`", test_label(test));
+            }
+        }
+    }
+
+    if !summary.warnings.is_empty() {
+        _ = writeln!(md, "\n### Warnings\n");
+        for warning in &summary.warnings {
+            _ = writeln!(md, "- {warning}");
+        }
+    }
+
+    _ = writeln!(md, "\nLog: `{}`", log_file.display());
+    if let Some(dir) = kept_dir {
+        _ = writeln!(md, "\nTemporary files: `{}`", dir.display());
+    }
+
+    md
+}
+
+/// Renders how every step of a job turned out for every package as a Markdown table.
+fn render_package_table(md: &mut String, job: &JobResult) {
+    let packages: BTreeSet<_> = job
+        .steps
+        .iter()
+        .flat_map(|step| &step.packages)
+        .map(|pkg| pkg.package.as_str())
+        .collect();
+
+    _ = writeln!(md, "| Step | {} |", packages.iter().copied().collect::<Vec<_>>().join(" | "));
+    _ = writeln!(md, "| --- |{}", " --- |".repeat(packages.len()));
+    for step in &job.steps {
+        let statuses: Vec<_> = packages
+            .iter()
+            .map(|package| Msg::StepStatus(step.status_for(package)).localize(Lang::En).into_owned())
+            .collect();
+
+        _ = writeln!(md, "| {} | {} |", step.name, statuses.join(" | "));
+    }
+}
+
+/// Counts the tests which passed, failed, and were ignored.
+fn test_counts(tests: &[TestResult]) -> [usize; 3] {
+    let count = |outcome| tests.iter().filter(|test| test.outcome == outcome).count();
+    [count(TestOutcome::Passed), count(TestOutcome::Failed), count(TestOutcome::Ignored)]
+}
+
+fn test_label(test: &TestResult) -> String {
+    test.binary
+        .as_ref()
+        .map_or_else(|| test.name.clone(), |binary| format!("{binary}: {}", test.name))
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.2}s", duration.as_secs_f64())
+}
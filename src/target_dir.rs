@@ -0,0 +1,100 @@
+use crate::long_paths;
+use anyhow::Context;
+use cargo_metadata::Metadata;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The directories of a build profile in which cargo keeps artifacts named after the crate they belong to.
+const ARTIFACT_DIRS: [&str; 5] = [".fingerprint", "build", "deps", "examples", "incremental"];
+
+/// Adds up the sizes of the files within a directory and its subdirectories, without following symbolic links.
+///
+/// Files hard-linked under several names, as cargo does for the binaries it builds, are counted once per name.
+pub fn size(dir: &Path) -> anyhow::Result<u64> {
+    let entries = fs::read_dir(long_paths::extended(dir)?).with_context(|| format!("unable to list the files in {}", dir.display()))?;
+
+    let mut total = 0;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("unable to list the files in {}", dir.display()))?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            total += size(&dir.join(entry.file_name()))?;
+        } else if !file_type.is_symlink() {
+            total += entry.metadata()?.len();
+        }
+    }
+
+    Ok(total)
+}
+
+/// Finds the artifacts in a target directory which belong to crates that are neither part of the workspace nor one of
+/// its dependencies anymore.
+///
+/// The metadata must include the workspace's dependencies. Only entries named like cargo names its artifacts, after a
+/// crate and followed by a hash, are considered, so anything else in the target directory is left alone.
+pub fn stale_artifacts(target_dir: &Path, metadata: &Metadata) -> anyhow::Result<Vec<PathBuf>> {
+    let known: HashSet<_> = metadata
+        .packages
+        .iter()
+        .flat_map(|pkg| core::iter::once(pkg.name.as_str()).chain(pkg.targets.iter().map(|target| target.name.as_str())))
+        .map(|name| name.replace('-', "_"))
+        .collect();
+
+    let mut stale = Vec::new();
+    for profile_dir in profile_dirs(target_dir)? {
+        for dir in ARTIFACT_DIRS.iter().map(|name| profile_dir.join(name)).filter(|dir| dir.is_dir()) {
+            let entries =
+                fs::read_dir(long_paths::extended(&dir)?).with_context(|| format!("unable to list the files in {}", dir.display()))?;
+            for entry in entries {
+                let entry = entry.with_context(|| format!("unable to list the files in {}", dir.display()))?;
+                let name = entry.file_name();
+                if crate_of(&name.to_string_lossy()).is_some_and(|crate_name| !is_known(crate_name, &known)) {
+                    stale.push(dir.join(name));
+                }
+            }
+        }
+    }
+
+    stale.sort();
+    Ok(stale)
+}
+
+/// Finds the directories of build profiles, such as `debug` or `x86_64-unknown-linux-gnu/release`, which are told apart
+/// by cargo's `.fingerprint` directory.
+fn profile_dirs(target_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    let entries =
+        fs::read_dir(long_paths::extended(target_dir)?).with_context(|| format!("unable to list the files in {}", target_dir.display()))?;
+    for entry in entries {
+        let path = target_dir.join(entry?.file_name());
+        if path.join(".fingerprint").is_dir() {
+            dirs.push(path);
+        } else if path.is_dir() {
+            // builds for an explicit target triple have their profiles one level down
+            for entry in fs::read_dir(long_paths::extended(&path)?)? {
+                let nested = path.join(entry?.file_name());
+                if nested.join(".fingerprint").is_dir() {
+                    dirs.push(nested);
+                }
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Picks the name of the crate out of the name of an artifact, as in `serde` out of `libserde-0123456789abcdef.rlib`.
+fn crate_of(artifact: &str) -> Option<&str> {
+    let stem = artifact.split_once('.').map_or(artifact, |(stem, _)| stem);
+    let (crate_name, hash) = stem.rsplit_once('-')?;
+    let is_hash = hash.len() >= 10 && hash.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+    (is_hash && !crate_name.is_empty()).then_some(crate_name)
+}
+
+/// Whether an artifact's crate is still around, given that libraries get a `lib` prefix, which crates named like
+/// `libc` have on their own.
+fn is_known(crate_name: &str, known: &HashSet<String>) -> bool {
+    let crate_name = crate_name.replace('-', "_");
+    known.contains(&crate_name) || crate_name.strip_prefix("lib").is_some_and(|name| known.contains(name))
+}
@@ -0,0 +1,202 @@
+use crate::host::Host;
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Local};
+use core::sync::atomic::{AtomicU64, Ordering};
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, PoisonError};
+
+const CONNECT_TIMEOUT_SECS: &str = "5";
+const REQUEST_TIMEOUT_SECS: &str = "15";
+
+/// Identifies a span within a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanId(u64);
+
+/// A span which has started but not yet finished.
+#[derive(Debug)]
+pub struct ActiveSpan {
+    id: SpanId,
+    parent: Option<SpanId>,
+    name: String,
+    start: DateTime<Local>,
+    attributes: Vec<(&'static str, String)>,
+}
+
+impl ActiveSpan {
+    pub const fn id(&self) -> SpanId {
+        self.id
+    }
+
+    /// Attaches a piece of metadata to the span.
+    pub fn attribute(&mut self, key: &'static str, value: impl Into<String>) {
+        self.attributes.push((key, value.into()));
+    }
+}
+
+#[derive(Debug)]
+struct FinishedSpan {
+    span: ActiveSpan,
+    end: DateTime<Local>,
+    error: Option<String>,
+}
+
+/// Collects the spans of a run, so they can be exported to an OpenTelemetry collector once the run completes.
+///
+/// When disabled, spans are handed out as usual but never recorded, so callers don't need to care.
+#[derive(Debug)]
+pub struct Telemetry {
+    enabled: bool,
+    trace_id: String,
+    next_span_id: AtomicU64,
+    spans: Mutex<Vec<FinishedSpan>>,
+}
+
+impl Telemetry {
+    pub fn new(enabled: bool, now: DateTime<Local>) -> Self {
+        let nanos = now.timestamp_nanos_opt().unwrap_or_default().cast_unsigned();
+        Self {
+            enabled,
+            trace_id: format!("{nanos:016x}{:016x}", std::process::id()),
+            next_span_id: AtomicU64::new(1),
+            spans: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Starts a span, nested within the given parent span.
+    pub fn start(&self, parent: Option<SpanId>, name: impl Into<String>, now: DateTime<Local>) -> ActiveSpan {
+        ActiveSpan {
+            id: SpanId(self.next_span_id.fetch_add(1, Ordering::Relaxed)),
+            parent,
+            name: name.into(),
+            start: now,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Finishes a span, marking it as failed when given an error.
+    pub fn finish(&self, span: ActiveSpan, now: DateTime<Local>, error: Option<String>) {
+        if self.enabled {
+            self.spans
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(FinishedSpan { span, end: now, error });
+        }
+    }
+
+    /// Sends the finished spans to an OTLP/HTTP endpoint, encoded as JSON, going through the given file.
+    ///
+    /// Both `http://` and `https://` endpoints are supported. When the endpoint has no path, the standard `/v1/traces`
+    /// path is used.
+    pub fn export(&self, host: &impl Host, endpoint: &str, body_file: &Path) -> anyhow::Result<()> {
+        let spans: Vec<_> = self
+            .spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|span| self.encode(span))
+            .collect();
+
+        let body = json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [string_attribute("service.name", "cargo-ci")],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "cargo-ci", "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        });
+
+        fs::write(body_file, body.to_string()).with_context(|| format!("unable to write {}", body_file.display()))?;
+        post(host, endpoint, body_file)
+    }
+
+    fn encode(&self, finished: &FinishedSpan) -> Value {
+        let span = &finished.span;
+        let status = finished
+            .error
+            .as_ref()
+            .map_or_else(|| json!({ "code": 1 }), |error| json!({ "code": 2, "message": error }));
+
+        json!({
+            "traceId": self.trace_id,
+            "spanId": format!("{:016x}", span.id.0),
+            "parentSpanId": span.parent.map(|parent| format!("{:016x}", parent.0)).unwrap_or_default(),
+            "name": span.name,
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(span.start),
+            "endTimeUnixNano": unix_nanos(finished.end),
+            "attributes": span.attributes.iter().map(|(key, value)| string_attribute(key, value)).collect::<Vec<_>>(),
+            "status": status,
+        })
+    }
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// OTLP/JSON encodes 64-bit integers as strings.
+fn unix_nanos(time: DateTime<Local>) -> String {
+    time.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+/// Posts a JSON document, held in a file, to a collector through curl, which also takes care of `https://` endpoints.
+fn post(host: &impl Host, endpoint: &str, body: &Path) -> anyhow::Result<()> {
+    let rest = endpoint
+        .strip_prefix("http://")
+        .or_else(|| endpoint.strip_prefix("https://"))
+        .ok_or_else(|| anyhow!("unsupported OTLP endpoint '{endpoint}', only http:// and https:// endpoints are supported"))?;
+
+    // an IPv6 authority is bracketed, so it never contains a '/'
+    let url = match rest.find('/') {
+        Some(index) if rest.len() > index + 1 => endpoint.to_string(),
+        Some(_) => format!("{endpoint}v1/traces"),
+        None => format!("{endpoint}/v1/traces"),
+    };
+
+    let mut cmd = Command::new("curl");
+    _ = cmd
+        .args([
+            "--silent",
+            "--show-error",
+            "--connect-timeout",
+            CONNECT_TIMEOUT_SECS,
+            "--max-time",
+            REQUEST_TIMEOUT_SECS,
+        ])
+        .args([
+            "--proto",
+            "=http,https",
+            "--request",
+            "POST",
+            "--header",
+            "Content-Type: application/json",
+        ])
+        .arg("--data-binary")
+        .arg(format!("@{}", body.display()))
+        .args(["--write-out", "\n%{http_code}"])
+        .arg(&url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let output = host.output(&mut cmd).context("unable to run curl to send the spans")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "unable to send spans to '{url}': {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (response, status) = stdout.trim_end().rsplit_once('\n').unwrap_or_else(|| ("", stdout.trim()));
+    if status.starts_with('2') {
+        Ok(())
+    } else {
+        Err(anyhow!("'{url}' rejected the spans: HTTP status {status} {}", response.trim()))
+    }
+}
@@ -0,0 +1,57 @@
+use crate::config::Notifications;
+use crate::host::Host;
+use crate::summary::{JobStatus, RunSummary, format_duration};
+use core::time::Duration;
+
+/// Saves the terminal's title, for [`restore_title`] to bring it back once the run no longer shows its progress there.
+///
+/// This uses the title stack of xterm, which most terminals support, and others ignore.
+pub fn save_title<H: Host>(host: &H) {
+    host.print_fmt(format_args!("\x1b[22;0t"));
+}
+
+/// Brings back the terminal's title saved by [`save_title`].
+pub fn restore_title<H: Host>(host: &H) {
+    host.print_fmt(format_args!("\x1b[23;0t"));
+}
+
+/// Shows what a run is up to in the title of the terminal, for users who switched to another tab or window.
+pub fn set_title<H: Host>(host: &H, title: &str) {
+    host.print_fmt(format_args!("\x1b]0;{}\x07", sanitize(title)));
+}
+
+/// Lets the user know a run is over, as configured: the bell rings or a desktop notification is sent, through the
+/// OSC 9 sequence which terminals such as iTerm2, Windows Terminal, and kitty understand, when the run took long
+/// enough.
+pub fn notify_completion<H: Host>(host: &H, settings: &Notifications, summary: &RunSummary, succeeded: bool, elapsed: Duration) {
+    if !settings.long_enough(elapsed) {
+        return;
+    }
+
+    if settings.desktop().applies(succeeded) {
+        let jobs = summary.jobs().count();
+        let message = if succeeded {
+            format!("cargo ci passed, {jobs} job(s) in {}", format_duration(elapsed))
+        } else {
+            let failed = summary
+                .jobs()
+                .filter(|job| matches!(job.status, JobStatus::Failed | JobStatus::Cancelled))
+                .count();
+            format!(
+                "cargo ci failed, {failed} of {jobs} job(s) failed after {}",
+                format_duration(elapsed)
+            )
+        };
+
+        host.print_fmt(format_args!("\x1b]9;{}\x07", sanitize(&message)));
+    }
+
+    if settings.bell().applies(succeeded) {
+        host.print_fmt(format_args!("\x07"));
+    }
+}
+
+/// Keeps control characters, which would end the escape sequence early, out of the text it carries.
+fn sanitize(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+
+/// How a single test turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// The outcome of a single test, as reported by the test harness or by cargo-nextest.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestResult {
+    /// The test binary, when the output says which one the test belongs to.
+    pub binary: Option<String>,
+    pub name: String,
+    pub outcome: TestOutcome,
+}
+
+/// Picks the outcomes of individual tests out of the output of `cargo test` or `cargo nextest run`.
+///
+/// The standard test harness reports tests as `test <name> ... ok` on stdout, after a `running <n> tests` line, while
+/// cargo announces each test binary on stderr with a `Running <source> (<path to binary>)` line. Since the two streams
+/// are captured separately, binaries are matched with the `running` lines in the order they come. cargo-nextest reports
+/// tests as `PASS [<duration>] <binary> <name>`, and lists failed tests once more at the end, which are only counted
+/// once.
+#[must_use]
+pub fn parse(stdout: &str, stderr: &str) -> Vec<TestResult> {
+    let mut results = Vec::new();
+    let mut seen = HashSet::new();
+    let mut announced = VecDeque::new();
+    let mut binary = None;
+
+    for line in stderr.lines().chain(stdout.lines()).map(str::trim) {
+        if let Some(running) = line.strip_prefix("Running ") {
+            announced.push_back(
+                running
+                    .rsplit_once('(')
+                    .and_then(|(_, path)| path.strip_suffix(')'))
+                    .and_then(|path| path.rsplit(['/', '\\']).next())
+                    .map(|file| file.rsplit_once('-').map_or(file, |(name, _hash)| name).to_string()),
+            );
+        } else if let Some(name) = line.strip_prefix("Doc-tests ") {
+            announced.push_back(Some(format!("{name} (doc)")));
+        } else if line.starts_with("running ") && (line.ends_with(" tests") || line.ends_with(" test")) {
+            binary = announced.pop_front().flatten();
+        } else if let Some(result) = parse_libtest(line, binary.as_deref()).or_else(|| parse_nextest(line))
+            && seen.insert((result.binary.clone(), result.name.clone()))
+        {
+            results.push(result);
+        }
+    }
+
+    results
+}
+
+/// Parses `test <name> ... <outcome>`.
+fn parse_libtest(line: &str, binary: Option<&str>) -> Option<TestResult> {
+    let (name, outcome) = line.strip_prefix("test ")?.rsplit_once(" ... ")?;
+    let outcome = match outcome {
+        "ok" => TestOutcome::Passed,
+        "FAILED" => TestOutcome::Failed,
+        outcome if outcome.starts_with("ignored") => TestOutcome::Ignored,
+        _ => return None,
+    };
+
+    Some(TestResult {
+        binary: binary.map(ToString::to_string),
+        name: name.to_string(),
+        outcome,
+    })
+}
+
+/// Parses `<OUTCOME> [<duration>] <binary> <name>`.
+fn parse_nextest(line: &str) -> Option<TestResult> {
+    let (status, rest) = line.split_once(' ')?;
+    let outcome = match status {
+        "PASS" => TestOutcome::Passed,
+        "FAIL" | "TIMEOUT" | "SIGSEGV" | "SIGABRT" => TestOutcome::Failed,
+        "SKIP" => TestOutcome::Ignored,
+        _ => return None,
+    };
+
+    let (_, test) = rest.trim_start().strip_prefix('[')?.split_once(']')?;
+    let (binary, name) = test.trim().split_once(' ')?;
+
+    Some(TestResult {
+        binary: Some(binary.to_string()),
+        name: name.trim().to_string(),
+        outcome,
+    })
+}
@@ -0,0 +1,44 @@
+use crate::config::RateLimit;
+use core::time::Duration;
+use std::time::Instant;
+
+/// Classic token bucket: allows bursts of up to `capacity` operations, refilling at a steady rate.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Option<Instant>,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit) -> Self {
+        let capacity = f64::from(limit.count());
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / limit.period().as_secs_f64(),
+            last_refill: None,
+        }
+    }
+
+    /// Takes a token, returning how long the caller must wait before proceeding.
+    ///
+    /// The token is considered consumed once the returned delay has elapsed.
+    pub fn take(&mut self, now: Instant) -> Duration {
+        if let Some(last) = self.last_refill {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            self.tokens = elapsed.mul_add(self.refill_per_sec, self.tokens).min(self.capacity);
+        }
+
+        let delay = if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec)
+        };
+
+        self.tokens = (self.tokens - 1.0).max(0.0);
+        self.last_refill = Some(now + delay);
+        delay
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::Context;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Finds the root of the workspace a manifest belongs to by reading manifests, without running `cargo metadata`.
+///
+/// The root is the directory named by the manifest's `package.workspace` entry, or else the closest directory, starting
+/// with the manifest's own, whose manifest has a `[workspace]` table. A package outside of any workspace is its own root.
+pub fn find(manifest_path: &Path) -> anyhow::Result<PathBuf> {
+    let manifest_path = std::path::absolute(manifest_path).with_context(|| format!("unable to find {}", manifest_path.display()))?;
+    let package_dir = manifest_path.parent().unwrap_or(&manifest_path).to_path_buf();
+
+    let manifest = read_manifest(&manifest_path)?;
+    if let Some(dir) = manifest
+        .get("package")
+        .and_then(|package| package.get("workspace"))
+        .and_then(toml::Value::as_str)
+    {
+        return Ok(package_dir.join(dir));
+    }
+
+    for dir in package_dir.ancestors() {
+        let candidate = dir.join("Cargo.toml");
+        if candidate.is_file() && read_manifest(&candidate)?.contains_key("workspace") {
+            return Ok(dir.to_path_buf());
+        }
+    }
+
+    Ok(package_dir)
+}
+
+/// Reads and parses a `Cargo.toml` file.
+pub fn read_manifest(path: &Path) -> anyhow::Result<toml::Table> {
+    let text = fs::read_to_string(path).with_context(|| format!("unable to read {}", path.display()))?;
+    text.parse().with_context(|| format!("unable to parse {}", path.display()))
+}